@@ -145,6 +145,21 @@ impl Elem {
         }
     }
 
+    pub fn span(&self) -> Span {
+        match self {
+            &ElemFunction(ref fct) => fct.span,
+            &ElemClass(ref class) => class.span,
+            &ElemStruct(ref s) => s.span,
+            &ElemTrait(ref t) => t.span,
+            &ElemImpl(ref i) => i.span,
+            &ElemModule(ref m) => m.span,
+            &ElemGlobal(ref g) => g.span,
+            &ElemConst(ref c) => c.span,
+            &ElemEnum(ref e) => e.span,
+            &ElemAlias(ref e) => e.span,
+        }
+    }
+
     pub fn to_function(&self) -> Option<&Function> {
         match self {
             &ElemFunction(ref fct) => Some(fct),
@@ -446,6 +461,15 @@ impl Type {
         }
     }
 
+    pub fn span(&self) -> Span {
+        match *self {
+            Type::TypeSelf(ref val) => val.span,
+            Type::TypeBasic(ref val) => val.span,
+            Type::TypeTuple(ref val) => val.span,
+            Type::TypeLambda(ref val) => val.span,
+        }
+    }
+
     pub fn id(&self) -> NodeId {
         match *self {
             Type::TypeSelf(ref val) => val.id,
@@ -501,6 +525,7 @@ pub struct Module {
     pub id: NodeId,
     pub name: Name,
     pub pos: Position,
+    pub span: Span,
     pub parent_class: Option<ParentClass>,
     pub internal: bool,
     pub has_constructor: bool,
@@ -686,6 +711,7 @@ pub enum Stmt {
     StmtContinue(StmtContinueType),
     StmtReturn(StmtReturnType),
     StmtFor(StmtForType),
+    StmtError(StmtErrorType),
 }
 
 impl Stmt {
@@ -756,6 +782,10 @@ impl Stmt {
         })
     }
 
+    pub fn create_error(id: NodeId, pos: Position, span: Span) -> Stmt {
+        Stmt::StmtError(StmtErrorType { id, pos, span })
+    }
+
     pub fn create_break(id: NodeId, pos: Position, span: Span) -> Stmt {
         Stmt::StmtBreak(StmtBreakType { id, pos, span })
     }
@@ -779,6 +809,7 @@ impl Stmt {
             Stmt::StmtVar(ref stmt) => stmt.id,
             Stmt::StmtWhile(ref stmt) => stmt.id,
             Stmt::StmtFor(ref stmt) => stmt.id,
+            Stmt::StmtError(ref stmt) => stmt.id,
             Stmt::StmtExpr(ref stmt) => stmt.id,
             Stmt::StmtBreak(ref stmt) => stmt.id,
             Stmt::StmtContinue(ref stmt) => stmt.id,
@@ -791,6 +822,7 @@ impl Stmt {
             Stmt::StmtVar(ref stmt) => stmt.pos,
             Stmt::StmtWhile(ref stmt) => stmt.pos,
             Stmt::StmtFor(ref stmt) => stmt.pos,
+            Stmt::StmtError(ref stmt) => stmt.pos,
             Stmt::StmtExpr(ref stmt) => stmt.pos,
             Stmt::StmtBreak(ref stmt) => stmt.pos,
             Stmt::StmtContinue(ref stmt) => stmt.pos,
@@ -803,6 +835,7 @@ impl Stmt {
             Stmt::StmtVar(ref stmt) => stmt.span,
             Stmt::StmtWhile(ref stmt) => stmt.span,
             Stmt::StmtFor(ref stmt) => stmt.span,
+            Stmt::StmtError(ref stmt) => stmt.span,
             Stmt::StmtExpr(ref stmt) => stmt.span,
             Stmt::StmtBreak(ref stmt) => stmt.span,
             Stmt::StmtContinue(ref stmt) => stmt.span,
@@ -880,6 +913,13 @@ impl Stmt {
         }
     }
 
+    pub fn is_error(&self) -> bool {
+        match *self {
+            Stmt::StmtError(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn to_break(&self) -> Option<&StmtBreakType> {
         match *self {
             Stmt::StmtBreak(ref val) => Some(val),
@@ -961,6 +1001,14 @@ pub struct StmtReturnType {
     pub expr: Option<Box<Expr>>,
 }
 
+// Placeholder for a statement that could not be parsed.
+#[derive(Clone, Debug)]
+pub struct StmtErrorType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug)]
 pub struct StmtBreakType {
     pub id: NodeId,
@@ -1098,6 +1146,7 @@ pub enum Expr {
     ExprBlock(ExprBlockType),
     ExprIf(ExprIfType),
     ExprTuple(ExprTupleType),
+    ExprError(ExprErrorType),
 }
 
 impl Expr {
@@ -1270,6 +1319,10 @@ impl Expr {
         Expr::ExprSuper(ExprSuperType { id, pos, span })
     }
 
+    pub fn create_error(id: NodeId, pos: Position, span: Span) -> Expr {
+        Expr::ExprError(ExprErrorType { id, pos, span })
+    }
+
     pub fn create_nil(id: NodeId, pos: Position, span: Span) -> Expr {
         Expr::ExprNil(ExprNilType { id, pos, span })
     }
@@ -1698,6 +1751,13 @@ impl Expr {
         }
     }
 
+    pub fn is_error(&self) -> bool {
+        match *self {
+            Expr::ExprError(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn needs_semicolon(&self) -> bool {
         match self {
             &Expr::ExprBlock(_) => false,
@@ -1730,6 +1790,7 @@ impl Expr {
             Expr::ExprBlock(ref val) => val.pos,
             Expr::ExprIf(ref val) => val.pos,
             Expr::ExprTuple(ref val) => val.pos,
+            Expr::ExprError(ref val) => val.pos,
         }
     }
 
@@ -1757,6 +1818,7 @@ impl Expr {
             Expr::ExprBlock(ref val) => val.span,
             Expr::ExprIf(ref val) => val.span,
             Expr::ExprTuple(ref val) => val.span,
+            Expr::ExprError(ref val) => val.span,
         }
     }
 
@@ -1784,6 +1846,7 @@ impl Expr {
             Expr::ExprBlock(ref val) => val.id,
             Expr::ExprIf(ref val) => val.id,
             Expr::ExprTuple(ref val) => val.id,
+            Expr::ExprError(ref val) => val.id,
         }
     }
 }
@@ -1931,6 +1994,14 @@ pub struct ExprSelfType {
     pub span: Span,
 }
 
+// Placeholder for an expression that could not be parsed.
+#[derive(Clone, Debug)]
+pub struct ExprErrorType {
+    pub id: NodeId,
+    pub pos: Position,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug)]
 pub struct ExprNilType {
     pub id: NodeId,
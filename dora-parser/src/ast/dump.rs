@@ -374,6 +374,7 @@ impl<'a> AstDumper<'a> {
             StmtVar(ref stmt) => self.dump_stmt_var(stmt),
             StmtWhile(ref stmt) => self.dump_stmt_while(stmt),
             StmtFor(ref stmt) => self.dump_stmt_for(stmt),
+            StmtError(ref stmt) => self.dump_stmt_error(stmt),
         }
     }
 
@@ -423,6 +424,10 @@ impl<'a> AstDumper<'a> {
         });
     }
 
+    fn dump_stmt_error(&mut self, stmt: &StmtErrorType) {
+        dump!(self, "error @ {} {}", stmt.pos, stmt.id);
+    }
+
     fn dump_stmt_while(&mut self, stmt: &StmtWhileType) {
         dump!(self, "while @ {} {}", stmt.pos, stmt.id);
 
@@ -490,6 +495,7 @@ impl<'a> AstDumper<'a> {
             ExprBlock(ref expr) => self.dump_expr_block(expr),
             ExprIf(ref expr) => self.dump_expr_if(expr),
             ExprTuple(ref expr) => self.dump_expr_tuple(expr),
+            ExprError(ref expr) => self.dump_expr_error(expr),
         }
     }
 
@@ -567,6 +573,10 @@ impl<'a> AstDumper<'a> {
         dump!(self, "nil @ {} {}", nil.pos, nil.id);
     }
 
+    fn dump_expr_error(&mut self, expr: &ExprErrorType) {
+        dump!(self, "error @ {} {}", expr.pos, expr.id);
+    }
+
     fn dump_expr_lit_char(&mut self, lit: &ExprLitCharType) {
         dump!(
             self,
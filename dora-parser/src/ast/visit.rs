@@ -262,6 +262,7 @@ pub fn walk_stmt<'v, V: Visitor<'v>>(v: &mut V, s: &'v Stmt) {
 
         StmtBreak(_) => {}
         StmtContinue(_) => {}
+        StmtError(_) => {}
     }
 }
 
@@ -365,5 +366,6 @@ pub fn walk_expr<'v, V: Visitor<'v>>(v: &mut V, e: &'v Expr) {
         ExprLitBool(_) => {}
         ExprIdent(_) => {}
         ExprNil(_) => {}
+        ExprError(_) => {}
     }
 }
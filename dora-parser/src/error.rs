@@ -21,9 +21,34 @@ pub enum ParseError {
     NumberOverflow,
     UnclosedStringTemplate,
     ExpectedIdentifier(String),
+    InvalidNumber,
 }
 
 impl ParseError {
+    // Stable identifier for each kind of error, used in machine-readable
+    // diagnostics. Codes are never reused, new variants get the next number.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnknownChar(..) => "P0001",
+            ParseError::UnclosedComment => "P0002",
+            ParseError::UnclosedString => "P0003",
+            ParseError::UnclosedChar => "P0004",
+            ParseError::InvalidEscapeSequence(..) => "P0005",
+            ParseError::ExpectedTopLevelElement(..) => "P0006",
+            ParseError::UnknownAnnotation(..) => "P0007",
+            ParseError::RedundantAnnotation(..) => "P0008",
+            ParseError::MisplacedAnnotation(..) => "P0009",
+            ParseError::ExpectedToken(..) => "P0010",
+            ParseError::ExpectedType(..) => "P0011",
+            ParseError::MisplacedElse => "P0012",
+            ParseError::ExpectedFactor(..) => "P0013",
+            ParseError::NumberOverflow => "P0014",
+            ParseError::UnclosedStringTemplate => "P0015",
+            ParseError::ExpectedIdentifier(..) => "P0016",
+            ParseError::InvalidNumber => "P0017",
+        }
+    }
+
     pub fn message(&self) -> String {
         match self {
             ParseError::UnknownChar(ch) => {
@@ -56,6 +81,7 @@ impl ParseError {
             ParseError::ExpectedIdentifier(ref tok) => {
                 format!("identifier expected but got {}.", tok)
             }
+            ParseError::InvalidNumber => "invalid number.".into(),
         }
     }
 }
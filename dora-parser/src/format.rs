@@ -0,0 +1,1324 @@
+use std::cmp::max;
+
+use crate::ast::Expr::*;
+use crate::ast::Stmt::*;
+use crate::ast::*;
+use crate::error::ParseErrorAndPos;
+use crate::format::doc::Doc;
+use crate::interner::{Interner, Name};
+use crate::lexer::position::Span;
+use crate::lexer::reader::Reader;
+use crate::parser::{NodeIdGenerator, Parser};
+
+mod doc;
+
+const MAX_WIDTH: usize = 100;
+
+// Parses the file and prints it in canonical layout. Comments are kept,
+// literals are printed as written and parentheses only where the
+// precedence requires them. Files with syntax errors are not formatted.
+pub fn format(reader: Reader) -> Result<String, Vec<ParseErrorAndPos>> {
+    let id_generator = NodeIdGenerator::new();
+    let mut interner = Interner::new();
+    let mut ast = Ast::new();
+
+    let parser = Parser::new(reader, &id_generator, &mut ast, &mut interner);
+    let (file, errors) = parser.parse();
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut formatter = Formatter {
+        interner: &interner,
+        content: &file.content,
+        comments: &file.comments,
+        next_comment: 0,
+        last: 0,
+    };
+
+    let doc = formatter.file(ast.files.last().unwrap());
+    Ok(doc::render(&doc, MAX_WIDTH))
+}
+
+// Statements and members, printed one per line.
+enum Member<'b> {
+    Elem(&'b Elem),
+    Fct(&'b Function),
+    Field(&'b Field),
+    // the flag forces a semicolon after an if or block
+    Stmt(&'b Stmt, bool),
+    Expr(&'b Expr),
+}
+
+impl<'b> Member<'b> {
+    fn span(&self) -> Span {
+        match *self {
+            Member::Elem(elem) => elem.span(),
+            Member::Fct(fct) => fct.span,
+            Member::Field(field) => field.span,
+            Member::Stmt(stmt, _) => stmt.span(),
+            Member::Expr(expr) => expr.span(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Layout {
+    // `(a, b)`
+    Compact,
+    // `{ a, b }`
+    Spaced,
+    // one entry per line
+    Vertical,
+}
+
+struct Formatter<'a> {
+    interner: &'a Interner,
+    content: &'a str,
+    comments: &'a [Span],
+    next_comment: usize,
+    // end of the source text printed so far
+    last: u32,
+}
+
+impl<'a> Formatter<'a> {
+    fn file(&mut self, file: &File) -> Doc {
+        let members = file.elements.iter().map(Member::Elem).collect();
+        self.lines(members, self.content.len() as u32)
+    }
+
+    fn member(&mut self, member: &Member) -> Doc {
+        match *member {
+            Member::Elem(elem) => self.elem(elem),
+            Member::Fct(fct) => self.function(fct),
+            Member::Field(field) => self.field(field),
+            Member::Stmt(stmt, semicolon) => self.stmt(stmt, semicolon),
+            Member::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    fn elem(&mut self, elem: &Elem) -> Doc {
+        match *elem {
+            Elem::ElemFunction(ref fct) => self.function(fct),
+            Elem::ElemClass(ref cls) => self.class(cls),
+            Elem::ElemStruct(ref struc) => self.struc(struc),
+            Elem::ElemTrait(ref xtrait) => self.xtrait(xtrait),
+            Elem::ElemImpl(ref ximpl) => self.ximpl(ximpl),
+            Elem::ElemModule(ref module) => self.module(module),
+            Elem::ElemGlobal(ref global) => self.global(global),
+            Elem::ElemConst(ref xconst) => self.xconst(xconst),
+            Elem::ElemEnum(ref xenum) => self.xenum(xenum),
+            Elem::ElemAlias(ref alias) => self.alias(alias),
+        }
+    }
+
+    fn function(&mut self, fct: &Function) -> Doc {
+        let annotations = [
+            (fct.internal, "internal"),
+            (fct.use_cannon, "cannon"),
+            (fct.has_optimize, "optimize"),
+            (fct.has_optimize_immediately, "optimize_immediately"),
+            (fct.is_test, "test"),
+            (fct.is_pub, "pub"),
+            (fct.is_static, "static"),
+            (fct.has_open, "open"),
+            (fct.is_abstract, "abstract"),
+            (fct.has_override, "override"),
+            (fct.has_final, "final"),
+        ];
+
+        let mut parts = vec![
+            annotations_doc(&annotations),
+            Doc::text("fun "),
+            self.name(fct.name),
+            self.type_params(&fct.type_params),
+            self.list(
+                ("(", ")"),
+                &fct.params,
+                None,
+                Layout::Compact,
+                |param| param.span,
+                |f, param| f.param(param),
+            ),
+        ];
+
+        if let Some(ref ret) = fct.return_type {
+            parts.push(Doc::text(" -> "));
+            parts.push(self.ty(ret));
+        }
+
+        match fct.block {
+            None => parts.push(Doc::text(";")),
+
+            Some(ref block) => {
+                if let Some(body) = expression_body(block) {
+                    parts.push(Doc::text(" = "));
+
+                    match body {
+                        Member::Stmt(stmt, _) => parts.push(self.stmt(stmt, false)),
+
+                        Member::Expr(expr) => {
+                            parts.push(self.expr(expr));
+                            parts.push(Doc::text(";"));
+                        }
+
+                        _ => unreachable!(),
+                    }
+                } else {
+                    parts.push(Doc::text(" "));
+                    parts.push(self.block(block, false));
+                }
+            }
+        }
+
+        Doc::concat(parts)
+    }
+
+    fn param(&mut self, param: &Param) -> Doc {
+        Doc::concat(vec![
+            self.name(param.name),
+            Doc::text(": "),
+            self.ty(&param.data_type),
+            Doc::text(if param.variadic { "..." } else { "" }),
+        ])
+    }
+
+    fn class(&mut self, cls: &Class) -> Doc {
+        // The parser moves the primary constructor, field initializers and
+        // statements of the class body into a generated constructor.
+        let ctor = cls.constructor.as_ref().unwrap();
+        let primary_fields = cls.fields.iter().filter(|field| field.primary_ctor);
+        let generated = cls.parent_class.iter().count()
+            + primary_fields.count()
+            + cls
+                .fields
+                .iter()
+                .filter(|field| field.expr.is_some())
+                .count();
+
+        let annotations = [
+            (cls.internal, "internal"),
+            (ctor.use_cannon, "cannon"),
+            (cls.has_open, "open"),
+            (cls.is_abstract, "abstract"),
+        ];
+
+        let mut parts = vec![
+            annotations_doc(&annotations),
+            Doc::text("class "),
+            self.name(cls.name),
+            self.type_params(&cls.type_params),
+        ];
+
+        if cls.has_constructor {
+            let params = ctor
+                .params
+                .iter()
+                .map(|param| {
+                    let field = cls
+                        .fields
+                        .iter()
+                        .find(|field| field.primary_ctor && field.name == param.name);
+
+                    (param, field)
+                })
+                .collect::<Vec<_>>();
+
+            parts.push(self.list(
+                ("(", ")"),
+                &params,
+                None,
+                Layout::Compact,
+                |_| Span::invalid(),
+                |f, &(param, field)| {
+                    let keyword = match field {
+                        Some(field) if field.reassignable => "var ",
+                        Some(_) => "let ",
+                        None => "",
+                    };
+
+                    Doc::concat(vec![Doc::text(keyword), f.param(param)])
+                },
+            ));
+        }
+
+        if let Some(ref parent_class) = cls.parent_class {
+            parts.push(self.parent_class(parent_class));
+        }
+
+        let mut members = Vec::new();
+        members.extend(
+            cls.fields
+                .iter()
+                .filter(|field| !field.primary_ctor)
+                .map(Member::Field),
+        );
+        members.extend(cls.methods.iter().map(Member::Fct));
+        members.extend(
+            ctor.block().stmts[generated..]
+                .iter()
+                .map(|stmt| Member::Stmt(stmt, false)),
+        );
+        members.sort_by_key(|member| member.span().start());
+
+        parts.push(self.optional_body(members, cls.span));
+
+        Doc::concat(parts)
+    }
+
+    fn module(&mut self, module: &Module) -> Doc {
+        let mut parts = vec![
+            annotations_doc(&[(module.internal, "internal")]),
+            Doc::text("module "),
+            self.name(module.name),
+        ];
+
+        if let Some(ref parent_class) = module.parent_class {
+            parts.push(self.parent_class(parent_class));
+        }
+
+        let mut members = Vec::new();
+        members.extend(module.fields.iter().map(Member::Field));
+        members.extend(module.methods.iter().map(Member::Fct));
+        members.extend(
+            module
+                .initializers
+                .iter()
+                .map(|stmt| Member::Stmt(stmt, false)),
+        );
+        members.sort_by_key(|member| member.span().start());
+
+        parts.push(self.optional_body(members, module.span));
+
+        Doc::concat(parts)
+    }
+
+    fn parent_class(&mut self, parent_class: &ParentClass) -> Doc {
+        let mut parts = vec![
+            Doc::text(": "),
+            self.name(parent_class.name),
+            self.type_args(&parent_class.type_params),
+        ];
+
+        if !parent_class.params.is_empty() {
+            parts.push(self.args(&parent_class.params, parent_class.span.end() - 1));
+        }
+
+        Doc::concat(parts)
+    }
+
+    // Classes and modules without members are written without braces.
+    fn optional_body(&mut self, members: Vec<Member>, span: Span) -> Doc {
+        let has_braces = self.content[..span.end() as usize].ends_with('}');
+        let end = span.end() - 1;
+
+        if members.is_empty() && !(has_braces && self.has_comment_before(end)) {
+            return Doc::Nil;
+        }
+
+        Doc::concat(vec![Doc::text(" "), self.body(members, end)])
+    }
+
+    fn field(&mut self, field: &Field) -> Doc {
+        let mut parts = vec![
+            Doc::text(if field.reassignable { "var " } else { "let " }),
+            self.name(field.name),
+            Doc::text(": "),
+            self.ty(&field.data_type),
+        ];
+
+        if let Some(ref expr) = field.expr {
+            parts.push(Doc::text(" = "));
+            parts.push(self.expr(expr));
+        }
+
+        parts.push(Doc::text(";"));
+
+        Doc::concat(parts)
+    }
+
+    fn struc(&mut self, struc: &Struct) -> Doc {
+        Doc::concat(vec![
+            Doc::text("struct "),
+            self.name(struc.name),
+            Doc::text(" "),
+            self.list(
+                ("{", "}"),
+                &struc.fields,
+                Some(struc.span.end() - 1),
+                Layout::Vertical,
+                |field| field.span,
+                |f, field| {
+                    Doc::concat(vec![
+                        f.name(field.name),
+                        Doc::text(": "),
+                        f.ty(&field.data_type),
+                    ])
+                },
+            ),
+        ])
+    }
+
+    fn xtrait(&mut self, xtrait: &Trait) -> Doc {
+        let members = xtrait.methods.iter().map(Member::Fct).collect();
+
+        Doc::concat(vec![
+            Doc::text("trait "),
+            self.name(xtrait.name),
+            Doc::text(" "),
+            self.body(members, xtrait.span.end() - 1),
+        ])
+    }
+
+    fn ximpl(&mut self, ximpl: &Impl) -> Doc {
+        let mut parts = vec![Doc::text("impl"), self.type_params(&ximpl.type_params)];
+        parts.push(Doc::text(" "));
+
+        if let Some(ref trait_type) = ximpl.trait_type {
+            parts.push(self.ty(trait_type));
+            parts.push(Doc::text(" for "));
+        }
+
+        parts.push(self.ty(&ximpl.class_type));
+        parts.push(Doc::text(" "));
+
+        let members = ximpl.methods.iter().map(Member::Fct).collect();
+        parts.push(self.body(members, ximpl.span.end() - 1));
+
+        Doc::concat(parts)
+    }
+
+    fn global(&mut self, global: &Global) -> Doc {
+        let mut parts = vec![
+            Doc::text(if global.reassignable { "var " } else { "let " }),
+            self.name(global.name),
+            Doc::text(": "),
+            self.ty(&global.data_type),
+        ];
+
+        // the parser wraps the initial value into `name = value;`
+        if let Some(ref initializer) = global.initializer {
+            let stmt = initializer.block().stmts[0].to_expr().unwrap();
+            let assignment = stmt.expr.to_bin().unwrap();
+
+            parts.push(Doc::text(" = "));
+            parts.push(self.expr(&assignment.rhs));
+        }
+
+        parts.push(Doc::text(";"));
+
+        Doc::concat(parts)
+    }
+
+    fn xconst(&mut self, xconst: &Const) -> Doc {
+        Doc::concat(vec![
+            Doc::text("const "),
+            self.name(xconst.name),
+            Doc::text(": "),
+            self.ty(&xconst.data_type),
+            Doc::text(" = "),
+            self.expr(&xconst.expr),
+            Doc::text(";"),
+        ])
+    }
+
+    fn xenum(&mut self, xenum: &Enum) -> Doc {
+        Doc::concat(vec![
+            Doc::text("enum "),
+            self.name(xenum.name),
+            self.type_params(&xenum.type_params),
+            Doc::text(" "),
+            self.list(
+                ("{", "}"),
+                &xenum.variants,
+                Some(xenum.span.end() - 1),
+                Layout::Spaced,
+                |variant| variant.span,
+                |f, variant| {
+                    let mut parts = vec![f.name(variant.name)];
+
+                    if let Some(ref types) = variant.types {
+                        let types = types.iter().map(|ty| f.ty(ty)).collect();
+                        parts.push(Doc::text("("));
+                        parts.push(join(types, ", "));
+                        parts.push(Doc::text(")"));
+                    }
+
+                    Doc::concat(parts)
+                },
+            ),
+        ])
+    }
+
+    fn alias(&mut self, alias: &Alias) -> Doc {
+        Doc::concat(vec![
+            Doc::text("alias "),
+            self.name(alias.name),
+            Doc::text(" = "),
+            self.ty(&alias.ty),
+            Doc::text(";"),
+        ])
+    }
+
+    fn type_params(&mut self, type_params: &Option<Vec<TypeParam>>) -> Doc {
+        let type_params = match type_params {
+            Some(type_params) => type_params,
+            None => return Doc::Nil,
+        };
+
+        let params = type_params
+            .iter()
+            .map(|param| {
+                let mut parts = vec![self.name(param.name)];
+
+                if !param.bounds.is_empty() {
+                    let bounds = param.bounds.iter().map(|bound| self.ty(bound)).collect();
+                    parts.push(Doc::text(": "));
+                    parts.push(join(bounds, " + "));
+                }
+
+                Doc::concat(parts)
+            })
+            .collect();
+
+        Doc::concat(vec![Doc::text("["), join(params, ", "), Doc::text("]")])
+    }
+
+    fn type_args(&self, types: &[Type]) -> Doc {
+        if types.is_empty() {
+            return Doc::Nil;
+        }
+
+        let types = types.iter().map(|ty| self.ty(ty)).collect();
+        Doc::concat(vec![Doc::text("["), join(types, ", "), Doc::text("]")])
+    }
+
+    fn ty(&self, ty: &Type) -> Doc {
+        match *ty {
+            Type::TypeSelf(_) => Doc::text("Self"),
+
+            Type::TypeBasic(ref basic) => {
+                let params = basic
+                    .params
+                    .iter()
+                    .map(|ty| self.ty(ty))
+                    .collect::<Vec<_>>();
+
+                if params.is_empty() {
+                    self.name(basic.name)
+                } else {
+                    Doc::concat(vec![
+                        self.name(basic.name),
+                        Doc::text("["),
+                        join(params, ", "),
+                        Doc::text("]"),
+                    ])
+                }
+            }
+
+            Type::TypeTuple(ref tuple) => {
+                let types = tuple.subtypes.iter().map(|ty| self.ty(ty)).collect();
+                Doc::concat(vec![Doc::text("("), join(types, ", "), Doc::text(")")])
+            }
+
+            Type::TypeLambda(ref lambda) => {
+                let params = lambda.params.iter().map(|ty| self.ty(ty)).collect();
+
+                Doc::concat(vec![
+                    Doc::text("("),
+                    join(params, ", "),
+                    Doc::text(") -> "),
+                    self.ty(&lambda.ret),
+                ])
+            }
+        }
+    }
+
+    // Prints a braced body with one member per line, even if it is empty
+    // apart from comments. `end` is the offset of the closing brace.
+    fn body(&mut self, members: Vec<Member>, end: u32) -> Doc {
+        if members.is_empty() && !self.has_comment_before(end) {
+            self.last = end + 1;
+            return Doc::text("{}");
+        }
+
+        let lines = self.lines(members, end);
+        self.last = end + 1;
+
+        Doc::concat(vec![
+            Doc::text("{"),
+            Doc::nest(Doc::concat(vec![Doc::HardLine, lines])),
+            Doc::HardLine,
+            Doc::text("}"),
+        ])
+    }
+
+    // Prints members one per line together with all comments before `end`.
+    // A single blank line is kept wherever the source has at least one.
+    fn lines(&mut self, members: Vec<Member>, end: u32) -> Doc {
+        let mut parts = Vec::new();
+        let mut first = true;
+
+        for member in &members {
+            let span = member.span();
+            self.comment_lines(span.start(), &mut parts, &mut first);
+
+            if !first {
+                parts.push(Doc::HardLine);
+
+                if self.has_blank_line(self.last, span.start()) {
+                    parts.push(Doc::HardLine);
+                }
+            }
+
+            first = false;
+            parts.push(self.member(member));
+            self.last = max(self.last, span.end());
+            self.trailing_comments(&mut parts);
+        }
+
+        self.comment_lines(end, &mut parts, &mut first);
+
+        Doc::concat(parts)
+    }
+
+    fn comment_lines(&mut self, end: u32, parts: &mut Vec<Doc>, first: &mut bool) {
+        while let Some(comment) = self.take_comment_before(end) {
+            if !*first {
+                parts.push(Doc::HardLine);
+
+                if self.has_blank_line(self.last, comment.start()) {
+                    parts.push(Doc::HardLine);
+                }
+            }
+
+            *first = false;
+            parts.push(Doc::text(self.source(comment)));
+            self.last = comment.end();
+        }
+    }
+
+    // Appends comments on the same line as the end of the last printed
+    // construct. Comments from within it that were not placed anywhere else
+    // end up here as well.
+    fn trailing_comments(&mut self, parts: &mut Vec<Doc>) {
+        while let Some(&comment) = self.comments.get(self.next_comment) {
+            if comment.start() >= self.last {
+                let between = &self.content[self.last as usize..comment.start() as usize];
+
+                if !between
+                    .chars()
+                    .all(|ch| ch == ' ' || ch == '\t' || ch == ',' || ch == ';')
+                {
+                    break;
+                }
+            }
+
+            let text = self.source(comment);
+            parts.push(Doc::text(" "));
+            parts.push(Doc::text(text));
+            parts.push(Doc::BreakParent);
+
+            self.next_comment += 1;
+            self.last = max(self.last, comment.end());
+
+            // anything after a line comment would become part of it
+            if text.starts_with("//") {
+                break;
+            }
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        (open, close): (&str, &str),
+        items: &[T],
+        end: Option<u32>,
+        layout: Layout,
+        span: fn(&T) -> Span,
+        print: fn(&mut Formatter<'a>, &T) -> Doc,
+    ) -> Doc {
+        let has_comments = end.map_or(false, |end| self.has_comment_before(end));
+
+        if items.is_empty() && !has_comments {
+            return Doc::text(format!("{}{}", open, close));
+        }
+
+        let line = if layout == Layout::Compact {
+            Doc::SoftLine
+        } else {
+            Doc::Line
+        };
+
+        let mut inner = vec![line.clone()];
+
+        if layout == Layout::Vertical {
+            inner.push(Doc::BreakParent);
+        }
+
+        for (idx, item) in items.iter().enumerate() {
+            let span = span(item);
+            let last = idx + 1 == items.len();
+
+            if span.is_valid() {
+                while let Some(comment) = self.take_comment_before(span.start()) {
+                    inner.push(Doc::text(self.source(comment)));
+                    inner.push(Doc::HardLine);
+                }
+            }
+
+            inner.push(print(self, item));
+
+            if last {
+                inner.push(Doc::if_break(Doc::text(","), Doc::Nil));
+            } else {
+                inner.push(Doc::text(","));
+            }
+
+            if span.is_valid() {
+                self.last = max(self.last, span.end());
+                self.trailing_comments(&mut inner);
+            }
+
+            if !last {
+                inner.push(Doc::Line);
+            }
+        }
+
+        if let Some(end) = end {
+            while let Some(comment) = self.take_comment_before(end) {
+                if !items.is_empty() {
+                    inner.push(Doc::HardLine);
+                }
+
+                inner.push(Doc::text(self.source(comment)));
+                inner.push(Doc::BreakParent);
+            }
+        }
+
+        Doc::group(Doc::concat(vec![
+            Doc::text(open),
+            Doc::nest(Doc::concat(inner)),
+            line,
+            Doc::text(close),
+        ]))
+    }
+
+    fn args(&mut self, args: &[Box<Expr>], end: u32) -> Doc {
+        self.list(
+            ("(", ")"),
+            args,
+            Some(end),
+            Layout::Compact,
+            |arg| arg.span(),
+            |f, arg| f.expr(arg),
+        )
+    }
+
+    // Prints a block, with `inline` a block with only a value may be
+    // printed on a single line if the enclosing group fits.
+    fn block(&mut self, block: &ExprBlockType, inline: bool) -> Doc {
+        let end = block.span.end() - 1;
+
+        if inline && is_value_block(block) && !self.has_comment_in(block.span) {
+            let expr = self.expr(block.expr.as_ref().unwrap());
+            self.last = block.span.end();
+
+            return Doc::concat(vec![
+                Doc::text("{"),
+                Doc::nest(Doc::concat(vec![Doc::Line, expr])),
+                Doc::Line,
+                Doc::text("}"),
+            ]);
+        }
+
+        let mut members = Vec::new();
+
+        for (idx, stmt) in block.stmts.iter().enumerate() {
+            // without semicolon the last statement would become the value
+            let last = idx + 1 == block.stmts.len() && block.expr.is_none();
+            members.push(Member::Stmt(stmt, last));
+        }
+
+        if let Some(ref expr) = block.expr {
+            members.push(Member::Expr(expr));
+        }
+
+        self.body(members, end)
+    }
+
+    fn stmt_block(&mut self, stmt: &Stmt) -> Doc {
+        let block = stmt.to_expr().unwrap().expr.to_block().unwrap();
+        self.block(block, false)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, semicolon: bool) -> Doc {
+        match *stmt {
+            StmtVar(ref var) => {
+                let mut parts = vec![
+                    Doc::text(if var.reassignable { "var " } else { "let " }),
+                    self.name(var.name),
+                ];
+
+                if let Some(ref data_type) = var.data_type {
+                    parts.push(Doc::text(": "));
+                    parts.push(self.ty(data_type));
+                }
+
+                if let Some(ref expr) = var.expr {
+                    parts.push(Doc::text(" = "));
+                    parts.push(self.expr(expr));
+                }
+
+                parts.push(Doc::text(";"));
+
+                Doc::concat(parts)
+            }
+
+            StmtWhile(ref xwhile) => Doc::concat(vec![
+                Doc::text("while "),
+                self.expr(&xwhile.cond),
+                Doc::text(" "),
+                self.stmt_block(&xwhile.block),
+            ]),
+
+            StmtFor(ref xfor) => Doc::concat(vec![
+                Doc::text("for "),
+                self.name(xfor.name),
+                Doc::text(" in "),
+                self.expr(&xfor.expr),
+                Doc::text(" "),
+                self.stmt_block(&xfor.block),
+            ]),
+
+            StmtExpr(ref stmt) => {
+                let expr = self.expr(&stmt.expr);
+
+                if semicolon || stmt.expr.needs_semicolon() {
+                    Doc::concat(vec![expr, Doc::text(";")])
+                } else {
+                    expr
+                }
+            }
+
+            StmtBreak(_) => Doc::text("break;"),
+
+            StmtContinue(_) => Doc::text("continue;"),
+
+            StmtReturn(ref ret) => match ret.expr {
+                Some(ref expr) => {
+                    Doc::concat(vec![Doc::text("return "), self.expr(expr), Doc::text(";")])
+                }
+
+                None => Doc::text("return;"),
+            },
+
+            StmtError(_) => unreachable!("files with syntax errors are not formatted"),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Doc {
+        match *expr {
+            ExprUn(ref un) => Doc::concat(vec![
+                Doc::text(un.op.as_str()),
+                self.operand(&un.opnd, PREC_UNARY + 1, false),
+            ]),
+
+            ExprBin(ref bin) => self.binary(bin),
+
+            ExprLitChar(ref lit) => Doc::text(self.source(lit.span)),
+            ExprLitInt(ref lit) => Doc::text(self.source(lit.span)),
+            ExprLitFloat(ref lit) => Doc::text(self.source(lit.span)),
+            ExprLitStr(ref lit) => Doc::text(self.source(lit.span)),
+
+            ExprTemplate(ref template) => {
+                let mut parts = Vec::new();
+
+                // string parts are printed as written, they include the
+                // opening `${` but not the closing `}`
+                for (idx, part) in template.parts.iter().enumerate() {
+                    if idx % 2 == 1 {
+                        parts.push(self.expr(part));
+                        parts.push(Doc::text("}"));
+                    } else {
+                        parts.push(Doc::text(self.source(part.span())));
+                    }
+                }
+
+                Doc::concat(parts)
+            }
+
+            ExprLitBool(ref lit) => Doc::text(if lit.value { "true" } else { "false" }),
+
+            ExprIdent(ref ident) => self.name(ident.name),
+
+            ExprCall(ref call) => Doc::concat(vec![
+                self.postfix_operand(&call.callee),
+                self.args(&call.args, call.span.end() - 1),
+            ]),
+
+            ExprTypeParam(ref type_param) => Doc::concat(vec![
+                self.postfix_operand(&type_param.callee),
+                self.type_args(&type_param.args),
+            ]),
+
+            ExprPath(ref path) => Doc::concat(vec![
+                self.postfix_operand(&path.lhs),
+                Doc::text("::"),
+                self.expr(&path.rhs),
+            ]),
+
+            ExprDot(ref dot) => {
+                // `(x.0).1` without parentheses would be lexed as `x` and `.0.1`
+                let tuple_index = dot.lhs.to_dot().map_or(false, |lhs| lhs.rhs.is_lit_int());
+
+                let lhs = if tuple_index {
+                    self.parenthesized(&dot.lhs)
+                } else {
+                    self.postfix_operand(&dot.lhs)
+                };
+
+                Doc::concat(vec![lhs, Doc::text("."), self.expr(&dot.rhs)])
+            }
+
+            ExprDelegation(_) => unreachable!("delegations are generated by the parser"),
+
+            ExprSelf(_) => Doc::text("self"),
+            ExprSuper(_) => Doc::text("super"),
+            ExprNil(_) => Doc::text("nil"),
+
+            ExprConv(ref conv) => Doc::concat(vec![
+                self.operand(&conv.object, PREC_CONV, true),
+                Doc::text(if conv.is { " is " } else { " as " }),
+                self.ty(&conv.data_type),
+            ]),
+
+            ExprLambda(ref lambda) => {
+                let params = lambda
+                    .params
+                    .iter()
+                    .map(|param| self.param(param))
+                    .collect::<Vec<_>>();
+
+                let mut parts = if params.is_empty() {
+                    vec![Doc::text("||")]
+                } else {
+                    vec![Doc::text("|"), join(params, ", "), Doc::text("|")]
+                };
+
+                if let Some(ref ret) = lambda.ret {
+                    parts.push(Doc::text(" -> "));
+                    parts.push(self.ty(ret));
+                }
+
+                let block = lambda.block.to_expr().unwrap().expr.to_block().unwrap();
+                parts.push(Doc::text(" "));
+                parts.push(self.block(block, true));
+
+                Doc::group(Doc::concat(parts))
+            }
+
+            ExprBlock(ref block) => self.block(block, false),
+
+            ExprIf(ref xif) => self.xif(xif),
+
+            ExprTuple(ref tuple) => {
+                if tuple.values.len() == 1 {
+                    Doc::concat(vec![
+                        Doc::text("("),
+                        self.expr(&tuple.values[0]),
+                        Doc::text(",)"),
+                    ])
+                } else {
+                    self.args(&tuple.values, tuple.span.end() - 1)
+                }
+            }
+
+            ExprError(_) => unreachable!("files with syntax errors are not formatted"),
+        }
+    }
+
+    // Chains of operators with the same precedence are broken before each
+    // operator.
+    fn binary(&mut self, bin: &ExprBinType) -> Doc {
+        let precedence = bin_precedence(bin.op);
+
+        if bin.op == BinOp::Assign {
+            return Doc::concat(vec![
+                self.operand(&bin.lhs, precedence, true),
+                Doc::text(" = "),
+                self.operand(&bin.rhs, precedence + 1, false),
+            ]);
+        }
+
+        let mut chain = vec![bin];
+
+        while let ExprBin(ref lhs) = *chain.last().unwrap().lhs {
+            if bin_precedence(lhs.op) != precedence {
+                break;
+            }
+
+            chain.push(lhs);
+        }
+
+        // Bit operators bind weaker than arithmetic which is easy to misread,
+        // so binary operands of them always keep their parentheses.
+        let operand_precedence = if is_bit_precedence(precedence) {
+            PREC_CONV
+        } else {
+            precedence
+        };
+
+        let first = self.operand(&chain.last().unwrap().lhs, operand_precedence, true);
+        let mut rest = Vec::new();
+
+        for bin in chain.iter().rev() {
+            rest.push(Doc::Line);
+            rest.push(Doc::text(format!("{} ", bin.op.as_str())));
+            rest.push(self.operand(&bin.rhs, operand_precedence.max(precedence + 1), false));
+        }
+
+        Doc::group(Doc::concat(vec![first, Doc::nest(Doc::concat(rest))]))
+    }
+
+    // Parenthesizes the operand if it binds weaker than `precedence`. An if
+    // or block at the start of an expression would end the expression.
+    fn operand(&mut self, expr: &Expr, precedence: u32, leftmost: bool) -> Doc {
+        let block_like = expr.is_if() || expr.is_block();
+
+        if expr_precedence(expr) < precedence || (leftmost && block_like) {
+            self.parenthesized(expr)
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    fn postfix_operand(&mut self, expr: &Expr) -> Doc {
+        if expr_precedence(expr) < PREC_PRIMARY || expr.is_lambda() {
+            self.parenthesized(expr)
+        } else {
+            self.operand(expr, PREC_PRIMARY, true)
+        }
+    }
+
+    fn parenthesized(&mut self, expr: &Expr) -> Doc {
+        Doc::concat(vec![Doc::text("("), self.expr(expr), Doc::text(")")])
+    }
+
+    fn xif(&mut self, xif: &ExprIfType) -> Doc {
+        let inline = is_inline_if(xif) && !self.has_comment_in(xif.span);
+        let mut parts = Vec::new();
+        let mut current = xif;
+
+        loop {
+            parts.push(Doc::text("if "));
+            parts.push(self.expr(&current.cond));
+            parts.push(Doc::text(" "));
+            parts.push(self.block(current.then_block.to_block().unwrap(), inline));
+
+            match current.else_block {
+                Some(ref else_block) => {
+                    parts.push(Doc::text(" else "));
+
+                    match **else_block {
+                        ExprIf(ref else_if) => current = else_if,
+
+                        ExprBlock(ref block) => {
+                            parts.push(self.block(block, inline));
+                            break;
+                        }
+
+                        _ => unreachable!(),
+                    }
+                }
+
+                None => break,
+            }
+        }
+
+        if inline {
+            Doc::group(Doc::concat(parts))
+        } else {
+            Doc::concat(parts)
+        }
+    }
+
+    fn name(&self, name: Name) -> Doc {
+        Doc::text(self.interner.str(name).to_string())
+    }
+
+    fn source(&self, span: Span) -> &'a str {
+        &self.content[span.start() as usize..span.end() as usize]
+    }
+
+    fn take_comment_before(&mut self, end: u32) -> Option<Span> {
+        let comment = *self.comments.get(self.next_comment)?;
+
+        if comment.start() < end {
+            self.next_comment += 1;
+            Some(comment)
+        } else {
+            None
+        }
+    }
+
+    fn has_comment_before(&self, end: u32) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .map_or(false, |comment| comment.start() < end)
+    }
+
+    fn has_comment_in(&self, span: Span) -> bool {
+        self.comments[self.next_comment..]
+            .iter()
+            .skip_while(|comment| comment.start() < span.start())
+            .next()
+            .map_or(false, |comment| comment.start() < span.end())
+    }
+
+    fn has_blank_line(&self, start: u32, end: u32) -> bool {
+        if start >= end {
+            return false;
+        }
+
+        let text = &self.content[start as usize..end as usize];
+        let lines = text.split('\n').collect::<Vec<_>>();
+
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|line| line.trim().is_empty())
+    }
+}
+
+const PREC_CONV: u32 = 10;
+const PREC_UNARY: u32 = 11;
+const PREC_PRIMARY: u32 = 12;
+
+// Same precedences as in the parser.
+fn bin_precedence(op: BinOp) -> u32 {
+    match op {
+        BinOp::Or => 1,
+        BinOp::And => 2,
+        BinOp::Assign => 3,
+        BinOp::Cmp(CmpOp::Is) | BinOp::Cmp(CmpOp::IsNot) => 5,
+        BinOp::Cmp(_) => 4,
+        BinOp::BitOr | BinOp::BitAnd | BinOp::BitXor => 6,
+        BinOp::ShiftL | BinOp::ArithShiftR | BinOp::LogicalShiftR => 7,
+        BinOp::Add | BinOp::Sub => 8,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 9,
+    }
+}
+
+fn is_bit_precedence(precedence: u32) -> bool {
+    precedence == bin_precedence(BinOp::BitOr) || precedence == bin_precedence(BinOp::ShiftL)
+}
+
+fn expr_precedence(expr: &Expr) -> u32 {
+    match *expr {
+        ExprBin(ref bin) => bin_precedence(bin.op),
+        ExprConv(_) => PREC_CONV,
+        ExprUn(_) => PREC_UNARY,
+        _ => PREC_PRIMARY,
+    }
+}
+
+fn annotations_doc(annotations: &[(bool, &str)]) -> Doc {
+    let annotations = annotations
+        .iter()
+        .filter(|&&(present, _)| present)
+        .map(|&(_, name)| Doc::text(format!("@{} ", name)))
+        .collect();
+
+    Doc::concat(annotations)
+}
+
+fn join(docs: Vec<Doc>, separator: &str) -> Doc {
+    let mut parts = Vec::new();
+
+    for (idx, doc) in docs.into_iter().enumerate() {
+        if idx > 0 {
+            parts.push(Doc::text(separator));
+        }
+
+        parts.push(doc);
+    }
+
+    Doc::concat(parts)
+}
+
+// Returns the body of functions written as `fun f() = value;`, for those
+// the block has the span of its only statement or value.
+fn expression_body<'b>(block: &'b ExprBlockType) -> Option<Member<'b>> {
+    let same_span =
+        |span: Span| span.start() == block.span.start() && span.end() == block.span.end();
+
+    match (block.stmts.len(), &block.expr) {
+        (0, Some(expr)) if same_span(expr.span()) => Some(Member::Expr(expr)),
+        (1, None) if block.stmts[0].is_return() && same_span(block.stmts[0].span()) => {
+            Some(Member::Stmt(&block.stmts[0], false))
+        }
+        _ => None,
+    }
+}
+
+fn is_value_block(block: &ExprBlockType) -> bool {
+    block.stmts.is_empty() && block.expr.is_some()
+}
+
+// An if with else whose branches only have values may go on a single line.
+fn is_inline_if(xif: &ExprIfType) -> bool {
+    if !is_value_block(xif.then_block.to_block().unwrap()) {
+        return false;
+    }
+
+    match xif.else_block {
+        Some(ref else_block) => match **else_block {
+            ExprIf(ref else_if) => is_inline_if(else_if),
+            ExprBlock(ref block) => is_value_block(block),
+            _ => false,
+        },
+
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    fn fmt(code: &str) -> String {
+        format(Reader::from_string("<<code>>", code)).unwrap()
+    }
+
+    fn assert_fmt(code: &str, expected: &str) {
+        let formatted = fmt(code);
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, fmt(&formatted));
+    }
+
+    #[test]
+    fn test_spacing_and_indentation() {
+        assert_fmt(
+            "fun  main( )->Int32{let x:Int32=1+2*3;\nif x>2{return x;}else{ x }}",
+            "fun main() -> Int32 {\n    let x: Int32 = 1 + 2 * 3;\n    if x > 2 {\n        return x;\n    } else {\n        x\n    }\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_parentheses() {
+        assert_fmt(
+            "fun f() { let a = ((1 + 2)) * 3; let b = 1 - (2 - 3); let c = -(a.b); let d = (-a).b; let e = (a + 1) & (b << 2) | c; }",
+            "fun f() {\n    let a = (1 + 2) * 3;\n    let b = 1 - (2 - 3);\n    let c = -a.b;\n    let d = (-a).b;\n    let e = (a + 1) & (b << 2) | c;\n}\n",
+        );
+        assert_fmt(
+            "fun f() { (if a { 1 } else { 2 }) + 1; let x = (a as Foo).b; }",
+            "fun f() {\n    (if a { 1 } else { 2 }) + 1;\n    let x = (a as Foo).b;\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_fmt(
+            "// header\n\nfun main() { // start\n  foo(); // call\n\n\n  /* block */\n  bar();\n  // end\n}\n",
+            "// header\n\nfun main() {\n    // start\n    foo(); // call\n\n    /* block */\n    bar();\n    // end\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_line_breaking() {
+        assert_fmt(
+            "fun f() { foo(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccccccccc, dddddddddddddddddd); }",
+            "fun f() {\n    foo(\n        aaaaaaaaaaaaaaaaaaaa,\n        bbbbbbbbbbbbbbbbbbbbbbbbbb,\n        cccccccccccccccccccccccccccc,\n        dddddddddddddddddd,\n    );\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_elements() {
+        assert_fmt(
+            "@open @abstract class A[T: Foo + Bar](let a: Int32, b: T): B(a) { var x: Int32 = 1; @override fun f() = 1; }\nclass C\nstruct S { a: Int32 }\nenum E { X, Y(Int32) }\nlet g: Int32 = 1;\nconst C: Int32 = 2;\nalias X = Int32;\ntrait T { fun f(); }\nimpl[T] Foo for Bar[T] {}",
+            "@open @abstract class A[T: Foo + Bar](let a: Int32, b: T): B(a) {\n    var x: Int32 = 1;\n    @override fun f() = 1;\n}\nclass C\nstruct S {\n    a: Int32,\n}\nenum E { X, Y(Int32) }\nlet g: Int32 = 1;\nconst C: Int32 = 2;\nalias X = Int32;\ntrait T {\n    fun f();\n}\nimpl[T] Foo for Bar[T] {}\n",
+        );
+    }
+
+    #[test]
+    fn test_literals_and_templates() {
+        assert_fmt(
+            "fun f() { let x = \"a${ 1+2 }b${x}\"; let y = 0x1F + 1_000L + 1.5e3F; let z = '\\n'; g(|a: Int32| -> Int32 { a }, ||{}); }",
+            "fun f() {\n    let x = \"a${1 + 2}b${x}\";\n    let y = 0x1F + 1_000L + 1.5e3F;\n    let z = '\\n';\n    g(|a: Int32| -> Int32 { a }, || {});\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        assert!(format(Reader::from_string("<<code>>", "fun f( {")).is_err());
+    }
+
+    fn dora_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.is_dir() {
+                dora_files(&path, files);
+            } else if path.extension().map_or(false, |ext| ext == "dora") {
+                files.push(path);
+            }
+        }
+    }
+
+    // Formatting has to be idempotent and must not lose any comments.
+    #[test]
+    fn test_idempotence() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut files = Vec::new();
+
+        for dir in &["tests", "bench", "dora/stdlib", "dora-boots"] {
+            dora_files(&root.join(dir), &mut files);
+        }
+
+        assert!(!files.is_empty());
+
+        for path in files {
+            let reader = Reader::from_file(path.to_str().unwrap()).unwrap();
+            let content = reader.content().to_string();
+
+            // some tests check syntax errors
+            let formatted = match format(reader) {
+                Ok(formatted) => formatted,
+                Err(_) => continue,
+            };
+
+            let reformatted = format(Reader::from_string("<<formatted>>", &formatted))
+                .unwrap_or_else(|_| panic!("{} does not parse after formatting", path.display()));
+
+            assert_eq!(
+                formatted,
+                reformatted,
+                "formatting {} is not idempotent",
+                path.display()
+            );
+
+            assert_eq!(
+                count_comments(&content),
+                count_comments(&formatted),
+                "formatting {} lost comments",
+                path.display()
+            );
+        }
+    }
+
+    fn count_comments(content: &str) -> usize {
+        let reader = Reader::from_string("<<code>>", content);
+        let id_generator = NodeIdGenerator::new();
+        let mut interner = Interner::new();
+        let mut ast = Ast::new();
+        let parser = Parser::new(reader, &id_generator, &mut ast, &mut interner);
+        let (file, _) = parser.parse();
+
+        file.comments.len()
+    }
+}
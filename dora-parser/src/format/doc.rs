@@ -0,0 +1,231 @@
+// A small Wadler-style pretty printer. The formatter describes the layout
+// as a tree of documents, a group is printed on a single line if it fits
+// into the remaining width and otherwise all its lines are broken.
+
+const INDENT: usize = 4;
+
+#[derive(Clone, Debug)]
+pub enum Doc {
+    Nil,
+    Text(String),
+    // space when flat, newline when broken
+    Line,
+    // nothing when flat, newline when broken
+    SoftLine,
+    // always a newline, forces all enclosing groups to break
+    HardLine,
+    // prints nothing but forces all enclosing groups to break
+    BreakParent,
+    Concat(Vec<Doc>),
+    Nest(Box<Doc>),
+    Group(Box<Doc>),
+    // first document when broken, second one when flat
+    IfBreak(Box<Doc>, Box<Doc>),
+}
+
+impl Doc {
+    pub fn text<S: Into<String>>(text: S) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub fn concat(docs: Vec<Doc>) -> Doc {
+        Doc::Concat(docs)
+    }
+
+    pub fn nest(doc: Doc) -> Doc {
+        Doc::Nest(Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+
+    pub fn if_break(broken: Doc, flat: Doc) -> Doc {
+        Doc::IfBreak(Box::new(broken), Box::new(flat))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+pub fn render(doc: &Doc, width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Nil | Doc::BreakParent => {}
+
+            Doc::Text(text) => {
+                output.push_str(text);
+
+                column = match text.rfind('\n') {
+                    Some(idx) => text[idx + 1..].chars().count(),
+                    None => column + text.chars().count(),
+                };
+            }
+
+            Doc::Line if mode == Mode::Flat => {
+                output.push(' ');
+                column += 1;
+            }
+
+            Doc::SoftLine if mode == Mode::Flat => {}
+
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                let len = output.trim_end_matches(' ').len();
+                output.truncate(len);
+                output.push('\n');
+
+                for _ in 0..indent {
+                    output.push(' ');
+                }
+
+                column = indent;
+            }
+
+            Doc::Concat(docs) => {
+                for doc in docs.iter().rev() {
+                    stack.push((indent, mode, doc));
+                }
+            }
+
+            Doc::Nest(doc) => stack.push((indent + INDENT, mode, doc)),
+
+            Doc::Group(doc) => {
+                let mode =
+                    if mode == Mode::Flat || fits(width as isize - column as isize, doc, &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+
+                stack.push((indent, mode, doc));
+            }
+
+            Doc::IfBreak(broken, flat) => {
+                let doc = if mode == Mode::Break { broken } else { flat };
+                stack.push((indent, mode, doc));
+            }
+        }
+    }
+
+    let len = output.trim_end().len();
+    output.truncate(len);
+    output.push('\n');
+
+    output
+}
+
+// Checks whether the group fits flat into the remaining width, including
+// the rest of the current line after the group.
+fn fits(mut remaining: isize, group: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, group)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(entry) => entry,
+
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Nil => {}
+
+            Doc::Text(text) => {
+                if let Some(idx) = text.find('\n') {
+                    let first_line = text[..idx].chars().count() as isize;
+                    return mode == Mode::Break && remaining >= first_line;
+                }
+
+                remaining -= text.chars().count() as isize;
+            }
+
+            Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::BreakParent if mode == Mode::Break => {
+                return true;
+            }
+
+            Doc::HardLine | Doc::BreakParent => return false,
+
+            Doc::Line => remaining -= 1,
+
+            Doc::SoftLine => {}
+
+            Doc::Concat(docs) => {
+                for doc in docs.iter().rev() {
+                    stack.push((mode, doc));
+                }
+            }
+
+            Doc::Nest(doc) | Doc::Group(doc) => stack.push((mode, doc)),
+
+            Doc::IfBreak(broken, flat) => {
+                stack.push((mode, if mode == Mode::Break { broken } else { flat }));
+            }
+        }
+
+        if remaining < 0 {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str]) -> Doc {
+        let mut inner = vec![Doc::SoftLine];
+
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                inner.push(Doc::text(","));
+                inner.push(Doc::Line);
+            }
+
+            inner.push(Doc::text(*item));
+        }
+
+        inner.push(Doc::if_break(Doc::text(","), Doc::Nil));
+
+        Doc::group(Doc::concat(vec![
+            Doc::text("f("),
+            Doc::nest(Doc::concat(inner)),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]))
+    }
+
+    #[test]
+    fn test_group_fits() {
+        assert_eq!("f(a, b)\n", render(&list(&["a", "b"]), 10));
+    }
+
+    #[test]
+    fn test_group_breaks() {
+        assert_eq!(
+            "f(\n    aaaa,\n    bbbb,\n)\n",
+            render(&list(&["aaaa", "bbbb"]), 10)
+        );
+    }
+
+    #[test]
+    fn test_break_parent() {
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("a"),
+            Doc::Line,
+            Doc::text("b"),
+            Doc::BreakParent,
+        ]));
+
+        assert_eq!("a\nb\n", render(&doc, 80));
+    }
+}
@@ -48,6 +48,7 @@ pub struct Interner {
     data: Mutex<Internal>,
 }
 
+#[derive(Clone)]
 struct Internal {
     map: HashMap<ArcStr, Name>,
     vec: Vec<ArcStr>,
@@ -85,6 +86,14 @@ impl Interner {
     }
 }
 
+impl Clone for Interner {
+    fn clone(&self) -> Interner {
+        Interner {
+            data: Mutex::new(self.data.lock().clone()),
+        }
+    }
+}
+
 #[test]
 fn interner() {
     let interner = Interner::new();
@@ -9,16 +9,18 @@ pub mod position;
 pub mod reader;
 pub mod token;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct File {
     pub name: String,
     pub content: String,
     pub line_ends: Vec<u32>,
+    pub comments: Vec<Span>,
 }
 
 pub struct Lexer {
     reader: Reader,
     keywords: HashMap<&'static str, TokenKind>,
+    comments: Vec<Span>,
 }
 
 impl Lexer {
@@ -31,7 +33,11 @@ impl Lexer {
     pub fn new(reader: Reader) -> Lexer {
         let keywords = keywords_in_map();
 
-        Lexer { reader, keywords }
+        Lexer {
+            reader,
+            keywords,
+            comments: Vec::new(),
+        }
     }
 
     pub fn path(&self) -> &str {
@@ -66,6 +72,7 @@ impl Lexer {
                 return self.read_operator();
             } else {
                 let ch = ch.unwrap();
+                self.read_char();
 
                 return Err(ParseErrorAndPos::new(pos, ParseError::UnknownChar(ch)));
             }
@@ -79,15 +86,20 @@ impl Lexer {
     }
 
     fn read_comment(&mut self) -> Result<(), ParseErrorAndPos> {
+        let idx = self.reader.idx();
+
         while !self.curr().is_none() && !is_newline(self.curr()) {
             self.read_char();
         }
 
+        self.comments.push(self.span_from(idx));
+
         Ok(())
     }
 
     fn read_multi_comment(&mut self) -> Result<(), ParseErrorAndPos> {
         let pos = self.reader.pos();
+        let idx = self.reader.idx();
 
         self.read_char();
         self.read_char();
@@ -103,6 +115,8 @@ impl Lexer {
         self.read_char();
         self.read_char();
 
+        self.comments.push(self.span_from(idx));
+
         Ok(())
     }
 
@@ -505,8 +519,12 @@ impl Lexer {
         self.curr() == Some('*') && self.next() == Some('/')
     }
 
+    // Comments are not tokens, their spans are kept so tools like the
+    // formatter can reproduce them.
     pub fn file(self) -> File {
-        self.reader.file()
+        let mut file = self.reader.file();
+        file.comments = self.comments;
+        file
     }
 }
 
@@ -698,6 +716,32 @@ mod tests {
         assert_end(&mut reader, 2, 2);
     }
 
+    #[test]
+    fn test_record_comments() {
+        let mut reader = Lexer::from_str("1 // a\n/* b\n */ 2");
+        assert_tok(
+            &mut reader,
+            TokenKind::LitInt("1".into(), IntBase::Dec, IntSuffix::None),
+            1,
+            1,
+        );
+        assert_tok(
+            &mut reader,
+            TokenKind::LitInt("2".into(), IntBase::Dec, IntSuffix::None),
+            3,
+            5,
+        );
+        assert_end(&mut reader, 3, 6);
+
+        let file = reader.file();
+        let comments: Vec<&str> = file
+            .comments
+            .iter()
+            .map(|span| &file.content[span.start() as usize..span.end() as usize])
+            .collect();
+        assert_eq!(vec!["// a", "/* b\n */"], comments);
+    }
+
     #[test]
     fn test_unfinished_line_comment() {
         let mut reader = Lexer::from_str("//abc");
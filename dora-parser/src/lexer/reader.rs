@@ -44,6 +44,10 @@ impl Reader {
         &self.name
     }
 
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
     pub fn advance(&mut self) -> Option<char> {
         let curr = self.curr();
 
@@ -78,6 +82,7 @@ impl Reader {
             name: self.name,
             content: self.content,
             line_ends: self.line_ends,
+            comments: Vec::new(),
         }
     }
 
@@ -85,14 +90,9 @@ impl Reader {
         self.nth(0)
     }
 
+    // `offset` counts characters, not bytes
     pub fn nth(&self, offset: usize) -> Option<char> {
-        let pos = self.idx + offset;
-
-        if pos < self.content.len() {
-            self.content[pos..].chars().next()
-        } else {
-            None
-        }
+        self.content[self.idx..].chars().nth(offset)
     }
 
     pub fn pos(&self) -> Position {
@@ -122,6 +122,20 @@ fn common_init(name: String, content: String) -> Reader {
 mod tests {
     use super::*;
 
+    #[test]
+    fn nth_steps_by_chars() {
+        let mut reader = Reader::from_string("<<code>>", "a你好b");
+
+        assert_eq!(Some('你'), reader.nth(1));
+        assert_eq!(Some('好'), reader.nth(2));
+        assert_eq!(Some('b'), reader.nth(3));
+        assert_eq!(None, reader.nth(4));
+
+        reader.advance();
+        assert_eq!(Some('你'), reader.curr());
+        assert_eq!(Some('b'), reader.nth(2));
+    }
+
     #[test]
     fn read_from_str() {
         let mut reader = Reader::from_string("<<code>>", "abc");
@@ -3,6 +3,7 @@ pub use lexer::position::Position;
 pub mod ast;
 mod builder;
 pub mod error;
+pub mod format;
 pub mod interner;
 pub mod lexer;
 pub mod parser;
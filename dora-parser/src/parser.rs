@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::cmp::max;
 use std::mem;
 
 use crate::ast;
@@ -25,12 +26,46 @@ pub struct Parser<'a> {
     in_class_or_module: bool,
     parse_struct_lit: bool,
     last_end: Option<u32>,
+    errors: Vec<ParseErrorAndPos>,
 }
 
 type ExprResult = Result<Box<Expr>, ParseErrorAndPos>;
 type StmtResult = Result<Box<Stmt>, ParseErrorAndPos>;
 type StmtOrExprResult = Result<StmtOrExpr, ParseErrorAndPos>;
 
+// Tokens parsing can continue with after skipping over a syntax error.
+const TOP_LEVEL_START: &[TokenKind] = &[
+    TokenKind::Fun,
+    TokenKind::Class,
+    TokenKind::Struct,
+    TokenKind::Trait,
+    TokenKind::Impl,
+    TokenKind::Module,
+    TokenKind::Alias,
+    TokenKind::Let,
+    TokenKind::Var,
+    TokenKind::Const,
+    TokenKind::Enum,
+    TokenKind::At,
+];
+
+const MEMBER_START: &[TokenKind] = &[
+    TokenKind::Fun,
+    TokenKind::Let,
+    TokenKind::Var,
+    TokenKind::At,
+];
+
+const STATEMENT_START: &[TokenKind] = &[
+    TokenKind::Let,
+    TokenKind::Var,
+    TokenKind::While,
+    TokenKind::For,
+    TokenKind::Return,
+    TokenKind::Break,
+    TokenKind::Continue,
+];
+
 enum StmtOrExpr {
     Stmt(Box<Stmt>),
     Expr(Box<Expr>),
@@ -56,6 +91,7 @@ impl<'a> Parser<'a> {
             parse_struct_lit: true,
             ast,
             last_end: Some(0),
+            errors: Vec::new(),
         };
 
         parser
@@ -65,12 +101,24 @@ impl<'a> Parser<'a> {
         self.id_generator.next()
     }
 
-    pub fn parse(mut self) -> Result<LexerFile, ParseErrorAndPos> {
-        self.init()?;
+    // Parses the whole file and returns it together with all syntax errors.
+    // Broken elements, members and statements are skipped or replaced with
+    // error nodes, so the file can still be checked if there were errors.
+    pub fn parse(mut self) -> (LexerFile, Vec<ParseErrorAndPos>) {
+        if let Err(error) = self.init() {
+            self.errors.push(error);
+            self.skip_token();
+        }
+
         let mut elements = vec![];
 
         while !self.token.is_eof() {
-            self.parse_top_level_element(&mut elements)?;
+            let start = self.token.span.start();
+
+            if let Err(error) = self.parse_top_level_element(&mut elements) {
+                self.errors.push(error);
+                self.synchronize(start, TOP_LEVEL_START, false);
+            }
         }
 
         let file = self.lexer.file();
@@ -80,7 +128,7 @@ impl<'a> Parser<'a> {
             elements,
         });
 
-        Ok(file)
+        (file, self.errors)
     }
 
     fn init(&mut self) -> Result<(), ParseErrorAndPos> {
@@ -267,15 +315,15 @@ impl<'a> Parser<'a> {
 
         let mut methods = Vec::new();
 
-        while !self.token.is(TokenKind::RBrace) {
-            let modifiers = self.parse_annotations()?;
+        self.parse_members(|p| {
+            let modifiers = p.parse_annotations()?;
             let mods = &[Modifier::Static, Modifier::Internal, Modifier::Cannon];
-            self.restrict_modifiers(&modifiers, mods)?;
+            p.restrict_modifiers(&modifiers, mods)?;
 
-            methods.push(self.parse_function(&modifiers)?);
-        }
+            methods.push(p.parse_function(&modifiers)?);
+            Ok(())
+        })?;
 
-        self.expect_token(TokenKind::RBrace)?;
         let span = self.span_from(start);
 
         Ok(Impl {
@@ -339,15 +387,15 @@ impl<'a> Parser<'a> {
 
         let mut methods = Vec::new();
 
-        while !self.token.is(TokenKind::RBrace) {
-            let modifiers = self.parse_annotations()?;
+        self.parse_members(|p| {
+            let modifiers = p.parse_annotations()?;
             let mods = &[Modifier::Static];
-            self.restrict_modifiers(&modifiers, mods)?;
+            p.restrict_modifiers(&modifiers, mods)?;
 
-            methods.push(self.parse_function(&modifiers)?);
-        }
+            methods.push(p.parse_function(&modifiers)?);
+            Ok(())
+        })?;
 
-        self.expect_token(TokenKind::RBrace)?;
         let span = self.span_from(start);
 
         Ok(Trait {
@@ -470,6 +518,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_module(&mut self, modifiers: &Modifiers) -> Result<Module, ParseErrorAndPos> {
+        let start = self.token.span.start();
         let internal = modifiers.contains(Modifier::Internal);
 
         let pos = self.expect_token(TokenKind::Module)?.position;
@@ -478,6 +527,7 @@ impl<'a> Parser<'a> {
             id: self.generate_id(),
             name: ident,
             pos: pos,
+            span: Span::invalid(),
             parent_class: None,
             internal: internal,
             has_constructor: false,
@@ -505,6 +555,7 @@ impl<'a> Parser<'a> {
         };
 
         self.parse_module_body(&mut module)?;
+        module.span = self.span_from(start);
         self.in_class_or_module = false;
 
         Ok(module)
@@ -659,10 +710,10 @@ impl<'a> Parser<'a> {
 
         self.advance_token()?;
 
-        while !self.token.is(TokenKind::RBrace) {
-            let modifiers = self.parse_annotations()?;
+        self.parse_members(|p| {
+            let modifiers = p.parse_annotations()?;
 
-            match self.token.kind {
+            match p.token.kind {
                 TokenKind::Fun => {
                     let mods = &[
                         Modifier::Abstract,
@@ -674,28 +725,27 @@ impl<'a> Parser<'a> {
                         Modifier::Static,
                         Modifier::Cannon,
                     ];
-                    self.restrict_modifiers(&modifiers, mods)?;
+                    p.restrict_modifiers(&modifiers, mods)?;
 
-                    let fct = self.parse_function(&modifiers)?;
+                    let fct = p.parse_function(&modifiers)?;
                     cls.methods.push(fct);
                 }
 
                 TokenKind::Var | TokenKind::Let => {
-                    self.ban_modifiers(&modifiers)?;
+                    p.ban_modifiers(&modifiers)?;
 
-                    let field = self.parse_field()?;
+                    let field = p.parse_field()?;
                     cls.fields.push(field);
                 }
 
                 _ => {
-                    let initializer = self.parse_statement()?;
+                    let initializer = p.parse_statement()?;
                     cls.initializers.push(initializer);
                 }
             }
-        }
 
-        self.advance_token()?;
-        Ok(())
+            Ok(())
+        })
     }
 
     fn parse_module_body(&mut self, module: &mut Module) -> Result<(), ParseErrorAndPos> {
@@ -705,10 +755,10 @@ impl<'a> Parser<'a> {
 
         self.advance_token()?;
 
-        while !self.token.is(TokenKind::RBrace) {
-            let modifiers = self.parse_annotations()?;
+        self.parse_members(|p| {
+            let modifiers = p.parse_annotations()?;
 
-            match self.token.kind {
+            match p.token.kind {
                 TokenKind::Fun => {
                     let mods = &[
                         Modifier::Abstract,
@@ -719,27 +769,46 @@ impl<'a> Parser<'a> {
                         Modifier::Pub,
                         Modifier::Static,
                     ];
-                    self.restrict_modifiers(&modifiers, mods)?;
+                    p.restrict_modifiers(&modifiers, mods)?;
 
-                    let fct = self.parse_function(&modifiers)?;
+                    let fct = p.parse_function(&modifiers)?;
                     module.methods.push(fct);
                 }
 
                 TokenKind::Var | TokenKind::Let => {
-                    self.ban_modifiers(&modifiers)?;
+                    p.ban_modifiers(&modifiers)?;
 
-                    let field = self.parse_field()?;
+                    let field = p.parse_field()?;
                     module.fields.push(field);
                 }
 
                 _ => {
-                    let initializer = self.parse_statement()?;
+                    let initializer = p.parse_statement()?;
                     module.initializers.push(initializer);
                 }
             }
+
+            Ok(())
+        })
+    }
+
+    // Parses the members of a class, module, trait or impl up to and including
+    // the closing brace. A member with a syntax error is skipped.
+    fn parse_members<F>(&mut self, mut parse: F) -> Result<(), ParseErrorAndPos>
+    where
+        F: FnMut(&mut Parser) -> Result<(), ParseErrorAndPos>,
+    {
+        while !self.token.is(TokenKind::RBrace) && !self.token.is_eof() {
+            let start = self.token.span.start();
+
+            if let Err(error) = parse(self) {
+                self.errors.push(error);
+                self.synchronize(start, MEMBER_START, true);
+            }
         }
 
-        self.advance_token()?;
+        self.expect_token(TokenKind::RBrace)?;
+
         Ok(())
     }
 
@@ -1123,7 +1192,11 @@ impl<'a> Parser<'a> {
         let data_type = self.parse_var_type()?;
         let expr = self.parse_var_assignment()?;
 
-        self.expect_semicolon()?;
+        // a broken initializer was already skipped including the semicolon
+        if !expr.as_ref().map_or(false, |expr| expr.is_error()) {
+            self.expect_semicolon()?;
+        }
+
         let span = self.span_from(start);
 
         Ok(Box::new(Stmt::create_var(
@@ -1150,9 +1223,25 @@ impl<'a> Parser<'a> {
     fn parse_var_assignment(&mut self) -> Result<Option<Box<Expr>>, ParseErrorAndPos> {
         if self.token.is(TokenKind::Eq) {
             self.expect_token(TokenKind::Eq)?;
-            let expr = self.parse_expression()?;
+            let start = self.token.span.start();
+            let pos = self.token.position;
 
-            Ok(Some(expr))
+            match self.parse_expression() {
+                Ok(expr) => Ok(Some(expr)),
+
+                Err(error) => {
+                    // keep the variable, otherwise all its uses would be reported
+                    self.errors.push(error);
+                    self.synchronize(start, STATEMENT_START, true);
+                    let span = self.error_span(start);
+
+                    Ok(Some(Box::new(Expr::create_error(
+                        self.generate_id(),
+                        pos,
+                        span,
+                    ))))
+                }
+            }
         } else {
             Ok(None)
         }
@@ -1175,14 +1264,28 @@ impl<'a> Parser<'a> {
         let mut expr = None;
 
         while !self.token.is(TokenKind::RBrace) && !self.token.is_eof() {
-            let stmt_or_expr = self.parse_statement_or_expression()?;
+            let start = self.token.span.start();
+            let stmt_pos = self.token.position;
 
-            match stmt_or_expr {
-                StmtOrExpr::Stmt(stmt) => stmts.push(stmt),
-                StmtOrExpr::Expr(curr_expr) => {
+            match self.parse_statement_or_expression() {
+                Ok(StmtOrExpr::Stmt(stmt)) => stmts.push(stmt),
+                Ok(StmtOrExpr::Expr(curr_expr)) => {
                     if curr_expr.needs_semicolon() {
-                        expr = Some(curr_expr);
-                        break;
+                        if self.token.is(TokenKind::RBrace) || self.token.is_eof() {
+                            expr = Some(curr_expr);
+                            break;
+                        }
+
+                        // missing semicolon, report it and continue with the next statement
+                        let error = self.expect_semicolon().unwrap_err();
+                        self.errors.push(error);
+
+                        stmts.push(Box::new(Stmt::create_expr(
+                            self.generate_id(),
+                            curr_expr.pos(),
+                            curr_expr.span(),
+                            curr_expr,
+                        )));
                     } else if !self.token.is(TokenKind::RBrace) {
                         stmts.push(Box::new(Stmt::create_expr(
                             self.generate_id(),
@@ -1194,6 +1297,18 @@ impl<'a> Parser<'a> {
                         expr = Some(curr_expr);
                     }
                 }
+
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(start, STATEMENT_START, true);
+                    let span = self.error_span(start);
+
+                    stmts.push(Box::new(Stmt::create_error(
+                        self.generate_id(),
+                        stmt_pos,
+                        span,
+                    )));
+                }
             }
         }
 
@@ -1713,10 +1828,13 @@ impl<'a> Parser<'a> {
         let filtered = value.chars().filter(|&ch| ch != '_').collect::<String>();
         let parsed = filtered.parse::<f64>();
 
-        let num = parsed.expect("unparsable float");
-
-        let expr = Expr::create_lit_float(self.generate_id(), pos, span, num, suffix);
-        Ok(Box::new(expr))
+        match parsed {
+            Ok(num) => {
+                let expr = Expr::create_lit_float(self.generate_id(), pos, span, num, suffix);
+                Ok(Box::new(expr))
+            }
+            _ => Err(ParseErrorAndPos::new(pos, ParseError::InvalidNumber)),
+        }
     }
 
     fn parse_string(&mut self) -> ExprResult {
@@ -1874,16 +1992,15 @@ impl<'a> Parser<'a> {
     }
 
     fn expect_identifier(&mut self) -> Result<Name, ParseErrorAndPos> {
-        let tok = self.advance_token()?;
-
-        if let TokenKind::Identifier(ref value) = tok.kind {
+        if let TokenKind::Identifier(ref value) = self.token.kind {
             let interned = self.interner.intern(value);
+            self.advance_token()?;
 
             Ok(interned)
         } else {
             Err(ParseErrorAndPos::new(
-                tok.position,
-                ParseError::ExpectedIdentifier(tok.name()),
+                self.token.position,
+                ParseError::ExpectedIdentifier(self.token.name()),
             ))
         }
     }
@@ -1924,6 +2041,60 @@ impl<'a> Parser<'a> {
         Span::new(start, self.last_end.unwrap() - start)
     }
 
+    // Like span_from but also works if no token was consumed since start.
+    fn error_span(&self, start: u32) -> Span {
+        match self.last_end {
+            Some(end) if end >= start => Span::new(start, end - start),
+            _ => Span::at(start),
+        }
+    }
+
+    // Moves to the next token, lexer errors are recorded and skipped.
+    fn skip_token(&mut self) {
+        loop {
+            match self.lexer.read_token() {
+                Ok(token) => {
+                    self.advance_token_with(token);
+                    return;
+                }
+
+                Err(error) => self.errors.push(error),
+            }
+        }
+    }
+
+    // Skips tokens after a syntax error in the construct starting at start.
+    // Stops after a semicolon, before the closing brace of the enclosing
+    // block or before a token that starts the next construct.
+    fn synchronize(&mut self, start: u32, stop: &[TokenKind], in_block: bool) {
+        let mut depth = 0;
+
+        loop {
+            let at_start = self.token.span.start() == start;
+
+            match self.token.kind {
+                TokenKind::End => return,
+
+                TokenKind::Semicolon if depth == 0 => {
+                    self.skip_token();
+                    return;
+                }
+
+                TokenKind::RBrace if depth == 0 && !at_start && in_block => return,
+
+                TokenKind::LBrace => depth += 1,
+
+                TokenKind::RBrace => depth = max(depth - 1, 0),
+
+                ref kind if depth == 0 && !at_start && stop.contains(kind) => return,
+
+                _ => {}
+            }
+
+            self.skip_token();
+        }
+    }
+
     fn generate_global_initializer(&mut self, global: &Global, initializer: Box<Expr>) -> Function {
         let builder = Builder::new(self.id_generator);
         let mut block = builder.build_block();
@@ -1999,7 +2170,7 @@ struct Delegation {
     pub args: Vec<Box<Expr>>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NodeIdGenerator {
     value: RefCell<usize>,
 }
@@ -2024,7 +2195,7 @@ mod tests {
     use crate::ast::*;
     use crate::interner::*;
 
-    use crate::error::ParseError;
+    use crate::error::{ParseError, ParseErrorAndPos};
     use crate::lexer::position::Position;
     use crate::lexer::reader::Reader;
     use crate::parser::{NodeIdGenerator, Parser};
@@ -2117,9 +2288,8 @@ mod tests {
         let mut ast = Ast::new();
 
         let reader = Reader::from_string("<<code>>", code);
-        Parser::new(reader, &id_generator, &mut ast, &mut interner)
-            .parse()
-            .unwrap();
+        let (_, errors) = Parser::new(reader, &id_generator, &mut ast, &mut interner).parse();
+        assert!(errors.is_empty());
 
         (ast, interner)
     }
@@ -2130,9 +2300,8 @@ mod tests {
         let mut ast = Ast::new();
 
         let reader = Reader::from_string("<<code>>", code);
-        let err = Parser::new(reader, &id_generator, &mut ast, &mut interner)
-            .parse()
-            .unwrap_err();
+        let (_, errors) = Parser::new(reader, &id_generator, &mut ast, &mut interner).parse();
+        let err = &errors[0];
 
         assert_eq!(msg, err.error);
         assert_eq!(line, err.pos.line);
@@ -3309,6 +3478,11 @@ mod tests {
         assert_eq!(1.2, lit.value);
     }
 
+    #[test]
+    fn parse_lit_float_without_exponent() {
+        err_expr("1.2E-", ParseError::InvalidNumber, 1, 1);
+    }
+
     #[test]
     fn parse_template() {
         let (expr, _) = parse_expr("\"a${1}b${2}c\"");
@@ -3704,4 +3878,97 @@ mod tests {
         let (prog, _) = parse("alias NewType = Int;");
         let _alias = prog.alias0();
     }
+
+    fn parse_with_errors(code: &'static str) -> (Ast, Vec<ParseErrorAndPos>) {
+        let id_generator = NodeIdGenerator::new();
+        let mut interner = Interner::new();
+        let mut ast = Ast::new();
+
+        let reader = Reader::from_string("<<code>>", code);
+        let (_, errors) = Parser::new(reader, &id_generator, &mut ast, &mut interner).parse();
+
+        (ast, errors)
+    }
+
+    fn assert_error(error: &ParseErrorAndPos, msg: ParseError, line: u32, col: u32) {
+        assert_eq!(msg, error.error);
+        assert_eq!(line, error.pos.line);
+        assert_eq!(col, error.pos.column);
+    }
+
+    #[test]
+    fn parse_recover_statements() {
+        let (prog, errors) =
+            parse_with_errors("fun f() {\n let a = ;\n foo(1 2);\n let b = a;\n b\n}");
+        assert_eq!(2, errors.len());
+        assert_error(&errors[0], ParseError::ExpectedFactor(";".into()), 2, 10);
+        assert_error(
+            &errors[1],
+            ParseError::ExpectedToken(",".into(), "2".into()),
+            3,
+            8,
+        );
+
+        let block = prog.fct0().block.as_ref().unwrap();
+        assert_eq!(3, block.stmts.len());
+        let var = block.stmts[0].to_var().unwrap();
+        assert!(var.expr.as_ref().unwrap().is_error());
+        assert!(block.stmts[1].is_error());
+        assert!(block.stmts[2].is_var());
+        assert!(block.expr.is_some());
+    }
+
+    #[test]
+    fn parse_recover_missing_semicolon() {
+        let (prog, errors) = parse_with_errors("fun f() { a() b(); }");
+        assert_eq!(1, errors.len());
+        assert_error(
+            &errors[0],
+            ParseError::ExpectedToken(";".into(), "b".into()),
+            1,
+            15,
+        );
+        assert_eq!(2, prog.fct0().block.as_ref().unwrap().stmts.len());
+    }
+
+    #[test]
+    fn parse_recover_top_level() {
+        let (prog, errors) = parse_with_errors("fun f( { 1 }\nfun g() {}\n}\nfun h() {}");
+        assert_eq!(2, errors.len());
+        assert_error(&errors[0], ParseError::ExpectedIdentifier("{".into()), 1, 8);
+        assert_error(
+            &errors[1],
+            ParseError::ExpectedTopLevelElement("}".into()),
+            3,
+            1,
+        );
+        assert_eq!(2, prog.files[0].elements.len());
+    }
+
+    #[test]
+    fn parse_recover_members() {
+        let (prog, errors) = parse_with_errors("class A { var x: ; fun f() {} }\nfun g() {}");
+        assert_eq!(1, errors.len());
+        assert_error(&errors[0], ParseError::ExpectedType(";".into()), 1, 18);
+        assert_eq!(1, prog.cls0().methods.len());
+        assert!(prog.files[0].elements[1].to_function().is_some());
+    }
+
+    #[test]
+    fn parse_recover_lexer_error() {
+        let (_, errors) = parse_with_errors("fun f() { let x = 1 # 2; }\nfun g() { 'a }");
+        assert_eq!(2, errors.len());
+        assert_error(&errors[0], ParseError::UnknownChar('#'), 1, 21);
+        assert_error(&errors[1], ParseError::UnclosedChar, 2, 11);
+    }
+
+    #[test]
+    fn parse_recover_multibyte_after_unclosed_string() {
+        let (_, errors) =
+            parse_with_errors("fun f() { a(\"\")\"); }\nfun g() { \"你,好\"; 1 +你 }");
+        assert_eq!(5, errors.len());
+        assert_error(&errors[1], ParseError::UnknownChar('你'), 2, 12);
+        assert_error(&errors[2], ParseError::UnknownChar('好'), 2, 14);
+        assert_error(&errors[3], ParseError::UnclosedString, 2, 15);
+    }
 }
@@ -230,9 +230,39 @@ impl ManagedStackFrame {
             }
         }
 
+        if vm.args.flag_gc_verify_stackmaps {
+            return GcPoint::with_live_slots(offsets, self.live_slots(vm));
+        }
+
         GcPoint::from_offsets(offsets)
     }
 
+    // words of the frame covered by initialized variables, all other words
+    // may still hold stale values of variables that were freed
+    fn live_slots(&self, vm: &VM) -> Vec<i32> {
+        let word = mem::ptr_width();
+        let mut slots = Vec::new();
+
+        for (_, data) in &self.vars {
+            if !data.initialized {
+                continue;
+            }
+
+            let size = if data.ty.is_nil() {
+                mem::ptr_width()
+            } else {
+                data.ty.size(vm)
+            };
+
+            let start = data.offset - data.offset.rem_euclid(word);
+            slots.extend((start..data.offset + size).step_by(word as usize));
+        }
+
+        slots.sort();
+        slots.dedup();
+        slots
+    }
+
     fn stacksize(&self) -> i32 {
         mem::align_i32(self.stacksize, STACK_FRAME_ALIGNMENT as i32)
     }
@@ -370,8 +400,9 @@ fn is_aligned(value: u32, size: u32) -> bool {
     value % size == 0
 }
 
+// Rounds value up to the next multiple of alignment.
 fn align(value: u32, alignment: u32) -> u32 {
-    (value * alignment + alignment - 1) / alignment
+    ((value + alignment - 1) / alignment) * alignment
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -466,7 +497,16 @@ impl From<FReg> for ExprStore {
 
 #[cfg(test)]
 mod tests {
-    use super::{FreeSlot, FreeSlots};
+    use super::{align, FreeSlot, FreeSlots};
+
+    #[test]
+    fn align_rounds_up() {
+        assert_eq!(align(0, 8), 0);
+        assert_eq!(align(4, 8), 8);
+        assert_eq!(align(8, 8), 8);
+        assert_eq!(align(9, 4), 12);
+        assert_eq!(align(17, 16), 32);
+    }
 
     #[test]
     fn merge_free_slots() {
@@ -501,4 +541,16 @@ mod tests {
         assert_eq!(free_slots.alloc(4, 4), Some(12));
         assert_eq!(free_slots.slots, vec![FreeSlot::new(0, 8)]);
     }
+
+    #[test]
+    fn alloc_aligned_free_slot() {
+        let mut free_slots = FreeSlots::new();
+        free_slots.free(FreeSlot::new(4, 16));
+
+        assert_eq!(free_slots.alloc(8, 8), Some(8));
+        assert_eq!(
+            free_slots.slots,
+            vec![FreeSlot::new(4, 4), FreeSlot::new(16, 4)]
+        );
+    }
 }
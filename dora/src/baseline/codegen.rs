@@ -13,7 +13,7 @@ use crate::baseline::{
 };
 use crate::compiler::asm::BaselineAssembler;
 use crate::compiler::codegen::{ensure_native_stub, should_emit_debug, AllocationSize, AnyReg};
-use crate::compiler::fct::{Code, GcPoint, JitDescriptor};
+use crate::compiler::fct::{Code, GcPoint, JitDescriptor, VariableInfo};
 use crate::compiler::native_stub::{NativeFct, NativeFctDescriptor};
 use crate::cpu::{
     next_param_offset, FReg, Mem, Reg, FREG_PARAMS, FREG_RESULT, FREG_TMP1, PARAM_OFFSET,
@@ -22,13 +22,13 @@ use crate::cpu::{
 use crate::gc::Address;
 use crate::masm::*;
 use crate::mem;
-use crate::object::{offset_of_array_data, Header, Str};
+use crate::object::{Header, Str};
 use crate::semck::specialize::{replace_type_param, specialize_class_ty, specialize_for_call_type};
 use crate::size::InstanceSize;
 use crate::ty::{BuiltinType, MachineMode, TypeList, TypeParamId};
 use crate::vm::{
-    CallType, ClassDefId, ConstId, Fct, FctId, FctKind, FctSrc, FieldId, IdentType, Intrinsic,
-    TraitId, Trap, TupleId, VarId, VM,
+    CallType, ClassDef, ClassDefId, ConstId, Fct, FctId, FctKind, FctSrc, FieldId, IdentType,
+    Intrinsic, TraitId, Trap, TupleId, VarId, VM,
 };
 use crate::vtable::{VTable, DISPLAY_SIZE};
 
@@ -55,6 +55,9 @@ pub(super) fn generate<'a, 'ast: 'a>(
         var_to_offset: HashMap::new(),
         return_value: None,
 
+        variables: Vec::new(),
+        variable_scopes: Vec::new(),
+
         cls_type_params,
         fct_type_params,
     }
@@ -81,6 +84,10 @@ struct AstCodeGen<'a, 'ast: 'a> {
     var_to_slot: HashMap<VarId, ManagedStackSlot>,
     var_to_offset: HashMap<VarId, i32>,
 
+    // stack locations of variables, only recorded when debugging
+    variables: Vec<VariableInfo>,
+    variable_scopes: Vec<Vec<usize>>,
+
     cls_type_params: &'a TypeList,
     fct_type_params: &'a TypeList,
 }
@@ -94,7 +101,9 @@ where
             self.asm.debug();
         }
 
-        self.managed_stack.push_scope();
+        self.asm.set_file(self.fct.file);
+
+        self.push_scope();
         self.emit_prolog();
         self.store_register_params_on_stack();
         self.emit_stack_guard();
@@ -109,6 +118,7 @@ where
             }
 
             if let Some(ref value) = block.expr {
+                self.emit_debug_hook(value.pos());
                 let return_type = self.specialize_type(self.fct.return_type);
                 let dest = self.alloc_expr_store(return_type);
                 self.emit_expr(value, dest);
@@ -134,7 +144,7 @@ where
             }
         }
 
-        self.managed_stack.pop_scope(self.vm);
+        self.pop_scope();
         assert!(self.managed_stack.is_empty());
 
         if !always_returns {
@@ -144,14 +154,62 @@ where
         self.asm
             .patch_stacksize(self.stacksize_offset, self.managed_stack.stacksize());
 
-        let jit_fct = self.asm.jit(
+        let variables = std::mem::replace(&mut self.variables, Vec::new());
+
+        let mut jit_fct = self.asm.jit(
             self.managed_stack.stacksize(),
             JitDescriptor::DoraFct(self.fct.id),
         );
 
+        jit_fct.set_variables(variables);
+
         jit_fct
     }
 
+    fn push_scope(&mut self) {
+        self.managed_stack.push_scope();
+        self.variable_scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.managed_stack.pop_scope(self.vm);
+
+        let end = self.asm.pos() as u32;
+
+        for idx in self.variable_scopes.pop().expect("no scope") {
+            self.variables[idx].end = end;
+        }
+    }
+
+    fn add_debug_variable(&mut self, id: VarId) {
+        if !self.vm.args.cmd_debug {
+            return;
+        }
+
+        let var = &self.src.vars[id];
+
+        self.variables.push(VariableInfo {
+            name: var.name,
+            ty: self.var_ty(id),
+            offset: self.var_offset(id),
+            start: self.asm.pos() as u32,
+            end: u32::max_value(),
+        });
+
+        let idx = self.variables.len() - 1;
+        self.variable_scopes.last_mut().expect("no scope").push(idx);
+    }
+
+    // Calls into the debugger before every statement when debugging.
+    fn emit_debug_hook(&mut self, pos: Position) {
+        if !self.vm.args.cmd_debug {
+            return;
+        }
+
+        let gcpoint = self.create_gcpoint();
+        self.asm.debug_hook(pos, gcpoint);
+    }
+
     fn store_register_params_on_stack(&mut self) {
         let mut reg_idx = 0;
         let mut freg_idx = 0;
@@ -183,6 +241,7 @@ where
 
             let slot_param = self.managed_stack.add_scope(var.ty, self.vm);
             assert!(self.var_to_slot.insert(var.id, slot_param).is_none());
+            self.add_debug_variable(var.id);
 
             self.asm
                 .store_mem(mode, Mem::Local(slot_param.offset()), dest);
@@ -205,6 +264,7 @@ where
             } else if let Some(tuple_id) = ty.tuple_id() {
                 let slot_param = self.managed_stack.add_scope(ty, self.vm);
                 assert!(self.var_to_slot.insert(varid, slot_param).is_none());
+                self.add_debug_variable(varid);
 
                 {
                     let var = &self.src.vars[varid];
@@ -238,6 +298,7 @@ where
 
                 let slot_param = self.managed_stack.add_scope(ty, self.vm);
                 assert!(self.var_to_slot.insert(varid, slot_param).is_none());
+                self.add_debug_variable(varid);
 
                 {
                     let var = &self.src.vars[varid];
@@ -252,6 +313,7 @@ where
 
                 let slot_param = self.managed_stack.add_scope(ty, self.vm);
                 assert!(self.var_to_slot.insert(varid, slot_param).is_none());
+                self.add_debug_variable(varid);
 
                 {
                     let var = &self.src.vars[varid];
@@ -265,6 +327,7 @@ where
                 // all other parameters are stored on stack, just use
                 // that offset
                 self.var_to_offset.insert(varid, param_offset);
+                self.add_debug_variable(varid);
                 param_offset = next_param_offset(param_offset, ty);
             }
         }
@@ -354,7 +417,7 @@ where
     }
 
     fn emit_stmt_for_array(&mut self, stmt: &'ast StmtForType) {
-        self.managed_stack.push_scope();
+        self.push_scope();
 
         let lbl_start = self.asm.create_label();
         let lbl_end = self.asm.create_label();
@@ -367,6 +430,7 @@ where
         let var_ty = self.var_ty(for_var_id);
         let var_slot = self.managed_stack.add_scope(var_ty, self.vm);
         assert!(self.var_to_slot.insert(for_var_id, var_slot).is_none());
+        self.add_debug_variable(for_var_id);
 
         // evaluate and store array
         self.emit_expr(&stmt.expr, REG_RESULT.into());
@@ -469,7 +533,7 @@ where
             this.asm.jump(lbl_start);
         });
 
-        self.managed_stack.pop_scope(&self.vm);
+        self.pop_scope();
 
         self.asm.bind_label(lbl_end);
     }
@@ -477,7 +541,7 @@ where
     fn emit_stmt_for_iterator(&mut self, stmt: &'ast StmtForType) {
         let for_type_info = self.src.map_fors.get(stmt.id).unwrap().clone();
 
-        self.managed_stack.push_scope();
+        self.push_scope();
 
         // emit: <iterator> = obj.makeIterator()
         let object_type = self.ty(stmt.expr.id());
@@ -527,6 +591,7 @@ where
         let var_ty = self.var_ty(for_var_id);
         let slot_var = self.managed_stack.add_scope(var_ty, self.vm);
         assert!(self.var_to_slot.insert(for_var_id, slot_var).is_none());
+        self.add_debug_variable(for_var_id);
 
         self.asm
             .var_store(slot_var.offset(), var_ty, dest.any_reg());
@@ -539,7 +604,7 @@ where
             this.asm.jump(lbl_start);
         });
 
-        self.managed_stack.pop_scope(&self.vm);
+        self.pop_scope();
 
         self.asm.bind_label(lbl_end);
     }
@@ -599,6 +664,7 @@ where
 
         let slot_var = self.managed_stack.add_scope(ty, self.vm);
         assert!(self.var_to_slot.insert(var, slot_var).is_none());
+        self.add_debug_variable(var);
 
         if let Some(value) = value {
             if let Some(tuple_id) = ty.tuple_id() {
@@ -623,6 +689,26 @@ where
         }
     }
 
+    // `narrow` is false for references in tuples, they are never compressed
+    fn emit_pre_barrier(&mut self, ty: BuiltinType, base: Reg, offset: i32, narrow: bool) {
+        if let Some(tuple_id) = ty.tuple_id() {
+            let subtypes = self.vm.tuples.lock().get(tuple_id);
+            let offsets = self
+                .vm
+                .tuples
+                .lock()
+                .get_tuple(tuple_id)
+                .offsets()
+                .to_owned();
+
+            for (&subtype, &subtype_offset) in subtypes.iter().zip(&offsets) {
+                self.emit_pre_barrier(subtype, base, offset + subtype_offset, false);
+            }
+        } else if ty.reference_type() {
+            self.asm.emit_pre_barrier(Mem::Base(base, offset), narrow);
+        }
+    }
+
     fn copy_tuple(&mut self, tuple_id: TupleId, dest: RegOrOffset, src: RegOrOffset) {
         let subtypes = self.vm.tuples.lock().get(tuple_id);
         let offsets = self
@@ -729,6 +815,7 @@ where
             ExprBlock(ref expr) => self.emit_block(expr, dest),
             ExprIf(ref expr) => self.emit_if(expr, dest),
             ExprTuple(ref expr) => self.emit_tuple(expr, dest),
+            ExprError(_) => unreachable!(),
         }
     }
 
@@ -826,7 +913,7 @@ where
     }
 
     fn emit_block(&mut self, block: &'ast ExprBlockType, dest: ExprStore) {
-        self.managed_stack.push_scope();
+        self.push_scope();
 
         for stmt in &block.stmts {
             self.visit_stmt(stmt);
@@ -836,7 +923,7 @@ where
             self.emit_expr(expr, dest);
         }
 
-        self.managed_stack.pop_scope(self.vm);
+        self.pop_scope();
     }
 
     fn emit_template(&mut self, e: &'ast ExprTemplateType, dest: Reg) {
@@ -851,7 +938,7 @@ where
 
         for part in &e.parts {
             if let Some(ref lit_str) = part.to_lit_str() {
-                self.emit_lit_str_value(&lit_str.value, REG_RESULT);
+                self.emit_lit_str_value(lit_str.id, &lit_str.value, REG_RESULT);
             } else {
                 let ty = self.ty(part.id());
 
@@ -1197,11 +1284,8 @@ where
         } else if field.ty.is_unit() {
             assert!(dest.is_none());
         } else {
-            self.asm.load_mem(
-                field.ty.mode(),
-                dest.any_reg(),
-                Mem::Base(src, field.offset),
-            );
+            self.asm
+                .load_field(field.ty, dest.any_reg(), Mem::Base(src, field.offset));
         }
     }
 
@@ -1250,11 +1334,11 @@ where
     }
 
     fn emit_lit_str(&mut self, lit: &'ast ExprLitStrType, dest: Reg) {
-        self.emit_lit_str_value(&lit.value, dest);
+        self.emit_lit_str_value(lit.id, &lit.value, dest);
     }
 
-    fn emit_lit_str_value(&mut self, lit_value: &str, dest: Reg) {
-        let handle = Str::from_buffer_in_perm(self.vm, lit_value.as_bytes());
+    fn emit_lit_str_value(&mut self, id: NodeId, lit_value: &str, dest: Reg) {
+        let handle = Str::from_literal(self.vm, self.fct.id, id.0, lit_value);
 
         let disp = self.asm.add_addr(handle.raw() as *const u8);
         let pos = self.asm.pos() as i32;
@@ -1673,6 +1757,10 @@ where
 
                 self.asm.test_if_nil_bailout(e.pos, REG_TMP1, Trap::NIL);
 
+                if self.vm.gc.needs_pre_barrier() {
+                    self.emit_pre_barrier(field.ty, REG_TMP1, field.offset, true);
+                }
+
                 let needs_write_barrier = if let Some(tuple_id) = field.ty.tuple_id() {
                     self.copy_tuple(
                         tuple_id,
@@ -1688,8 +1776,8 @@ where
                 } else if field.ty.is_unit() {
                     false
                 } else {
-                    self.asm.store_mem(
-                        field.ty.mode(),
+                    self.asm.store_field(
+                        field.ty,
                         Mem::Base(REG_TMP1, field.offset),
                         value.any_reg(),
                     );
@@ -2447,6 +2535,10 @@ where
             self.asm
                 .array_address(REG_TMP1, REG_TMP1, REG_TMP2, element_size);
 
+            if self.vm.gc.needs_pre_barrier() {
+                self.emit_pre_barrier(element_type, REG_TMP1, 0, false);
+            }
+
             self.copy_tuple(
                 tuple_id,
                 RegOrOffset::Reg(REG_TMP1),
@@ -2478,19 +2570,19 @@ where
             self.asm
                 .load_mem(mode, value.any_reg(), Mem::Local(slot_value.offset()));
 
-            self.asm.store_mem(
-                mode,
-                Mem::Index(array, index, mode.size(), offset_of_array_data()),
-                value.any_reg(),
-            );
+            let elem = self.asm.array_elem(mode, array, index);
+
+            if self.vm.gc.needs_pre_barrier() && element_type.reference_type() {
+                self.asm.emit_pre_barrier(elem, true);
+            }
+
+            self.asm
+                .store_array_elem(mode, array, index, value.any_reg());
 
             if self.vm.gc.needs_write_barrier() && element_type.reference_type() {
                 let card_table_offset = self.vm.gc.card_table_offset();
                 let scratch = self.asm.get_scratch();
-                self.asm.lea(
-                    *scratch,
-                    Mem::Index(array, index, mode.size(), offset_of_array_data()),
-                );
+                self.asm.lea(*scratch, elem);
                 self.asm.emit_barrier(*scratch, card_table_offset);
             }
         }
@@ -3259,7 +3351,8 @@ where
         );
 
         let header_size = Header::size() + mem::ptr_width();
-        let array_offset = header_size + array_idx as i32 * ty.size(self.vm);
+        let (element_size, _) = ClassDef::field_layout(self.vm, ty);
+        let array_offset = header_size + array_idx as i32 * element_size;
 
         let needs_write_barrier = if let Some(tuple_id) = ty.tuple_id() {
             self.copy_tuple(
@@ -3287,7 +3380,7 @@ where
             self.asm
                 .load_mem(mode, REG_TMP2.into(), Mem::Local(arg.offset()));
             self.asm
-                .store_mem(mode, Mem::Base(REG_TMP1, array_offset), REG_TMP2.into());
+                .store_field(ty, Mem::Base(REG_TMP1, array_offset), REG_TMP2.into());
             ty.reference_type()
         };
 
@@ -3373,7 +3466,13 @@ where
         let dest = REG_TMP1;
 
         let gcpoint = self.create_gcpoint();
-        self.asm.allocate(dest, alloc_size, pos, array_ref, gcpoint);
+        let site = if self.vm.args.alloc_site_tracking() {
+            Some(self.vm.gc.alloc_sites.add(self.fct.id, pos, cls_id))
+        } else {
+            None
+        };
+        self.asm
+            .allocate(dest, alloc_size, pos, array_ref, gcpoint, site.as_deref());
 
         // store gc object in temporary storage
         let temp_slot = self.managed_stack.add_temp(BuiltinType::Ptr, self.vm);
@@ -3399,7 +3498,8 @@ where
 
         // clear mark/fwdptr word in header
         assert!(Header::size() == 2 * mem::ptr_width());
-        self.asm.load_int_const(MachineMode::Ptr, temp, 0);
+        let header_word = site.map_or(0, |site| site.header_word());
+        self.asm.load_int_const(MachineMode::Ptr, temp, header_word);
         self.asm.store_mem(
             MachineMode::Ptr,
             Mem::Base(dest, mem::ptr_width()),
@@ -3716,6 +3816,8 @@ where
 
 impl<'a, 'ast> visit::Visitor<'ast> for AstCodeGen<'a, 'ast> {
     fn visit_stmt(&mut self, s: &'ast Stmt) {
+        self.emit_debug_hook(s.pos());
+
         match *s {
             StmtExpr(ref stmt) => self.emit_stmt_expr(stmt),
             StmtWhile(ref stmt) => self.emit_stmt_while(stmt),
@@ -3724,6 +3826,7 @@ impl<'a, 'ast> visit::Visitor<'ast> for AstCodeGen<'a, 'ast> {
             StmtBreak(ref stmt) => self.emit_stmt_break(stmt),
             StmtContinue(ref stmt) => self.emit_stmt_continue(stmt),
             StmtVar(ref stmt) => self.emit_stmt_var(stmt),
+            StmtError(_) => unreachable!(),
         }
     }
 
@@ -2,7 +2,7 @@ use std::mem;
 use std::ptr;
 
 use crate::bytecode::{self, BytecodeFunction, ConstPoolEntry, ConstPoolOpcode};
-use crate::compiler::codegen::should_emit_bytecode;
+use crate::compiler::codegen::{should_emit_bytecode, should_emit_graph};
 use crate::compiler::fct::{Code, JitDescriptor};
 use crate::gc::Address;
 use crate::handle::{root, Handle};
@@ -11,7 +11,7 @@ use crate::object::{
 };
 use crate::threads::THREAD;
 use crate::ty::TypeList;
-use crate::vm::{Fct, FctSrc, VM};
+use crate::vm::{Fct, FctDefId, FctSrc, VM};
 
 pub fn compile<'a, 'ast: 'a>(
     vm: &'a VM<'ast>,
@@ -20,6 +20,7 @@ pub fn compile<'a, 'ast: 'a>(
     cls_type_params: &TypeList,
     fct_type_params: &TypeList,
 ) -> Code {
+    let _compilation = vm.gc.start_boots_compilation();
     let bytecode_fct = bytecode::generate(vm, fct, src, cls_type_params, fct_type_params);
 
     if should_emit_bytecode(vm, fct) {
@@ -30,6 +31,11 @@ pub fn compile<'a, 'ast: 'a>(
     let compile_fct = vm.ensure_compiled(compile_fct_id);
 
     let encoded_compilation_info = root(allocate_compilation_info(vm, &bytecode_fct));
+    write_emit_graph(
+        vm,
+        encoded_compilation_info.direct(),
+        should_emit_graph(vm, fct),
+    );
 
     let tld_address = THREAD.with(|thread| {
         let thread = thread.borrow();
@@ -77,6 +83,31 @@ pub fn bytecode(vm: &VM, name: &str) -> Ref<Obj> {
     allocate_compilation_info(vm, &bytecode_fct)
 }
 
+pub fn bytecode_for_fct_def(vm: &VM, fct_def_id: FctDefId) -> Ref<Obj> {
+    let fct_def = vm.fct_defs.idx(fct_def_id);
+    let fct_def = fct_def.read();
+
+    let fct = vm.fcts.idx(fct_def.fct_id);
+    let fct = fct.read();
+
+    if !fct.is_src() {
+        return Ref::null();
+    }
+
+    let src = fct.src();
+    let src = src.read();
+
+    let bytecode_fct = bytecode::generate(
+        vm,
+        &*fct,
+        &*src,
+        &fct_def.cls_type_params,
+        &fct_def.fct_type_params,
+    );
+
+    allocate_compilation_info(vm, &bytecode_fct)
+}
+
 fn allocate_compilation_info(vm: &VM, bytecode_fct: &BytecodeFunction) -> Ref<Obj> {
     let bytecode_array = root(byte_array_from_buffer(vm, bytecode_fct.code()));
     let constpool_array = root(allocate_constpool_array(vm, &bytecode_fct));
@@ -91,6 +122,13 @@ fn allocate_compilation_info(vm: &VM, bytecode_fct: &BytecodeFunction) -> Ref<Ob
     )
 }
 
+fn write_emit_graph(vm: &VM, obj: Ref<Obj>, emit_graph: bool) {
+    let cls_id = vm.cls_def_by_name("EncodedCompilationInfo");
+
+    let fid = vm.field_in_class(cls_id, "emitGraph");
+    object::write_bool(vm, obj, cls_id, fid, emit_graph);
+}
+
 fn allocate_registers_array(vm: &VM, fct: &BytecodeFunction) -> Ref<Int32Array> {
     let mut array = int_array_alloc_heap(vm, fct.registers().len());
 
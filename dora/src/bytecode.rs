@@ -1,3 +1,4 @@
+pub mod checks;
 pub mod data;
 pub mod dumper;
 pub mod generator;
@@ -9,6 +10,7 @@ pub mod writer;
 #[cfg(test)]
 mod tests;
 
+pub use checks::{find_redundant_checks, RedundantChecks};
 pub use data::*;
 pub use dumper::dump;
 pub use generator::{generate, generate_fct};
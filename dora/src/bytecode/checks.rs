@@ -0,0 +1,1483 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::bytecode::{
+    read, BytecodeFunction, BytecodeOffset, BytecodeVisitor, ConstPoolIdx, Register,
+};
+use crate::vm::{ClassDefId, FctDefId, FieldId, GlobalId, TupleId};
+
+// Largest constant that can be added to an index known to be smaller than
+// an array length without overflowing.
+const MAX_INDEX_INCREMENT: i64 = 1 << 32;
+
+/// Nil and bounds checks that are proven to never fail. Checks at all other
+/// offsets need to be emitted to keep `Trap::NIL` and
+/// `Trap::INDEX_OUT_OF_BOUNDS` semantics.
+pub struct RedundantChecks {
+    nil_checks: HashSet<BytecodeOffset>,
+    bound_checks: HashSet<BytecodeOffset>,
+}
+
+impl RedundantChecks {
+    pub fn is_nil_check_redundant(&self, offset: BytecodeOffset) -> bool {
+        self.nil_checks.contains(&offset)
+    }
+
+    pub fn is_bound_check_redundant(&self, offset: BytecodeOffset) -> bool {
+        self.bound_checks.contains(&offset)
+    }
+}
+
+/// Forward dataflow analysis over the bytecode that tracks registers known
+/// to be non-nil and indices known to be within array bounds. Indices are
+/// in bounds when they are non-negative and smaller than the array length,
+/// e.g. in canonical loops like `while i < arr.size() { arr(i); i = i + 1L; }`.
+pub fn find_redundant_checks(bc: &BytecodeFunction) -> RedundantChecks {
+    let mut collector = InstCollector {
+        bc,
+        insts: Vec::new(),
+        offset: BytecodeOffset(0),
+    };
+    read(bc.code(), &mut collector);
+
+    let insts = collector.insts;
+    let index: HashMap<BytecodeOffset, usize> = insts
+        .iter()
+        .enumerate()
+        .map(|(idx, &(offset, _))| (offset, idx))
+        .collect();
+
+    let mut states: Vec<Option<Facts>> = vec![None; insts.len()];
+    let mut worklist = Vec::new();
+
+    if !insts.is_empty() {
+        states[0] = Some(Facts::new());
+        worklist.push(0);
+    }
+
+    while let Some(idx) = worklist.pop() {
+        let (_, ref inst) = insts[idx];
+        let mut facts = states[idx].clone().expect("unreachable instruction");
+        facts.transfer(inst);
+
+        let mut successors = Vec::new();
+
+        match *inst {
+            Inst::Jump { target } => {
+                successors.push((index[&target], facts));
+            }
+
+            Inst::JumpIf {
+                cond,
+                target,
+                jump_if_true,
+            } => {
+                let mut taken = facts.clone();
+                let mut fallthrough = facts;
+
+                if jump_if_true {
+                    taken.assume(cond);
+                } else {
+                    fallthrough.assume(cond);
+                }
+
+                successors.push((index[&target], taken));
+                successors.push((idx + 1, fallthrough));
+            }
+
+            Inst::Return => {}
+
+            _ => {
+                successors.push((idx + 1, facts));
+            }
+        }
+
+        for (succ, facts) in successors {
+            let changed = match states[succ] {
+                Some(ref mut existing) => existing.meet(&facts),
+                None => {
+                    states[succ] = Some(facts);
+                    true
+                }
+            };
+
+            if changed && !worklist.contains(&succ) {
+                worklist.push(succ);
+            }
+        }
+    }
+
+    let mut result = RedundantChecks {
+        nil_checks: HashSet::new(),
+        bound_checks: HashSet::new(),
+    };
+
+    for (&(offset, ref inst), state) in insts.iter().zip(states.iter()) {
+        let facts = match *state {
+            Some(ref facts) => facts,
+            None => continue,
+        };
+
+        match *inst {
+            Inst::NilCheck { obj, .. } | Inst::ArrayLength { arr: obj, .. } => {
+                if facts.non_nil.contains(&obj) {
+                    result.nil_checks.insert(offset);
+                }
+            }
+
+            Inst::ArrayAccess { arr, idx, .. } => {
+                if facts.non_nil.contains(&arr) {
+                    result.nil_checks.insert(offset);
+                }
+
+                if facts.is_in_bounds(idx, arr) {
+                    result.bound_checks.insert(offset);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    result
+}
+
+#[derive(Clone)]
+struct Facts {
+    // registers that are known to be non-nil
+    non_nil: HashSet<Register>,
+
+    // Int64 registers that are known to be non-negative
+    non_negative: HashSet<Register>,
+
+    // Int64 registers holding a constant in 0..=MAX_INDEX_INCREMENT
+    small_constants: HashSet<Register>,
+
+    // (idx, arr): idx is smaller than the length of arr
+    less_than_length: HashSet<(Register, Register)>,
+
+    // register holds the length of the array
+    length_of: HashMap<Register, Register>,
+
+    // condition register is true iff (idx, arr) is in less_than_length
+    conditions: HashMap<Register, (Register, Register)>,
+}
+
+impl Facts {
+    fn new() -> Facts {
+        Facts {
+            non_nil: HashSet::new(),
+            non_negative: HashSet::new(),
+            small_constants: HashSet::new(),
+            less_than_length: HashSet::new(),
+            length_of: HashMap::new(),
+            conditions: HashMap::new(),
+        }
+    }
+
+    fn is_in_bounds(&self, idx: Register, arr: Register) -> bool {
+        self.non_negative.contains(&idx) && self.less_than_length.contains(&(idx, arr))
+    }
+
+    fn assume(&mut self, cond: Register) {
+        if let Some(&fact) = self.conditions.get(&cond) {
+            self.less_than_length.insert(fact);
+        }
+    }
+
+    // Intersects facts with the facts of another incoming edge,
+    // returns true if facts changed.
+    fn meet(&mut self, other: &Facts) -> bool {
+        let before = (
+            self.non_nil.len(),
+            self.non_negative.len(),
+            self.small_constants.len(),
+            self.less_than_length.len(),
+            self.length_of.len(),
+            self.conditions.len(),
+        );
+
+        self.non_nil.retain(|reg| other.non_nil.contains(reg));
+        self.non_negative
+            .retain(|reg| other.non_negative.contains(reg));
+        self.small_constants
+            .retain(|reg| other.small_constants.contains(reg));
+        self.less_than_length
+            .retain(|fact| other.less_than_length.contains(fact));
+        self.length_of
+            .retain(|reg, arr| other.length_of.get(reg) == Some(arr));
+        self.conditions
+            .retain(|reg, fact| other.conditions.get(reg) == Some(fact));
+
+        let after = (
+            self.non_nil.len(),
+            self.non_negative.len(),
+            self.small_constants.len(),
+            self.less_than_length.len(),
+            self.length_of.len(),
+            self.conditions.len(),
+        );
+
+        before != after
+    }
+
+    // Removes all facts about the previous value of the register.
+    fn kill(&mut self, reg: Register) {
+        self.non_nil.remove(&reg);
+        self.non_negative.remove(&reg);
+        self.small_constants.remove(&reg);
+        self.less_than_length
+            .retain(|&(idx, arr)| idx != reg && arr != reg);
+        self.length_of
+            .retain(|&len, &mut arr| len != reg && arr != reg);
+        self.conditions
+            .retain(|&cond, &mut (idx, arr)| cond != reg && idx != reg && arr != reg);
+    }
+
+    fn transfer(&mut self, inst: &Inst) {
+        match *inst {
+            Inst::Def { dest } => {
+                self.kill(dest);
+            }
+
+            Inst::NonNilDef { dest } => {
+                self.kill(dest);
+                self.non_nil.insert(dest);
+            }
+
+            Inst::ConstInt64 { dest, value } => {
+                self.kill(dest);
+
+                if value >= 0 {
+                    self.non_negative.insert(dest);
+                }
+
+                if value >= 0 && value <= MAX_INDEX_INCREMENT {
+                    self.small_constants.insert(dest);
+                }
+            }
+
+            Inst::MovPtr { dest, src } => {
+                if dest != src {
+                    let non_nil = self.non_nil.contains(&src);
+                    self.kill(dest);
+
+                    if non_nil {
+                        self.non_nil.insert(dest);
+                    }
+                }
+            }
+
+            Inst::MovInt64 { dest, src } => {
+                if dest != src {
+                    self.kill(dest);
+
+                    if self.non_negative.contains(&src) {
+                        self.non_negative.insert(dest);
+                    }
+
+                    if self.small_constants.contains(&src) {
+                        self.small_constants.insert(dest);
+                    }
+
+                    let arrays: Vec<Register> = self
+                        .less_than_length
+                        .iter()
+                        .filter(|&&(idx, _)| idx == src)
+                        .map(|&(_, arr)| arr)
+                        .collect();
+
+                    for arr in arrays {
+                        self.less_than_length.insert((dest, arr));
+                    }
+
+                    if let Some(&arr) = self.length_of.get(&src) {
+                        self.length_of.insert(dest, arr);
+                    }
+                }
+            }
+
+            Inst::AddInt64 { dest, lhs, rhs } => {
+                // idx + c with 0 <= idx < length and small c can't overflow
+                let non_negative = self.is_bounded_index(lhs) && self.is_small_constant(rhs)
+                    || self.is_bounded_index(rhs) && self.is_small_constant(lhs);
+                self.kill(dest);
+
+                if non_negative {
+                    self.non_negative.insert(dest);
+                }
+            }
+
+            Inst::ArrayLength { dest, arr } => {
+                self.non_nil.insert(arr);
+                self.kill(dest);
+                self.non_negative.insert(dest);
+                self.length_of.insert(dest, arr);
+            }
+
+            Inst::TestLtInt64 { dest, lhs, rhs } => {
+                let fact = self.length_of.get(&rhs).map(|&arr| (lhs, arr));
+                self.kill(dest);
+
+                if let Some(fact) = fact {
+                    self.conditions.insert(dest, fact);
+                }
+            }
+
+            Inst::TestGtInt64 { dest, lhs, rhs } => {
+                let fact = self.length_of.get(&lhs).map(|&arr| (rhs, arr));
+                self.kill(dest);
+
+                if let Some(fact) = fact {
+                    self.conditions.insert(dest, fact);
+                }
+            }
+
+            Inst::NilCheck { obj, dest } => {
+                self.non_nil.insert(obj);
+
+                if let Some(dest) = dest {
+                    self.kill(dest);
+                }
+            }
+
+            Inst::ArrayAccess { arr, idx, dest } => {
+                // execution only continues if the checks succeeded
+                self.non_nil.insert(arr);
+                self.non_negative.insert(idx);
+                self.less_than_length.insert((idx, arr));
+
+                if let Some(dest) = dest {
+                    self.kill(dest);
+                }
+            }
+
+            Inst::Other | Inst::Jump { .. } | Inst::JumpIf { .. } | Inst::Return => {}
+        }
+    }
+
+    fn is_bounded_index(&self, reg: Register) -> bool {
+        self.non_negative.contains(&reg) && self.less_than_length.iter().any(|&(idx, _)| idx == reg)
+    }
+
+    fn is_small_constant(&self, reg: Register) -> bool {
+        self.small_constants.contains(&reg)
+    }
+}
+
+enum Inst {
+    Def {
+        dest: Register,
+    },
+    NonNilDef {
+        dest: Register,
+    },
+    ConstInt64 {
+        dest: Register,
+        value: i64,
+    },
+    MovPtr {
+        dest: Register,
+        src: Register,
+    },
+    MovInt64 {
+        dest: Register,
+        src: Register,
+    },
+    AddInt64 {
+        dest: Register,
+        lhs: Register,
+        rhs: Register,
+    },
+    TestLtInt64 {
+        dest: Register,
+        lhs: Register,
+        rhs: Register,
+    },
+    TestGtInt64 {
+        dest: Register,
+        lhs: Register,
+        rhs: Register,
+    },
+    ArrayLength {
+        dest: Register,
+        arr: Register,
+    },
+    NilCheck {
+        obj: Register,
+        dest: Option<Register>,
+    },
+    ArrayAccess {
+        arr: Register,
+        idx: Register,
+        dest: Option<Register>,
+    },
+    Jump {
+        target: BytecodeOffset,
+    },
+    JumpIf {
+        cond: Register,
+        target: BytecodeOffset,
+        jump_if_true: bool,
+    },
+    Return,
+    Other,
+}
+
+struct InstCollector<'a> {
+    bc: &'a BytecodeFunction,
+    insts: Vec<(BytecodeOffset, Inst)>,
+    offset: BytecodeOffset,
+}
+
+impl<'a> InstCollector<'a> {
+    fn add(&mut self, inst: Inst) {
+        self.insts.push((self.offset, inst));
+    }
+
+    fn def(&mut self, dest: Register) {
+        self.add(Inst::Def { dest });
+    }
+
+    fn other(&mut self) {
+        self.add(Inst::Other);
+    }
+
+    fn load_field(&mut self, dest: Register, obj: Register) {
+        self.add(Inst::NilCheck {
+            obj,
+            dest: Some(dest),
+        });
+    }
+
+    fn store_field(&mut self, obj: Register) {
+        self.add(Inst::NilCheck { obj, dest: None });
+    }
+
+    fn load_array(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.add(Inst::ArrayAccess {
+            arr,
+            idx,
+            dest: Some(dest),
+        });
+    }
+
+    fn store_array(&mut self, arr: Register, idx: Register) {
+        self.add(Inst::ArrayAccess {
+            arr,
+            idx,
+            dest: None,
+        });
+    }
+
+    fn jump(&mut self, offset: i64) {
+        let target = BytecodeOffset((self.offset.to_u32() as i64 + offset) as u32);
+        self.add(Inst::Jump { target });
+    }
+
+    fn jump_if(&mut self, cond: Register, offset: u32, jump_if_true: bool) {
+        let target = BytecodeOffset(self.offset.to_u32() + offset);
+        self.add(Inst::JumpIf {
+            cond,
+            target,
+            jump_if_true,
+        });
+    }
+
+    fn const_offset(&self, idx: ConstPoolIdx) -> u32 {
+        self.bc.const_pool(idx).to_int32().expect("int expected") as u32
+    }
+}
+
+impl<'a> BytecodeVisitor for InstCollector<'a> {
+    fn visit_instruction(&mut self, offset: BytecodeOffset) {
+        self.offset = offset;
+    }
+    fn visit_add_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_add_int64(&mut self, dest: Register, lhs: Register, rhs: Register) {
+        self.add(Inst::AddInt64 { dest, lhs, rhs });
+    }
+    fn visit_add_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_add_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_sub_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_sub_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_sub_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_sub_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_neg_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_neg_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_neg_float32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_neg_float64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mul_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_mul_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_mul_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_mul_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_div_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_div_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_div_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_div_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_mod_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_mod_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_and_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_and_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_or_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_or_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_xor_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_xor_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_not_bool(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_not_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_not_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_shl_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_shr_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_sar_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_shl_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_shr_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_sar_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_rol_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_ror_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_rol_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_ror_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_reinterpret_float32_as_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_reinterpret_int32_as_float32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_reinterpret_float64_as_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_reinterpret_int64_as_float64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_extend_byte_to_char(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_extend_byte_to_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_extend_byte_to_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_extend_int32_to_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_extend_char_to_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_cast_char_to_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_cast_int32_to_uint8(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_cast_int32_to_char(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_cast_int64_to_uint8(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_cast_int64_to_char(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_cast_int64_to_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_convert_int32_to_float32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_convert_int32_to_float64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_convert_int64_to_float32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_convert_int64_to_float64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_truncate_float32_to_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_truncate_float32_to_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_truncate_float64_to_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_truncate_float64_to_int64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_promote_float32_to_float64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_demote_float64_to_float32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_instance_of(&mut self, dest: Register, _src: Register, _cls_id: ClassDefId) {
+        self.def(dest);
+    }
+    fn visit_checked_cast(&mut self, _src: Register, _cls_id: ClassDefId) {
+        self.other();
+    }
+    fn visit_mov_bool(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mov_uint8(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mov_char(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mov_int32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mov_int64(&mut self, dest: Register, src: Register) {
+        self.add(Inst::MovInt64 { dest, src });
+    }
+    fn visit_mov_float32(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mov_float64(&mut self, dest: Register, _src: Register) {
+        self.def(dest);
+    }
+    fn visit_mov_ptr(&mut self, dest: Register, src: Register) {
+        self.add(Inst::MovPtr { dest, src });
+    }
+    fn visit_mov_tuple(&mut self, dest: Register, _src: Register, _tuple_id: TupleId) {
+        self.def(dest);
+    }
+    fn visit_load_tuple_element(
+        &mut self,
+        dest: Register,
+        _src: Register,
+        _tuple_id: TupleId,
+        _element: u32,
+    ) {
+        self.def(dest);
+    }
+    fn visit_store_tuple_element(
+        &mut self,
+        _src: Register,
+        dest: Register,
+        _tuple_id: TupleId,
+        _element: u32,
+    ) {
+        self.def(dest);
+    }
+    fn visit_load_field_bool(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_uint8(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_char(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_int32(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_int64(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_float32(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_float64(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_ptr(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_load_field_tuple(
+        &mut self,
+        dest: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.load_field(dest, obj);
+    }
+    fn visit_store_field_bool(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_uint8(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_char(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_int32(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_int64(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_float32(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_float64(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_ptr(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_store_field_tuple(
+        &mut self,
+        _src: Register,
+        obj: Register,
+        _cls: ClassDefId,
+        _field: FieldId,
+    ) {
+        self.store_field(obj);
+    }
+    fn visit_load_global_bool(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_uint8(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_char(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_int32(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_int64(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_float32(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_float64(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_ptr(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_load_global_tuple(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_store_global_bool(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_uint8(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_char(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_int32(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_int64(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_float32(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_float64(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_ptr(&mut self, _src: Register, _glob: GlobalId) {
+        self.other();
+    }
+    fn visit_store_global_tuple(&mut self, dest: Register, _glob: GlobalId) {
+        self.def(dest);
+    }
+    fn visit_push_register(&mut self, _src: Register) {
+        self.other();
+    }
+    fn visit_const_nil(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_true(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_false(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_zero_uint8(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_zero_char(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_zero_int32(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_zero_int64(&mut self, dest: Register) {
+        self.add(Inst::ConstInt64 { dest, value: 0 });
+    }
+    fn visit_const_zero_float32(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_zero_float64(&mut self, dest: Register) {
+        self.def(dest);
+    }
+    fn visit_const_char(&mut self, dest: Register, _value: ConstPoolIdx) {
+        self.def(dest);
+    }
+    fn visit_const_uint8(&mut self, dest: Register, _value: u8) {
+        self.def(dest);
+    }
+    fn visit_const_int32(&mut self, dest: Register, _value: ConstPoolIdx) {
+        self.def(dest);
+    }
+    fn visit_const_int64(&mut self, dest: Register, idx: ConstPoolIdx) {
+        let value = self.bc.const_pool(idx).to_int64().expect("int64 expected");
+        self.add(Inst::ConstInt64 { dest, value });
+    }
+    fn visit_const_float32(&mut self, dest: Register, _value: ConstPoolIdx) {
+        self.def(dest);
+    }
+    fn visit_const_float64(&mut self, dest: Register, _value: ConstPoolIdx) {
+        self.def(dest);
+    }
+    fn visit_const_string(&mut self, dest: Register, _value: ConstPoolIdx) {
+        self.add(Inst::NonNilDef { dest });
+    }
+    fn visit_test_eq_ptr(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_ptr(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_bool(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_bool(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_uint8(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_uint8(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_gt_uint8(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ge_uint8(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_lt_uint8(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_le_uint8(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_char(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_char(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_gt_char(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ge_char(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_lt_char(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_le_char(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_enum(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_enum(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_gt_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ge_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_lt_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_le_int32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_gt_int64(&mut self, dest: Register, lhs: Register, rhs: Register) {
+        self.add(Inst::TestGtInt64 { dest, lhs, rhs });
+    }
+    fn visit_test_ge_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_lt_int64(&mut self, dest: Register, lhs: Register, rhs: Register) {
+        self.add(Inst::TestLtInt64 { dest, lhs, rhs });
+    }
+    fn visit_test_le_int64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_gt_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ge_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_lt_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_le_float32(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_eq_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ne_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_gt_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_ge_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_lt_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_test_le_float64(&mut self, dest: Register, _lhs: Register, _rhs: Register) {
+        self.def(dest);
+    }
+    fn visit_assert(&mut self, _value: Register) {
+        self.other();
+    }
+    fn visit_jump_if_false(&mut self, opnd: Register, offset: u32) {
+        self.jump_if(opnd, offset, false);
+    }
+    fn visit_jump_if_false_const(&mut self, opnd: Register, idx: ConstPoolIdx) {
+        let offset = self.const_offset(idx);
+        self.jump_if(opnd, offset, false);
+    }
+    fn visit_jump_if_true(&mut self, opnd: Register, offset: u32) {
+        self.jump_if(opnd, offset, true);
+    }
+    fn visit_jump_if_true_const(&mut self, opnd: Register, idx: ConstPoolIdx) {
+        let offset = self.const_offset(idx);
+        self.jump_if(opnd, offset, true);
+    }
+    fn visit_jump_loop(&mut self, offset: u32) {
+        self.jump(-(offset as i64));
+    }
+    fn visit_jump(&mut self, offset: u32) {
+        self.jump(offset as i64);
+    }
+    fn visit_jump_const(&mut self, idx: ConstPoolIdx) {
+        let offset = self.const_offset(idx);
+        self.jump(offset as i64);
+    }
+    fn visit_invoke_direct_void(&mut self, _fctdef: FctDefId) {
+        self.other();
+    }
+    fn visit_invoke_direct_bool(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_uint8(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_char(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_int32(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_int64(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_float32(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_float64(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_ptr(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_direct_tuple(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_void(&mut self, _fctdef: FctDefId) {
+        self.other();
+    }
+    fn visit_invoke_virtual_bool(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_uint8(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_char(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_int32(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_int64(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_float32(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_float64(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_ptr(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_virtual_tuple(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_void(&mut self, _fctdef: FctDefId) {
+        self.other();
+    }
+    fn visit_invoke_static_bool(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_uint8(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_char(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_int32(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_int64(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_float32(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_float64(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_ptr(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_invoke_static_tuple(&mut self, dest: Register, _fctdef: FctDefId) {
+        self.def(dest);
+    }
+    fn visit_new_object(&mut self, dest: Register, _cls: ClassDefId) {
+        self.add(Inst::NonNilDef { dest });
+    }
+    fn visit_new_array(&mut self, dest: Register, _cls: ClassDefId, _length: Register) {
+        self.add(Inst::NonNilDef { dest });
+    }
+    fn visit_new_tuple(&mut self, dest: Register, _tuple: TupleId) {
+        self.def(dest);
+    }
+    fn visit_nil_check(&mut self, obj: Register) {
+        self.add(Inst::NilCheck { obj, dest: None });
+    }
+    fn visit_array_length(&mut self, dest: Register, arr: Register) {
+        self.add(Inst::ArrayLength { dest, arr });
+    }
+    fn visit_array_bound_check(&mut self, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_load_array_bool(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_uint8(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_char(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_int32(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_int64(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_float32(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_float64(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_ptr(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_load_array_tuple(&mut self, dest: Register, arr: Register, idx: Register) {
+        self.load_array(dest, arr, idx);
+    }
+    fn visit_store_array_bool(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_uint8(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_char(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_int32(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_int64(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_float32(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_float64(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_ptr(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_store_array_tuple(&mut self, _src: Register, arr: Register, idx: Register) {
+        self.store_array(arr, idx);
+    }
+    fn visit_ret_void(&mut self) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_bool(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_uint8(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_char(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_int32(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_int64(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_float32(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_float64(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_ptr(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+    fn visit_ret_tuple(&mut self, _opnd: Register) {
+        self.add(Inst::Return);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode;
+    use crate::test;
+    use crate::ty::TypeList;
+
+    fn redundant_checks(code: &'static str) -> (Vec<u32>, Vec<u32>) {
+        test::parse(code, |vm| {
+            let fct_id = vm.fct_by_name("f").expect("no function `f`.");
+            let tp = TypeList::empty();
+            let fct = bytecode::generate_fct(vm, fct_id, &tp, &tp);
+            let checks = find_redundant_checks(&fct);
+
+            let mut nil_checks: Vec<u32> = checks.nil_checks.iter().map(|o| o.to_u32()).collect();
+            let mut bound_checks: Vec<u32> =
+                checks.bound_checks.iter().map(|o| o.to_u32()).collect();
+            nil_checks.sort();
+            bound_checks.sort();
+
+            (nil_checks, bound_checks)
+        })
+    }
+
+    #[test]
+    fn check_canonical_loop() {
+        // 14: LoadArrayInt32 r5, r0, r1
+        let (nil_checks, bound_checks) = redundant_checks(
+            "fun f(arr: Array[Int32]) -> Int32 {
+                var i = 0L;
+                var s = 0;
+                while i < arr.size() {
+                    s = s + arr(i);
+                    i = i + 1L;
+                }
+                s
+            }",
+        );
+        assert_eq!(vec![14], nil_checks);
+        assert_eq!(vec![14], bound_checks);
+    }
+
+    #[test]
+    fn check_loop_with_modified_index() {
+        let (_, bound_checks) = redundant_checks(
+            "fun f(arr: Array[Int32]) -> Int32 {
+                var i = 0L;
+                var s = 0;
+                while i < arr.size() {
+                    i = i - 1L;
+                    s = s + arr(i);
+                    i = i + 2L;
+                }
+                s
+            }",
+        );
+        assert!(bound_checks.is_empty());
+    }
+
+    #[test]
+    fn check_loop_with_negative_start() {
+        let (_, bound_checks) = redundant_checks(
+            "fun f(arr: Array[Int32]) -> Int32 {
+                var i = -1L;
+                var s = 0;
+                while i < arr.size() {
+                    s = s + arr(i);
+                    i = i + 1L;
+                }
+                s
+            }",
+        );
+        assert!(bound_checks.is_empty());
+    }
+
+    #[test]
+    fn check_loop_over_other_array() {
+        let (_, bound_checks) = redundant_checks(
+            "fun f(a: Array[Int32], b: Array[Int32]) -> Int32 {
+                var i = 0L;
+                var s = 0;
+                while i < a.size() {
+                    s = s + b(i);
+                    i = i + 1L;
+                }
+                s
+            }",
+        );
+        assert!(bound_checks.is_empty());
+    }
+
+    #[test]
+    fn check_repeated_access() {
+        let (nil_checks, bound_checks) = redundant_checks(
+            "fun f(arr: Array[Int32], i: Int64) -> Int32 {
+                arr(i) + arr(i)
+            }",
+        );
+        assert_eq!(1, nil_checks.len());
+        assert_eq!(1, bound_checks.len());
+    }
+
+    #[test]
+    fn check_fresh_object() {
+        let (nil_checks, _) = redundant_checks(
+            "class Foo(let x: Int32)
+            fun f() -> Int32 {
+                let foo = Foo(1);
+                foo.x
+            }",
+        );
+        assert_eq!(1, nil_checks.len());
+    }
+
+    #[test]
+    fn check_argument_needs_nil_check() {
+        let (nil_checks, _) = redundant_checks(
+            "class Foo(let x: Int32)
+            fun f(foo: Foo) -> Int32 {
+                foo.x
+            }",
+        );
+        assert!(nil_checks.is_empty());
+    }
+}
@@ -529,7 +529,7 @@ impl BytecodeOpcode {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Register(pub usize);
 
 impl Register {
@@ -138,6 +138,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             StmtVar(ref stmt) => self.visit_stmt_var(stmt),
             StmtWhile(ref stmt) => self.visit_stmt_while(stmt),
             StmtFor(ref stmt) => self.visit_stmt_for(stmt),
+            StmtError(_) => unreachable!(),
         }
     }
 
@@ -342,6 +343,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
             ExprNil(ref nil) => self.visit_expr_nil(nil, dest),
             ExprTuple(ref tuple) => self.visit_expr_tuple(tuple, dest),
             ExprLambda(_) => unimplemented!(),
+            ExprError(_) => unreachable!(),
         }
     }
 
@@ -913,7 +915,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
 
                 if is_super_call {
                     self.emit_invoke_direct(return_type, return_reg, fct_def_id);
-                } else if fct.is_virtual() {
+                } else if fct.is_virtual() && !self.devirtualize(fct) {
                     self.emit_invoke_virtual(return_type, return_reg, fct_def_id);
                 } else if arg_bytecode_types[0] != BytecodeType::Ptr {
                     self.emit_invoke_static(return_type, return_reg, fct_def_id);
@@ -932,7 +934,7 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
                 self.emit_invoke_static(return_type, return_reg, fct_def_id);
             }
             CallType::Expr(_, _) => {
-                if fct.is_virtual() {
+                if fct.is_virtual() && !self.devirtualize(fct) {
                     self.emit_invoke_virtual(return_type, return_reg, fct_def_id);
                 } else if arg_bytecode_types[0] != BytecodeType::Ptr {
                     self.emit_invoke_static(return_type, return_reg, fct_def_id);
@@ -948,6 +950,20 @@ impl<'a, 'ast> AstBytecodeGen<'a, 'ast> {
         }
     }
 
+    // Virtual methods without any override have a single implementation
+    // and can be invoked directly. This function is recorded as dependent
+    // on that assumption.
+    fn devirtualize(&self, fct: &Fct) -> bool {
+        let mut cha = self.vm.cha.write();
+
+        if cha.has_single_implementation(fct) {
+            cha.add_dependency(fct.id, self.fct.id);
+            true
+        } else {
+            false
+        }
+    }
+
     fn emit_call_result(
         &mut self,
         call_type: &CallType,
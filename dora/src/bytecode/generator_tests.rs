@@ -2288,7 +2288,10 @@ fn gen_virtual_method_call_void_check_correct_self() {
             @open @abstract class Bar {
                 @open @abstract fun g();
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g() {}
+            }
+            class Baz : Foo {
                 @override fun g() {}
             }
             ",
@@ -2310,7 +2313,10 @@ fn gen_virtual_method_call_void_with_0_args() {
             @open @abstract class Bar {
                 @open @abstract fun g();
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g() {}
+            }
+            class Baz : Foo {
                 @override fun g() {}
             }
             ",
@@ -2332,7 +2338,10 @@ fn gen_virtual_method_call_void_with_1_arg() {
             @open @abstract class Bar {
                 @open @abstract fun g(a: Int32);
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g(a: Int32) {}
+            }
+            class Baz : Foo {
                 @override fun g(a: Int32) {}
             }
             ",
@@ -2360,7 +2369,10 @@ fn gen_virtual_method_call_void_with_3_args() {
             @open @abstract class Bar {
                 @open @abstract fun g(a: Int32, b: Int32, c: Int32);
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g(a: Int32, b: Int32, c: Int32) {}
+            }
+            class Baz : Foo {
                 @override fun g(a: Int32, b: Int32, c: Int32) {}
             }
             ",
@@ -2392,7 +2404,10 @@ fn gen_virtual_method_call_int_with_0_args() {
             @open @abstract class Bar {
                 @open @abstract fun g() -> Int32;
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g() -> Int32 { 1 }
+            }
+            class Baz : Foo {
                 @override fun g() -> Int32 { 1 }
             }
             ",
@@ -2418,7 +2433,10 @@ fn gen_virtual_method_call_int_with_1_arg() {
             @open @abstract class Bar {
                 @open @abstract fun g(a: Int32) -> Int32;
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g(a: Int32) -> Int32 { 1 }
+            }
+            class Baz : Foo {
                 @override fun g(a: Int32) -> Int32 { 1 }
             }
             ",
@@ -2446,7 +2464,10 @@ fn gen_virtual_method_call_int_with_3_args() {
             @open @abstract class Bar {
                 @open @abstract fun g(a: Int32, b: Int32, c: Int32) -> Int32;
             }
-            class Foo : Bar {
+            @open class Foo : Bar {
+                @open @override fun g(a: Int32, b: Int32, c: Int32) -> Int32 { 1 }
+            }
+            class Baz : Foo {
                 @override fun g(a: Int32, b: Int32, c: Int32) -> Int32 { 1 }
             }
             ",
@@ -2470,6 +2491,59 @@ fn gen_virtual_method_call_int_with_3_args() {
     );
 }
 
+#[test]
+fn gen_devirtualized_method_call() {
+    gen(
+        "
+            fun f(foo: Foo) { foo.g(); }
+            @open @abstract class Bar {
+                @open @abstract fun g();
+            }
+            class Foo : Bar {
+                @override fun g() {}
+            }
+            ",
+        |vm, code| {
+            let fct_id = vm
+                .cls_method_def_by_name("Foo", "g", false)
+                .expect("g not found");
+            let expected = vec![PushRegister(r(0)), InvokeDirectVoid(fct_id), RetVoid];
+            assert_eq!(expected, code);
+
+            let g = vm.cls_method_by_name("Foo", "g", false).unwrap();
+            let f = vm.fct_by_name("f").unwrap();
+            assert_eq!(&[f], vm.cha.read().dependencies(g));
+        },
+    );
+}
+
+#[test]
+fn gen_devirtualized_method_call_in_open_class() {
+    gen(
+        "
+            fun f(foo: Foo) -> Int32 { foo.g() }
+            @open class Foo {
+                @open fun g() -> Int32 { 1 }
+                @open fun h() -> Int32 { 2 }
+            }
+            class Baz : Foo {
+                @override fun h() -> Int32 { 3 }
+            }
+            ",
+        |vm, code| {
+            let fct_id = vm
+                .cls_method_def_by_name("Foo", "g", false)
+                .expect("g not found");
+            let expected = vec![
+                PushRegister(r(0)),
+                InvokeDirectInt32(r(1), fct_id),
+                RetInt32(r(1)),
+            ];
+            assert_eq!(expected, code);
+        },
+    );
+}
+
 #[test]
 fn gen_new_object() {
     gen("fun f() -> Object { return Object(); }", |vm, code| {
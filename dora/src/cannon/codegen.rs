@@ -1,8 +1,12 @@
 use dora_parser::ast::*;
 use std::collections::hash_map::HashMap;
+use std::sync::Arc;
+
+use dora_parser::lexer::position::Position;
 
 use crate::bytecode::{
-    self, BytecodeFunction, BytecodeOffset, BytecodeType, BytecodeVisitor, ConstPoolIdx, Register,
+    self, BytecodeFunction, BytecodeOffset, BytecodeType, BytecodeVisitor, ConstPoolIdx,
+    RedundantChecks, Register,
 };
 use crate::compiler::asm::BaselineAssembler;
 use crate::compiler::codegen::{ensure_native_stub, should_emit_debug, AllocationSize, AnyReg};
@@ -12,10 +16,12 @@ use crate::cpu::{
     Mem, Reg, FREG_PARAMS, FREG_RESULT, FREG_TMP1, REG_PARAMS, REG_RESULT, REG_SP, REG_TMP1,
     REG_TMP2, STACK_FRAME_ALIGNMENT,
 };
+use crate::gc::allocsite::AllocSite;
+use crate::gc::compressed;
 use crate::gc::Address;
 use crate::masm::*;
 use crate::mem::{self, align_i32};
-use crate::object::{offset_of_array_data, Header, Str};
+use crate::object::{Header, Str};
 use crate::semck::specialize::specialize_type;
 use crate::size::InstanceSize;
 use crate::ty::{BuiltinType, MachineMode, TypeList};
@@ -70,6 +76,7 @@ pub struct CannonCodeGen<'a, 'ast: 'a> {
 
     forward_jumps: Vec<ForwardJump>,
     current_offset: BytecodeOffset,
+    redundant_checks: RedundantChecks,
     argument_stack: Vec<Register>,
 
     references: Vec<i32>,
@@ -117,6 +124,7 @@ where
             offset_to_address: HashMap::new(),
             forward_jumps: Vec::new(),
             current_offset: BytecodeOffset(0),
+            redundant_checks: bytecode::find_redundant_checks(bytecode),
             argument_stack: Vec::new(),
             references: Vec::new(),
             offsets: Vec::new(),
@@ -130,6 +138,8 @@ where
             self.asm.debug();
         }
 
+        self.asm.set_file(self.fct.file);
+
         self.calculate_offsets();
         self.initialize_references();
 
@@ -197,7 +207,7 @@ where
     }
 
     fn create_gcpoint(&self) -> GcPoint {
-        GcPoint::from_offsets(self.references.clone())
+        GcPoint::from_bytecode(self.references.clone(), self.current_offset.to_u32())
     }
 
     fn has_result_address(&self) -> bool {
@@ -1093,6 +1103,26 @@ where
         }
     }
 
+    // `narrow` is false for references in tuples, they are never compressed
+    fn emit_pre_barrier(&mut self, ty: BuiltinType, base: Reg, offset: i32, narrow: bool) {
+        if let Some(tuple_id) = ty.tuple_id() {
+            let subtypes = self.vm.tuples.lock().get(tuple_id);
+            let offsets = self
+                .vm
+                .tuples
+                .lock()
+                .get_tuple(tuple_id)
+                .offsets()
+                .to_owned();
+
+            for (&subtype, &subtype_offset) in subtypes.iter().zip(&offsets) {
+                self.emit_pre_barrier(subtype, base, offset + subtype_offset, false);
+            }
+        } else if ty.reference_type() {
+            self.asm.emit_pre_barrier(Mem::Base(base, offset), narrow);
+        }
+    }
+
     fn copy_tuple(&mut self, tuple_id: TupleId, dest: RegOrOffset, src: RegOrOffset) {
         let subtypes = self.vm.tuples.lock().get(tuple_id);
         let offsets = self
@@ -1186,7 +1216,9 @@ where
 
         let pos = self.bytecode.offset_position(self.current_offset.to_u32());
 
-        self.asm.test_if_nil_bailout(pos, obj_reg, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm.test_if_nil_bailout(pos, obj_reg, Trap::NIL);
+        }
 
         if let Some(tuple_id) = bytecode_type.tuple_id() {
             let dest_offset = self.register_offset(dest);
@@ -1198,7 +1230,7 @@ where
         } else {
             let dest_reg = result_reg(bytecode_type);
             self.asm
-                .load_mem(field.ty.mode(), dest_reg, Mem::Base(obj_reg, field.offset));
+                .load_field(field.ty, dest_reg, Mem::Base(obj_reg, field.offset));
 
             self.emit_store_register(dest_reg.into(), dest);
         }
@@ -1240,7 +1272,13 @@ where
         self.emit_load_register(obj, obj_reg.into());
 
         let pos = self.bytecode.offset_position(self.current_offset.to_u32());
-        self.asm.test_if_nil_bailout(pos, obj_reg, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm.test_if_nil_bailout(pos, obj_reg, Trap::NIL);
+        }
+
+        if self.vm.gc.needs_pre_barrier() {
+            self.emit_pre_barrier(field.ty, obj_reg, field.offset, true);
+        }
 
         let needs_write_barrier = if let Some(tuple_id) = bytecode_type.tuple_id() {
             let src_offset = self.register_offset(src);
@@ -1260,7 +1298,7 @@ where
 
             self.emit_load_register(src, value.into());
             self.asm
-                .store_mem(field.ty.mode(), Mem::Base(obj_reg, field.offset), value);
+                .store_field(field.ty, Mem::Base(obj_reg, field.offset), value);
 
             field.ty.reference_type()
         };
@@ -1403,7 +1441,12 @@ where
     fn emit_const_string(&mut self, dest: Register, lit_value: &str) {
         assert_eq!(self.bytecode.register_type(dest), BytecodeType::Ptr);
 
-        let handle = Str::from_buffer_in_perm(self.vm, lit_value.as_bytes());
+        let handle = Str::from_literal(
+            self.vm,
+            self.fct.id,
+            self.current_offset.to_usize(),
+            lit_value,
+        );
         let disp = self.asm.add_addr(handle.raw() as *const u8);
         let pos = self.asm.pos() as i32;
 
@@ -1547,8 +1590,15 @@ where
 
         let gcpoint = self.create_gcpoint();
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
-        self.asm
-            .allocate(REG_RESULT.into(), alloc_size, position, false, gcpoint);
+        let site = self.alloc_site(position, class_def_id);
+        self.asm.allocate(
+            REG_RESULT.into(),
+            alloc_size,
+            position,
+            false,
+            gcpoint,
+            site.as_deref(),
+        );
 
         // store gc object in temporary storage
         self.emit_store_register(REG_RESULT.into(), dest);
@@ -1567,7 +1617,9 @@ where
 
         // clear mark/fwdptr word in header
         assert!(Header::size() == 2 * mem::ptr_width());
-        self.asm.load_int_const(MachineMode::Ptr, REG_TMP1, 0);
+        let header_word = site.map_or(0, |site| site.header_word());
+        self.asm
+            .load_int_const(MachineMode::Ptr, REG_TMP1, header_word);
         self.asm.store_mem(
             MachineMode::Ptr,
             Mem::Base(REG_RESULT, mem::ptr_width()),
@@ -1582,6 +1634,14 @@ where
         }
     }
 
+    fn alloc_site(&self, pos: Position, class_def_id: ClassDefId) -> Option<Arc<AllocSite>> {
+        if self.vm.args.alloc_site_tracking() {
+            Some(self.vm.gc.alloc_sites.add(self.fct.id, pos, class_def_id))
+        } else {
+            None
+        }
+    }
+
     fn emit_new_array(&mut self, dest: Register, class_def_id: ClassDefId, length: Register) {
         assert_eq!(self.bytecode.register_type(dest), BytecodeType::Ptr);
         assert_eq!(self.bytecode.register_type(length), BytecodeType::Int64);
@@ -1608,7 +1668,7 @@ where
             }
             InstanceSize::ObjArray => {
                 self.asm
-                    .determine_array_size(REG_TMP1, REG_TMP1, mem::ptr_width(), true);
+                    .determine_array_size(REG_TMP1, REG_TMP1, compressed::ref_size(), true);
                 AllocationSize::Dynamic(REG_TMP1)
             }
             InstanceSize::UnitArray => AllocationSize::Fixed(array_header_size),
@@ -1622,8 +1682,15 @@ where
 
         let gcpoint = self.create_gcpoint();
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
-        self.asm
-            .allocate(REG_RESULT.into(), alloc_size, position, array_ref, gcpoint);
+        let site = self.alloc_site(position, class_def_id);
+        self.asm.allocate(
+            REG_RESULT.into(),
+            alloc_size,
+            position,
+            array_ref,
+            gcpoint,
+            site.as_deref(),
+        );
 
         // store gc object in temporary storage
         self.emit_store_register(REG_RESULT.into(), dest);
@@ -1642,7 +1709,9 @@ where
 
         // clear mark/fwdptr word in header
         assert!(Header::size() == 2 * mem::ptr_width());
-        self.asm.load_int_const(MachineMode::Ptr, REG_TMP1, 0);
+        let header_word = site.map_or(0, |site| site.header_word());
+        self.asm
+            .load_int_const(MachineMode::Ptr, REG_TMP1, header_word);
         self.asm.store_mem(
             MachineMode::Ptr,
             Mem::Base(REG_RESULT, mem::ptr_width()),
@@ -1662,7 +1731,7 @@ where
                 self.emit_array_initialization(REG_RESULT, REG_TMP1, size);
             }
             InstanceSize::ObjArray => {
-                self.emit_array_initialization(REG_RESULT, REG_TMP1, mem::ptr_width());
+                self.emit_array_initialization(REG_RESULT, REG_TMP1, compressed::ref_size());
             }
             InstanceSize::UnitArray => {}
             _ => unreachable!(),
@@ -1734,14 +1803,29 @@ where
         }
     }
 
+    fn needs_nil_check(&self) -> bool {
+        !self
+            .redundant_checks
+            .is_nil_check_redundant(self.current_offset)
+    }
+
+    fn needs_bound_check(&self) -> bool {
+        !self.vm.args.flag_omit_bounds_check
+            && !self
+                .redundant_checks
+                .is_bound_check_redundant(self.current_offset)
+    }
+
     fn emit_nil_check(&mut self, obj: Register) {
         assert_eq!(self.bytecode.register_type(obj), BytecodeType::Ptr);
 
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
 
         self.emit_load_register(obj, REG_RESULT.into());
-        self.asm
-            .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm
+                .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        }
     }
 
     fn emit_array_length(&mut self, dest: Register, arr: Register) {
@@ -1751,8 +1835,10 @@ where
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
 
         self.emit_load_register(arr, REG_RESULT.into());
-        self.asm
-            .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm
+                .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        }
 
         self.asm.load_mem(
             MachineMode::Int64,
@@ -1770,12 +1856,14 @@ where
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
 
         self.emit_load_register(arr, REG_RESULT.into());
-        self.asm
-            .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm
+                .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        }
 
         self.emit_load_register(idx, REG_TMP1.into());
 
-        if !self.vm.args.flag_omit_bounds_check {
+        if self.needs_bound_check() {
             self.asm
                 .check_index_out_of_bounds(position, REG_RESULT, REG_TMP1);
         }
@@ -1788,12 +1876,14 @@ where
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
 
         self.emit_load_register(arr, REG_RESULT.into());
-        self.asm
-            .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm
+                .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        }
 
         self.emit_load_register(idx, REG_TMP1.into());
 
-        if !self.vm.args.flag_omit_bounds_check {
+        if self.needs_bound_check() {
             self.asm
                 .check_index_out_of_bounds(position, REG_RESULT, REG_TMP1);
         }
@@ -1806,6 +1896,10 @@ where
                 .array_address(REG_TMP1, REG_RESULT, REG_TMP1, element_size);
             let src_offset = self.register_offset(src);
 
+            if self.vm.gc.needs_pre_barrier() {
+                self.emit_pre_barrier(BuiltinType::Tuple(tuple_id), REG_TMP1, 0, false);
+            }
+
             self.copy_tuple(
                 tuple_id,
                 RegOrOffset::Reg(REG_TMP1),
@@ -1832,31 +1926,21 @@ where
 
             self.emit_load_register(src, value_reg.into());
 
-            self.asm.store_mem(
-                src_type.mode(),
-                Mem::Index(
-                    REG_RESULT,
-                    REG_TMP1,
-                    src_type.mode().size(),
-                    offset_of_array_data(),
-                ),
-                value_reg,
-            );
+            let elem = self.asm.array_elem(src_type.mode(), REG_RESULT, REG_TMP1);
+
+            if self.vm.gc.needs_pre_barrier() && src_type.is_ptr() {
+                self.asm.emit_pre_barrier(elem, true);
+            }
+
+            self.asm
+                .store_array_elem(src_type.mode(), REG_RESULT, REG_TMP1, value_reg);
 
             let needs_write_barrier = src_type.is_ptr();
 
             if self.vm.gc.needs_write_barrier() && needs_write_barrier {
                 let card_table_offset = self.vm.gc.card_table_offset();
                 let scratch = self.asm.get_scratch();
-                self.asm.lea(
-                    *scratch,
-                    Mem::Index(
-                        REG_RESULT,
-                        REG_TMP1,
-                        src_type.mode().size(),
-                        offset_of_array_data(),
-                    ),
-                );
+                self.asm.lea(*scratch, elem);
                 self.asm.emit_barrier(*scratch, card_table_offset);
             }
         }
@@ -1869,12 +1953,14 @@ where
         let position = self.bytecode.offset_position(self.current_offset.to_u32());
 
         self.emit_load_register(arr, REG_RESULT.into());
-        self.asm
-            .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        if self.needs_nil_check() {
+            self.asm
+                .test_if_nil_bailout(position, REG_RESULT, Trap::NIL);
+        }
 
         self.emit_load_register(idx, REG_TMP1.into());
 
-        if !self.vm.args.flag_omit_bounds_check {
+        if self.needs_bound_check() {
             self.asm
                 .check_index_out_of_bounds(position, REG_RESULT, REG_TMP1);
         }
@@ -1950,7 +2036,7 @@ where
         };
 
         let self_index = if result_register.is_some() { 1 } else { 0 };
-        self.asm.indirect_call(
+        self.asm.inline_cached_call(
             vtable_index,
             self_index,
             position,
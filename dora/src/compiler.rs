@@ -10,3 +10,4 @@ pub mod dora_stub;
 pub mod fct;
 pub mod map;
 pub mod native_stub;
+pub mod pre_barrier_stub;
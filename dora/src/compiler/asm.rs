@@ -8,6 +8,7 @@ use crate::compiler::native_stub::{NativeFct, NativeFctDescriptor};
 use crate::cpu::{
     FReg, Mem, Reg, FREG_RESULT, REG_PARAMS, REG_RESULT, REG_THREAD, REG_TMP1, REG_TMP2,
 };
+use crate::gc::allocsite::AllocSite;
 use crate::gc::tlab::TLAB_OBJECT_SIZE;
 use crate::gc::Address;
 use crate::masm::{CondCode, Label, MacroAssembler, ScratchReg};
@@ -15,7 +16,7 @@ use crate::stdlib;
 use crate::threads::ThreadLocalData;
 use crate::ty::{BuiltinType, MachineMode, TypeList};
 use crate::vm::FctId;
-use crate::vm::{GlobalData, Trap, VM};
+use crate::vm::{FileId, GlobalData, Trap, VM};
 
 pub struct BaselineAssembler<'a, 'ast: 'a> {
     masm: MacroAssembler,
@@ -39,6 +40,10 @@ where
         self.masm.debug();
     }
 
+    pub fn set_file(&mut self, file: FileId) {
+        self.masm.set_file(file);
+    }
+
     pub fn prolog_size(&mut self, stacksize: i32) {
         self.masm.prolog_size(stacksize);
     }
@@ -61,6 +66,13 @@ where
         ));
     }
 
+    pub fn debug_hook(&mut self, pos: Position, gcpoint: GcPoint) {
+        self.masm.emit_comment("debug hook".into());
+        self.masm.raw_call(self.vm.debug_stub().to_ptr());
+        self.masm.emit_gcpoint(gcpoint);
+        self.masm.emit_position(pos);
+    }
+
     pub fn patch_stacksize(&mut self, patch_offset: usize, stacksize: i32) {
         self.masm.patch_stacksize(patch_offset, stacksize);
     }
@@ -142,6 +154,14 @@ where
         self.masm.load_mem(mode, dest, mem);
     }
 
+    pub fn load_field(&mut self, ty: BuiltinType, dest: AnyReg, mem: Mem) {
+        self.masm.load_field(ty, dest, mem);
+    }
+
+    pub fn store_field(&mut self, ty: BuiltinType, mem: Mem, src: AnyReg) {
+        self.masm.store_field(ty, mem, src);
+    }
+
     pub fn test_and_jump_if(&mut self, cond: CondCode, reg: Reg, lbl: Label) {
         self.masm.test_and_jump_if(cond, reg, lbl);
     }
@@ -182,6 +202,11 @@ where
         self.masm.emit_barrier(src, card_table_offset);
     }
 
+    pub fn emit_pre_barrier(&mut self, slot: Mem, narrow: bool) {
+        let stub = self.vm.pre_barrier_stub();
+        self.masm.emit_pre_barrier(slot, narrow, stub);
+    }
+
     pub fn emit_bailout(&mut self, lbl: Label, trap: Trap, pos: Position) {
         self.masm.emit_bailout(lbl, trap, pos);
     }
@@ -370,6 +395,14 @@ where
         self.masm.load_array_elem(mode, dest, array, index);
     }
 
+    pub fn array_elem(&self, mode: MachineMode, array: Reg, index: Reg) -> Mem {
+        self.masm.array_elem(mode, array, index)
+    }
+
+    pub fn store_array_elem(&mut self, mode: MachineMode, array: Reg, index: Reg, src: AnyReg) {
+        self.masm.store_array_elem(mode, array, index, src);
+    }
+
     pub fn array_address(&mut self, dest: Reg, obj: Reg, index: Reg, element_size: i32) {
         self.masm.array_address(dest, obj, index, element_size);
     }
@@ -504,6 +537,22 @@ where
         self.call_epilog(pos, return_type, dest, gcpoint);
     }
 
+    pub fn inline_cached_call(
+        &mut self,
+        vtable_index: u32,
+        self_index: u32,
+        pos: Position,
+        gcpoint: GcPoint,
+        return_type: BuiltinType,
+        cls_type_params: TypeList,
+        dest: AnyReg,
+    ) {
+        let compile_stub = self.vm.compile_stub().to_ptr();
+        self.masm
+            .inline_cached_call(pos, vtable_index, self_index, cls_type_params, compile_stub);
+        self.call_epilog(pos, return_type, dest, gcpoint);
+    }
+
     fn call_epilog(&mut self, pos: Position, ty: BuiltinType, dest: AnyReg, gcpoint: GcPoint) {
         self.masm.emit_position(pos);
         self.masm.emit_gcpoint(gcpoint);
@@ -659,24 +708,48 @@ where
         pos: Position,
         array_ref: bool,
         gcpoint: GcPoint,
+        site: Option<&AllocSite>,
     ) {
-        if self.vm.args.flag_disable_tlab {
-            self.gc_allocate(dest, size, pos, array_ref, gcpoint);
-            return;
+        let site = site.filter(|_| self.vm.args.flag_gc_pretenure);
+        let lbl_pretenure = self.masm.create_label();
+
+        if let Some(site) = site {
+            let scratch = self.masm.get_scratch();
+            let disp = self.masm.add_addr(site.pretenure_address().to_ptr());
+            let pos = self.masm.pos() as i32;
+            self.masm.load_constpool(*scratch, disp + pos);
+            self.masm
+                .load_mem(MachineMode::Int8, (*scratch).into(), Mem::Base(*scratch, 0));
+            self.masm.cmp_reg_imm(MachineMode::Ptr, *scratch, 0);
+            self.masm.jump_if(CondCode::NonZero, lbl_pretenure);
         }
 
-        match size {
-            AllocationSize::Fixed(fixed_size) => {
-                if fixed_size < TLAB_OBJECT_SIZE {
-                    self.tlab_allocate(dest, size, pos, array_ref, gcpoint);
-                } else {
-                    self.gc_allocate(dest, size, pos, array_ref, gcpoint);
-                }
-            }
+        let tlab = match size {
+            _ if self.vm.args.flag_disable_tlab => false,
+            AllocationSize::Fixed(fixed_size) => fixed_size < TLAB_OBJECT_SIZE,
+            AllocationSize::Dynamic(_) => true,
+        };
 
-            AllocationSize::Dynamic(_) => {
-                self.tlab_allocate(dest, size, pos, array_ref, gcpoint);
-            }
+        if tlab {
+            self.tlab_allocate(dest, size, pos, array_ref, gcpoint.clone());
+        } else {
+            self.gc_allocate(dest, size, pos, array_ref, gcpoint.clone());
+        }
+
+        if let Some(site) = site {
+            let lbl_return = self.masm.create_label();
+            self.masm.bind_label(lbl_return);
+
+            self.slow_paths.push(SlowPathKind::Pretenure(
+                lbl_pretenure,
+                lbl_return,
+                dest,
+                size,
+                pos,
+                array_ref,
+                gcpoint,
+                site.id.to_u32(),
+            ));
         }
     }
 
@@ -727,6 +800,21 @@ where
                     );
                 }
 
+                SlowPathKind::Pretenure(
+                    lbl_start,
+                    lbl_return,
+                    dest,
+                    size,
+                    pos,
+                    array_ref,
+                    gcpoint,
+                    site_id,
+                ) => {
+                    self.slow_path_pretenure(
+                        lbl_start, lbl_return, dest, size, pos, array_ref, gcpoint, site_id,
+                    );
+                }
+
                 SlowPathKind::StackOverflow(lbl_start, lbl_return, pos, gcpoint) => {
                     self.slow_path_stack_overflow(lbl_start, lbl_return, pos, gcpoint);
                 }
@@ -768,6 +856,52 @@ where
         self.masm.jump(lbl_return);
     }
 
+    fn slow_path_pretenure(
+        &mut self,
+        lbl_start: Label,
+        lbl_return: Label,
+        dest: Reg,
+        size: AllocationSize,
+        pos: Position,
+        array_ref: bool,
+        gcpoint: GcPoint,
+        site_id: u32,
+    ) {
+        self.masm.bind_label(lbl_start);
+        self.masm
+            .emit_comment("slow path pretenure allocation".into());
+
+        match size {
+            AllocationSize::Fixed(size) => {
+                self.masm
+                    .load_int_const(MachineMode::Ptr, REG_PARAMS[0], size as i64);
+            }
+
+            AllocationSize::Dynamic(reg) => {
+                self.masm.copy_reg(MachineMode::Ptr, REG_PARAMS[0], reg);
+            }
+        }
+
+        self.masm.load_int_const(
+            MachineMode::Int8,
+            REG_PARAMS[1],
+            if array_ref { 1 } else { 0 },
+        );
+        self.masm
+            .load_int_const(MachineMode::Int32, REG_PARAMS[2], site_id as i64);
+
+        let internal_fct = NativeFct {
+            ptr: Address::from_ptr(stdlib::gc_alloc_old as *const u8),
+            args: &[BuiltinType::Int64, BuiltinType::Bool, BuiltinType::Int32],
+            return_type: BuiltinType::Ptr,
+            desc: NativeFctDescriptor::AllocStub,
+        };
+
+        self.native_call(internal_fct, pos, gcpoint, dest.into());
+        self.masm.test_if_nil_bailout(pos, dest, Trap::OOM);
+        self.masm.jump(lbl_return);
+    }
+
     fn slow_path_stack_overflow(
         &mut self,
         lbl_stack_overflow: Label,
@@ -819,6 +953,16 @@ where
 
 enum SlowPathKind {
     TlabAllocationFailure(Label, Label, Reg, AllocationSize, Position, bool, GcPoint),
+    Pretenure(
+        Label,
+        Label,
+        Reg,
+        AllocationSize,
+        Position,
+        bool,
+        GcPoint,
+        u32,
+    ),
     StackOverflow(Label, Label, Position, GcPoint),
     Assert(Label, Position),
     InitializeGlobal(Label, Label, FctId, Address, Position, GcPoint),
@@ -22,6 +22,8 @@ pub fn generate<'ast>(
     cls_type_params: &TypeList,
     fct_type_params: &TypeList,
 ) -> Address {
+    vm.gc.collect_code(vm);
+
     let fct = vm.fcts.idx(id);
     let fct = fct.read();
     let src = fct.src();
@@ -54,7 +56,10 @@ pub fn generate_fct<'ast>(
         }
     }
 
-    let bc = if fct.use_cannon {
+    let bc = if vm.args.cmd_debug {
+        // only the baseline compiler supports debugging
+        CompilerName::Baseline
+    } else if fct.use_cannon {
         CompilerName::Cannon
     } else if fct.has_optimize_immediately {
         CompilerName::Boots
@@ -74,6 +79,12 @@ pub fn generate_fct<'ast>(
         os::perf::register_with_perf(&code, vm, fct.ast.name);
     }
 
+    if vm.args.flag_perf_jitdump {
+        os::perf::register_with_jitdump(&code, vm, &fct.full_name(vm));
+    }
+
+    os::gdb::register_with_gdb(&code, vm, &fct.full_name(vm));
+
     if should_emit_asm(vm, &*fct) {
         disassembler::disassemble(
             vm,
@@ -158,6 +169,14 @@ pub fn should_emit_bytecode(vm: &VM, fct: &Fct) -> bool {
     }
 }
 
+pub fn should_emit_graph(vm: &VM, fct: &Fct) -> bool {
+    if let Some(ref dbg_names) = vm.args.flag_emit_graph {
+        fct_pattern_match(vm, fct, dbg_names)
+    } else {
+        false
+    }
+}
+
 pub fn fct_pattern_match(vm: &VM, fct: &Fct, pattern: &str) -> bool {
     if pattern == "all" {
         return true;
@@ -222,6 +241,7 @@ impl From<FReg> for AnyReg {
     }
 }
 
+#[derive(Copy, Clone)]
 pub enum AllocationSize {
     Fixed(usize),
     Dynamic(Reg),
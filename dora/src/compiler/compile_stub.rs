@@ -1,7 +1,10 @@
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
 use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::compiler;
-use crate::compiler::fct::{Code, JitDescriptor, JitFct, LazyCompilationSite};
+use crate::compiler::fct::{Code, InlineCacheSite, JitDescriptor, JitFct, LazyCompilationSite};
 use crate::compiler::map::CodeDescriptor;
 use crate::cpu::{
     Mem, CCALL_REG_PARAMS, FREG_PARAMS, REG_FP, REG_PARAMS, REG_RESULT, REG_SP, REG_THREAD,
@@ -11,6 +14,7 @@ use crate::gc::Address;
 use crate::masm::MacroAssembler;
 use crate::mem;
 use crate::object::Obj;
+use crate::os;
 use crate::stack::DoraToNativeInfo;
 use crate::threads::ThreadLocalData;
 use crate::ty::{MachineMode, TypeList};
@@ -33,6 +37,8 @@ pub fn generate<'a, 'ast: 'a>(vm: &'a VM<'ast>) -> Address {
     };
 
     let jit_fct = ngen.generate();
+    os::gdb::register_with_gdb(&jit_fct, vm, "compile_stub");
+
     let addr = jit_fct.instruction_start();
     vm.insert_code_map(
         jit_fct.ptr_start(),
@@ -238,6 +244,10 @@ fn compile_request(ra: usize, receiver1: Address, receiver2: Address) -> Address
             cls_tps,
             fct_tps,
         ),
+
+        LazyCompilationSite::InlineCache(ref site) => {
+            patch_inline_cache(vm, ra, receiver1, receiver2, site)
+        }
     }
 }
 
@@ -271,6 +281,74 @@ fn patch_vtable_call(
     fct_ptr
 }
 
+// serializes updates of inline caches, a cache entry is only
+// claimed by one thread
+lazy_static! {
+    static ref INLINE_CACHE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn patch_inline_cache(
+    vm: &VM,
+    ra: usize,
+    receiver1: Address,
+    receiver2: Address,
+    site: &InlineCacheSite,
+) -> Address {
+    let receiver = if site.receiver_is_first {
+        receiver1
+    } else {
+        receiver2
+    };
+
+    let obj = unsafe { &mut *receiver.to_mut_ptr::<Obj>() };
+    let vtable_addr = obj.header().vtblptr().to_usize();
+
+    // the cache falls back to the vtable, which makes sure the
+    // vtable entry gets compiled as well
+    let fct_ptr = patch_vtable_call(
+        vm,
+        site.receiver_is_first,
+        receiver1,
+        receiver2,
+        site.vtable_index,
+        &site.cls_tps,
+        &TypeList::empty(),
+    );
+
+    let slot = |disp: i32| (ra as isize - disp as isize) as *mut usize;
+
+    let _lock = INLINE_CACHE_LOCK.lock();
+
+    for &(vtable_disp, fct_disp) in &site.entries {
+        let cached_vtable = unsafe { *slot(vtable_disp) };
+
+        if cached_vtable == vtable_addr {
+            return fct_ptr;
+        }
+
+        if cached_vtable == 0 {
+            // the function needs to be stored before the vtable,
+            // other threads might execute this call site concurrently.
+            // The release store keeps both stores in that order.
+            unsafe {
+                *slot(fct_disp) = fct_ptr.to_usize();
+                let cached_vtable = &*(slot(vtable_disp) as *const AtomicUsize);
+                cached_vtable.store(vtable_addr, Ordering::Release);
+            }
+
+            return fct_ptr;
+        }
+    }
+
+    // cache is full: call site is megamorphic, from now on
+    // misses go through the vtable
+    unsafe {
+        *slot(site.miss_target) = 0;
+    }
+
+    fct_ptr
+}
+
 fn patch_fct_call(
     vm: &VM,
     ra: usize,
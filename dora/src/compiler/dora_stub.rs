@@ -1,9 +1,11 @@
 use crate::compiler::fct::{Code, JitDescriptor, JitFct};
 use crate::compiler::map::CodeDescriptor;
-use crate::cpu::{Mem, CCALL_REG_PARAMS, REG_PARAMS, REG_SP, REG_THREAD, REG_TMP1};
+use crate::cpu::{Mem, CCALL_REG_PARAMS, REG_HEAP_BASE, REG_PARAMS, REG_SP, REG_THREAD, REG_TMP1};
+use crate::gc::compressed;
 use crate::gc::Address;
 use crate::masm::MacroAssembler;
 use crate::mem;
+use crate::os;
 use crate::ty::MachineMode;
 use crate::vm::VM;
 
@@ -15,6 +17,8 @@ pub fn generate<'a, 'ast: 'a>(vm: &'a VM<'ast>) -> Address {
     };
 
     let jit_fct = ngen.generate();
+    os::gdb::register_with_gdb(&jit_fct, vm, "dora_stub");
+
     let ptr = jit_fct.instruction_start();
 
     vm.insert_code_map(
@@ -38,10 +42,11 @@ where
     'ast: 'a,
 {
     pub fn generate(mut self) -> Code {
-        let framesize = mem::ptr_width_usize();
+        let framesize = 2 * mem::ptr_width_usize();
         let framesize = mem::align_usize(framesize, 16) as i32;
 
         let offset_thread = 0;
+        let offset_heap_base = mem::ptr_width();
 
         if self.dbg {
             self.masm.debug();
@@ -54,6 +59,19 @@ where
             REG_THREAD.into(),
         );
 
+        if compressed::enabled() {
+            self.masm.store_mem(
+                MachineMode::Ptr,
+                Mem::Base(REG_SP, offset_heap_base),
+                REG_HEAP_BASE.into(),
+            );
+            self.masm.load_int_const(
+                MachineMode::Ptr,
+                REG_HEAP_BASE,
+                compressed::heap_base().to_usize() as i64,
+            );
+        }
+
         self.masm
             .copy_reg(MachineMode::Ptr, REG_THREAD, CCALL_REG_PARAMS[0]);
         self.masm
@@ -67,6 +85,15 @@ where
             REG_THREAD.into(),
             Mem::Base(REG_SP, offset_thread),
         );
+
+        if compressed::enabled() {
+            self.masm.load_mem(
+                MachineMode::Ptr,
+                REG_HEAP_BASE.into(),
+                Mem::Base(REG_SP, offset_heap_base),
+            );
+        }
+
         self.masm.epilog();
 
         self.masm.jit(self.vm, framesize, JitDescriptor::DoraStub)
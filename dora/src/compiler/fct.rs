@@ -6,14 +6,15 @@ use std::sync::Arc;
 use crate::cpu::flush_icache;
 use crate::dseg::DSeg;
 use crate::gc::Address;
-use crate::ty::TypeList;
+use crate::ty::{BuiltinType, TypeList};
 use crate::utils::GrowableVec;
-use crate::vm::FctId;
 use crate::vm::VM;
+use crate::vm::{FctId, FileId};
 
+use dora_parser::interner::Name;
 use dora_parser::Position;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct JitFctId(usize);
 
 impl JitFctId {
@@ -100,13 +101,27 @@ impl JitFct {
         }
     }
 
-    pub fn position_for_offset(&self, offset: u32) -> Option<Position> {
+    pub fn position_for_offset(&self, offset: u32) -> Option<SourcePosition> {
         match self {
             &JitFct::Compiled(ref base) => base.position_for_offset(offset),
             &JitFct::Uncompiled => unreachable!(),
         }
     }
 
+    pub fn frames_for_offset(&self, offset: u32) -> Option<Vec<(FctId, SourcePosition)>> {
+        match self {
+            &JitFct::Compiled(ref base) => base.frames_for_offset(offset),
+            &JitFct::Uncompiled => unreachable!(),
+        }
+    }
+
+    pub fn frames_for_instruction(&self, offset: u32) -> Option<Vec<(FctId, SourcePosition)>> {
+        match self {
+            &JitFct::Compiled(ref base) => base.frames_for_instruction(offset),
+            &JitFct::Uncompiled => unreachable!(),
+        }
+    }
+
     pub fn comment_for_offset(&self, offset: u32) -> Option<&String> {
         match self {
             &JitFct::Compiled(ref base) => base.comment_for_offset(offset),
@@ -132,6 +147,8 @@ pub enum JitDescriptor {
     NativeStub(FctId),
     DoraStub,
     GuardCheckStub,
+    DebugStub,
+    PreBarrierStub,
 }
 
 pub struct Code {
@@ -149,6 +166,7 @@ pub struct Code {
     gcpoints: GcPoints,
     comments: Comments,
     positions: PositionTable,
+    variables: Vec<VariableInfo>,
 }
 
 impl Code {
@@ -211,14 +229,45 @@ impl Code {
             instruction_start,
             instruction_end,
             positions,
+            variables: Vec::new(),
             desc,
         }
     }
 
-    pub fn position_for_offset(&self, offset: u32) -> Option<Position> {
+    pub fn position_for_offset(&self, offset: u32) -> Option<SourcePosition> {
         self.positions.get(offset)
     }
 
+    pub fn positions(&self) -> &[(u32, SourcePosition)] {
+        self.positions.entries()
+    }
+
+    pub fn set_variables(&mut self, variables: Vec<VariableInfo>) {
+        self.variables = variables;
+    }
+
+    // Returns all local variables that are live at the given offset.
+    pub fn variables_for_offset(&self, offset: u32) -> Vec<&VariableInfo> {
+        self.variables
+            .iter()
+            .filter(|var| var.start <= offset && offset < var.end)
+            .collect()
+    }
+
+    // Returns the logical frames for the given offset, starting with the
+    // innermost inlined function and ending with the function of this code.
+    pub fn frames_for_offset(&self, offset: u32) -> Option<Vec<(FctId, SourcePosition)>> {
+        let position = self.positions.get(offset)?;
+        Some(self.positions.frames(self.fct_id(), position))
+    }
+
+    // Returns the logical frames for an arbitrary instruction, e.g. the
+    // interrupted program counter of a sample.
+    pub fn frames_for_instruction(&self, offset: u32) -> Option<Vec<(FctId, SourcePosition)>> {
+        let position = self.positions.get_for_instruction(offset)?;
+        Some(self.positions.frames(self.fct_id(), position))
+    }
+
     pub fn gcpoint_for_offset(&self, offset: u32) -> Option<&GcPoint> {
         self.gcpoints.get(offset)
     }
@@ -258,6 +307,10 @@ impl Code {
     pub fn lazy_for_offset(&self, offset: u32) -> Option<&LazyCompilationSite> {
         self.lazy_compilation.get(offset)
     }
+
+    pub fn lazy_compilation_sites(&self) -> &[(u32, LazyCompilationSite)] {
+        self.lazy_compilation.entries()
+    }
 }
 
 impl fmt::Debug for Code {
@@ -304,15 +357,24 @@ impl GcPoints {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct GcPoint {
     pub offsets: Vec<i32>,
+
+    // offset of the bytecode instruction for functions compiled from bytecode
+    pub bytecode_offset: Option<u32>,
+
+    // words of the frame holding live values, only recorded with
+    // --gc-verify-stackmaps. All words of the frame are live if missing.
+    pub live_slots: Option<Vec<i32>>,
 }
 
 impl GcPoint {
     pub fn new() -> GcPoint {
         GcPoint {
             offsets: Vec::new(),
+            bytecode_offset: None,
+            live_slots: None,
         }
     }
 
@@ -327,11 +389,35 @@ impl GcPoint {
             offsets.insert(offset);
         }
 
-        GcPoint::from_offsets(offsets.drain().collect())
+        GcPoint {
+            offsets: offsets.drain().collect(),
+            bytecode_offset: lhs.bytecode_offset.or(rhs.bytecode_offset),
+            live_slots: None,
+        }
     }
 
     pub fn from_offsets(offsets: Vec<i32>) -> GcPoint {
-        GcPoint { offsets }
+        GcPoint {
+            offsets,
+            bytecode_offset: None,
+            live_slots: None,
+        }
+    }
+
+    pub fn from_bytecode(offsets: Vec<i32>, bytecode_offset: u32) -> GcPoint {
+        GcPoint {
+            offsets,
+            bytecode_offset: Some(bytecode_offset),
+            live_slots: None,
+        }
+    }
+
+    pub fn with_live_slots(offsets: Vec<i32>, live_slots: Vec<i32>) -> GcPoint {
+        GcPoint {
+            offsets,
+            bytecode_offset: None,
+            live_slots: Some(live_slots),
+        }
     }
 }
 
@@ -366,19 +452,56 @@ impl Comments {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InlinedFunctionId(u32);
+
+impl InlinedFunctionId {
+    pub fn idx(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// Source position of a machine code instruction. Instructions of inlined
+// functions refer to the inlined function, whose call site is stored
+// in the position table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourcePosition {
+    pub file: FileId,
+    pub pos: Position,
+    pub inlined_function: Option<InlinedFunctionId>,
+}
+
+impl SourcePosition {
+    pub fn new(file: FileId, pos: Position) -> SourcePosition {
+        SourcePosition {
+            file,
+            pos,
+            inlined_function: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InlinedFunction {
+    pub fct_id: FctId,
+    pub call_site: SourcePosition,
+}
+
 #[derive(Debug)]
 pub struct PositionTable {
-    entries: Vec<(u32, Position)>,
+    entries: Vec<(u32, SourcePosition)>,
+    inlined_functions: Vec<InlinedFunction>,
 }
 
 impl PositionTable {
     pub fn new() -> PositionTable {
         PositionTable {
             entries: Vec::new(),
+            inlined_functions: Vec::new(),
         }
     }
 
-    pub fn insert(&mut self, offset: u32, position: Position) {
+    pub fn insert(&mut self, offset: u32, position: SourcePosition) {
         if let Some(last) = self.entries.last() {
             debug_assert!(offset > last.0);
         }
@@ -386,7 +509,18 @@ impl PositionTable {
         self.entries.push((offset, position));
     }
 
-    pub fn get(&self, offset: u32) -> Option<Position> {
+    pub fn entries(&self) -> &[(u32, SourcePosition)] {
+        &self.entries
+    }
+
+    pub fn add_inlined_function(&mut self, inlined_function: InlinedFunction) -> InlinedFunctionId {
+        let id = InlinedFunctionId(self.inlined_functions.len() as u32);
+        self.inlined_functions.push(inlined_function);
+
+        id
+    }
+
+    pub fn get(&self, offset: u32) -> Option<SourcePosition> {
         let result = self
             .entries
             .binary_search_by_key(&offset, |&(offset, _)| offset);
@@ -396,6 +530,49 @@ impl PositionTable {
             Err(_) => None,
         }
     }
+
+    // Positions are recorded after their instruction, so the instruction at
+    // the given offset belongs to the first position behind it. Code after
+    // the last position (e.g. the epilogue) is attributed to the last one.
+    pub fn get_for_instruction(&self, offset: u32) -> Option<SourcePosition> {
+        let idx = match self
+            .entries
+            .binary_search_by_key(&offset, |&(offset, _)| offset)
+        {
+            Ok(idx) => idx + 1,
+            Err(idx) => idx,
+        };
+
+        self.entries
+            .get(idx)
+            .or_else(|| self.entries.last())
+            .map(|&(_, position)| position)
+    }
+
+    pub fn frames(&self, fct_id: FctId, position: SourcePosition) -> Vec<(FctId, SourcePosition)> {
+        let mut frames = Vec::new();
+        let mut position = position;
+
+        while let Some(inlined_function_id) = position.inlined_function {
+            let inlined_function = &self.inlined_functions[inlined_function_id.idx()];
+            frames.push((inlined_function.fct_id, position));
+            position = inlined_function.call_site;
+        }
+
+        frames.push((fct_id, position));
+        frames
+    }
+}
+
+// Stack location of a local variable, only recorded when debugging. The
+// variable is live between the code offsets start and end.
+#[derive(Clone, Debug)]
+pub struct VariableInfo {
+    pub name: Name,
+    pub ty: BuiltinType,
+    pub offset: i32,
+    pub start: u32,
+    pub end: u32,
 }
 
 #[derive(Debug)]
@@ -418,6 +595,10 @@ impl LazyCompilationData {
         self.entries.push((offset, info));
     }
 
+    pub fn entries(&self) -> &[(u32, LazyCompilationSite)] {
+        &self.entries
+    }
+
     pub fn get(&self, offset: u32) -> Option<&LazyCompilationSite> {
         let result = self
             .entries
@@ -434,4 +615,84 @@ impl LazyCompilationData {
 pub enum LazyCompilationSite {
     Compile(FctId, i32, TypeList, TypeList),
     VirtCompile(bool, u32, TypeList, TypeList),
+    InlineCache(InlineCacheSite),
+}
+
+// number of receiver classes an inline cache remembers before
+// the call site falls back to the vtable
+pub const INLINE_CACHE_ENTRIES: usize = 2;
+
+// Virtual call site that compares the receiver's vtable against the cached
+// vtables in the data segment and calls the cached function on a hit. All
+// displacements are relative to the return address of the call.
+#[derive(Clone, Debug)]
+pub struct InlineCacheSite {
+    pub receiver_is_first: bool,
+    pub vtable_index: u32,
+    pub cls_tps: TypeList,
+
+    // (vtable, fct) entries
+    pub entries: Vec<(i32, i32)>,
+
+    // target on cache miss, initially the compile stub
+    // and 0 after the cache got full
+    pub miss_target: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_with_inlined_functions() {
+        let file = FileId::from(0);
+        let mut positions = PositionTable::new();
+
+        let outer = SourcePosition::new(file, Position::new(10, 5));
+        let g = positions.add_inlined_function(InlinedFunction {
+            fct_id: FctId(2),
+            call_site: outer,
+        });
+
+        let mut inner = SourcePosition::new(FileId::from(1), Position::new(3, 7));
+        inner.inlined_function = Some(g);
+        let h = positions.add_inlined_function(InlinedFunction {
+            fct_id: FctId(3),
+            call_site: inner,
+        });
+
+        let mut innermost = SourcePosition::new(FileId::from(1), Position::new(20, 1));
+        innermost.inlined_function = Some(h);
+
+        positions.insert(4, outer);
+        positions.insert(8, innermost);
+
+        assert_eq!(
+            vec![(FctId(1), outer)],
+            positions.frames(FctId(1), positions.get(4).unwrap())
+        );
+        assert_eq!(
+            vec![(FctId(3), innermost), (FctId(2), inner), (FctId(1), outer)],
+            positions.frames(FctId(1), positions.get(8).unwrap())
+        );
+        assert_eq!(None, positions.get(6));
+    }
+
+    #[test]
+    fn test_position_for_instruction() {
+        let file = FileId::from(0);
+        let mut positions = PositionTable::new();
+        assert_eq!(None, positions.get_for_instruction(0));
+
+        let first = SourcePosition::new(file, Position::new(1, 1));
+        let second = SourcePosition::new(file, Position::new(2, 1));
+        positions.insert(4, first);
+        positions.insert(8, second);
+
+        assert_eq!(Some(first), positions.get_for_instruction(0));
+        assert_eq!(Some(first), positions.get_for_instruction(3));
+        assert_eq!(Some(second), positions.get_for_instruction(4));
+        assert_eq!(Some(second), positions.get_for_instruction(7));
+        assert_eq!(Some(second), positions.get_for_instruction(12));
+    }
 }
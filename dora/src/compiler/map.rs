@@ -43,6 +43,8 @@ impl CodeMap {
                 }
                 &CodeDescriptor::DoraStub => println!("dora_stub"),
                 &CodeDescriptor::GuardCheckStub => println!("guard_check_stub"),
+                &CodeDescriptor::DebugStub => println!("debug_stub"),
+                &CodeDescriptor::PreBarrierStub => println!("pre_barrier_stub"),
             }
         }
 
@@ -54,6 +56,22 @@ impl CodeMap {
         assert!(self.tree.insert(span, data).is_none());
     }
 
+    pub fn remove(&mut self, start: Address) -> Option<CodeDescriptor> {
+        let span = CodeSpan::new(start, start.offset(1));
+
+        self.tree.remove(&span)
+    }
+
+    pub fn dora_fcts(&self) -> Vec<JitFctId> {
+        self.tree
+            .values()
+            .filter_map(|data| match data {
+                &CodeDescriptor::DoraFct(jit_fct_id) => Some(jit_fct_id),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn get(&self, ptr: Address) -> Option<CodeDescriptor> {
         let span = CodeSpan::new(ptr, ptr.offset(1));
 
@@ -71,6 +89,8 @@ pub enum CodeDescriptor {
     NativeStub(JitFctId),
     DoraStub,
     GuardCheckStub,
+    DebugStub,
+    PreBarrierStub,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -166,6 +186,26 @@ mod tests {
         assert_eq!(None, map.get(9.into()));
     }
 
+    #[test]
+    fn test_remove() {
+        let mut map = CodeMap::new();
+
+        map.insert(5.into(), 7.into(), CodeDescriptor::DoraFct(1.into()));
+        map.insert(7.into(), 9.into(), CodeDescriptor::CompileStub);
+
+        assert_eq!(vec![JitFctId::from(1)], map.dora_fcts());
+        assert_eq!(
+            Some(CodeDescriptor::DoraFct(1.into())),
+            map.remove(5.into())
+        );
+        assert_eq!(None, map.get(6.into()));
+        assert_eq!(Some(CodeDescriptor::CompileStub), map.get(7.into()));
+        assert!(map.dora_fcts().is_empty());
+
+        map.insert(5.into(), 7.into(), CodeDescriptor::DoraFct(2.into()));
+        assert_eq!(Some(CodeDescriptor::DoraFct(2.into())), map.get(5.into()));
+    }
+
     #[test]
     #[should_panic]
     fn test_insert_fails() {
@@ -11,6 +11,7 @@ use crate::cpu::{
 use crate::gc::Address;
 use crate::masm::MacroAssembler;
 use crate::mem;
+use crate::os;
 use crate::stack::DoraToNativeInfo;
 use crate::threads::ThreadLocalData;
 use crate::ty::{BuiltinType, MachineMode};
@@ -44,6 +45,7 @@ pub enum NativeFctDescriptor {
     VerifyStub,
     TrapStub,
     GuardCheckStub,
+    DebugStub,
 }
 
 pub struct NativeFct<'a> {
@@ -64,6 +66,21 @@ pub fn generate<'a, 'ast: 'a>(vm: &'a VM<'ast>, fct: NativeFct, dbg: bool) -> Ji
     };
 
     let jit_fct = ngen.generate();
+
+    let name = match fct_desc {
+        NativeFctDescriptor::NativeStub(fct_id) => {
+            let fct = vm.fcts.idx(fct_id);
+            let fct = fct.read();
+            fct.full_name(vm)
+        }
+        NativeFctDescriptor::TrapStub => "trap_stub".into(),
+        NativeFctDescriptor::VerifyStub => "verify_stub".into(),
+        NativeFctDescriptor::AllocStub => "alloc_stub".into(),
+        NativeFctDescriptor::GuardCheckStub => "guard_check_stub".into(),
+        NativeFctDescriptor::DebugStub => "debug_stub".into(),
+    };
+    os::gdb::register_with_gdb(&jit_fct, vm, &name);
+
     let jit_start = jit_fct.ptr_start();
     let jit_end = jit_fct.ptr_end();
     let jit_fct_id: JitFctId = vm.jit_fcts.push(JitFct::Compiled(jit_fct)).into();
@@ -74,6 +91,7 @@ pub fn generate<'a, 'ast: 'a>(vm: &'a VM<'ast>, fct: NativeFct, dbg: bool) -> Ji
         NativeFctDescriptor::VerifyStub => CodeDescriptor::VerifyStub,
         NativeFctDescriptor::AllocStub => CodeDescriptor::AllocStub,
         NativeFctDescriptor::GuardCheckStub => CodeDescriptor::GuardCheckStub,
+        NativeFctDescriptor::DebugStub => CodeDescriptor::DebugStub,
     };
 
     vm.insert_code_map(jit_start, jit_end, code_desc);
@@ -257,6 +275,7 @@ where
             NativeFctDescriptor::VerifyStub => JitDescriptor::VerifyStub,
             NativeFctDescriptor::TrapStub => JitDescriptor::TrapStub,
             NativeFctDescriptor::GuardCheckStub => JitDescriptor::GuardCheckStub,
+            NativeFctDescriptor::DebugStub => JitDescriptor::DebugStub,
         };
 
         self.masm.jit(self.vm, framesize, desc)
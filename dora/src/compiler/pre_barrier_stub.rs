@@ -0,0 +1,110 @@
+use crate::compiler::fct::{Code, JitDescriptor, JitFct};
+use crate::compiler::map::CodeDescriptor;
+use crate::cpu::{
+    Mem, CALLER_SAVED_FREGS, CALLER_SAVED_REGS, CCALL_REG_PARAMS, REG_SP, REG_THREAD,
+};
+use crate::gc::Address;
+use crate::masm::MacroAssembler;
+use crate::mem;
+use crate::os;
+use crate::stdlib;
+use crate::threads::ThreadLocalData;
+use crate::ty::MachineMode;
+use crate::vm::VM;
+
+// This code generates the slow path of the snapshot-at-the-beginning
+// pre-write barrier. Compiled code stores the overwritten reference into
+// the thread-local data and calls this stub. Since the barrier can be
+// emitted anywhere a reference is stored, the stub preserves all registers
+// that are not preserved by native calls. The native function never triggers
+// a GC, so there is no need to set up a DoraToNativeInfo.
+
+pub fn generate<'a, 'ast: 'a>(vm: &'a VM<'ast>) -> Address {
+    let ngen = PreBarrierGen {
+        vm,
+        masm: MacroAssembler::new(),
+    };
+
+    let jit_fct = ngen.generate();
+    os::gdb::register_with_gdb(&jit_fct, vm, "pre_barrier_stub");
+
+    let addr = jit_fct.instruction_start();
+    vm.insert_code_map(
+        jit_fct.ptr_start(),
+        jit_fct.ptr_end(),
+        CodeDescriptor::PreBarrierStub,
+    );
+    vm.jit_fcts.push(JitFct::Compiled(jit_fct));
+
+    addr
+}
+
+struct PreBarrierGen<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    masm: MacroAssembler,
+}
+
+impl<'a, 'ast> PreBarrierGen<'a, 'ast>
+where
+    'ast: 'a,
+{
+    pub fn generate(mut self) -> Code {
+        let saved = CALLER_SAVED_REGS.len() + CALLER_SAVED_FREGS.len();
+        let framesize = mem::align_i32(saved as i32 * mem::ptr_width(), 16);
+
+        self.masm.prolog_size(0);
+        self.masm.increase_stack_frame(framesize);
+        self.store_registers();
+
+        self.masm.load_mem(
+            MachineMode::Ptr,
+            CCALL_REG_PARAMS[0].into(),
+            Mem::Base(REG_THREAD, ThreadLocalData::satb_value_offset()),
+        );
+        self.masm.raw_call(stdlib::gc_pre_barrier as *const u8);
+
+        self.load_registers();
+        self.masm.epilog();
+
+        self.masm
+            .jit(self.vm, framesize, JitDescriptor::PreBarrierStub)
+    }
+
+    fn store_registers(&mut self) {
+        let mut offset = 0;
+
+        for reg in CALLER_SAVED_REGS.iter() {
+            self.masm
+                .store_mem(MachineMode::Ptr, Mem::Base(REG_SP, offset), (*reg).into());
+            offset += mem::ptr_width();
+        }
+
+        for reg in CALLER_SAVED_FREGS.iter() {
+            self.masm.store_mem(
+                MachineMode::Float64,
+                Mem::Base(REG_SP, offset),
+                (*reg).into(),
+            );
+            offset += mem::ptr_width();
+        }
+    }
+
+    fn load_registers(&mut self) {
+        let mut offset = 0;
+
+        for reg in CALLER_SAVED_REGS.iter() {
+            self.masm
+                .load_mem(MachineMode::Ptr, (*reg).into(), Mem::Base(REG_SP, offset));
+            offset += mem::ptr_width();
+        }
+
+        for reg in CALLER_SAVED_FREGS.iter() {
+            self.masm.load_mem(
+                MachineMode::Float64,
+                (*reg).into(),
+                Mem::Base(REG_SP, offset),
+            );
+            offset += mem::ptr_width();
+        }
+    }
+}
@@ -8,6 +8,15 @@ pub static CCALL_FREG_PARAMS: [FReg; 8] = [F0, F1, F2, F3, F4, F5, F6, F7];
 
 pub static SCRATCH: [Reg; 5] = [R9, R12, R13, R14, R15];
 
+// registers not preserved across native calls
+pub static CALLER_SAVED_REGS: [Reg; 18] = [
+    R0, R1, R2, R3, R4, R5, R6, R7, R8, R9, R10, R11, R12, R13, R14, R15, R16, R17,
+];
+pub static CALLER_SAVED_FREGS: [FReg; 24] = [
+    F0, F1, F2, F3, F4, F5, F6, F7, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25, F26, F27,
+    F28, F29, F30, F31,
+];
+
 pub const REG_RESULT: Reg = R0;
 pub const REG_TMP1: Reg = R10;
 pub const REG_TMP2: Reg = R11;
@@ -15,6 +24,9 @@ pub const REG_FP: Reg = R29;
 pub const REG_LR: Reg = R30;
 pub const REG_THREAD: Reg = R28;
 
+// holds the heap base when compressed references are enabled
+pub const REG_HEAP_BASE: Reg = R27;
+
 pub const REG_SP: Reg = Reg(32);
 pub const REG_ZERO: Reg = Reg(33);
 
@@ -27,11 +27,21 @@ pub const REG_SP: Reg = RSP;
 pub const REG_FP: Reg = RBP;
 pub const REG_THREAD: Reg = R15;
 
+// holds the heap base when compressed references are enabled
+pub const REG_HEAP_BASE: Reg = R14;
+
 #[cfg(target_family = "unix")]
 pub static SCRATCH: [Reg; 4] = [RDI, RSI, RDX, RCX];
 #[cfg(target_family = "windows")]
 pub static SCRATCH: [Reg; 4] = [RCX, RDX, R8, R9];
 
+// registers not preserved across native calls
+pub static CALLER_SAVED_REGS: [Reg; 9] = [RAX, RCX, RDX, RSI, RDI, R8, R9, R10, R11];
+pub static CALLER_SAVED_FREGS: [FReg; 16] = [
+    XMM0, XMM1, XMM2, XMM3, XMM4, XMM5, XMM6, XMM7, XMM8, XMM9, XMM10, XMM11, XMM12, XMM13, XMM14,
+    XMM15,
+];
+
 pub const FREG_RESULT: FReg = XMM0;
 
 #[cfg(target_family = "unix")]
@@ -0,0 +1,747 @@
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use crate::compiler::fct::{SourcePosition, VariableInfo};
+use crate::compiler::map::{CodeDescriptor, CodeMap};
+use crate::compiler::JitFctId;
+use crate::gc::root::Slot;
+use crate::gc::Address;
+use crate::object::{offset_of_array_length, Obj, Str};
+use crate::size::InstanceSize;
+use crate::threads::THREAD;
+use crate::ty::BuiltinType;
+use crate::vm::{get_vm, ClassDefId, FctId, VM};
+
+use dora_parser::interner::Name;
+
+// Source-level debugger for `dora debug <file>`. The baseline compiler calls
+// `statement_hook` before every statement, the hook decides whether execution
+// should stop there and then reads commands from stdin until execution is
+// resumed. Commands are line-based, see HELP.
+
+const HELP: &str = "commands:
+  break [<file>:]<line>  set breakpoint (b)
+  delete [<id>]          delete breakpoint or all breakpoints (d)
+  breakpoints            list breakpoints
+  continue               continue execution (c)
+  step                   step to next statement, enters calls (s)
+  next                   step to next statement in this function (n)
+  finish                 run until the current function returns
+  backtrace              print all frames (bt)
+  frame <n>              select frame n (f)
+  locals                 print variables of selected frame
+  print <var>[.<field>]* print variable or field (p)
+  quit                   exit program (q)";
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_id: usize,
+    mode: StepMode,
+}
+
+struct Breakpoint {
+    id: usize,
+    file: String,
+    line: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum StepMode {
+    Continue,
+
+    // stop at next statement in thread (or any thread)
+    Into(Option<usize>),
+
+    // stop at next statement in thread with at most the given frame depth
+    Over(usize, usize),
+
+    // stop at next statement in thread with less than the given frame depth
+    Out(usize, usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Break(Option<String>, u32),
+    Delete(Option<usize>),
+    Breakpoints,
+    Continue,
+    Step,
+    Next,
+    Finish,
+    Backtrace,
+    Frame(usize),
+    Locals,
+    Print(Vec<String>),
+    Quit,
+    Help,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            next_breakpoint_id: 1,
+
+            // stop at the first statement of the program
+            mode: StepMode::Into(None),
+        }
+    }
+
+    fn add_breakpoint(&mut self, file: String, line: u32) -> usize {
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        self.breakpoints.push(Breakpoint { id, file, line });
+
+        id
+    }
+
+    fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|bp| bp.id != id);
+        self.breakpoints.len() != len
+    }
+
+    // Breakpoints given with a partial path match files ending in it.
+    fn breakpoint_at(&self, file: &str, line: u32) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .find(|bp| {
+                bp.line == line && (file == bp.file || file.ends_with(&format!("/{}", bp.file)))
+            })
+            .map(|bp| bp.id)
+    }
+
+    fn step_finished(&self, thread_id: usize, depth: usize) -> bool {
+        match self.mode {
+            StepMode::Continue => false,
+            StepMode::Into(None) => true,
+            StepMode::Into(Some(thread)) => thread == thread_id,
+            StepMode::Over(thread, max_depth) => thread == thread_id && depth <= max_depth,
+            StepMode::Out(thread, max_depth) => thread == thread_id && depth < max_depth,
+        }
+    }
+}
+
+struct Frame {
+    fct_id: FctId,
+    position: SourcePosition,
+    fp: usize,
+
+    // only set for Dora functions
+    code: Option<(JitFctId, u32)>,
+}
+
+pub extern "C" fn statement_hook() {
+    let vm = get_vm();
+    let thread = THREAD.with(|thread| thread.borrow().clone());
+
+    let breakpoint = {
+        let mut debugger = vm.debugger.lock();
+
+        if debugger.mode == StepMode::Continue && debugger.breakpoints.is_empty() {
+            return;
+        }
+
+        // walking the whole stack is only necessary for the frame depth
+        let limit = match debugger.mode {
+            StepMode::Over(_, _) | StepMode::Out(_, _) => usize::max_value(),
+            _ => 1,
+        };
+
+        let frames = collect_frames(vm, limit);
+
+        let top = &frames[0];
+        let file = &vm.file(top.position.file).name;
+        let line = top.position.pos.line;
+
+        let breakpoint = debugger.breakpoint_at(file, line);
+
+        if breakpoint.is_none() && !debugger.step_finished(thread.id(), frames.len()) {
+            return;
+        }
+
+        debugger.mode = StepMode::Continue;
+        breakpoint
+    };
+
+    // Only one thread reads commands at a time. Threads stay parked while
+    // waiting here or for input, such that collections can still happen.
+    thread.park(vm);
+    let _session = vm.debugger_session.lock();
+    thread.unpark(vm);
+
+    let frames = collect_frames(vm, usize::max_value());
+    let top = &frames[0];
+
+    if let Some(id) = breakpoint {
+        print!("Breakpoint {}, ", id);
+    } else {
+        print!("Stopped at ");
+    }
+
+    print_frame_location(vm, top);
+    print_source_line(vm, top);
+
+    command_loop(vm, &frames, thread.id());
+}
+
+// The debugger is only locked while executing a command, not while waiting
+// for the next one.
+fn command_loop(vm: &VM, frames: &[Frame], thread_id: usize) {
+    let mut selected = 0;
+
+    loop {
+        print!("(dora) ");
+        io::stdout().flush().unwrap();
+
+        let line = match read_line(vm) {
+            Some(line) => line,
+            None => {
+                // no more commands: run the rest of the program without stopping
+                println!();
+                let mut debugger = vm.debugger.lock();
+                debugger.breakpoints.clear();
+                debugger.mode = StepMode::Continue;
+                return;
+            }
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let cmd = match parse_command(&line) {
+            Ok(cmd) => cmd,
+            Err(msg) => {
+                println!("{}", msg);
+                continue;
+            }
+        };
+
+        let mut debugger = vm.debugger.lock();
+
+        match cmd {
+            Command::Break(file, line) => {
+                let file = file.unwrap_or_else(|| {
+                    let position = frames[selected].position;
+                    vm.file(position.file).name.clone()
+                });
+                let id = debugger.add_breakpoint(file.clone(), line);
+                println!("Breakpoint {} at {}:{}", id, file, line);
+            }
+
+            Command::Delete(Some(id)) => {
+                if !debugger.remove_breakpoint(id) {
+                    println!("no breakpoint {}", id);
+                }
+            }
+
+            Command::Delete(None) => debugger.breakpoints.clear(),
+
+            Command::Breakpoints => {
+                if debugger.breakpoints.is_empty() {
+                    println!("no breakpoints");
+                }
+
+                for bp in &debugger.breakpoints {
+                    println!("{}: {}:{}", bp.id, bp.file, bp.line);
+                }
+            }
+
+            Command::Continue => {
+                debugger.mode = StepMode::Continue;
+                return;
+            }
+
+            Command::Step => {
+                debugger.mode = StepMode::Into(Some(thread_id));
+                return;
+            }
+
+            Command::Next => {
+                debugger.mode = StepMode::Over(thread_id, frames.len());
+                return;
+            }
+
+            Command::Finish => {
+                debugger.mode = StepMode::Out(thread_id, frames.len());
+                return;
+            }
+
+            Command::Backtrace => {
+                for (idx, frame) in frames.iter().enumerate() {
+                    let marker = if idx == selected { "*" } else { " " };
+                    print!("{}#{} ", marker, idx);
+                    print_frame_location(vm, frame);
+                }
+            }
+
+            Command::Frame(idx) => {
+                if idx < frames.len() {
+                    selected = idx;
+                    print!("#{} ", idx);
+                    print_frame_location(vm, &frames[idx]);
+                    print_source_line(vm, &frames[idx]);
+                } else {
+                    println!("no frame {}", idx);
+                }
+            }
+
+            Command::Locals => {
+                let variables = frame_variables(vm, &frames[selected]);
+
+                if variables.is_empty() {
+                    println!("no variables");
+                }
+
+                for var in variables {
+                    let addr = frames[selected].fp as isize + var.offset as isize;
+                    println!(
+                        "{}: {} = {}",
+                        vm.interner.str(var.name),
+                        var.ty.name(vm),
+                        unsafe { format_value(vm, var.ty, addr as usize, false, false) }
+                    );
+                }
+            }
+
+            Command::Print(path) => match unsafe { lookup(vm, &frames[selected], &path) } {
+                Ok((ty, addr, in_object)) => {
+                    println!("{} = {}", path.join("."), unsafe {
+                        format_value(vm, ty, addr, in_object, true)
+                    });
+                }
+
+                Err(msg) => println!("{}", msg),
+            },
+
+            Command::Quit => process::exit(0),
+
+            Command::Help => println!("{}", HELP),
+        }
+    }
+}
+
+// Reads the next command from stdin, the thread is parked while blocking.
+fn read_line(vm: &VM) -> Option<String> {
+    let thread = THREAD.with(|thread| thread.borrow().clone());
+    let mut line = String::new();
+
+    thread.park(vm);
+    let result = io::stdin().lock().read_line(&mut line);
+    thread.unpark(vm);
+
+    match result {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line),
+    }
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let args = &words[1..];
+
+    let cmd = match (words[0], args.len()) {
+        ("b", 1) | ("break", 1) => {
+            let (file, line) = match args[0].rfind(':') {
+                Some(idx) => (Some(args[0][..idx].to_string()), &args[0][idx + 1..]),
+                None => (None, args[0]),
+            };
+
+            let line = line
+                .parse()
+                .map_err(|_| format!("invalid line `{}`", line))?;
+
+            Command::Break(file, line)
+        }
+
+        ("d", _) | ("delete", _) if args.len() <= 1 => {
+            let id = match args.first() {
+                Some(id) => Some(id.parse().map_err(|_| format!("invalid id `{}`", id))?),
+                None => None,
+            };
+
+            Command::Delete(id)
+        }
+
+        ("breakpoints", 0) => Command::Breakpoints,
+        ("c", 0) | ("continue", 0) => Command::Continue,
+        ("s", 0) | ("step", 0) => Command::Step,
+        ("n", 0) | ("next", 0) => Command::Next,
+        ("finish", 0) => Command::Finish,
+        ("bt", 0) | ("backtrace", 0) => Command::Backtrace,
+
+        ("f", 1) | ("frame", 1) => {
+            let idx = args[0]
+                .parse()
+                .map_err(|_| format!("invalid frame `{}`", args[0]))?;
+
+            Command::Frame(idx)
+        }
+
+        ("locals", 0) => Command::Locals,
+
+        ("p", 1) | ("print", 1) => {
+            let path: Vec<String> = args[0].split('.').map(|name| name.to_string()).collect();
+
+            if path.iter().any(|name| name.is_empty()) {
+                return Err(format!("invalid expression `{}`", args[0]));
+            }
+
+            Command::Print(path)
+        }
+
+        ("q", 0) | ("quit", 0) => Command::Quit,
+        ("h", 0) | ("help", 0) => Command::Help,
+
+        _ => return Err(format!("unknown command `{}`, try `help`", line.trim())),
+    };
+
+    Ok(cmd)
+}
+
+// Walks the frames of the current thread, starting with the function that
+// called the debug stub. Stops after limit frames.
+fn collect_frames(vm: &VM, limit: usize) -> Vec<Frame> {
+    let code_map = vm.code_map.lock();
+    let mut frames = Vec::new();
+    let mut dtn_ptr = THREAD.with(|thread| thread.borrow().dtn());
+
+    while !dtn_ptr.is_null() && frames.len() < limit {
+        let dtn = unsafe { &*dtn_ptr };
+        walk_frames(vm, &code_map, dtn.pc, dtn.fp, limit, &mut frames);
+        dtn_ptr = dtn.last;
+    }
+
+    frames
+}
+
+fn walk_frames(
+    vm: &VM,
+    code_map: &CodeMap,
+    mut pc: usize,
+    mut fp: usize,
+    limit: usize,
+    frames: &mut Vec<Frame>,
+) {
+    while frames.len() < limit {
+        match code_map.get(pc.into()) {
+            Some(CodeDescriptor::DoraFct(jit_fct_id)) => {
+                let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+                let offset = (pc - jit_fct.instruction_start().to_usize()) as u32;
+
+                // pc is a return address and points behind the call
+                let inlined_frames = jit_fct
+                    .frames_for_instruction(offset - 1)
+                    .expect("position not found for program point");
+
+                for (fct_id, position) in inlined_frames {
+                    frames.push(Frame {
+                        fct_id,
+                        position,
+                        fp,
+                        code: Some((jit_fct_id, offset)),
+                    });
+                }
+            }
+
+            Some(CodeDescriptor::NativeStub(jit_fct_id)) => {
+                let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+                let fct = vm.fcts.idx(jit_fct.fct_id());
+                let fct = fct.read();
+
+                frames.push(Frame {
+                    fct_id: fct.id,
+                    position: SourcePosition::new(fct.file, fct.ast.pos),
+                    fp,
+                    code: None,
+                });
+            }
+
+            Some(CodeDescriptor::DoraStub) | None => return,
+            Some(_) => {}
+        }
+
+        if fp == 0 {
+            return;
+        }
+
+        pc = unsafe { *((fp + 8) as *const usize) };
+        fp = unsafe { *(fp as *const usize) };
+    }
+}
+
+fn print_frame_location(vm: &VM, frame: &Frame) {
+    let fct = vm.fcts.idx(frame.fct_id);
+    let fct = fct.read();
+
+    println!(
+        "{}: {}:{}:{}",
+        fct.full_name(vm),
+        vm.file(frame.position.file).name,
+        frame.position.pos.line,
+        frame.position.pos.column
+    );
+}
+
+fn print_source_line(vm: &VM, frame: &Frame) {
+    let line = frame.position.pos.line;
+    let file = vm.file(frame.position.file);
+
+    if let Some(content) = file.content.lines().nth(line as usize - 1) {
+        println!("{:4} | {}", line, content);
+    }
+}
+
+fn frame_variables(vm: &VM, frame: &Frame) -> Vec<VariableInfo> {
+    match frame.code {
+        Some((jit_fct_id, offset)) => {
+            let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+            let code = jit_fct.to_code().expect("uncompiled function");
+
+            code.variables_for_offset(offset)
+                .into_iter()
+                .cloned()
+                .collect()
+        }
+
+        None => Vec::new(),
+    }
+}
+
+// Returns type and address of a variable or a (nested) field and whether
+// the value is stored in an object.
+unsafe fn lookup(
+    vm: &VM,
+    frame: &Frame,
+    path: &[String],
+) -> Result<(BuiltinType, usize, bool), String> {
+    let var = frame_variables(vm, frame)
+        .into_iter()
+        .rev()
+        .find(|var| *vm.interner.str(var.name) == path[0])
+        .ok_or_else(|| format!("no variable `{}` in selected frame", path[0]))?;
+
+    let mut ty = var.ty;
+    let mut addr = (frame.fp as isize + var.offset as isize) as usize;
+    let mut in_object = false;
+
+    for name in &path[1..] {
+        if !ty.reference_type() {
+            return Err(format!("`{}` is not an object", name));
+        }
+
+        let obj = read_reference(addr, in_object);
+
+        if obj == 0 {
+            return Err(format!("cannot access field `{}` of nil", name));
+        }
+
+        let cls_def_id = object_class(obj);
+        let field = class_fields(vm, cls_def_id)
+            .into_iter()
+            .find(|&(field_name, _, _)| *vm.interner.str(field_name) == *name)
+            .ok_or_else(|| format!("no field `{}`", name))?;
+
+        addr = obj + field.1 as usize;
+        ty = field.2;
+        in_object = true;
+    }
+
+    Ok((ty, addr, in_object))
+}
+
+// Formats the value of the given type stored at addr. Objects are only
+// expanded into their fields at the top level.
+unsafe fn format_value(
+    vm: &VM,
+    ty: BuiltinType,
+    addr: usize,
+    in_object: bool,
+    expand: bool,
+) -> String {
+    match ty {
+        BuiltinType::Unit => "()".into(),
+        BuiltinType::Bool => format!("{}", *(addr as *const bool)),
+        BuiltinType::UInt8 => format!("{}", *(addr as *const u8)),
+        BuiltinType::Char => {
+            let value = *(addr as *const u32);
+            std::char::from_u32(value)
+                .map(|ch| format!("{:?}", ch))
+                .unwrap_or_else(|| format!("{}", value))
+        }
+        BuiltinType::Int32 => format!("{}", *(addr as *const i32)),
+        BuiltinType::Int64 => format!("{}", *(addr as *const i64)),
+        BuiltinType::Float32 => format!("{}", *(addr as *const f32)),
+        BuiltinType::Float64 => format!("{}", *(addr as *const f64)),
+
+        BuiltinType::Enum(enum_id, _) => {
+            let xenum = vm.enums[enum_id].read();
+            let value = *(addr as *const i32);
+
+            if xenum
+                .variants
+                .iter()
+                .all(|variant| variant.types.is_empty())
+            {
+                let variant = &xenum.variants[value as usize];
+                format!(
+                    "{}::{}",
+                    vm.interner.str(xenum.name),
+                    vm.interner.str(variant.name)
+                )
+            } else {
+                format!("<{}>", ty.name(vm))
+            }
+        }
+
+        _ if ty.reference_type() => format_object(vm, read_reference(addr, in_object), expand),
+        _ => format!("<{}>", ty.name(vm)),
+    }
+}
+
+unsafe fn format_object(vm: &VM, obj: usize, expand: bool) -> String {
+    if obj == 0 {
+        return "nil".into();
+    }
+
+    let cls_def_id = object_class(obj);
+    let cls_def = vm.class_defs.idx(cls_def_id);
+    let cls_def = cls_def.read();
+    let name = cls_def.name(vm);
+
+    match cls_def.size {
+        InstanceSize::Str => {
+            let string = &*(obj as *const Str);
+            format!("{:?}", String::from_utf8_lossy(string.content()))
+        }
+
+        InstanceSize::Fixed(_) if expand => {
+            let fields = class_fields(vm, cls_def_id)
+                .into_iter()
+                .map(|(field_name, offset, ty)| {
+                    format!(
+                        "{}: {}",
+                        vm.interner.str(field_name),
+                        format_value(vm, ty, obj + offset as usize, true, false)
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            if fields.is_empty() {
+                name
+            } else {
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+        }
+
+        InstanceSize::Fixed(_) => format!("{}@{:#x}", name, obj),
+
+        _ => {
+            let length = *((obj + offset_of_array_length() as usize) as *const usize);
+            format!("{}(length {})@{:#x}", name, length, obj)
+        }
+    }
+}
+
+// With --compressed-refs only references in objects are narrow, stack
+// slots always hold the full pointer.
+unsafe fn read_reference(addr: usize, in_object: bool) -> usize {
+    let slot = if in_object {
+        Slot::field(Address::from(addr))
+    } else {
+        Slot::at(Address::from(addr))
+    };
+
+    slot.get().to_usize()
+}
+
+unsafe fn object_class(obj: usize) -> ClassDefId {
+    let obj = &*(obj as *const Obj);
+    obj.header().vtbl().class().id
+}
+
+// All fields of a class including the ones of its super classes.
+fn class_fields(vm: &VM, cls_def_id: ClassDefId) -> Vec<(Name, i32, BuiltinType)> {
+    let cls_def = vm.class_defs.idx(cls_def_id);
+    let cls_def = cls_def.read();
+
+    let mut fields = match cls_def.parent_id {
+        Some(parent_id) => class_fields(vm, parent_id),
+        None => Vec::new(),
+    };
+
+    if let Some(cls_id) = cls_def.cls_id {
+        let cls = vm.classes.idx(cls_id);
+        let cls = cls.read();
+
+        for (field, field_def) in cls.fields.iter().zip(&cls_def.fields) {
+            fields.push((field.name, field_def.offset, field_def.ty));
+        }
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Ok(Command::Break(None, 12)), parse_command("b 12\n"));
+        assert_eq!(
+            Ok(Command::Break(Some("foo.dora".into()), 3)),
+            parse_command("break foo.dora:3")
+        );
+        assert!(parse_command("break foo.dora:x").is_err());
+        assert_eq!(Ok(Command::Delete(None)), parse_command("delete"));
+        assert_eq!(Ok(Command::Delete(Some(2))), parse_command("d 2"));
+        assert_eq!(Ok(Command::Next), parse_command("  n  "));
+        assert_eq!(Ok(Command::Frame(1)), parse_command("frame 1"));
+        assert_eq!(
+            Ok(Command::Print(vec!["foo".into(), "bar".into()])),
+            parse_command("p foo.bar")
+        );
+        assert!(parse_command("p foo.").is_err());
+        assert!(parse_command("step 1").is_err());
+        assert!(parse_command("jump").is_err());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new();
+        let id = debugger.add_breakpoint("foo.dora".into(), 3);
+
+        assert_eq!(Some(id), debugger.breakpoint_at("foo.dora", 3));
+        assert_eq!(Some(id), debugger.breakpoint_at("tests/foo.dora", 3));
+        assert_eq!(None, debugger.breakpoint_at("tests/xfoo.dora", 3));
+        assert_eq!(None, debugger.breakpoint_at("foo.dora", 4));
+
+        assert!(debugger.remove_breakpoint(id));
+        assert!(!debugger.remove_breakpoint(id));
+        assert_eq!(None, debugger.breakpoint_at("foo.dora", 3));
+    }
+
+    #[test]
+    fn test_step_finished() {
+        let mut debugger = Debugger::new();
+        assert!(debugger.step_finished(1, 3));
+
+        debugger.mode = StepMode::Continue;
+        assert!(!debugger.step_finished(1, 3));
+
+        debugger.mode = StepMode::Into(Some(1));
+        assert!(debugger.step_finished(1, 5));
+        assert!(!debugger.step_finished(2, 5));
+
+        debugger.mode = StepMode::Over(1, 3);
+        assert!(debugger.step_finished(1, 3));
+        assert!(debugger.step_finished(1, 2));
+        assert!(!debugger.step_finished(1, 4));
+
+        debugger.mode = StepMode::Out(1, 3);
+        assert!(!debugger.step_finished(1, 3));
+        assert!(debugger.step_finished(1, 2));
+    }
+}
@@ -1,4 +1,6 @@
 pub use self::start::*;
 
 pub mod cmd;
+pub mod fmt;
+pub mod gc_report;
 pub mod start;
@@ -19,6 +19,9 @@ pub fn parse() -> Args {
 // Write the Docopt usage string.
 static USAGE: &'static str = "
 Usage: dora test [options] <file>
+       dora debug [options] <file> [--] [<argument>...]
+       dora fmt [options] <path>...
+       dora gc-report [options] <file>
        dora [options] <file> [--] [<argument>...]
        dora (--version | --help)
 
@@ -30,32 +33,46 @@ Options:
     --emit-asm=<fct>        Emits assembly code to stdout.
     --emit-asm-file         Emits assembly code into file `dora-<pid>.asm`.
     --emit-bytecode=<fct>   Emits bytecode to stdout.
+    --emit-graph=<fct>      Runs the boots optimization passes and emits the graph after each pass to stdout.
     --emit-stubs            Emits generated stubs.
     --emit-debug=<fct>      Emits debug instruction at beginning of functions.
     --emit-debug-native     Emits debug instruction at beginning of native stub.
     --emit-debug-compile    Emits debug instruction at beginning of compile stub.
     --emit-debug-entry      Emits debug instruction at beginning of entry stub.
     --omit-bounds-check     Omit array index out of bounds checks.
-    --check                 Only type check given program.
+    --check                 Only type check given program. With fmt only list unformatted files.
+    --error-format=<fmt>    Format of compiler errors. Possible values: short (default), human, json.
+    --deny-warnings         Fail if the program has warnings.
+    --allow=<lints>         Comma separated list of lints that are not reported.
     --asm-syntax TYPE       Emits assembly with Intel or AT&T syntax.
                             Allowed values: intel, att.
     --enable-perf           Enable dump for perf.
+    --profile=<file>        Sample Dora code and write a profile to file.
+    --perf-jitdump          Write jitdump file with code and line info for perf.
+    --enable-gdb            Register generated code with the GDB JIT interface.
     --gc-events             Dump GC events.
     --gc-stress             Collect garbage at every allocation.
     --gc-stress-minor       Minor collection at every allocation.
     --gc-parallel-full      Enable parallel full collection.
     --gc-parallel-minor     Enable parallel minor collection.
     --gc-parallel           Enable both parallel minor and full collection.
+    --gc-concurrent-mark    Mark old generation concurrently to the mutator.
     --gc-stats              Print GC statistics.
+    --gc-log=<file>         Write one JSON record per collection to file.
     --gc-verbose            Verbose GC.
     --gc-dev-verbose        Verbose GC for developers.
     --gc-verify             Verify heap before and after collections.
     --gc-verify-write       Verify references when storing in the heap.
+    --gc-verify-stackmaps   Verify stack maps of compiled code at each collection.
     --gc-worker=<num>       Number of GC worker threads.
-    --gc=<name>             Switch GC. Possible values: zero, copy, swiper (default).
+    --gc=<name>             Switch GC. Possible values: zero, copy, immix, swiper (default).
     --gc-young-size=<SIZE>  Use fixed size for young generation.
     --gc-young-appel        Use Appel dynamic resizing of young generation.
     --gc-semi-ratio=<num>   Use fixed ratio of semi space in young generation.
+    --gc-uncommit-delay=<ms>  Return free heap memory to the OS at the first collection after it was unused for this delay.
+    --gc-max-pause=<ms>     Adapt young generation to keep GC pauses below this goal.
+    --gc-alloc-sites        Track allocation sites and print the sites that allocate most.
+    --gc-pretenure          Allocate objects of sites with high survival rate directly in the old generation.
 
     --compiler=<name>       Switch default compiler. Possible values: cannon, baseline [default: cannon].
     --test-filter=<name>    Filter tests.
@@ -65,6 +82,7 @@ Options:
 
     --min-heap-size=<SIZE>  Set minimum heap size.
     --max-heap-size=<SIZE>  Set maximum heap size.
+    --compressed-refs       Store references in the heap as 32-bit offsets, all memory reserved by the collector needs to fit into 32G (swiper: heaps below 8G), references inside tuples keep full pointer width.
     --code-size=<SIZE>      Set code size limit.
     --perm-size=<SIZE>      Set perm size limit.
 
@@ -76,14 +94,19 @@ Options:
 pub struct Args {
     pub arg_argument: Option<Vec<String>>,
     pub arg_file: String,
+    pub arg_path: Vec<String>,
 
     pub flag_emit_ast: bool,
     pub flag_emit_asm: Option<String>,
     pub flag_emit_asm_file: bool,
     pub flag_emit_bytecode: Option<String>,
+    pub flag_emit_graph: Option<String>,
     pub flag_emit_llvm: bool,
     pub flag_emit_stubs: bool,
     pub flag_enable_perf: bool,
+    pub flag_perf_jitdump: bool,
+    pub flag_profile: Option<String>,
+    pub flag_enable_gdb: bool,
     pub flag_omit_bounds_check: bool,
     pub flag_version: bool,
     pub flag_emit_debug: Option<String>,
@@ -97,20 +120,31 @@ pub struct Args {
     flag_gc_parallel_full: bool,
     flag_gc_parallel_minor: bool,
     flag_gc_parallel: bool,
+    pub flag_gc_concurrent_mark: bool,
     pub flag_gc_stats: bool,
+    pub flag_gc_log: Option<String>,
     pub flag_gc_verbose: bool,
     pub flag_gc_dev_verbose: bool,
     pub flag_gc_verify: bool,
+    pub flag_gc_verify_stackmaps: bool,
     pub flag_gc_worker: usize,
     flag_gc_young_size: Option<MemSize>,
     pub flag_gc_semi_ratio: Option<usize>,
+    flag_gc_uncommit_delay: Option<usize>,
+    flag_gc_max_pause: Option<f32>,
+    pub flag_gc_alloc_sites: bool,
+    pub flag_gc_pretenure: bool,
     pub flag_gc: Option<CollectorName>,
     pub flag_compiler: Option<CompilerName>,
     pub flag_min_heap_size: Option<MemSize>,
     pub flag_max_heap_size: Option<MemSize>,
+    pub flag_compressed_refs: bool,
     pub flag_code_size: Option<MemSize>,
     pub flag_perm_size: Option<MemSize>,
     pub flag_check: bool,
+    pub flag_error_format: Option<ErrorFormat>,
+    pub flag_deny_warnings: bool,
+    pub flag_allow: Option<String>,
     pub flag_disable_tlab: bool,
     pub flag_disable_barrier: bool,
     pub flag_stdlib: Option<String>,
@@ -118,6 +152,9 @@ pub struct Args {
     pub flag_test_filter: Option<String>,
 
     pub cmd_test: bool,
+    pub cmd_debug: bool,
+    pub cmd_fmt: bool,
+    pub cmd_gc_report: bool,
 }
 
 impl Args {
@@ -158,6 +195,33 @@ impl Args {
         self.flag_gc_young_size.map(|young_size| *young_size)
     }
 
+    pub fn gc_uncommit_delay(&self) -> usize {
+        self.flag_gc_uncommit_delay.unwrap_or(0)
+    }
+
+    // phases of a collection are only timed when somebody looks at them
+    pub fn gc_phase_timing(&self) -> bool {
+        self.flag_gc_stats || self.flag_gc_log.is_some()
+    }
+
+    pub fn gc_max_pause(&self) -> Option<f32> {
+        self.flag_gc_max_pause
+    }
+
+    // profilers, gdb and the debugger remember code addresses
+    pub fn code_collection(&self) -> bool {
+        self.flag_profile.is_none()
+            && !self.flag_enable_perf
+            && !self.flag_perf_jitdump
+            && !self.flag_enable_gdb
+            && !self.cmd_debug
+    }
+
+    // pretenuring needs the survival rate of each site
+    pub fn alloc_site_tracking(&self) -> bool {
+        self.flag_gc_alloc_sites || self.flag_gc_pretenure
+    }
+
     pub fn young_appel(&self) -> bool {
         self.flag_gc_young_size.is_none()
     }
@@ -173,6 +237,25 @@ impl Args {
     pub fn compiler(&self) -> CompilerName {
         self.flag_compiler.unwrap_or(CompilerName::Cannon)
     }
+
+    pub fn error_format(&self) -> ErrorFormat {
+        self.flag_error_format.unwrap_or(ErrorFormat::Short)
+    }
+
+    pub fn allowed_lints(&self) -> Vec<&str> {
+        match self.flag_allow {
+            Some(ref lints) => lints
+                .split(',')
+                .map(|lint| lint.trim())
+                .filter(|lint| !lint.is_empty())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn allows_lint(&self, lint: &str) -> bool {
+        self.allowed_lints().contains(&lint)
+    }
 }
 
 impl Default for Args {
@@ -180,11 +263,13 @@ impl Default for Args {
         Args {
             arg_argument: None,
             arg_file: "".into(),
+            arg_path: Vec::new(),
 
             flag_emit_ast: false,
             flag_emit_asm: None,
             flag_emit_asm_file: false,
             flag_emit_bytecode: None,
+            flag_emit_graph: None,
             flag_emit_llvm: false,
             flag_emit_stubs: false,
             flag_emit_debug: None,
@@ -192,6 +277,9 @@ impl Default for Args {
             flag_emit_debug_native: false,
             flag_emit_debug_entry: false,
             flag_enable_perf: false,
+            flag_perf_jitdump: false,
+            flag_profile: None,
+            flag_enable_gdb: false,
             flag_omit_bounds_check: false,
             flag_version: false,
             flag_asm_syntax: None,
@@ -201,20 +289,31 @@ impl Default for Args {
             flag_gc_parallel_full: false,
             flag_gc_parallel_minor: false,
             flag_gc_parallel: false,
+            flag_gc_concurrent_mark: false,
             flag_gc_stats: false,
+            flag_gc_log: None,
             flag_gc_verbose: false,
             flag_gc_dev_verbose: false,
             flag_gc_verify: false,
+            flag_gc_verify_stackmaps: false,
             flag_gc_worker: 0,
             flag_gc_young_size: None,
             flag_gc_semi_ratio: None,
+            flag_gc_uncommit_delay: None,
+            flag_gc_max_pause: None,
+            flag_gc_alloc_sites: false,
+            flag_gc_pretenure: false,
             flag_gc: None,
             flag_compiler: None,
             flag_min_heap_size: None,
             flag_max_heap_size: None,
+            flag_compressed_refs: false,
             flag_code_size: None,
             flag_perm_size: None,
             flag_check: false,
+            flag_error_format: None,
+            flag_deny_warnings: false,
+            flag_allow: None,
             flag_disable_tlab: false,
             flag_disable_barrier: false,
             flag_stdlib: None,
@@ -222,6 +321,9 @@ impl Default for Args {
             flag_test_filter: None,
 
             cmd_test: false,
+            cmd_debug: false,
+            cmd_fmt: false,
+            cmd_gc_report: false,
         }
     }
 }
@@ -234,6 +336,7 @@ pub enum CollectorName {
     Sweep,
     Swiper,
     SweepSwiper,
+    Immix,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -243,6 +346,13 @@ pub enum CompilerName {
     Boots,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum ErrorFormat {
+    Short,
+    Human,
+    Json,
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub enum AsmSyntax {
     Intel,
@@ -0,0 +1,100 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::driver::cmd::Args;
+use crate::error::diag::{Report, Severity};
+use dora_parser::format;
+use dora_parser::lexer::reader::Reader;
+
+// Formats the given files and all Dora files in the given directories in
+// place. With `--check` nothing is written, instead all files that are not
+// formatted are listed and the exit code is 1.
+pub fn start(args: &Args) -> i32 {
+    let mut files = Vec::new();
+    let mut code = 0;
+
+    for path in &args.arg_path {
+        let path = Path::new(path);
+
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+        } else if let Err(err) = collect_files(path, &mut files) {
+            eprintln!("cannot read {}: {}", path.display(), err);
+            code = 1;
+        }
+    }
+
+    for path in files {
+        let name = path.to_string_lossy().into_owned();
+
+        let reader = match Reader::from_file(&name) {
+            Ok(reader) => reader,
+
+            Err(err) => {
+                eprintln!("cannot read {}: {}", name, err);
+                code = 1;
+                continue;
+            }
+        };
+
+        let content = reader.content().to_string();
+
+        let formatted = match format::format(reader) {
+            Ok(formatted) => formatted,
+
+            Err(errors) => {
+                for err in errors {
+                    let report = Report {
+                        file: &name,
+                        content: Some(&content),
+                        pos: err.pos,
+                        span: None,
+                        severity: Severity::Error,
+                        code: err.error.code(),
+                        message: err.error.message(),
+                    };
+
+                    report.emit(args.error_format());
+                }
+
+                code = 1;
+                continue;
+            }
+        };
+
+        if formatted == content {
+            continue;
+        }
+
+        if args.flag_check {
+            println!("{}", name);
+            code = 1;
+        } else if let Err(err) = fs::write(&path, formatted) {
+            eprintln!("cannot write {}: {}", name, err);
+            code = 1;
+        }
+    }
+
+    code
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        entries.push(entry?.path());
+    }
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.extension().map_or(false, |ext| ext == "dora") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::fs;
+
+use crate::driver::cmd::Args;
+use crate::gc::formatted_size;
+use crate::json::Json;
+
+// Summarizes a log written with --gc-log: pause time percentiles for minor
+// and full collections and the share of the runtime spent in the mutator.
+pub fn start(args: &Args) -> i32 {
+    let content = match fs::read_to_string(&args.arg_file) {
+        Ok(content) => content,
+
+        Err(err) => {
+            eprintln!("cannot read {}: {}", args.arg_file, err);
+            return 1;
+        }
+    };
+
+    match GcReport::parse(&content) {
+        Ok(report) => {
+            report.print();
+            0
+        }
+
+        Err(msg) => {
+            eprintln!("{}: {}", args.arg_file, msg);
+            1
+        }
+    }
+}
+
+struct GcReport {
+    minor: Vec<f64>,
+    full: Vec<f64>,
+    promoted: usize,
+    runtime: f64,
+}
+
+impl GcReport {
+    fn parse(content: &str) -> Result<GcReport, String> {
+        let mut report = GcReport {
+            minor: Vec::new(),
+            full: Vec::new(),
+            promoted: 0,
+            runtime: 0.0,
+        };
+
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record = Json::parse(line).map_err(|msg| format!("line {}: {}", idx + 1, msg))?;
+            let number = |key: &str| {
+                record
+                    .get(key)
+                    .and_then(|value| value.as_f64())
+                    .ok_or_else(|| format!("line {}: missing `{}`", idx + 1, key))
+            };
+
+            let time = number("time")?;
+
+            match record.get("kind").and_then(|kind| kind.as_str()) {
+                Some("end") => {
+                    report.runtime = time;
+                    continue;
+                }
+
+                Some("minor") => report.minor.push(number("pause")?),
+                Some("full") => report.full.push(number("pause")?),
+
+                _ => return Err(format!("line {}: unknown `kind`", idx + 1)),
+            }
+
+            // without end record the program ran at least until the last collection
+            report.runtime = f64::max(report.runtime, time + number("pause")?);
+            report.promoted += number("promoted")? as usize;
+        }
+
+        Ok(report)
+    }
+
+    fn print(&self) {
+        let mut all = self.minor.clone();
+        all.extend_from_slice(&self.full);
+
+        let gc = total(&all);
+        let throughput = if self.runtime > 0.0 {
+            100.0 * (self.runtime - gc) / self.runtime
+        } else {
+            100.0
+        };
+
+        println!(
+            "collections: {} ({} minor, {} full)",
+            all.len(),
+            self.minor.len(),
+            self.full.len()
+        );
+        println!(
+            "runtime: {:.1}ms, GC: {:.1}ms, throughput: {:.1}%",
+            self.runtime, gc, throughput
+        );
+        println!("promoted: {}", formatted_size(self.promoted));
+        println!("");
+        println!(
+            "{:<8}{:>8}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "pauses", "count", "p50", "p90", "p99", "max", "total"
+        );
+
+        for (name, pauses) in &[("minor", &self.minor), ("full", &self.full), ("all", &all)] {
+            print_pauses(name, pauses);
+        }
+    }
+}
+
+fn print_pauses(name: &str, pauses: &[f64]) {
+    let mut pauses = pauses.to_vec();
+    pauses.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap());
+
+    println!(
+        "{:<8}{:>8}{:>10.2}{:>10.2}{:>10.2}{:>10.2}{:>10.2}",
+        name,
+        pauses.len(),
+        percentile(&pauses, 50.0),
+        percentile(&pauses, 90.0),
+        percentile(&pauses, 99.0),
+        percentile(&pauses, 100.0),
+        total(&pauses)
+    );
+}
+
+fn total(pauses: &[f64]) -> f64 {
+    pauses.iter().fold(0.0, |sum, pause| sum + pause)
+}
+
+// nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+        assert_eq!(5.0, percentile(&values, 50.0));
+        assert_eq!(9.0, percentile(&values, 90.0));
+        assert_eq!(10.0, percentile(&values, 99.0));
+        assert_eq!(1.0, percentile(&values, 0.0));
+        assert_eq!(0.0, percentile(&[], 50.0));
+    }
+
+    #[test]
+    fn test_parse() {
+        let log = r#"{"gc":1,"time":10,"kind":"minor","pause":2.5,"promoted":1024}
+{"gc":2,"time":20,"kind":"full","pause":7.5,"promoted":0}
+{"kind":"end","time":100}
+"#;
+        let report = GcReport::parse(log).unwrap();
+        assert_eq!(vec![2.5], report.minor);
+        assert_eq!(vec![7.5], report.full);
+        assert_eq!(1024, report.promoted);
+        assert_eq!(100.0, report.runtime);
+
+        let report = GcReport::parse(&log[..log.rfind("{\"kind\"").unwrap()]).unwrap();
+        assert_eq!(27.5, report.runtime);
+
+        assert!(GcReport::parse("{\"kind\":\"minor\"}").is_err());
+        assert!(GcReport::parse("{").is_err());
+    }
+}
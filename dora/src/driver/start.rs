@@ -1,13 +1,18 @@
 use std::fs;
 use std::path::Path;
 
-use crate::error::msg::SemError;
-use crate::vm::VM;
+use crate::driver::cmd::{CollectorName, ErrorFormat};
+use crate::error::msg::{SemError, LINTS};
 use crate::vm::{Fct, FctId};
+use crate::vm::{FileId, VM};
 use dora_parser::ast::{self, Ast};
 
 use crate::driver::cmd;
+use crate::driver::fmt;
+use crate::driver::gc_report;
+use crate::gc::{self, compressed, formatted_size};
 use crate::object;
+use crate::profiler;
 use crate::timer::Timer;
 use dora_parser::lexer::reader::Reader;
 
@@ -30,6 +35,50 @@ pub fn start(content: Option<&str>) -> i32 {
         return 0;
     }
 
+    if args.cmd_fmt {
+        return fmt::start(&args);
+    }
+
+    if args.cmd_gc_report {
+        return gc_report::start(&args);
+    }
+
+    for lint in args.allowed_lints() {
+        if !LINTS.contains(&lint) {
+            eprintln!(
+                "unknown lint `{}`, possible values: {}.",
+                lint,
+                LINTS.join(", ")
+            );
+            return 1;
+        }
+    }
+
+    if args.flag_compressed_refs {
+        // the collector reserves more than the heap size, e.g. swiper
+        // reserves 4 times the heap size and its card table
+        let window_size = gc::compressed_window_size(&args);
+
+        if window_size > compressed::WINDOW_SIZE {
+            println!(
+                "heap reservation of {} does not fit into the {} window of --compressed-refs.",
+                formatted_size(window_size),
+                formatted_size(compressed::WINDOW_SIZE)
+            );
+            return 1;
+        }
+    }
+
+    if args.flag_gc_log.is_some() {
+        match args.flag_gc.unwrap_or(CollectorName::Swiper) {
+            CollectorName::Swiper => {}
+            _ => {
+                println!("--gc-log is only supported by the swiper collector.");
+                return 1;
+            }
+        }
+    }
+
     let mut ast = Ast::new();
     let empty = Ast::new();
     let mut vm = VM::new(args, &empty);
@@ -56,9 +105,11 @@ pub fn start(content: Option<&str>) -> i32 {
 
     if vm.diag.lock().has_errors() {
         vm.diag.lock().dump(&vm);
-        let no_errors = vm.diag.lock().errors().len();
+        let no_errors = vm.diag.lock().len();
 
-        if no_errors == 1 {
+        if vm.args.error_format() == ErrorFormat::Json {
+            // keep output parseable: one diagnostic per line and nothing else
+        } else if no_errors == 1 {
             eprintln!("{} error found.", no_errors);
         } else {
             eprintln!("{} errors found.", no_errors);
@@ -67,6 +118,24 @@ pub fn start(content: Option<&str>) -> i32 {
         return 1;
     }
 
+    if vm.diag.lock().has_warnings() {
+        vm.diag.lock().dump(&vm);
+
+        if vm.args.flag_deny_warnings {
+            let no_warnings = vm.diag.lock().warnings().len();
+
+            if vm.args.error_format() == ErrorFormat::Json {
+                // keep output parseable like for errors
+            } else if no_warnings == 1 {
+                eprintln!("{} warning found, denied by --deny-warnings.", no_warnings);
+            } else {
+                eprintln!("{} warnings found, denied by --deny-warnings.", no_warnings);
+            }
+
+            return 1;
+        }
+    }
+
     if !vm.args.cmd_test && main.is_none() {
         println!("error: no `main` function found in the program");
         return 1;
@@ -79,7 +148,9 @@ pub fn start(content: Option<&str>) -> i32 {
 
     let mut timer = Timer::new(vm.args.flag_gc_stats);
 
+    profiler::start(&vm);
     vm.threads.attach_current_thread();
+    let sampler = profiler::register_current_thread();
 
     let code = if vm.args.cmd_test {
         run_tests(&vm)
@@ -87,14 +158,21 @@ pub fn start(content: Option<&str>) -> i32 {
         run_main(&vm, main.unwrap())
     };
 
+    drop(sampler);
     vm.threads.detach_current_thread();
     vm.threads.join_all();
+    vm.gc.shutdown();
+    profiler::finish(&vm);
 
     if vm.args.flag_gc_stats {
         let duration = timer.stop();
         vm.dump_gc_summary(duration);
     }
 
+    if vm.args.flag_gc_alloc_sites {
+        vm.gc.alloc_sites.dump(&vm);
+    }
+
     code
 }
 
@@ -115,6 +193,8 @@ fn parse_all_files(vm: &mut VM, ast: &mut Ast, content: Option<&str>) -> Result<
         parse_dir(&boots, vm, ast)?;
     }
 
+    vm.program_files = vm.files.len();
+
     if fuzzing {
         return parse_str(content.unwrap(), vm, ast);
     }
@@ -311,29 +391,20 @@ fn parse_str(file: &str, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
     parse_reader(reader, vm, ast)
 }
 
-fn parse_reader(reader: Reader, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
-    let filename: String = reader.path().into();
+pub fn parse_reader(reader: Reader, vm: &mut VM, ast: &mut Ast) -> Result<(), i32> {
     let parser = Parser::new(reader, &vm.id_generator, ast, &mut vm.interner);
+    let (file, errors) = parser.parse();
 
-    match parser.parse() {
-        Ok(file) => {
-            vm.files.push(file);
-            assert_eq!(ast.files.len(), vm.files.len());
-            Ok(())
-        }
+    let file_id = FileId::from(vm.files.len() as u32);
+    vm.files.push(file);
+    assert_eq!(ast.files.len(), vm.files.len());
 
-        Err(error) => {
-            println!(
-                "error in {} at {}: {}",
-                filename,
-                error.pos,
-                error.error.message()
-            );
-            println!("error during parsing.");
-
-            Err(1)
-        }
+    // syntax errors are reported together with semantic errors after semck
+    for error in errors {
+        vm.diag.lock().report_parse_error(file_id, error);
     }
+
+    Ok(())
 }
 
 fn find_main<'ast>(vm: &VM<'ast>) -> Option<FctId> {
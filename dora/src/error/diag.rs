@@ -1,32 +1,387 @@
-use crate::error::msg::{SemError, SemErrorAndPos};
+use crate::driver::cmd::ErrorFormat;
+use crate::error::msg::{SemError, SemErrorAndPos, SemWarning, SemWarningAndPos};
+use crate::json::Json;
 use crate::vm::{FileId, VM};
 
-use dora_parser::lexer::position::Position;
+use dora_parser::error::ParseErrorAndPos;
+use dora_parser::lexer::position::{Position, Span};
 
 pub struct Diagnostic {
     errors: Vec<SemErrorAndPos>,
+    parse_errors: Vec<(FileId, ParseErrorAndPos)>,
+    warnings: Vec<SemWarningAndPos>,
 }
 
 impl Diagnostic {
     pub fn new() -> Diagnostic {
-        Diagnostic { errors: Vec::new() }
+        Diagnostic {
+            errors: Vec::new(),
+            parse_errors: Vec::new(),
+            warnings: Vec::new(),
+        }
     }
 
     pub fn errors(&self) -> &[SemErrorAndPos] {
         &self.errors
     }
 
+    pub fn parse_errors(&self) -> &[(FileId, ParseErrorAndPos)] {
+        &self.parse_errors
+    }
+
+    pub fn warnings(&self) -> &[SemWarningAndPos] {
+        &self.warnings
+    }
+
+    pub fn report_parse_error(&mut self, file: FileId, error: ParseErrorAndPos) {
+        self.parse_errors.push((file, error));
+    }
+
     pub fn report(&mut self, file: FileId, pos: Position, msg: SemError) {
         self.errors.push(SemErrorAndPos::new(file, pos, msg));
     }
 
+    pub fn report_span(&mut self, file: FileId, pos: Position, span: Span, msg: SemError) {
+        self.errors
+            .push(SemErrorAndPos::with_span(file, pos, span, msg));
+    }
+
+    pub fn warn(&mut self, file: FileId, pos: Position, span: Option<Span>, msg: SemWarning) {
+        self.warnings.push(SemWarningAndPos {
+            file,
+            pos,
+            span,
+            msg,
+        });
+    }
+
     pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty() || !self.parse_errors.is_empty()
+    }
+
+    // Syntax errors don't stop semantic analysis, the parser already
+    // recovered from them. Passes only bail out after semantic errors.
+    pub fn has_sem_errors(&self) -> bool {
         !self.errors.is_empty()
     }
 
+    pub fn has_warnings(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len() + self.parse_errors.len()
+    }
+
     pub fn dump(&self, vm: &VM) {
+        let format = vm.args.error_format();
+
+        for (file, err) in &self.parse_errors {
+            let file = vm.file(*file);
+
+            let report = Report {
+                file: &file.name,
+                content: Some(&file.content),
+                pos: err.pos,
+                span: None,
+                severity: Severity::Error,
+                code: err.error.code(),
+                message: err.error.message(),
+            };
+
+            report.emit(format);
+        }
+
         for err in &self.errors {
-            eprintln!("{}", &err.message(vm));
+            let file = vm.file(err.file);
+
+            let report = Report {
+                file: &file.name,
+                content: Some(&file.content),
+                pos: err.pos,
+                span: err.span,
+                severity: Severity::Error,
+                code: err.msg.code(),
+                message: err.msg.message(),
+            };
+
+            report.emit(format);
+        }
+
+        for warning in &self.warnings {
+            let file = vm.file(warning.file);
+
+            let report = Report {
+                file: &file.name,
+                content: Some(&file.content),
+                pos: warning.pos,
+                span: warning.span,
+                severity: Severity::Warning,
+                code: warning.msg.code(),
+                message: warning.msg.message(),
+            };
+
+            report.emit(format);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
         }
     }
 }
+
+// A single error or warning ready to be printed in one of the supported
+// formats.
+pub struct Report<'a> {
+    pub file: &'a str,
+    pub content: Option<&'a str>,
+    pub pos: Position,
+    pub span: Option<Span>,
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl<'a> Report<'a> {
+    pub fn emit(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Short => eprintln!("{}", self.short()),
+            ErrorFormat::Human => eprint!("{}", self.human()),
+            ErrorFormat::Json => eprintln!("{}", self.json()),
+        }
+    }
+
+    pub fn short(&self) -> String {
+        format!(
+            "{} in {} at {}: {}",
+            self.severity.as_str(),
+            self.file,
+            self.pos,
+            self.message
+        )
+    }
+
+    pub fn human(&self) -> String {
+        let mut out = format!(
+            "{}[{}]: {}\n",
+            self.severity.as_str(),
+            self.code,
+            self.message
+        );
+
+        let snippet = self.content.and_then(|content| self.snippet(content));
+
+        if let Some((line, text, column, length)) = snippet {
+            let gutter = " ".repeat(line.to_string().len());
+
+            out.push_str(&format!("{}--> {}:{}\n", gutter, self.file, self.pos));
+            out.push_str(&format!("{} |\n", gutter));
+            out.push_str(&format!("{} | {}\n", line, text));
+            out.push_str(&format!(
+                "{} | {}{}\n",
+                gutter,
+                " ".repeat(column),
+                "^".repeat(length)
+            ));
+        } else {
+            out.push_str(&format!(" --> {}:{}\n", self.file, self.pos));
+        }
+
+        out
+    }
+
+    pub fn json(&self) -> String {
+        let span = match self.span {
+            Some(span) if span.is_valid() => Json::object(vec![
+                ("start", span.start().into()),
+                ("end", span.end().into()),
+            ]),
+            _ => Json::Null,
+        };
+
+        Json::object(vec![
+            ("file", self.file.into()),
+            ("line", self.pos.line.into()),
+            ("column", self.pos.column.into()),
+            ("span", span),
+            ("code", self.code.into()),
+            ("severity", self.severity.as_str().into()),
+            ("message", self.message.as_str().into()),
+        ])
+        .to_string()
+    }
+
+    // Returns line number, line text with expanded tabs, start column and
+    // length of the caret. Uses the span if there is one, otherwise marks
+    // the single character at the error position.
+    fn snippet(&self, content: &str) -> Option<(u32, String, usize, usize)> {
+        if let Some(span) = self.span {
+            let start = span.start() as usize;
+            let end = span.end() as usize;
+
+            if span.is_valid()
+                && end <= content.len()
+                && content.is_char_boundary(start)
+                && content.is_char_boundary(end)
+            {
+                let line_start = content[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+                let line_end = content[start..]
+                    .find('\n')
+                    .map(|i| start + i)
+                    .unwrap_or(content.len());
+                let line = content[..start].matches('\n').count() as u32 + 1;
+
+                let column = expand_tabs(&content[line_start..start]).chars().count();
+                let marked = expand_tabs(&content[line_start..end.min(line_end)])
+                    .chars()
+                    .count();
+                let text = expand_tabs(&content[line_start..line_end]);
+
+                return Some((line, text, column, (marked - column).max(1)));
+            }
+        }
+
+        // positions without a line or column get no snippet
+        let line = (self.pos.line as usize).checked_sub(1)?;
+        let column = (self.pos.column as usize).checked_sub(1)?;
+        let text = content.lines().nth(line)?;
+
+        Some((self.pos.line, expand_tabs(text), column, 1))
+    }
+}
+
+// Expands tabs the same way the reader computes columns.
+fn expand_tabs(text: &str) -> String {
+    let tabwidth = 4;
+    let mut result = String::with_capacity(text.len());
+    let mut column = 0;
+
+    for ch in text.chars() {
+        if ch == '\t' {
+            let spaces = tabwidth - column % tabwidth;
+            result.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else if ch != '\r' {
+            result.push(ch);
+            column += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report<'a>(content: &'a str, pos: Position, span: Option<Span>) -> Report<'a> {
+        Report {
+            file: "foo.dora",
+            content: Some(content),
+            pos,
+            span,
+            severity: Severity::Error,
+            code: "E0004",
+            message: "unknown identifier `bar`.".into(),
+        }
+    }
+
+    #[test]
+    fn test_human_with_span() {
+        let content = "fun main() {\n\tlet x = bar;\n}\n";
+        let span = Span::new(22, 3);
+        let report = report(content, Position::new(2, 13), Some(span));
+
+        assert_eq!(
+            "error[E0004]: unknown identifier `bar`.\n \
+             --> foo.dora:2:13\n  \
+             |\n\
+             2 |     let x = bar;\n  \
+             |             ^^^\n",
+            report.human()
+        );
+    }
+
+    #[test]
+    fn test_human_without_span() {
+        let content = "fun main() {\n  bar;\n}\n";
+        let report = report(content, Position::new(2, 3), None);
+
+        assert_eq!(
+            "error[E0004]: unknown identifier `bar`.\n \
+             --> foo.dora:2:3\n  \
+             |\n\
+             2 |   bar;\n  \
+             |   ^\n",
+            report.human()
+        );
+    }
+
+    #[test]
+    fn test_human_without_position() {
+        let content = "fun main() {\n  bar;\n}\n";
+
+        for &(line, column) in &[(0, 3), (2, 0)] {
+            let report = report(content, Position { line, column }, None);
+
+            assert_eq!(
+                format!(
+                    "error[E0004]: unknown identifier `bar`.\n --> foo.dora:{}:{}\n",
+                    line, column
+                ),
+                report.human()
+            );
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let content = "fun main() {\n  bar;\n}\n";
+        let report = report(content, Position::new(2, 3), Some(Span::new(15, 3)));
+
+        assert_eq!(
+            "{\"file\":\"foo.dora\",\"line\":2,\"column\":3,\"span\":{\"start\":15,\"end\":18},\
+             \"code\":\"E0004\",\"severity\":\"error\",\"message\":\"unknown identifier `bar`.\"}",
+            report.json()
+        );
+    }
+
+    #[test]
+    fn test_warning() {
+        let content = "fun main() {\n  let x = 1;\n}\n";
+        let mut report = report(content, Position::new(2, 3), Some(Span::new(15, 10)));
+        report.severity = Severity::Warning;
+        report.code = "W0001";
+        report.message = "unused variable `x`.".into();
+
+        assert_eq!(
+            "warning in foo.dora at 2:3: unused variable `x`.",
+            report.short()
+        );
+        assert!(report
+            .human()
+            .starts_with("warning[W0001]: unused variable `x`.\n"));
+        assert!(report.json().contains("\"severity\":\"warning\""));
+    }
+
+    #[test]
+    fn test_json_escapes_message() {
+        let mut report = report("", Position::new(1, 1), None);
+        report.message = "a\"b\\c\n\u{1}".into();
+
+        assert!(report
+            .json()
+            .ends_with("\"span\":null,\"code\":\"E0004\",\"severity\":\"error\",\"message\":\"a\\\"b\\\\c\\n\\u0001\"}"));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::vm::{FileId, VM};
-use dora_parser::lexer::position::Position;
+use dora_parser::lexer::position::{Position, Span};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum SemError {
@@ -140,9 +140,155 @@ pub enum SemError {
     NameExpected,
     IndexExpected,
     IllegalTupleIndex(u64, String),
+    TupleElementNeedsTypeInfo,
 }
 
 impl SemError {
+    // Stable identifier for each kind of error, used in machine-readable
+    // diagnostics. Codes are never reused, new variants get the next number.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemError::Unimplemented => "E0001",
+            SemError::UnknownClass(..) => "E0002",
+            SemError::UnknownType(..) => "E0003",
+            SemError::UnknownIdentifier(..) => "E0004",
+            SemError::UnknownStruct(..) => "E0005",
+            SemError::UnknownFunction(..) => "E0006",
+            SemError::UnknownField(..) => "E0007",
+            SemError::UnknownMethod(..) => "E0008",
+            SemError::UnknownEnumValue(..) => "E0009",
+            SemError::MultipleCandidatesForMethod(..) => "E0010",
+            SemError::VariadicParameterNeedsToBeLast => "E0011",
+            SemError::UnknownMethodForTypeParam(..) => "E0012",
+            SemError::MultipleCandidatesForTypeParam(..) => "E0013",
+            SemError::MultipleCandidatesForStaticMethodWithTypeParam => "E0014",
+            SemError::UnknownStaticMethodWithTypeParam => "E0015",
+            SemError::UnknownStaticMethod(..) => "E0016",
+            SemError::UnknownCtor(..) => "E0017",
+            SemError::MethodExists(..) => "E0018",
+            SemError::IncompatibleWithNil(..) => "E0019",
+            SemError::IdentifierExists(..) => "E0020",
+            SemError::ShadowFunction(..) => "E0021",
+            SemError::ShadowParam(..) => "E0022",
+            SemError::ShadowClass(..) => "E0023",
+            SemError::ShadowClassConstructor(..) => "E0024",
+            SemError::ShadowStruct(..) => "E0025",
+            SemError::ShadowStructConstructor(..) => "E0026",
+            SemError::ShadowTrait(..) => "E0027",
+            SemError::ShadowField(..) => "E0028",
+            SemError::ShadowGlobal(..) => "E0029",
+            SemError::ShadowConst(..) => "E0030",
+            SemError::ShadowModule(..) => "E0031",
+            SemError::ShadowEnum(..) => "E0032",
+            SemError::ShadowEnumValue(..) => "E0033",
+            SemError::InvalidLhsAssignment => "E0034",
+            SemError::NoEnumValue => "E0035",
+            SemError::EnumArgsIncompatible(..) => "E0036",
+            SemError::EnumArgsNoParens(..) => "E0037",
+            SemError::VarNeedsTypeInfo(..) => "E0038",
+            SemError::ParamTypesIncompatible(..) => "E0039",
+            SemError::WhileCondType(..) => "E0040",
+            SemError::IfCondType(..) => "E0041",
+            SemError::ReturnType(..) => "E0042",
+            SemError::LvalueExpected => "E0043",
+            SemError::AssignType(..) => "E0044",
+            SemError::AssignField(..) => "E0045",
+            SemError::UnOpType(..) => "E0046",
+            SemError::BinOpType(..) => "E0047",
+            SemError::ConstValueExpected => "E0048",
+            SemError::OutsideLoop => "E0049",
+            SemError::NoReturnValue => "E0050",
+            SemError::MainNotFound => "E0051",
+            SemError::WrongMainDefinition => "E0052",
+            SemError::ThisUnavailable => "E0053",
+            SemError::SelfTypeUnavailable => "E0054",
+            SemError::SuperUnavailable => "E0055",
+            SemError::SuperNeedsMethodCall => "E0056",
+            SemError::ReferenceTypeExpected(..) => "E0057",
+            SemError::LetMissingInitialization => "E0058",
+            SemError::LetReassigned => "E0059",
+            SemError::FctReassigned => "E0060",
+            SemError::ClassReassigned => "E0061",
+            SemError::TypeParamReassigned => "E0062",
+            SemError::FctUsedAsIdentifier => "E0063",
+            SemError::ClsUsedAsIdentifier => "E0064",
+            SemError::ModuleUsedAsIdentifier => "E0065",
+            SemError::TypeParamUsedAsIdentifier => "E0066",
+            SemError::EnumUsedAsIdentifier => "E0067",
+            SemError::TypeParamUsedAsCallee => "E0068",
+            SemError::UnderivableType(..) => "E0069",
+            SemError::CycleInHierarchy => "E0070",
+            SemError::SuperfluousOverride(..) => "E0071",
+            SemError::SuperfluousOpen(..) => "E0072",
+            SemError::MissingOverride(..) => "E0073",
+            SemError::MethodNotOverridable(..) => "E0074",
+            SemError::TypesIncompatible(..) => "E0075",
+            SemError::ReturnTypeMismatch(..) => "E0076",
+            SemError::OverrideMismatch => "E0077",
+            SemError::UnresolvedInternal => "E0078",
+            SemError::UnclosedComment => "E0079",
+            SemError::UnknownChar(..) => "E0080",
+            SemError::UnclosedChar => "E0081",
+            SemError::UnclosedString => "E0082",
+            SemError::NumberOverflow(..) => "E0083",
+            SemError::ExpectedClass(..) => "E0084",
+            SemError::ExpectedFactor(..) => "E0085",
+            SemError::ExpectedToken(..) => "E0086",
+            SemError::ExpectedTopLevelElement(..) => "E0087",
+            SemError::ExpectedTrait(..) => "E0088",
+            SemError::ExpectedType(..) => "E0089",
+            SemError::ExpectedIdentifier(..) => "E0090",
+            SemError::ExpectedStringable(..) => "E0091",
+            SemError::ExpectedSomeIdentifier => "E0092",
+            SemError::MisplacedElse => "E0093",
+            SemError::IoError => "E0094",
+            SemError::ExpectedClassElement(..) => "E0095",
+            SemError::MisplacedAnnotation(..) => "E0096",
+            SemError::RedundantAnnotation(..) => "E0097",
+            SemError::UnknownAnnotation(..) => "E0098",
+            SemError::InvalidEscapeSequence(..) => "E0099",
+            SemError::MissingFctBody => "E0100",
+            SemError::FctCallExpected => "E0101",
+            SemError::ThisOrSuperExpected(..) => "E0102",
+            SemError::NoSuperDelegationWithPrimaryCtor(..) => "E0103",
+            SemError::NoSuperClass(..) => "E0104",
+            SemError::RecursiveStructure => "E0105",
+            SemError::TraitMethodWithBody => "E0106",
+            SemError::TypeParamsExpected => "E0107",
+            SemError::TypeParamNameNotUnique(..) => "E0108",
+            SemError::StaticMethodNotInTrait(..) => "E0109",
+            SemError::MethodNotInTrait(..) => "E0110",
+            SemError::StaticMethodMissingFromTrait(..) => "E0111",
+            SemError::MethodMissingFromTrait(..) => "E0112",
+            SemError::WrongNumberTypeParams(..) => "E0113",
+            SemError::ClassExpected => "E0114",
+            SemError::ClassExpectedAsTypeParam => "E0115",
+            SemError::AssignmentToConst => "E0116",
+            SemError::BoundExpected => "E0117",
+            SemError::NoTypeParamsExpected => "E0118",
+            SemError::DuplicateTraitBound => "E0119",
+            SemError::TraitBoundNotSatisfied(..) => "E0120",
+            SemError::AbstractMethodNotInAbstractClass => "E0121",
+            SemError::AbstractMethodWithImplementation => "E0122",
+            SemError::NewAbstractClass => "E0123",
+            SemError::MissingAbstractOverride(..) => "E0124",
+            SemError::ModifierNotAllowedForStaticMethod(..) => "E0125",
+            SemError::GlobalInitializerNotSupported => "E0126",
+            SemError::MakeIteratorNotFound(..) => "E0127",
+            SemError::MakeIteratorReturnType(..) => "E0128",
+            SemError::UnknownStructField(..) => "E0129",
+            SemError::StructFieldNotInitialized(..) => "E0130",
+            SemError::InvalidLeftSideOfSeparator => "E0131",
+            SemError::InvalidUseOfTypeParams => "E0132",
+            SemError::NameOfStaticMethodExpected => "E0133",
+            SemError::IfBranchTypesIncompatible(..) => "E0134",
+            SemError::NameExpected => "E0135",
+            SemError::IndexExpected => "E0136",
+            SemError::IllegalTupleIndex(..) => "E0137",
+            SemError::TupleElementNeedsTypeInfo => "E0138",
+        }
+    }
+
     pub fn message(&self) -> String {
         match *self {
             SemError::Unimplemented => format!("feature not implemented yet."),
@@ -486,6 +632,7 @@ impl SemError {
             SemError::IllegalTupleIndex(idx, ref ty) => {
                 format!("illegal index `{}` for type `{}`", idx, ty)
             }
+            SemError::TupleElementNeedsTypeInfo => "tuple element needs a type, not `nil`.".into(),
         }
     }
 }
@@ -494,12 +641,27 @@ impl SemError {
 pub struct SemErrorAndPos {
     pub file: FileId,
     pub pos: Position,
+    pub span: Option<Span>,
     pub msg: SemError,
 }
 
 impl SemErrorAndPos {
     pub fn new(file: FileId, pos: Position, msg: SemError) -> SemErrorAndPos {
-        SemErrorAndPos { file, pos, msg }
+        SemErrorAndPos {
+            file,
+            pos,
+            span: None,
+            msg,
+        }
+    }
+
+    pub fn with_span(file: FileId, pos: Position, span: Span, msg: SemError) -> SemErrorAndPos {
+        SemErrorAndPos {
+            file,
+            pos,
+            span: Some(span),
+            msg,
+        }
     }
 
     pub fn message(&self, vm: &VM) -> String {
@@ -512,3 +674,72 @@ impl SemErrorAndPos {
         )
     }
 }
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SemWarning {
+    UnusedVariable(String),
+    UnusedParam(String),
+    VarNeverReassigned(String),
+    UnreachableCode,
+    UnusedFunction(String),
+    ReferenceEquality(String, String),
+}
+
+// Names of all lints, these can be passed to `--allow`.
+pub const LINTS: &[&str] = &[
+    "unused-variable",
+    "unused-parameter",
+    "never-reassigned",
+    "unreachable-code",
+    "unused-function",
+    "reference-equality",
+];
+
+impl SemWarning {
+    pub fn lint(&self) -> &'static str {
+        match self {
+            SemWarning::UnusedVariable(..) => "unused-variable",
+            SemWarning::UnusedParam(..) => "unused-parameter",
+            SemWarning::VarNeverReassigned(..) => "never-reassigned",
+            SemWarning::UnreachableCode => "unreachable-code",
+            SemWarning::UnusedFunction(..) => "unused-function",
+            SemWarning::ReferenceEquality(..) => "reference-equality",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemWarning::UnusedVariable(..) => "W0001",
+            SemWarning::UnusedParam(..) => "W0002",
+            SemWarning::VarNeverReassigned(..) => "W0003",
+            SemWarning::UnreachableCode => "W0004",
+            SemWarning::UnusedFunction(..) => "W0005",
+            SemWarning::ReferenceEquality(..) => "W0006",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match *self {
+            SemWarning::UnusedVariable(ref name) => format!("unused variable `{}`.", name),
+            SemWarning::UnusedParam(ref name) => format!("unused parameter `{}`.", name),
+            SemWarning::VarNeverReassigned(ref name) => format!(
+                "variable `{}` is never reassigned, use `let` instead of `var`.",
+                name
+            ),
+            SemWarning::UnreachableCode => "unreachable code.".into(),
+            SemWarning::UnusedFunction(ref name) => format!("function `{}` is never used.", name),
+            SemWarning::ReferenceEquality(ref op, ref identity) => format!(
+                "`{}` calls `equals` instead of comparing references, use `{}`.",
+                op, identity
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SemWarningAndPos {
+    pub file: FileId,
+    pub pos: Position,
+    pub span: Option<Span>,
+    pub msg: SemWarning,
+}
@@ -1,9 +1,13 @@
 use std::cmp::{Ord, Ordering, PartialOrd};
 use std::fmt;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::Arc;
 
 use crate::driver::cmd::{Args, CollectorName};
+use crate::gc::allocsite::AllocSites;
 use crate::gc::compact::MarkCompactCollector;
 use crate::gc::copy::CopyCollector;
+use crate::gc::immix::ImmixCollector;
 use crate::gc::space::{Space, SpaceConfig};
 use crate::gc::sweep::SweepCollector;
 use crate::gc::swiper::sweep::SweepSwiper;
@@ -13,17 +17,25 @@ use crate::gc::zero::ZeroCollector;
 use crate::mem;
 use crate::object::{Header, Obj};
 use crate::os;
+use crate::safepoint;
+use crate::threads::DoraThread;
 use crate::vm::VM;
 use crate::vtable::VTable;
 
+pub mod allocsite;
 pub mod bump;
+pub mod code;
 pub mod compact;
+pub mod compressed;
 pub mod copy;
 pub mod freelist;
+pub mod immix;
+pub mod log;
 pub mod marking;
 pub mod pmarking;
 pub mod root;
 pub mod space;
+pub mod stackmap;
 pub mod sweep;
 pub mod swiper;
 pub mod tlab;
@@ -31,6 +43,7 @@ pub mod zero;
 
 pub const K: usize = 1024;
 pub const M: usize = K * K;
+pub const G: usize = K * M;
 
 const CHUNK_SIZE: usize = 8 * K;
 pub const DEFAULT_CODE_SPACE_LIMIT: usize = 1 * M;
@@ -46,10 +59,31 @@ pub struct Gc {
 
     code_space: Space,
     perm_space: Space,
+
+    // code space usage that triggers the next code collection
+    code_threshold: AtomicUsize,
+
+    // largest code allocation so far, collections keep free space for
+    // compiling functions of this size
+    code_max_size: AtomicUsize,
+
+    // compilations that currently run Dora code, no code is collected
+    // while there are any
+    boots_compilations: AtomicUsize,
+
+    // set by compilations that couldn't collect code, because another
+    // thread was stopping the world
+    code_collection_requested: AtomicBool,
+
+    pub alloc_sites: AllocSites,
 }
 
 impl Gc {
     pub fn new(args: &Args) -> Gc {
+        if args.flag_compressed_refs {
+            compressed::init();
+        }
+
         let code_config = SpaceConfig {
             executable: true,
             chunk: CHUNK_SIZE,
@@ -73,6 +107,7 @@ impl Gc {
             CollectorName::Sweep => box SweepCollector::new(args),
             CollectorName::Swiper => box Swiper::new(args),
             CollectorName::SweepSwiper => box SweepSwiper::new(args),
+            CollectorName::Immix => box ImmixCollector::new(args),
         };
 
         let supports_tlab = !args.flag_disable_tlab && collector.supports_tlab();
@@ -83,6 +118,13 @@ impl Gc {
 
             code_space: Space::new(code_config, "code"),
             perm_space: Space::new(perm_config, "perm"),
+
+            code_threshold: AtomicUsize::new(args.code_size() / 2),
+            code_max_size: AtomicUsize::new(0),
+            boots_compilations: AtomicUsize::new(0),
+            code_collection_requested: AtomicBool::new(false),
+
+            alloc_sites: AllocSites::new(),
         }
     }
 
@@ -94,10 +136,116 @@ impl Gc {
         self.collector.card_table_offset()
     }
 
+    pub fn needs_pre_barrier(&self) -> bool {
+        self.collector.needs_pre_barrier()
+    }
+
+    pub fn pre_barrier(&self, reference: Address) {
+        self.collector.pre_barrier(reference);
+    }
+
     pub fn alloc_code(&self, size: usize) -> Address {
+        self.code_max_size.fetch_max(size, atomic::Ordering::Relaxed);
         self.code_space.alloc(size)
     }
 
+    pub fn free_code(&self, start: Address, size: usize) {
+        self.code_space.free(start, size);
+    }
+
+    // only compares against the reserved range, safe in signal handlers
+    pub fn code_contains(&self, addr: Address) -> bool {
+        self.code_space.contains(addr)
+    }
+
+    pub fn code_used(&self) -> usize {
+        self.code_space.used()
+    }
+
+    pub fn code_limit(&self) -> usize {
+        self.code_space.limit()
+    }
+
+    // free space that should be kept for compiling further functions,
+    // each of the threads might be compiling a function at the same time
+    pub fn code_reserve(&self, threads: usize) -> usize {
+        (threads + 1) * self.code_max_size.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn needs_code_collection(&self, threads: usize) -> bool {
+        let used = self.code_used();
+
+        used > self.code_threshold.load(atomic::Ordering::Relaxed)
+            || used + self.code_reserve(threads) > self.code_limit()
+    }
+
+    // Boots compiles by running Dora code. That code reaches safepoints and
+    // compiles further functions, while the outer compilation still holds
+    // addresses of compiled code (e.g. of compile() or of callees). Code
+    // collections are skipped until all returned guards are dropped.
+    pub fn start_boots_compilation(&self) -> BootsCompilation {
+        self.boots_compilations
+            .fetch_add(1, atomic::Ordering::SeqCst);
+
+        BootsCompilation { gc: self }
+    }
+
+    fn boots_compiling(&self) -> bool {
+        self.boots_compilations.load(atomic::Ordering::SeqCst) > 0
+    }
+
+    // Frees compiled code that can't be invoked anymore, should be called
+    // before compiling a function while no compiler locks are held.
+    // The current thread must not wait for another thread stopping the
+    // world: that thread might move objects, but the compile stub doesn't
+    // record the references in the argument registers. The other thread
+    // collects the code before resuming instead.
+    pub fn collect_code(&self, vm: &VM) {
+        if !vm.args.code_collection() || self.boots_compiling() {
+            return;
+        }
+
+        let threads = vm.threads.count();
+
+        if !self.needs_code_collection(threads) {
+            return;
+        }
+
+        let stopped = safepoint::try_stop_the_world(vm, |threads| {
+            self.collect_code_stopped(vm, threads);
+        });
+
+        if stopped.is_none() {
+            self.code_collection_requested
+                .store(true, atomic::Ordering::SeqCst);
+        }
+    }
+
+    // called before resuming the stopped threads
+    pub fn collect_requested_code(&self, vm: &VM, threads: &[Arc<DoraThread>]) {
+        if self
+            .code_collection_requested
+            .swap(false, atomic::Ordering::SeqCst)
+        {
+            self.collect_code_stopped(vm, threads);
+        }
+    }
+
+    fn collect_code_stopped(&self, vm: &VM, threads: &[Arc<DoraThread>]) {
+        // another thread might have collected or started a boots
+        // compilation in the meantime
+        if self.needs_code_collection(threads.len()) && !self.boots_compiling() {
+            code::collect(vm, threads);
+        }
+    }
+
+    // next collection when half of the remaining code space is used
+    fn update_code_threshold(&self) {
+        let used = self.code_used();
+        let threshold = used + (self.code_limit() - used.min(self.code_limit())) / 2;
+        self.code_threshold.store(threshold, atomic::Ordering::Relaxed);
+    }
+
     pub fn alloc_perm(&self, size: usize) -> Address {
         self.perm_space.alloc(size)
     }
@@ -118,7 +266,16 @@ impl Gc {
         }
     }
 
-    fn alloc_tlab(&self, vm: &VM, size: usize, _array_ref: bool) -> Address {
+    // allocates object of pretenured allocation site
+    pub fn alloc_old(&self, vm: &VM, size: usize, array_ref: bool) -> Address {
+        if vm.args.flag_gc_stress {
+            self.collect(vm, GcReason::Stress);
+        }
+
+        self.collector.alloc_old(vm, size, array_ref)
+    }
+
+    fn alloc_tlab(&self, vm: &VM, size: usize, array_ref: bool) -> Address {
         // try to allocate in current tlab
         if let Some(addr) = tlab::allocate(size) {
             return addr;
@@ -129,6 +286,13 @@ impl Gc {
 
         // allocate new tlab
         if let Some(tlab) = self.collector.alloc_tlab_area(vm, tlab::calculate_size()) {
+            // collector may hand out areas smaller than the requested size,
+            // keep area as TLAB and allocate object outside of it
+            if tlab.size() < size {
+                tlab::initialize(tlab);
+                return self.collector.alloc(vm, size, array_ref);
+            }
+
             let object_start = tlab.start;
             let tlab = Region::new(tlab.start.offset(size), tlab.end);
 
@@ -151,16 +315,69 @@ impl Gc {
         self.collector.minor_collect(vm, reason);
     }
 
+    pub fn wait_for_marking(&self, vm: &VM) {
+        self.collector.wait_for_marking(vm);
+    }
+
+    pub fn attach_thread(&self, thread: &DoraThread) {
+        self.collector.attach_thread(thread);
+    }
+
     pub fn dump_summary(&self, runtime: f32) {
         self.collector.dump_summary(runtime);
     }
 
-    pub fn verify_ref(&self, vm: &VM, reference: Address) {
+    pub fn shutdown(&self) {
+        self.collector.shutdown();
+    }
+
+    // checks whether reference is nil or points into the heap
+    pub fn verify_ref(&self, vm: &VM, reference: Address) -> bool {
         if reference.is_null() {
-            return;
+            return true;
         }
 
-        self.collector.verify_ref(vm, reference);
+        if !mem::is_aligned(reference.to_usize(), mem::ptr_width_usize()) {
+            return false;
+        }
+
+        self.perm_space.contains(reference) || self.collector.verify_ref(vm, reference)
+    }
+}
+
+// Size of the window needed with --compressed-refs: the heap of the
+// collector and the perm space, each reservation might need padding for its
+// alignment. The first page is never used.
+pub fn compressed_window_size(args: &Args) -> usize {
+    let max_heap_size = args.max_heap_size();
+
+    let heap_size = match args.flag_gc.unwrap_or(CollectorName::Swiper) {
+        CollectorName::Swiper | CollectorName::SweepSwiper => {
+            swiper::reserve_sizes(align_gen(max_heap_size)).reserve_size + GEN_SIZE
+        }
+        CollectorName::Immix => mem::align_usize(max_heap_size, immix::BLOCK_SIZE),
+        CollectorName::Copy => mem::align_usize(max_heap_size, 2 * os::page_size()),
+        CollectorName::Zero | CollectorName::Compact | CollectorName::Sweep => {
+            mem::page_align(max_heap_size)
+        }
+    };
+
+    let perm_size = mem::page_align(args.perm_size());
+
+    os::page_size() + heap_size + os::page_size() + perm_size
+}
+
+// Running boots compilation, finished when dropped (also on early
+// returns and panics).
+pub struct BootsCompilation<'a> {
+    gc: &'a Gc,
+}
+
+impl<'a> Drop for BootsCompilation<'a> {
+    fn drop(&mut self) {
+        self.gc
+            .boots_compilations
+            .fetch_sub(1, atomic::Ordering::SeqCst);
     }
 }
 
@@ -169,6 +386,11 @@ trait Collector {
     fn alloc_tlab_area(&self, vm: &VM, size: usize) -> Option<Region>;
     fn alloc(&self, vm: &VM, size: usize, array_ref: bool) -> Address;
 
+    // allocate object directly in the old generation if supported
+    fn alloc_old(&self, vm: &VM, size: usize, array_ref: bool) -> Address {
+        self.alloc(vm, size, array_ref)
+    }
+
     // collect garbage
     fn collect(&self, vm: &VM, reason: GcReason);
 
@@ -190,13 +412,39 @@ trait Collector {
         0
     }
 
+    // decides whether to emit the snapshot-at-the-beginning pre-write
+    // barrier needed for concurrent marking
+    fn needs_pre_barrier(&self) -> bool {
+        false
+    }
+
+    // remembers reference overwritten while marking concurrently
+    fn pre_barrier(&self, _reference: Address) {
+        // do nothing
+    }
+
+    // blocks until concurrent marking is finished, if supported
+    fn wait_for_marking(&self, _vm: &VM) {
+        // do nothing
+    }
+
+    // initializes thread-local state of a thread that is attached to
+    // the VM, called while holding the lock of the thread list
+    fn attach_thread(&self, _thread: &DoraThread) {
+        // do nothing
+    }
+
     // prints GC summary: minor/full collections, etc.
     fn dump_summary(&self, _runtime: f32);
 
-    // verify reference
-    fn verify_ref(&self, _vm: &VM, _addr: Address) {
+    // stops background threads of the collector, called after all
+    // mutator threads finished
+    fn shutdown(&self) {
         // do nothing
     }
+
+    // checks whether reference points into the heap of the collector
+    fn verify_ref(&self, vm: &VM, reference: Address) -> bool;
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -415,7 +663,7 @@ impl fmt::Display for Region {
     }
 }
 
-struct FormattedSize {
+pub struct FormattedSize {
     size: usize,
 }
 
@@ -443,7 +691,7 @@ impl fmt::Display for FormattedSize {
     }
 }
 
-fn formatted_size(size: usize) -> FormattedSize {
+pub fn formatted_size(size: usize) -> FormattedSize {
     FormattedSize { size }
 }
 
@@ -509,6 +757,7 @@ pub fn fill_region(vm: &VM, start: Address, end: Address) {
 
         unsafe {
             *start.to_mut_ptr::<usize>() = vtable as usize;
+            *start.add_ptr(1).to_mut_ptr::<usize>() = 0;
         }
     } else {
         // fill with int array
@@ -525,6 +774,7 @@ pub fn fill_region(vm: &VM, start: Address, end: Address) {
 
         unsafe {
             *start.to_mut_ptr::<usize>() = vtable as usize;
+            *start.add_ptr(1).to_mut_ptr::<usize>() = 0;
             *start.offset(Header::size() as usize).to_mut_ptr::<usize>() = length;
         }
     }
@@ -0,0 +1,227 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use dora_parser::lexer::position::Position;
+
+use crate::gc::{formatted_size, Address, Region, M};
+use crate::object::{Obj, ALLOC_SITE_SHIFT};
+use crate::vm::{ClassDefId, FctId, VM};
+
+// sites need to allocate this much in the young generation before
+// they are considered for pretenuring
+const PRETENURE_MIN_ALLOCATED: usize = 1 * M;
+
+// share of allocated bytes that needs to survive the first minor collection
+const PRETENURE_SURVIVAL_RATE: f64 = 0.8;
+
+// number of sites shown in the report
+const REPORT_SITES: usize = 20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AllocSiteId(u32);
+
+impl AllocSiteId {
+    pub fn to_u32(self) -> u32 {
+        self.0
+    }
+}
+
+// An allocation in compiled code (with --gc-alloc-sites or --gc-pretenure).
+// Compiled code stores the id of its site in the header of each allocated
+// object, minor collections use it to determine how many bytes of each
+// site survive.
+pub struct AllocSite {
+    pub id: AllocSiteId,
+    pub fct_id: FctId,
+    pub pos: Position,
+    pub cls_id: ClassDefId,
+
+    // checked by compiled code before each allocation
+    pretenure: AtomicBool,
+
+    // bytes allocated in the young generation, counted by minor collections
+    allocated: AtomicUsize,
+
+    // bytes that survived their first minor collection
+    survived: AtomicUsize,
+
+    // bytes allocated directly in the old generation
+    pretenured: AtomicUsize,
+}
+
+impl AllocSite {
+    pub fn pretenure_address(&self) -> Address {
+        Address::from_ptr(&self.pretenure as *const AtomicBool)
+    }
+
+    // value compiled code stores in the second header word
+    pub fn header_word(&self) -> i64 {
+        (self.id.0 as i64) << ALLOC_SITE_SHIFT
+    }
+
+    pub fn is_pretenured(&self) -> bool {
+        self.pretenure.load(Ordering::Relaxed)
+    }
+
+    fn survival_rate(&self) -> f64 {
+        let allocated = self.allocated.load(Ordering::Relaxed);
+
+        if allocated == 0 {
+            0.0
+        } else {
+            self.survived.load(Ordering::Relaxed) as f64 / allocated as f64
+        }
+    }
+
+    fn total_allocated(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed) + self.pretenured.load(Ordering::Relaxed)
+    }
+}
+
+pub struct AllocSites {
+    sites: Mutex<Vec<Arc<AllocSite>>>,
+}
+
+impl AllocSites {
+    pub fn new() -> AllocSites {
+        AllocSites {
+            sites: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn add(&self, fct_id: FctId, pos: Position, cls_id: ClassDefId) -> Arc<AllocSite> {
+        let mut sites = self.sites.lock();
+
+        // id 0 is used for objects allocated by the runtime
+        let id = AllocSiteId(sites.len() as u32 + 1);
+
+        let site = Arc::new(AllocSite {
+            id,
+            fct_id,
+            pos,
+            cls_id,
+            pretenure: AtomicBool::new(false),
+            allocated: AtomicUsize::new(0),
+            survived: AtomicUsize::new(0),
+            pretenured: AtomicUsize::new(0),
+        });
+
+        sites.push(site.clone());
+        site
+    }
+
+    pub fn len(&self) -> usize {
+        self.sites.lock().len()
+    }
+
+    pub fn record_pretenured(&self, site_id: u32, size: usize) {
+        if let Some(site) = self.get(site_id) {
+            site.pretenured.fetch_add(size, Ordering::Relaxed);
+        }
+    }
+
+    // counts bytes allocated per site in eden, needs to be called before
+    // the minor collection
+    pub fn record_allocated(&self, eden: Region) {
+        let sites = self.sites.lock();
+        let mut allocated = vec![0; sites.len() + 1];
+
+        super::swiper::walk_region(eden, |obj, _, size| {
+            if let Some(allocated) = allocated.get_mut(site_of(obj)) {
+                *allocated += size;
+            }
+        });
+
+        for site in sites.iter() {
+            let size = allocated[site.id.0 as usize];
+
+            if size > 0 {
+                site.allocated.fetch_add(size, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // adds the bytes per site that survived a minor collection, indexed by site id
+    pub fn record_survived(&self, survived: &[usize]) {
+        let sites = self.sites.lock();
+
+        for site in sites.iter() {
+            let size = survived.get(site.id.0 as usize).cloned().unwrap_or(0);
+
+            if size > 0 {
+                site.survived.fetch_add(size, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // allocates objects of sites with high survival rate directly
+    // in the old generation from now on, returns number of new sites
+    pub fn update_pretenuring(&self) -> usize {
+        let sites = self.sites.lock();
+        let mut pretenured = 0;
+
+        for site in sites.iter() {
+            if site.is_pretenured()
+                || site.allocated.load(Ordering::Relaxed) < PRETENURE_MIN_ALLOCATED
+            {
+                continue;
+            }
+
+            if site.survival_rate() >= PRETENURE_SURVIVAL_RATE {
+                site.pretenure.store(true, Ordering::Relaxed);
+                pretenured += 1;
+            }
+        }
+
+        pretenured
+    }
+
+    pub fn dump(&self, vm: &VM) {
+        let mut sites: Vec<_> = self
+            .sites
+            .lock()
+            .iter()
+            .filter(|site| site.total_allocated() > 0)
+            .cloned()
+            .collect();
+        sites.sort_by_key(|site| std::cmp::Reverse(site.total_allocated()));
+
+        println!("Allocation sites:");
+        println!(
+            "{:>10} {:>9} {:>10}  {}",
+            "allocated", "survived", "pretenured", "site"
+        );
+
+        for site in sites.iter().take(REPORT_SITES) {
+            let fct = vm.fcts.idx(site.fct_id);
+            let fct = fct.read();
+            let cls = vm.class_defs.idx(site.cls_id);
+            let cls = cls.read();
+
+            println!(
+                "{:>10} {:>8.1}% {:>10}  {} in {} ({}:{})",
+                formatted_size(site.total_allocated()).to_string(),
+                site.survival_rate() * 100.0,
+                formatted_size(site.pretenured.load(Ordering::Relaxed)).to_string(),
+                cls.name(vm),
+                fct.full_name(vm),
+                vm.file(fct.file).name,
+                site.pos,
+            );
+        }
+    }
+
+    fn get(&self, site_id: u32) -> Option<Arc<AllocSite>> {
+        if site_id == 0 {
+            return None;
+        }
+
+        self.sites.lock().get(site_id as usize - 1).cloned()
+    }
+}
+
+// site id stored in the header, only valid for objects in the young generation
+pub fn site_of(obj: &Obj) -> usize {
+    obj.header().alloc_site() as usize
+}
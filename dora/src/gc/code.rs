@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::compiler::fct::{JitFct, JitFctId, LazyCompilationSite};
+use crate::compiler::map::{CodeDescriptor, CodeMap};
+use crate::gc::{formatted_size, Address};
+use crate::stack::DoraToNativeInfo;
+use crate::threads::DoraThread;
+use crate::timer::Timer;
+use crate::vm::VM;
+
+// when live code still fills this share of the code space after a
+// collection (or leaves less than the reserve), all code that isn't
+// executing right now gets flushed
+const FLUSH_RATIO: f64 = 0.75;
+
+// Collects compiled functions that are unreachable from the stacks of all
+// threads, the vtables and the patched call sites of reachable code. Freed
+// functions get compiled again on their next invocation.
+// All threads need to be stopped.
+pub fn collect(vm: &VM, threads: &[Arc<DoraThread>]) {
+    let mut timer = Timer::new(vm.args.flag_gc_verbose);
+    let used_before = vm.gc.code_used();
+    let compile_stub = vm.compile_stub();
+
+    let mut code_map = vm.code_map.lock();
+
+    let mut marker = CodeMarker::new(vm, &code_map);
+    marker.mark_stacks(threads);
+    marker.mark_vtables();
+    marker.trace();
+
+    let mut live = marker.live;
+    let dora_fcts = code_map.dora_fcts();
+
+    // stubs are never freed and stay in the code space as well
+    let retained_size = used_before - code_size(vm, &dora_fcts) + code_size(vm, &live);
+    let limit = vm.gc.code_limit();
+    let flush = retained_size as f64 > limit as f64 * FLUSH_RATIO
+        || retained_size + vm.gc.code_reserve(threads.len()) > limit;
+
+    if flush {
+        // only code on the stack stays, everything else is reached
+        // through the compile stub again
+        let mut marker = CodeMarker::new(vm, &code_map);
+        marker.mark_stacks(threads);
+        live = marker.live;
+    }
+
+    let dead: HashSet<JitFctId> = dora_fcts
+        .into_iter()
+        .filter(|jit_fct_id| !live.contains(jit_fct_id))
+        .collect();
+
+    if !dead.is_empty() {
+        let is_dead = |addr: Address| match code_map.get(addr) {
+            Some(CodeDescriptor::DoraFct(jit_fct_id)) => dead.contains(&jit_fct_id),
+            _ => false,
+        };
+
+        reset_call_sites(vm, &live, compile_stub, &is_dead);
+        reset_vtables(vm, compile_stub, &is_dead);
+        free(vm, &mut code_map, &dead);
+    }
+
+    drop(code_map);
+    vm.gc.update_code_threshold();
+
+    if vm.args.flag_gc_verbose {
+        let duration = timer.stop();
+        println!(
+            "GC: Code{} {}/{} -> {}/{}; {} functions freed; {:.2} ms",
+            if flush { " (flush)" } else { "" },
+            formatted_size(used_before),
+            formatted_size(vm.gc.code_limit()),
+            formatted_size(vm.gc.code_used()),
+            formatted_size(vm.gc.code_limit()),
+            dead.len(),
+            duration
+        );
+    }
+}
+
+struct CodeMarker<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    code_map: &'a CodeMap,
+    live: HashSet<JitFctId>,
+    worklist: Vec<JitFctId>,
+}
+
+impl<'a, 'ast> CodeMarker<'a, 'ast> {
+    fn new(vm: &'a VM<'ast>, code_map: &'a CodeMap) -> CodeMarker<'a, 'ast> {
+        CodeMarker {
+            vm,
+            code_map,
+            live: HashSet::new(),
+            worklist: Vec::new(),
+        }
+    }
+
+    fn mark(&mut self, addr: Address) {
+        if let Some(CodeDescriptor::DoraFct(jit_fct_id)) = self.code_map.get(addr) {
+            if self.live.insert(jit_fct_id) {
+                self.worklist.push(jit_fct_id);
+            }
+        }
+    }
+
+    fn mark_stacks(&mut self, threads: &[Arc<DoraThread>]) {
+        for thread in threads {
+            let mut dtn = thread.dtn() as *const DoraToNativeInfo;
+
+            while !dtn.is_null() {
+                let info = unsafe { &*dtn };
+
+                let mut pc = info.pc;
+                let mut fp = info.fp;
+
+                while fp != 0 {
+                    if let Some(CodeDescriptor::DoraStub) = self.code_map.get(pc.into()) {
+                        break;
+                    }
+
+                    self.mark(pc.into());
+
+                    pc = unsafe { *((fp + 8) as *const usize) };
+                    fp = unsafe { *(fp as *const usize) };
+                }
+
+                dtn = info.last;
+            }
+        }
+    }
+
+    fn mark_vtables(&mut self) {
+        for cls_def in self.vm.class_defs.iter() {
+            let cls_def = cls_def.read();
+
+            if let Some(ref vtable) = cls_def.vtable {
+                for &entry in vtable.table() {
+                    self.mark(entry.into());
+                }
+            }
+        }
+    }
+
+    fn trace(&mut self) {
+        while let Some(jit_fct_id) = self.worklist.pop() {
+            let jit_fct = self.vm.jit_fcts.idx(jit_fct_id);
+
+            let code = match *jit_fct {
+                JitFct::Compiled(ref code) => code,
+                JitFct::Uncompiled => unreachable!(),
+            };
+
+            for &(offset, ref site) in code.lazy_compilation_sites() {
+                let ra = code.instruction_start().offset(offset as usize);
+
+                match site {
+                    LazyCompilationSite::Compile(_, disp, _, _) => {
+                        self.mark(load(ra, *disp));
+                    }
+
+                    LazyCompilationSite::InlineCache(ref site) => {
+                        for &(_, fct_disp) in &site.entries {
+                            self.mark(load(ra, fct_disp));
+                        }
+                    }
+
+                    LazyCompilationSite::VirtCompile(..) => {}
+                }
+            }
+        }
+    }
+}
+
+// call sites of surviving code that target freed code invoke the compile
+// stub again, which compiles the function and patches the call site
+fn reset_call_sites<F>(vm: &VM, live: &HashSet<JitFctId>, compile_stub: Address, is_dead: &F)
+where
+    F: Fn(Address) -> bool,
+{
+    for &jit_fct_id in live {
+        let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+
+        let code = match *jit_fct {
+            JitFct::Compiled(ref code) => code,
+            JitFct::Uncompiled => unreachable!(),
+        };
+
+        for &(offset, ref site) in code.lazy_compilation_sites() {
+            let ra = code.instruction_start().offset(offset as usize);
+
+            match site {
+                LazyCompilationSite::Compile(_, disp, _, _) => {
+                    if is_dead(load(ra, *disp)) {
+                        store(ra, *disp, compile_stub);
+                    }
+                }
+
+                LazyCompilationSite::InlineCache(ref site) => {
+                    for &(vtable_disp, fct_disp) in &site.entries {
+                        if is_dead(load(ra, fct_disp)) {
+                            // empty entries are claimed again on a cache miss
+                            store(ra, vtable_disp, Address::null());
+                            store(ra, fct_disp, Address::null());
+                        }
+                    }
+                }
+
+                LazyCompilationSite::VirtCompile(..) => {}
+            }
+        }
+    }
+}
+
+fn reset_vtables<F>(vm: &VM, compile_stub: Address, is_dead: &F)
+where
+    F: Fn(Address) -> bool,
+{
+    for cls_def in vm.class_defs.iter() {
+        let cls_def = cls_def.read();
+
+        if let Some(ref vtable) = cls_def.vtable {
+            for entry in vtable.table_mut() {
+                if is_dead((*entry).into()) {
+                    *entry = compile_stub.to_usize();
+                }
+            }
+        }
+    }
+}
+
+fn free(vm: &VM, code_map: &mut CodeMap, dead: &HashSet<JitFctId>) {
+    for &jit_fct_id in dead {
+        let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+
+        let code = match *jit_fct {
+            JitFct::Compiled(ref code) => code,
+            JitFct::Uncompiled => unreachable!(),
+        };
+
+        let fct = vm.fcts.idx(code.fct_id());
+        let fct = fct.read();
+        let src = fct.src();
+        let src = src.read();
+        src.specializations
+            .write()
+            .retain(|_, &mut id| id != jit_fct_id);
+
+        code_map.remove(code.ptr_start());
+        vm.gc.free_code(
+            code.ptr_start(),
+            code.ptr_end().offset_from(code.ptr_start()),
+        );
+
+        vm.jit_fcts.lock()[jit_fct_id.idx()] = Arc::new(JitFct::Uncompiled);
+    }
+}
+
+fn code_size<'a, I>(vm: &VM, jit_fct_ids: I) -> usize
+where
+    I: IntoIterator<Item = &'a JitFctId>,
+{
+    jit_fct_ids
+        .into_iter()
+        .map(|&jit_fct_id| {
+            let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+            jit_fct.ptr_end().offset_from(jit_fct.ptr_start())
+        })
+        .sum()
+}
+
+// slots in the data segment are addressed relative to the return address
+fn load(ra: Address, disp: i32) -> Address {
+    unsafe { *slot(ra, disp) }.into()
+}
+
+fn store(ra: Address, disp: i32, value: Address) {
+    unsafe {
+        *slot(ra, disp) = value.to_usize();
+    }
+}
+
+fn slot(ra: Address, disp: i32) -> *mut usize {
+    (ra.to_usize() as isize - disp as isize) as *mut usize
+}
@@ -2,13 +2,13 @@ use parking_lot::Mutex;
 
 use crate::driver::cmd::Args;
 use crate::gc::bump::BumpAllocator;
+use crate::gc::compressed;
 use crate::gc::marking;
 use crate::gc::root::{get_rootset, Slot};
 use crate::gc::space::Space;
 use crate::gc::tlab;
 use crate::gc::{formatted_size, Address, CollectionStats, Collector, GcReason, Region};
 use crate::object::Obj;
-use crate::os;
 use crate::safepoint;
 use crate::timer::Timer;
 use crate::vm::VM;
@@ -22,7 +22,7 @@ pub struct MarkCompactCollector {
 impl MarkCompactCollector {
     pub fn new(args: &Args) -> MarkCompactCollector {
         let heap_size = args.max_heap_size();
-        let heap_start = os::commit(heap_size, false);
+        let heap_start = compressed::commit(heap_size);
 
         if heap_start.is_null() {
             panic!("could not allocate heap of size {} bytes", heap_size);
@@ -119,11 +119,15 @@ impl Collector for MarkCompactCollector {
             gc,
         );
     }
+
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.heap.contains(reference)
+    }
 }
 
 impl Drop for MarkCompactCollector {
     fn drop(&mut self) {
-        os::free(self.heap.start, self.heap.size());
+        compressed::free(self.heap.start, self.heap.size());
     }
 }
 
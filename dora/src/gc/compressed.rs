@@ -0,0 +1,157 @@
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::gc::{formatted_size, Address, G};
+use crate::mem;
+use crate::os::{self, Reservation};
+
+// With --compressed-refs object fields and array elements store references
+// as 32-bit values: the offset from the heap base in units of the object
+// alignment. Shifting by the alignment extends the window to 32G.
+pub const WINDOW_SIZE: usize = 32 * G;
+const SHIFT: usize = 3;
+
+// start of the reserved window, 0 when compressed references are disabled
+static HEAP_BASE: AtomicUsize = AtomicUsize::new(0);
+
+// next free address in the window
+static HEAP_TOP: AtomicUsize = AtomicUsize::new(0);
+
+// reserves the window, all memory that can hold objects needs to be allocated
+// through `reserve_align` or `commit` afterwards
+pub fn init() {
+    assert!(!enabled());
+    let reservation = os::reserve_align(WINDOW_SIZE, 0);
+    let base = reservation.start.to_usize();
+
+    // offset 0 encodes nil, so no object may start at the heap base
+    HEAP_TOP.store(base + os::page_size(), Ordering::Relaxed);
+    HEAP_BASE.store(base, Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub fn enabled() -> bool {
+    HEAP_BASE.load(Ordering::Relaxed) != 0
+}
+
+#[inline(always)]
+pub fn heap_base() -> Address {
+    HEAP_BASE.load(Ordering::Relaxed).into()
+}
+
+// size of a reference stored in an object field or array element
+#[inline(always)]
+pub fn ref_size() -> i32 {
+    if enabled() {
+        4
+    } else {
+        mem::ptr_width()
+    }
+}
+
+#[inline(always)]
+pub fn ref_size_usize() -> usize {
+    ref_size() as usize
+}
+
+#[inline(always)]
+pub fn shift() -> usize {
+    SHIFT
+}
+
+#[inline(always)]
+pub fn encode(obj: Address) -> u32 {
+    if obj.is_null() {
+        return 0;
+    }
+
+    let offset = obj.offset_from(heap_base());
+    debug_assert!(offset < WINDOW_SIZE && offset & ((1 << SHIFT) - 1) == 0);
+
+    (offset >> SHIFT) as u32
+}
+
+#[inline(always)]
+pub fn decode(value: u32) -> Address {
+    if value == 0 {
+        return Address::null();
+    }
+
+    heap_base().offset((value as usize) << SHIFT)
+}
+
+// reserves memory for a collector, inside the window when compressed
+// references are enabled
+pub fn reserve_align(size: usize, align: usize) -> Reservation {
+    if !enabled() {
+        return os::reserve_align(size, align);
+    }
+
+    let align = if align == 0 { os::page_size() } else { align };
+    let mut top = HEAP_TOP.load(Ordering::Relaxed);
+
+    loop {
+        let start = mem::align_usize(top, align);
+        let end = start + size;
+
+        if end > heap_base().to_usize() + WINDOW_SIZE {
+            eprintln!(
+                "heap reservation of {} does not fit into the {} window of --compressed-refs.",
+                formatted_size(size),
+                formatted_size(WINDOW_SIZE)
+            );
+            process::exit(1);
+        }
+
+        match HEAP_TOP.compare_exchange_weak(top, end, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => {
+                return Reservation {
+                    start: start.into(),
+                    unaligned_start: start.into(),
+                    unaligned_size: size,
+                };
+            }
+
+            Err(actual) => top = actual,
+        }
+    }
+}
+
+// reserves and commits memory for a collector
+pub fn commit(size: usize) -> Address {
+    if !enabled() {
+        return os::commit(size, false);
+    }
+
+    let reservation = reserve_align(size, 0);
+    os::commit_at(reservation.start, size, false);
+
+    reservation.start
+}
+
+// memory in the window stays reserved until the process exits
+pub fn free(ptr: Address, size: usize) {
+    if !enabled() {
+        os::free(ptr, size);
+        return;
+    }
+
+    let base = heap_base();
+
+    if ptr >= base && ptr < base.offset(WINDOW_SIZE) {
+        os::discard(ptr, size);
+    } else {
+        os::free(ptr, size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ref_size_without_compressed_refs() {
+        assert!(!enabled());
+        assert_eq!(mem::ptr_width(), ref_size());
+    }
+}
@@ -2,6 +2,7 @@ use parking_lot::Mutex;
 
 use crate::driver::cmd::Args;
 use crate::gc::bump::BumpAllocator;
+use crate::gc::compressed;
 use crate::gc::root::{get_rootset, Slot};
 use crate::gc::tlab;
 use crate::gc::{formatted_size, Address, CollectionStats, Collector, GcReason, Region};
@@ -24,7 +25,7 @@ impl CopyCollector {
     pub fn new(args: &Args) -> CopyCollector {
         let alignment = 2 * os::page_size();
         let heap_size = mem::align_usize(args.max_heap_size(), alignment);
-        let heap_start = os::commit(heap_size, false);
+        let heap_start = compressed::commit(heap_size);
 
         if heap_start.is_null() {
             panic!("could not allocate semi space of size {} bytes", heap_size);
@@ -124,11 +125,15 @@ impl Collector for CopyCollector {
             gc,
         );
     }
+
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.total.contains(reference)
+    }
 }
 
 impl Drop for CopyCollector {
     fn drop(&mut self) {
-        os::free(self.total.start, self.total.size());
+        compressed::free(self.total.start, self.total.size());
     }
 }
 
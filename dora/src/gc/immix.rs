@@ -0,0 +1,1016 @@
+use parking_lot::Mutex;
+use scoped_threadpool::Pool;
+use std::cmp;
+use std::collections::HashSet;
+
+use crate::driver::cmd::Args;
+use crate::gc::compressed;
+use crate::gc::marking;
+use crate::gc::pmarking;
+use crate::gc::root::{get_rootset, Slot};
+use crate::gc::tlab;
+use crate::gc::{
+    fill_region, formatted_size, Address, CollectionStats, Collector, GcReason, Region,
+};
+use crate::mem;
+use crate::object::Header;
+use crate::safepoint;
+use crate::timer::Timer;
+use crate::vm::VM;
+
+// Immix-style mark-region collector: the heap is divided into blocks,
+// which are further divided into lines. Marking records which lines contain
+// live objects, the mutator then bump-allocates into runs of free lines
+// (holes). Objects are never moved, except when a collection opportunistically
+// evacuates objects out of fragmented blocks.
+
+pub const LINE_SIZE: usize = 256;
+pub const LINES_PER_BLOCK: usize = 128;
+pub const BLOCK_SIZE: usize = LINE_SIZE * LINES_PER_BLOCK;
+
+// line marks of a block fit into a single bitmap
+const ALL_LINES: u128 = !0;
+
+pub struct ImmixCollector {
+    heap: Region,
+    space: Mutex<ImmixSpace>,
+    threadpool: Option<Mutex<Pool>>,
+    stats: Mutex<CollectionStats>,
+}
+
+impl ImmixCollector {
+    pub fn new(args: &Args) -> ImmixCollector {
+        let heap_size = mem::align_usize(args.max_heap_size(), BLOCK_SIZE);
+        let heap_start = compressed::commit(heap_size);
+
+        if heap_start.is_null() {
+            panic!("could not allocate heap of size {} bytes", heap_size);
+        }
+
+        let heap_end = heap_start.offset(heap_size);
+        let heap = Region::new(heap_start, heap_end);
+
+        if args.flag_gc_verbose {
+            println!("GC: {} {}", heap, formatted_size(heap_size));
+        }
+
+        let threadpool = if args.parallel_full() {
+            Some(Mutex::new(Pool::new(args.gc_workers() as u32)))
+        } else {
+            None
+        };
+
+        ImmixCollector {
+            heap,
+            space: Mutex::new(ImmixSpace::new(heap)),
+            threadpool,
+            stats: Mutex::new(CollectionStats::new()),
+        }
+    }
+}
+
+impl Collector for ImmixCollector {
+    fn supports_tlab(&self) -> bool {
+        true
+    }
+
+    fn alloc_tlab_area(&self, vm: &VM, size: usize) -> Option<Region> {
+        if let Some(region) = self.space.lock().alloc_area(vm, size) {
+            return Some(region);
+        }
+
+        self.collect(vm, GcReason::AllocationFailure);
+        self.space.lock().alloc_area(vm, size)
+    }
+
+    fn alloc(&self, vm: &VM, size: usize, _array_ref: bool) -> Address {
+        let ptr = self.space.lock().alloc(vm, size);
+
+        if ptr.is_non_null() {
+            return ptr;
+        }
+
+        self.collect(vm, GcReason::AllocationFailure);
+        self.space.lock().alloc(vm, size)
+    }
+
+    fn collect(&self, vm: &VM, reason: GcReason) {
+        let mut timer = Timer::new(vm.args.flag_gc_stats);
+
+        safepoint::stop_the_world(vm, |threads| {
+            tlab::make_iterable_all(vm, threads);
+            let rootset = get_rootset(vm, threads);
+            self.mark_region(vm, &rootset, reason);
+        });
+
+        if vm.args.flag_gc_stats {
+            let duration = timer.stop();
+            let mut stats = self.stats.lock();
+            stats.add(duration);
+        }
+    }
+
+    fn minor_collect(&self, vm: &VM, reason: GcReason) {
+        self.collect(vm, reason);
+    }
+
+    fn dump_summary(&self, runtime: f32) {
+        let stats = self.stats.lock();
+        let (mutator, gc) = stats.percentage(runtime);
+
+        println!("GC stats: total={:.1}", runtime);
+        println!("GC stats: mutator={:.1}", stats.mutator(runtime));
+        println!("GC stats: collection={:.1}", stats.pause());
+
+        println!("");
+        println!("GC stats: collection-count={}", stats.collections());
+        println!("GC stats: collection-pauses={}", stats.pauses());
+
+        println!(
+            "GC summary: {:.1}ms collection ({}), {:.1}ms mutator, {:.1}ms total ({}% mutator, {}% GC)",
+            stats.pause(),
+            stats.collections(),
+            stats.mutator(runtime),
+            runtime,
+            mutator,
+            gc,
+        );
+    }
+
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.heap.contains(reference)
+    }
+}
+
+impl Drop for ImmixCollector {
+    fn drop(&mut self) {
+        compressed::free(self.heap.start, self.heap.size());
+    }
+}
+
+impl ImmixCollector {
+    fn mark_region(&self, vm: &VM, rootset: &[Slot], reason: GcReason) {
+        let timer = Timer::new(vm.args.flag_gc_verbose);
+        let mut space = self.space.lock();
+
+        space.make_iterable(vm);
+        let old_size = space.used_size();
+
+        let mut collector = MarkRegion {
+            vm,
+            heap: self.heap,
+            space: &mut *space,
+            rootset,
+            threadpool: self.threadpool.as_ref(),
+            evacuated_blocks: 0,
+        };
+
+        collector.collect();
+        let evacuated_blocks = collector.evacuated_blocks;
+
+        timer.stop_with(|time_pause| {
+            let new_size = space.live_size();
+            let garbage = old_size - cmp::min(old_size, new_size);
+            let garbage_ratio = if old_size == 0 {
+                0f64
+            } else {
+                (garbage as f64 / old_size as f64) * 100f64
+            };
+
+            println!(
+                "Immix GC: {:.1} ms, {}->{} size, {}/{:.0}% garbage, {} blocks evacuated, ({})",
+                time_pause,
+                formatted_size(old_size),
+                formatted_size(new_size),
+                formatted_size(garbage),
+                garbage_ratio,
+                evacuated_blocks,
+                reason
+            );
+        });
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum BlockState {
+    // block is completely empty
+    Free,
+
+    // block contains small or medium objects and is always fully iterable
+    Used,
+
+    // block starts a large object spanning the given number of blocks
+    Large(usize),
+
+    // block is covered by a large object starting in an earlier block
+    LargeContinuation,
+}
+
+struct Block {
+    start: Address,
+    state: BlockState,
+
+    // bit i is set when line i contained a live object after the last
+    // collection, blocks taken for allocation have all bits set
+    lines: u128,
+
+    // block was selected for evacuation in the current collection
+    evacuate: bool,
+}
+
+impl Block {
+    fn end(&self) -> Address {
+        self.start.offset(BLOCK_SIZE)
+    }
+
+    fn line_address(&self, line: usize) -> Address {
+        self.start.offset(line * LINE_SIZE)
+    }
+
+    fn free_lines(&self) -> usize {
+        self.lines.count_zeros() as usize
+    }
+
+    fn holes(&self) -> usize {
+        let mut holes = 0;
+        let mut line = 0;
+
+        while let Some((_, end)) = next_hole(self.lines, line) {
+            holes += 1;
+            line = end;
+        }
+
+        holes
+    }
+
+    fn is_fragmented(&self) -> bool {
+        self.state == BlockState::Used && self.holes() > 1
+    }
+}
+
+// finds first run of free lines starting at or after the given line
+fn next_hole(lines: u128, from: usize) -> Option<(usize, usize)> {
+    let mut start = from;
+
+    while start < LINES_PER_BLOCK && line_marked(lines, start) {
+        start += 1;
+    }
+
+    if start == LINES_PER_BLOCK {
+        return None;
+    }
+
+    let mut end = start;
+
+    while end < LINES_PER_BLOCK && !line_marked(lines, end) {
+        end += 1;
+    }
+
+    Some((start, end))
+}
+
+fn line_marked(lines: u128, line: usize) -> bool {
+    lines & (1u128 << line) != 0
+}
+
+// returns bitmap of all lines covered by the object
+fn object_lines(block_start: Address, object: Address, size: usize) -> u128 {
+    let first = object.offset_from(block_start) / LINE_SIZE;
+    let last = (object.offset_from(block_start) + size - 1) / LINE_SIZE;
+    let count = last - first + 1;
+
+    if count == LINES_PER_BLOCK {
+        ALL_LINES
+    } else {
+        ((1u128 << count) - 1) << first
+    }
+}
+
+// like fill_region, but also clears the mark bits of the filler
+fn fill_dead(vm: &VM, start: Address, end: Address) {
+    fill_region(vm, start, end);
+
+    if end.offset_from(start) >= Header::size() as usize {
+        start.to_obj().header().clear_fwdptr();
+    }
+}
+
+struct ImmixSpace {
+    heap: Region,
+    blocks: Vec<Block>,
+
+    // current hole used for bump allocation
+    top: Address,
+    limit: Address,
+
+    // block used for medium objects that do not fit into the current hole
+    overflow_top: Address,
+    overflow_limit: Address,
+
+    // next block/line to search for holes
+    next_block: usize,
+    next_line: usize,
+
+    // next block to search for free blocks
+    next_free_block: usize,
+
+    // free blocks are only used for allocation as long as this number of
+    // free blocks remains as headroom for evacuation
+    free_blocks: usize,
+    reserved_blocks: usize,
+
+    // last collection left fragmented blocks behind
+    defrag: bool,
+}
+
+impl ImmixSpace {
+    fn new(heap: Region) -> ImmixSpace {
+        let number_blocks = heap.size() / BLOCK_SIZE;
+        let mut blocks = Vec::with_capacity(number_blocks);
+
+        for idx in 0..number_blocks {
+            blocks.push(Block {
+                start: heap.start.offset(idx * BLOCK_SIZE),
+                state: BlockState::Free,
+                lines: 0,
+                evacuate: false,
+            });
+        }
+
+        ImmixSpace {
+            heap,
+            blocks,
+
+            top: Address::null(),
+            limit: Address::null(),
+
+            overflow_top: Address::null(),
+            overflow_limit: Address::null(),
+
+            next_block: 0,
+            next_line: 0,
+            next_free_block: 0,
+
+            free_blocks: number_blocks,
+            reserved_blocks: cmp::max(1, number_blocks / 32),
+
+            defrag: false,
+        }
+    }
+
+    fn block_index(&self, addr: Address) -> usize {
+        addr.offset_from(self.heap.start) / BLOCK_SIZE
+    }
+
+    fn alloc_area(&mut self, vm: &VM, size: usize) -> Option<Region> {
+        loop {
+            let remaining = self.limit.offset_from(self.top);
+
+            if remaining > 0 && remaining >= cmp::min(size, LINE_SIZE) {
+                let start = self.top;
+                let end = cmp::min(start.offset(size), self.limit);
+                self.top = end;
+
+                return Some(Region::new(start, end));
+            }
+
+            fill_dead(vm, self.top, self.limit);
+            self.top = Address::null();
+            self.limit = Address::null();
+
+            let hole = self.next_hole()?;
+
+            self.top = hole.start;
+            self.limit = hole.end;
+        }
+    }
+
+    fn alloc(&mut self, vm: &VM, size: usize) -> Address {
+        if size > BLOCK_SIZE {
+            return self.alloc_large(size);
+        }
+
+        loop {
+            let object = self.top;
+            let next_top = object.offset(size);
+
+            if object.is_non_null() && next_top <= self.limit {
+                self.top = next_top;
+                return object;
+            }
+
+            if size > LINE_SIZE {
+                // medium objects do not skip holes, they are moved into the
+                // overflow block instead
+                let object = self.alloc_overflow(vm, size);
+
+                if object.is_non_null() {
+                    return object;
+                }
+            }
+
+            fill_dead(vm, self.top, self.limit);
+            self.top = Address::null();
+            self.limit = Address::null();
+
+            match self.next_hole() {
+                Some(hole) => {
+                    self.top = hole.start;
+                    self.limit = hole.end;
+                }
+
+                None => return Address::null(),
+            }
+        }
+    }
+
+    fn alloc_overflow(&mut self, vm: &VM, size: usize) -> Address {
+        let object = self.overflow_top;
+        let next_top = object.offset(size);
+
+        if object.is_non_null() && next_top <= self.overflow_limit {
+            self.overflow_top = next_top;
+            return object;
+        }
+
+        let block = match self.take_free_block(false) {
+            Some(block) => block,
+            None => return Address::null(),
+        };
+
+        fill_dead(vm, self.overflow_top, self.overflow_limit);
+
+        self.overflow_top = block.start.offset(size);
+        self.overflow_limit = block.end;
+
+        block.start
+    }
+
+    fn alloc_large(&mut self, size: usize) -> Address {
+        let needed = mem::align_usize(size, BLOCK_SIZE) / BLOCK_SIZE;
+
+        if needed + self.reserved_blocks > self.free_blocks {
+            return Address::null();
+        }
+
+        let mut start = 0;
+        let mut count = 0;
+
+        for idx in 0..self.blocks.len() {
+            if self.blocks[idx].state != BlockState::Free {
+                count = 0;
+                continue;
+            }
+
+            if count == 0 {
+                start = idx;
+            }
+
+            count += 1;
+
+            if count == needed {
+                self.free_blocks -= needed;
+                self.blocks[start].state = BlockState::Large(needed);
+
+                for block in &mut self.blocks[start + 1..start + needed] {
+                    block.state = BlockState::LargeContinuation;
+                }
+
+                return self.blocks[start].start;
+            }
+        }
+
+        Address::null()
+    }
+
+    // takes the next run of free lines, either in a recyclable block or
+    // a completely free block
+    fn next_hole(&mut self) -> Option<Region> {
+        while self.next_block < self.blocks.len() {
+            let block = &mut self.blocks[self.next_block];
+
+            match block.state {
+                BlockState::Used => {
+                    if let Some((start, end)) = next_hole(block.lines, self.next_line) {
+                        self.next_line = end;
+                        return Some(Region::new(
+                            block.line_address(start),
+                            block.line_address(end),
+                        ));
+                    }
+                }
+
+                BlockState::Free if self.free_blocks > self.reserved_blocks => {
+                    block.state = BlockState::Used;
+                    block.lines = ALL_LINES;
+                    self.free_blocks -= 1;
+                    self.next_block += 1;
+                    self.next_line = 0;
+                    return Some(Region::new(block.start, block.end()));
+                }
+
+                _ => {}
+            }
+
+            self.next_block += 1;
+            self.next_line = 0;
+        }
+
+        None
+    }
+
+    // reserved blocks are only handed out for evacuation
+    fn take_free_block(&mut self, evacuation: bool) -> Option<Region> {
+        if !evacuation && self.free_blocks <= self.reserved_blocks {
+            return None;
+        }
+
+        while self.next_free_block < self.blocks.len() {
+            let block = &mut self.blocks[self.next_free_block];
+            self.next_free_block += 1;
+
+            if block.state == BlockState::Free {
+                block.state = BlockState::Used;
+                block.lines = ALL_LINES;
+                self.free_blocks -= 1;
+                return Some(Region::new(block.start, block.end()));
+            }
+        }
+
+        None
+    }
+
+    // fills the unused rest of the current hole and overflow block
+    fn make_iterable(&mut self, vm: &VM) {
+        fill_dead(vm, self.top, self.limit);
+        fill_dead(vm, self.overflow_top, self.overflow_limit);
+
+        self.top = Address::null();
+        self.limit = Address::null();
+        self.overflow_top = Address::null();
+        self.overflow_limit = Address::null();
+    }
+
+    fn reset_allocation(&mut self) {
+        self.next_block = 0;
+        self.next_line = 0;
+        self.next_free_block = 0;
+    }
+
+    fn used_size(&self) -> usize {
+        let used = self
+            .blocks
+            .iter()
+            .filter(|b| b.state != BlockState::Free)
+            .count();
+
+        used * BLOCK_SIZE
+    }
+
+    fn live_size(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|b| match b.state {
+                BlockState::Free => 0,
+                BlockState::Used => (LINES_PER_BLOCK - b.free_lines()) * LINE_SIZE,
+                BlockState::Large(_) | BlockState::LargeContinuation => BLOCK_SIZE,
+            })
+            .sum()
+    }
+}
+
+struct MarkRegion<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    heap: Region,
+    space: &'a mut ImmixSpace,
+    rootset: &'a [Slot],
+    threadpool: Option<&'a Mutex<Pool>>,
+    evacuated_blocks: usize,
+}
+
+impl<'a, 'ast> MarkRegion<'a, 'ast> {
+    fn collect(&mut self) {
+        let dev_verbose = self.vm.args.flag_gc_dev_verbose;
+
+        self.verify("pre", false);
+
+        if dev_verbose {
+            println!("Immix GC: Phase 1 (select evacuation candidates)");
+        }
+
+        self.select_candidates();
+
+        if dev_verbose {
+            println!("Immix GC: Phase 2 (marking)");
+        }
+
+        self.mark();
+
+        if dev_verbose {
+            println!("Immix GC: Phase 3 (sweep)");
+        }
+
+        self.sweep();
+        self.space.reset_allocation();
+
+        self.verify("post", true);
+
+        if dev_verbose {
+            println!("Immix GC: Stop");
+        }
+    }
+
+    // Evacuation is only attempted when the last collection left
+    // fragmented blocks behind. Blocks with the fewest live lines are
+    // evacuated first, as long as free blocks are available to copy into.
+    fn select_candidates(&mut self) {
+        if !self.space.defrag {
+            return;
+        }
+
+        let mut candidates: Vec<(usize, usize)> = self
+            .space
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.is_fragmented())
+            .map(|(idx, b)| (LINES_PER_BLOCK - b.free_lines(), idx))
+            .collect();
+
+        candidates.sort();
+
+        let mut available = self.space.free_blocks * LINES_PER_BLOCK;
+
+        for (live_lines, idx) in candidates {
+            if live_lines > available {
+                break;
+            }
+
+            available -= live_lines;
+            self.space.blocks[idx].evacuate = true;
+            self.evacuated_blocks += 1;
+        }
+    }
+
+    fn mark(&mut self) {
+        let perm = self.vm.gc.perm_space.total();
+
+        if self.evacuated_blocks > 0 {
+            let mut evacuation = Evacuation {
+                vm: self.vm,
+                heap: self.heap,
+                perm,
+                space: &mut *self.space,
+                top: Address::null(),
+                limit: Address::null(),
+                marking_stack: Vec::new(),
+            };
+
+            evacuation.trace(self.rootset);
+        } else if let Some(pool) = self.threadpool {
+            let mut pool = pool.lock();
+            pmarking::start(self.rootset, self.heap, perm, &mut pool);
+        } else {
+            marking::start(self.rootset, self.heap, perm);
+        }
+    }
+
+    fn sweep(&mut self) {
+        let vm = self.vm;
+
+        if let Some(pool) = self.threadpool {
+            let mut pool = pool.lock();
+            let number_workers = pool.thread_count() as usize;
+            let chunk_size = (self.space.blocks.len() + number_workers - 1) / number_workers;
+
+            pool.scoped(|scoped| {
+                for blocks in self.space.blocks.chunks_mut(chunk_size) {
+                    scoped.execute(move || {
+                        for block in blocks {
+                            sweep_block(vm, block);
+                        }
+                    });
+                }
+            });
+        } else {
+            for block in &mut self.space.blocks {
+                sweep_block(vm, block);
+            }
+        }
+
+        let mut idx = 0;
+
+        while idx < self.space.blocks.len() {
+            if let BlockState::Large(count) = self.space.blocks[idx].state {
+                let object = self.space.blocks[idx].start.to_mut_obj();
+
+                if object.header().is_marked_non_atomic() {
+                    object.header_mut().unmark_non_atomic();
+                } else {
+                    for block in &mut self.space.blocks[idx..idx + count] {
+                        block.state = BlockState::Free;
+                        block.lines = 0;
+                    }
+                }
+
+                idx += count;
+            } else {
+                idx += 1;
+            }
+        }
+
+        let mut free_blocks = 0;
+        let mut used_lines = 0;
+        let mut fragmented_lines = 0;
+
+        for block in &self.space.blocks {
+            if block.state == BlockState::Free {
+                free_blocks += 1;
+            } else if block.state == BlockState::Used {
+                used_lines += LINES_PER_BLOCK;
+
+                if block.is_fragmented() {
+                    fragmented_lines += block.free_lines();
+                }
+            }
+        }
+
+        self.space.free_blocks = free_blocks;
+        self.space.defrag = fragmented_lines > 0 && fragmented_lines * 8 >= used_lines;
+    }
+
+    fn verify(&mut self, name: &str, after_collection: bool) {
+        if !self.vm.args.flag_gc_verify {
+            return;
+        }
+
+        if self.vm.args.flag_gc_dev_verbose {
+            println!("GC: Verify {}", name);
+        }
+
+        let mut objects = HashSet::new();
+
+        for block in &self.space.blocks {
+            match block.state {
+                BlockState::Used => {
+                    let mut scan = block.start;
+
+                    while scan < block.end() {
+                        let object = scan.to_obj();
+
+                        if object.header().vtblptr().is_null() {
+                            scan = scan.add_ptr(1);
+                            continue;
+                        }
+
+                        assert!(
+                            object.header().vtblptr_forwarded().is_none(),
+                            "forwarded object at {} left in heap",
+                            scan
+                        );
+
+                        objects.insert(scan);
+                        scan = scan.offset(object.size());
+                    }
+
+                    assert!(scan == block.end(), "object crosses block boundary");
+                }
+
+                BlockState::Large(count) => {
+                    let object = block.start.to_obj();
+                    assert!(object.size() <= count * BLOCK_SIZE);
+                    objects.insert(block.start);
+                }
+
+                BlockState::Free | BlockState::LargeContinuation => {}
+            }
+        }
+
+        let perm = self.vm.gc.perm_space.total();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<Address> = Vec::new();
+
+        let mut verify_reference = |reference: Address, stack: &mut Vec<Address>| {
+            if reference.is_null() || perm.contains(reference) {
+                return;
+            }
+
+            assert!(
+                self.heap.contains(reference) && objects.contains(&reference),
+                "reference {} does not point to object",
+                reference
+            );
+
+            if visited.insert(reference) {
+                stack.push(reference);
+            }
+        };
+
+        for root in self.rootset {
+            verify_reference(root.get(), &mut stack);
+        }
+
+        while let Some(object_addr) = stack.pop() {
+            let object = object_addr.to_mut_obj();
+
+            assert!(
+                !object.header().is_marked_non_atomic(),
+                "object {} is still marked",
+                object_addr
+            );
+
+            let idx = self.space.block_index(object_addr);
+            let block = &self.space.blocks[idx];
+
+            if after_collection && block.state == BlockState::Used {
+                let lines = object_lines(block.start, object_addr, object.size());
+                assert!(
+                    block.lines & lines == lines,
+                    "line of live object {} not marked",
+                    object_addr
+                );
+            }
+
+            object.visit_reference_fields(|field| {
+                verify_reference(field.get(), &mut stack);
+            });
+        }
+
+        if self.vm.args.flag_gc_dev_verbose {
+            println!("GC: Verify {} finished", name);
+        }
+    }
+}
+
+fn sweep_block(vm: &VM, block: &mut Block) {
+    block.evacuate = false;
+
+    if block.state != BlockState::Used {
+        return;
+    }
+
+    let end = block.end();
+    let mut scan = block.start;
+    let mut garbage_start = Address::null();
+    let mut lines = 0;
+
+    while scan < end {
+        let object = scan.to_mut_obj();
+
+        if object.header().vtblptr().is_null() {
+            if garbage_start.is_null() {
+                garbage_start = scan;
+            }
+
+            scan = scan.add_ptr(1);
+            continue;
+        }
+
+        let object_size = if let Some(fwd) = object.header().vtblptr_forwarded() {
+            // object was evacuated, its memory can be reused
+            object.size_for_vtblptr(fwd.to_obj().header().vtblptr())
+        } else if object.header().is_marked_non_atomic() {
+            let object_size = object.size();
+            object.header_mut().unmark_non_atomic();
+            lines |= object_lines(block.start, scan, object_size);
+
+            free_garbage(vm, garbage_start, scan);
+            garbage_start = Address::null();
+
+            scan = scan.offset(object_size);
+            continue;
+        } else {
+            object.size()
+        };
+
+        if garbage_start.is_null() {
+            garbage_start = scan;
+        }
+
+        scan = scan.offset(object_size);
+    }
+
+    assert!(scan == end);
+    free_garbage(vm, garbage_start, end);
+
+    if lines == 0 {
+        block.state = BlockState::Free;
+    }
+
+    block.lines = lines;
+}
+
+// fills dead range such that each hole of free lines starts and ends
+// at an object boundary
+fn free_garbage(vm: &VM, start: Address, end: Address) {
+    if start.is_null() {
+        return;
+    }
+
+    let hole_start: Address = mem::align_usize(start.to_usize(), LINE_SIZE).into();
+    let hole_end: Address = (end.to_usize() & !(LINE_SIZE - 1)).into();
+
+    if hole_start < hole_end {
+        fill_dead(vm, start, hole_start);
+        fill_dead(vm, hole_start, hole_end);
+        fill_dead(vm, hole_end, end);
+    } else {
+        fill_dead(vm, start, end);
+    }
+}
+
+struct Evacuation<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    heap: Region,
+    perm: Region,
+    space: &'a mut ImmixSpace,
+
+    // free block objects are evacuated into
+    top: Address,
+    limit: Address,
+
+    marking_stack: Vec<Address>,
+}
+
+impl<'a, 'ast> Evacuation<'a, 'ast> {
+    fn trace(&mut self, rootset: &[Slot]) {
+        for &root in rootset {
+            self.trace_slot(root);
+        }
+
+        while let Some(object_addr) = self.marking_stack.pop() {
+            let object = object_addr.to_mut_obj();
+
+            object.visit_reference_fields(|field| {
+                self.trace_slot(field);
+            });
+        }
+
+        fill_dead(self.vm, self.top, self.limit);
+    }
+
+    fn trace_slot(&mut self, slot: Slot) {
+        let object_addr = slot.get();
+
+        if !self.heap.contains(object_addr) {
+            debug_assert!(object_addr.is_null() || self.perm.contains(object_addr));
+            return;
+        }
+
+        let object = object_addr.to_mut_obj();
+
+        if let Some(fwd) = object.header().vtblptr_forwarded() {
+            slot.set(fwd);
+            return;
+        }
+
+        if object.header().is_marked_non_atomic() {
+            return;
+        }
+
+        let idx = self.space.block_index(object_addr);
+
+        if self.space.blocks[idx].evacuate {
+            let object_size = object.size();
+            let copy_addr = self.alloc(object_size);
+
+            if copy_addr.is_non_null() {
+                object.copy_to(copy_addr, object_size);
+                copy_addr.to_mut_obj().header_mut().mark_non_atomic();
+                object.header_mut().vtblptr_forward(copy_addr);
+
+                slot.set(copy_addr);
+                self.marking_stack.push(copy_addr);
+                return;
+            }
+
+            // no space left for evacuation, keep object in place
+        }
+
+        object.header_mut().mark_non_atomic();
+        self.marking_stack.push(object_addr);
+    }
+
+    fn alloc(&mut self, size: usize) -> Address {
+        let object = self.top;
+        let next_top = object.offset(size);
+
+        if object.is_non_null() && next_top <= self.limit {
+            self.top = next_top;
+            return object;
+        }
+
+        match self.space.take_free_block(true) {
+            Some(block) => {
+                fill_dead(self.vm, self.top, self.limit);
+
+                self.top = block.start.offset(size);
+                self.limit = block.end;
+
+                block.start
+            }
+
+            None => Address::null(),
+        }
+    }
+}
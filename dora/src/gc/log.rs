@@ -0,0 +1,48 @@
+use std::fs::File;
+use std::io::Write;
+
+use crate::json::Json;
+use crate::timer;
+
+// Log for --gc-log with one JSON record per line. Each record is written
+// right away, so the log is complete even when the program is terminated.
+pub struct GcLog {
+    path: String,
+    file: File,
+    start: u64,
+}
+
+impl GcLog {
+    pub fn create(path: &str) -> Option<GcLog> {
+        match File::create(path) {
+            Ok(file) => Some(GcLog {
+                path: path.to_string(),
+                file,
+                start: timer::timestamp(),
+            }),
+
+            Err(err) => {
+                eprintln!("could not create GC log {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    // time in ms since the log was created
+    pub fn time(&self, timestamp: u64) -> f32 {
+        timer::in_ms(timestamp - self.start)
+    }
+
+    pub fn write(&mut self, record: Json) {
+        let line = format!("{}\n", record);
+
+        if let Err(err) = self.file.write_all(line.as_bytes()) {
+            eprintln!("could not write GC log {}: {}", self.path, err);
+        }
+    }
+}
+
+// durations are logged in ms with microsecond precision
+pub fn ms(duration: f32) -> Json {
+    Json::from((duration as f64 * 1000.0).round() / 1000.0)
+}
@@ -1,12 +1,18 @@
 use std::sync::Arc;
 
 use crate::compiler::map::CodeDescriptor;
+use crate::gc::compressed;
+use crate::gc::stackmap;
 use crate::gc::Address;
 use crate::stack::DoraToNativeInfo;
 use crate::threads::DoraThread;
 use crate::vm::VM;
 
 pub fn get_rootset(vm: &VM, threads: &[Arc<DoraThread>]) -> Vec<Slot> {
+    if vm.args.flag_gc_verify_stackmaps {
+        stackmap::verify(vm, threads);
+    }
+
     let mut rootset = Vec::new();
 
     determine_rootset_from_stack(&mut rootset, vm, threads);
@@ -119,6 +125,7 @@ fn determine_rootset(rootset: &mut Vec<Slot>, vm: &VM, fp: usize, pc: usize) ->
         Some(CodeDescriptor::AllocStub) => true,
         Some(CodeDescriptor::DoraStub) => false,
         Some(CodeDescriptor::GuardCheckStub) => true,
+        Some(CodeDescriptor::DebugStub) => true,
         Some(CodeDescriptor::CompileStub) => true,
 
         _ => {
@@ -129,24 +136,51 @@ fn determine_rootset(rootset: &mut Vec<Slot>, vm: &VM, fp: usize, pc: usize) ->
 }
 
 #[derive(Copy, Clone)]
-pub struct Slot(Address);
+pub struct Slot {
+    address: Address,
+
+    // holds a compressed reference
+    narrow: bool,
+}
 
 impl Slot {
+    // slot with a full pointer: stack, handles, globals and references in tuples
     pub fn at(addr: Address) -> Slot {
-        Slot(addr)
+        Slot {
+            address: addr,
+            narrow: false,
+        }
+    }
+
+    // reference field of an object or element of an object array
+    pub fn field(addr: Address) -> Slot {
+        Slot {
+            address: addr,
+            narrow: compressed::enabled(),
+        }
     }
 
     pub fn address(self) -> Address {
-        self.0
+        self.address
     }
 
     pub fn get(self) -> Address {
-        unsafe { *self.0.to_ptr::<Address>() }
+        if self.narrow {
+            compressed::decode(unsafe { *self.address.to_ptr::<u32>() })
+        } else {
+            unsafe { *self.address.to_ptr::<Address>() }
+        }
     }
 
     pub fn set(self, obj: Address) {
-        unsafe {
-            *self.0.to_mut_ptr::<Address>() = obj;
+        if self.narrow {
+            unsafe {
+                *self.address.to_mut_ptr::<u32>() = compressed::encode(obj);
+            }
+        } else {
+            unsafe {
+                *self.address.to_mut_ptr::<Address>() = obj;
+            }
         }
     }
 }
@@ -1,6 +1,7 @@
 use parking_lot::Mutex;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::gc::compressed;
 use crate::gc::{Address, Region};
 use crate::mem;
 use crate::os;
@@ -35,6 +36,10 @@ pub struct Space {
     end: AtomicUsize,
 
     allocate: Mutex<()>,
+
+    // memory returned with `free`, sorted by address and coalesced
+    free_list: Mutex<Vec<Region>>,
+    free_size: AtomicUsize,
 }
 
 impl Space {
@@ -42,7 +47,13 @@ impl Space {
     pub fn new(config: SpaceConfig, name: &'static str) -> Space {
         let config = adapt_to_page_size(config);
 
-        let reservation = os::reserve_align(config.limit, os::page_size());
+        // objects in the perm space are referenced from heap objects,
+        // code is never referenced with compressed references
+        let reservation = if config.executable {
+            os::reserve_align(config.limit, os::page_size())
+        } else {
+            compressed::reserve_align(config.limit, os::page_size())
+        };
         let space_start = reservation.start;
         let space_end = space_start.offset(config.limit);
 
@@ -58,16 +69,26 @@ impl Space {
             end: AtomicUsize::new(end.to_usize()),
 
             allocate: Mutex::new(()),
+
+            free_list: Mutex::new(Vec::new()),
+            free_size: AtomicUsize::new(0),
         }
     }
 
-    /// allocate memory in this space. This first tries to allocate space
-    /// in the current chunk. If this fails a new chunk is allocated.
-    /// Doesn't use a freelist right now so memory at the end of a chunk
-    /// is probably lost.
+    /// allocate memory in this space. This first tries to reuse freed memory,
+    /// then to allocate space in the current chunk. If this fails a new chunk
+    /// is allocated. Memory at the end of a chunk is probably lost.
     pub fn alloc(&self, size: usize) -> Address {
         let size = mem::align_usize(size, self.config.align);
 
+        if self.free_size.load(Ordering::Relaxed) >= size {
+            let ptr = self.alloc_free_list(size);
+
+            if !ptr.is_null() {
+                return ptr;
+            }
+        }
+
         loop {
             let ptr = self.raw_alloc(size);
             if !ptr.is_null() {
@@ -80,6 +101,55 @@ impl Space {
         }
     }
 
+    /// returns memory allocated with `alloc` to the space.
+    pub fn free(&self, addr: Address, size: usize) {
+        let size = mem::align_usize(size, self.config.align);
+        let mut region = Region::new(addr, addr.offset(size));
+        debug_assert!(self.used_region().contains(addr));
+
+        let mut free_list = self.free_list.lock();
+        let idx = match free_list.binary_search_by_key(&addr, |free| free.start) {
+            Ok(_) => panic!("memory freed twice in {} space", self.name),
+            Err(idx) => idx,
+        };
+
+        let merge_next = idx < free_list.len() && free_list[idx].start == region.end;
+        let merge_prev = idx > 0 && free_list[idx - 1].end == region.start;
+
+        if merge_next {
+            region.end = free_list.remove(idx).end;
+        }
+
+        if merge_prev {
+            free_list[idx - 1].end = region.end;
+        } else {
+            free_list.insert(idx, region);
+        }
+
+        self.free_size.fetch_add(size, Ordering::Relaxed);
+    }
+
+    fn alloc_free_list(&self, size: usize) -> Address {
+        let mut free_list = self.free_list.lock();
+        let idx = free_list.iter().position(|free| free.size() >= size);
+
+        let idx = match idx {
+            Some(idx) => idx,
+            None => return Address::null(),
+        };
+
+        let ptr = free_list[idx].start;
+
+        if free_list[idx].size() == size {
+            free_list.remove(idx);
+        } else {
+            free_list[idx].start = ptr.offset(size);
+        }
+
+        self.free_size.fetch_sub(size, Ordering::Relaxed);
+        ptr
+    }
+
     fn raw_alloc(&self, size: usize) -> Address {
         let mut old = self.top.load(Ordering::Relaxed);
         let mut new;
@@ -137,6 +207,15 @@ impl Space {
         self.total.clone()
     }
 
+    /// number of allocated bytes that were not freed again
+    pub fn used(&self) -> usize {
+        self.used_region().size() - self.free_size.load(Ordering::Relaxed)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.config.limit
+    }
+
     pub fn used_region(&self) -> Region {
         let start = self.total.start;
         let end = self.top.load(Ordering::Relaxed).into();
@@ -147,6 +226,10 @@ impl Space {
 
 impl Drop for Space {
     fn drop(&mut self) {
-        os::free(self.total.start, self.total.size());
+        if self.config.executable {
+            os::free(self.total.start, self.total.size());
+        } else {
+            compressed::free(self.total.start, self.total.size());
+        }
     }
 }
@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::compiler::fct::{GcPoint, JitFct};
+use crate::compiler::map::CodeDescriptor;
+use crate::gc::Address;
+use crate::mem;
+use crate::stack::DoraToNativeInfo;
+use crate::threads::DoraThread;
+use crate::vm::VM;
+
+// Scans the whole frame of each compiled function on the stacks of all
+// threads and compares it with the GcPoint of the current instruction:
+// every slot holding a heap reference needs to be in the GcPoint and every
+// slot in the GcPoint needs to hold nil or a valid reference.
+// Panics after all mismatches were reported. All threads need to be stopped.
+pub fn verify(vm: &VM, threads: &[Arc<DoraThread>]) {
+    let mut mismatches = 0;
+
+    for thread in threads {
+        let mut dtn = thread.dtn() as *const DoraToNativeInfo;
+
+        while !dtn.is_null() {
+            let info = unsafe { &*dtn };
+
+            let mut pc = info.pc;
+            let mut fp = info.fp;
+
+            while fp != 0 {
+                let code_map = vm.code_map.lock();
+
+                match code_map.get(pc.into()) {
+                    Some(CodeDescriptor::DoraStub) => break,
+
+                    Some(CodeDescriptor::DoraFct(jit_fct_id)) => {
+                        let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+                        let offset = pc - jit_fct.instruction_start().to_usize();
+
+                        for msg in verify_frame(vm, &jit_fct, fp, offset as u32) {
+                            eprintln!("{}", msg);
+                            mismatches += 1;
+                        }
+                    }
+
+                    _ => {}
+                }
+
+                pc = unsafe { *((fp + 8) as *const usize) };
+                fp = unsafe { *(fp as *const usize) };
+            }
+
+            dtn = info.last;
+        }
+    }
+
+    if mismatches > 0 {
+        panic!("stack map verification failed: {} mismatches", mismatches);
+    }
+}
+
+// Returns one message for each mismatch between the frame at fp and the
+// GcPoint at the given offset.
+fn verify_frame(vm: &VM, jit_fct: &JitFct, fp: usize, offset: u32) -> Vec<String> {
+    let gcpoint = match jit_fct.gcpoint_for_offset(offset) {
+        Some(gcpoint) => gcpoint,
+        None => {
+            let msg = "no GcPoint for return address";
+            return vec![report(vm, jit_fct, offset, None, msg)];
+        }
+    };
+
+    let listed: HashSet<i32> = gcpoint.offsets.iter().cloned().collect();
+    let mut mismatches = Vec::new();
+
+    for &slot in &gcpoint.offsets {
+        if !mem::is_aligned(slot.abs() as usize, mem::ptr_width_usize()) {
+            let msg = format!("slot fp{} in GcPoint is not aligned", slot);
+            mismatches.push(report(vm, jit_fct, offset, Some(gcpoint), &msg));
+            continue;
+        }
+
+        let value = load(fp, slot);
+
+        if !vm.gc.verify_ref(vm, value) {
+            let msg = format!(
+                "slot fp{} in GcPoint holds invalid reference {}",
+                slot, value
+            );
+            mismatches.push(report(vm, jit_fct, offset, Some(gcpoint), &msg));
+        }
+    }
+
+    let framesize = jit_fct.framesize();
+    let frame_slots: Vec<i32> = (-framesize..0).step_by(mem::ptr_width_usize()).collect();
+    let live_slots = gcpoint.live_slots.as_ref().unwrap_or(&frame_slots);
+
+    for &slot in live_slots {
+        if listed.contains(&slot) {
+            continue;
+        }
+
+        let value = load(fp, slot);
+
+        if !value.is_null() && vm.gc.verify_ref(vm, value) {
+            let msg = format!(
+                "slot fp{} holds reference {} but is missing in GcPoint",
+                slot, value
+            );
+            mismatches.push(report(vm, jit_fct, offset, Some(gcpoint), &msg));
+        }
+    }
+
+    mismatches
+}
+
+fn report(vm: &VM, jit_fct: &JitFct, offset: u32, gcpoint: Option<&GcPoint>, msg: &str) -> String {
+    let fct = vm.fcts.idx(jit_fct.fct_id());
+    let fct = fct.read();
+
+    let mut location = format!("{} at offset {}", fct.full_name(vm), offset);
+
+    if let Some(bytecode_offset) = gcpoint.and_then(|gcpoint| gcpoint.bytecode_offset) {
+        location.push_str(&format!(" (bytecode offset {})", bytecode_offset));
+    }
+
+    if let Some(position) = jit_fct.position_for_offset(offset) {
+        location.push_str(&format!(
+            " in {}:{}:{}",
+            vm.file(position.file).name,
+            position.pos.line,
+            position.pos.column
+        ));
+    }
+
+    format!("stack map mismatch in {}: {}", location, msg)
+}
+
+fn load(fp: usize, slot: i32) -> Address {
+    let addr = (fp as isize + slot as isize) as usize;
+    unsafe { *(addr as *const Address) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::fct::{
+        Code, Comments, GcPoints, JitDescriptor, LazyCompilationData, PositionTable,
+    };
+    use crate::dseg::DSeg;
+    use crate::test;
+
+    #[test]
+    fn test_verify_frame_reports_missing_and_invalid_slots() {
+        test::parse("fun f() {}", |vm| {
+            let fct_id = vm.fct_by_name("f").unwrap();
+
+            let mut gcpoints = GcPoints::new();
+            gcpoints.insert(4, GcPoint::from_bytecode(vec![-8, -24], 7));
+
+            let code = Code::from_buffer(
+                vm,
+                &DSeg::new(),
+                &[0; 8],
+                LazyCompilationData::new(),
+                gcpoints,
+                32,
+                Comments::new(),
+                PositionTable::new(),
+                JitDescriptor::DoraFct(fct_id),
+            );
+            let jit_fct = JitFct::Compiled(code);
+
+            let object = vm.gc.alloc(vm, 16, false);
+            let invalid = Address::from(8);
+
+            // frame below fp: fp-8 and fp-24 are listed, fp-16 is not
+            let frame: [Address; 4] = [Address::null(), object, object, invalid];
+            let fp = Address::from_ptr(frame.as_ptr()).add_ptr(4).to_usize();
+
+            let mismatches = verify_frame(vm, &jit_fct, fp, 4);
+
+            assert_eq!(
+                vec![
+                    format!(
+                        "stack map mismatch in f() at offset 4 (bytecode offset 7): \
+                         slot fp-8 in GcPoint holds invalid reference {}",
+                        invalid
+                    ),
+                    format!(
+                        "stack map mismatch in f() at offset 4 (bytecode offset 7): \
+                         slot fp-16 holds reference {} but is missing in GcPoint",
+                        object
+                    ),
+                ],
+                mismatches
+            );
+
+            assert_eq!(
+                vec!["stack map mismatch in f() at offset 5: no GcPoint for return address"],
+                verify_frame(vm, &jit_fct, fp, 5)
+            );
+        });
+    }
+}
@@ -1,18 +1,17 @@
 use parking_lot::Mutex;
+use std::collections::HashSet;
 
 use crate::driver::cmd::Args;
+use crate::gc::compressed;
 use crate::gc::freelist::FreeList;
 use crate::gc::marking;
 use crate::gc::root::{get_rootset, Slot};
-use crate::gc::space::Space;
 use crate::gc::tlab;
-use crate::gc::{
-    fill_region_with_free, formatted_size, Address, CollectionStats, Collector, GcReason, Region,
-};
-use crate::os;
+use crate::gc::{formatted_size, Address, CollectionStats, Collector, GcReason, Region, K};
 use crate::safepoint;
 use crate::timer::Timer;
 use crate::vm::VM;
+use crate::vtable::VTable;
 
 pub struct SweepCollector {
     heap: Region,
@@ -23,7 +22,7 @@ pub struct SweepCollector {
 impl SweepCollector {
     pub fn new(args: &Args) -> SweepCollector {
         let heap_size = args.max_heap_size();
-        let heap_start = os::commit(heap_size, false);
+        let heap_start = compressed::commit(heap_size);
 
         if heap_start.is_null() {
             panic!("could not allocate heap of size {} bytes", heap_size);
@@ -120,11 +119,15 @@ impl Collector for SweepCollector {
             gc,
         );
     }
+
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.heap.contains(reference)
+    }
 }
 
 impl Drop for SweepCollector {
     fn drop(&mut self) {
-        os::free(self.heap.start, self.heap.size());
+        compressed::free(self.heap.start, self.heap.size());
     }
 }
 
@@ -135,110 +138,139 @@ impl SweepCollector {
     }
 
     fn mark_sweep(&self, vm: &VM, rootset: &[Slot], reason: GcReason) {
-        let start = self.heap.start;
-        let top = self.alloc.lock().top;
-
-        let mut collector = MarkSweep {
-            vm,
-            heap: Region::new(start, top),
-            perm_space: &vm.gc.perm_space,
-
-            rootset,
-            reason,
-            free_list: FreeList::new(),
-        };
-
-        collector.collect();
-
         let mut alloc = self.alloc.lock();
-        alloc.free_list = collector.free_list;
-    }
-}
+        let dev_verbose = vm.args.flag_gc_dev_verbose;
 
-struct MarkSweep<'a, 'ast: 'a> {
-    vm: &'a VM<'ast>,
-    heap: Region,
-    perm_space: &'a Space,
-
-    rootset: &'a [Slot],
-    reason: GcReason,
-    free_list: FreeList,
-}
+        // the previous collection might have left some memory unswept
+        alloc.finish_sweep(vm);
 
-impl<'a, 'ast> MarkSweep<'a, 'ast> {
-    fn collect(&mut self) {
-        let dev_verbose = self.vm.args.flag_gc_dev_verbose;
+        let heap = Region::new(self.heap.start, alloc.top);
+        self.verify(vm, heap, rootset, "pre-mark", false);
 
         if dev_verbose {
-            println!("Sweep GC: Phase 1 (marking)");
+            println!("Sweep GC: Phase 1 (marking) because of {}", reason);
         }
 
-        self.mark();
+        marking::start(rootset, heap, vm.gc.perm_space.total());
+        self.verify(vm, heap, rootset, "post-mark", true);
 
         if dev_verbose {
-            println!("Sweep GC: Phase 2 (sweep)");
+            println!("Sweep GC: Phase 2 (sweep) lazily on allocation");
         }
 
-        self.sweep();
+        // sweeping is done by the mutator outside of the pause
+        alloc.start_sweep(heap);
 
         if dev_verbose {
             println!("Sweep GC: Stop");
         }
     }
 
-    fn mark(&mut self) {
-        marking::start(self.rootset, self.heap, self.perm_space.total());
-    }
+    fn verify(&self, vm: &VM, heap: Region, rootset: &[Slot], name: &str, marked: bool) {
+        if !vm.args.flag_gc_verify {
+            return;
+        }
 
-    fn sweep(&mut self) {
-        let start = self.heap.start;
-        let end = self.heap.end;
+        if vm.args.flag_gc_dev_verbose {
+            println!("GC: Verify {}", name);
+        }
 
-        let mut scan = start;
-        let mut garbage_start = Address::null();
+        let free_vtables = free_vtables(vm);
+        let mut objects = HashSet::new();
+        let mut scan = heap.start;
 
-        while scan < end {
-            let object = scan.to_mut_obj();
+        while scan < heap.end {
+            let object = scan.to_obj();
 
             if object.header().vtblptr().is_null() {
                 scan = scan.add_ptr(1);
                 continue;
             }
 
-            let object_size = object.size();
+            assert!(
+                marked || !object.header().is_marked_non_atomic(),
+                "object {} is still marked",
+                scan
+            );
 
-            if object.header().is_marked_non_atomic() {
-                self.add_freelist(garbage_start, scan);
-                garbage_start = Address::null();
-                object.header_mut().unmark_non_atomic();
-            } else if garbage_start.is_non_null() {
-                // more garbage, do nothing
-            } else {
-                // start garbage, last object was live
-                garbage_start = scan;
+            if !free_vtables.contains(&object.header().vtblptr()) {
+                objects.insert(scan);
             }
 
-            scan = scan.offset(object_size);
+            scan = scan.offset(object.size());
         }
 
-        assert!(scan == end);
-        self.add_freelist(garbage_start, end);
-    }
+        assert!(scan == heap.end, "object crosses end of heap");
 
-    fn add_freelist(&mut self, start: Address, end: Address) {
-        if start.is_null() {
-            return;
+        let perm = vm.gc.perm_space.total();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<Address> = Vec::new();
+
+        let mut verify_reference = |reference: Address, stack: &mut Vec<Address>| {
+            if reference.is_null() || perm.contains(reference) {
+                return;
+            }
+
+            assert!(
+                objects.contains(&reference),
+                "reference {} does not point to live object",
+                reference
+            );
+
+            if visited.insert(reference) {
+                stack.push(reference);
+            }
+        };
+
+        for root in rootset {
+            verify_reference(root.get(), &mut stack);
         }
 
-        let size = end.offset_from(start);
-        self.free_list.add(self.vm, start, size);
+        while let Some(object_addr) = stack.pop() {
+            let object = object_addr.to_mut_obj();
+
+            assert!(
+                object.header().is_marked_non_atomic() == marked,
+                "live object {} has wrong mark",
+                object_addr
+            );
+
+            object.visit_reference_fields(|field| {
+                verify_reference(field.get(), &mut stack);
+            });
+        }
+
+        if vm.args.flag_gc_dev_verbose {
+            println!("GC: Verify {} finished", name);
+        }
     }
 }
 
+fn free_vtables(vm: &VM) -> [Address; 2] {
+    let vtable = |cls_id| {
+        let cls = vm.class_defs.idx(cls_id);
+        let cls = cls.read();
+        let vtable: *const VTable = &**cls.vtable.as_ref().unwrap();
+        Address::from_ptr(vtable)
+    };
+
+    [
+        vtable(vm.vips.free_object_class_def),
+        vtable(vm.vips.free_array_class_def),
+    ]
+}
+
+// Bytes of heap swept at once when an allocation can't be satisfied.
+const SWEEP_STEP: usize = 32 * K;
+
 struct SweepAllocator {
     top: Address,
     limit: Address,
     free_list: FreeList,
+
+    // the region [sweep_scan, sweep_end) was marked but not swept yet
+    sweep_scan: Address,
+    sweep_end: Address,
 }
 
 impl SweepAllocator {
@@ -247,6 +279,9 @@ impl SweepAllocator {
             top: heap.start,
             limit: heap.end,
             free_list: FreeList::new(),
+
+            sweep_scan: heap.start,
+            sweep_end: heap.start,
         }
     }
 
@@ -259,22 +294,96 @@ impl SweepAllocator {
             return object;
         }
 
+        loop {
+            let object = self.alloc_free_list(vm, size);
+
+            if object.is_non_null() || !self.sweep_step(vm) {
+                return object;
+            }
+        }
+    }
+
+    fn alloc_free_list(&mut self, vm: &VM, size: usize) -> Address {
         let free_space = self.free_list.alloc(size);
 
-        if free_space.is_non_null() {
-            let object = free_space.addr();
-            let free_size = free_space.size();
-            assert!(size <= free_size);
+        if free_space.is_null() {
+            return Address::null();
+        }
+
+        let object = free_space.addr();
+        let free_size = free_space.size();
+        assert!(size <= free_size);
 
-            let free_start = object.offset(size);
-            let free_end = object.offset(free_size);
-            let new_free_size = free_end.offset_from(free_start);
+        let free_start = object.offset(size);
+        let new_free_size = free_size - size;
 
-            fill_region_with_free(vm, free_start, free_end, Address::null());
+        if new_free_size > 0 {
             self.free_list.add(vm, free_start, new_free_size);
-            return object;
         }
 
-        Address::null()
+        object
+    }
+
+    fn start_sweep(&mut self, heap: Region) {
+        self.free_list = FreeList::new();
+        self.sweep_scan = heap.start;
+        self.sweep_end = heap.end;
+    }
+
+    fn finish_sweep(&mut self, vm: &VM) {
+        while self.sweep_step(vm) {}
+    }
+
+    // Sweeps the next SWEEP_STEP bytes of the marked region, returns false
+    // when there was nothing left to sweep.
+    fn sweep_step(&mut self, vm: &VM) -> bool {
+        if self.sweep_scan >= self.sweep_end {
+            return false;
+        }
+
+        let end = self.sweep_end;
+        let step_end = self.sweep_scan.offset(SWEEP_STEP);
+
+        let mut scan = self.sweep_scan;
+        let mut garbage_start = Address::null();
+
+        while scan < end && scan < step_end {
+            let object = scan.to_mut_obj();
+
+            if object.header().vtblptr().is_null() {
+                scan = scan.add_ptr(1);
+                continue;
+            }
+
+            let object_size = object.size();
+
+            if object.header().is_marked_non_atomic() {
+                self.add_freelist(vm, garbage_start, scan);
+                garbage_start = Address::null();
+                object.header_mut().unmark_non_atomic();
+            } else if garbage_start.is_non_null() {
+                // more garbage, do nothing
+            } else {
+                // start garbage, last object was live
+                garbage_start = scan;
+            }
+
+            scan = scan.offset(object_size);
+        }
+
+        assert!(scan <= end);
+        self.add_freelist(vm, garbage_start, scan);
+        self.sweep_scan = scan;
+
+        true
+    }
+
+    fn add_freelist(&mut self, vm: &VM, start: Address, end: Address) {
+        if start.is_null() {
+            return;
+        }
+
+        let size = end.offset_from(start);
+        self.free_list.add(vm, start, size);
     }
 }
@@ -5,9 +5,11 @@ use std::mem::size_of;
 use std::sync::Arc;
 
 use crate::driver::cmd::Args;
+use crate::gc::compressed;
 use crate::gc::root::{get_rootset, Slot};
 use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::compact::FullCollector;
+use crate::gc::swiper::concurrent::{ConcurrentMarker, MarkerPause};
 use crate::gc::swiper::controller::{HeapConfig, SharedHeapConfig};
 use crate::gc::swiper::crossing::CrossingMap;
 use crate::gc::swiper::large::LargeSpace;
@@ -15,6 +17,7 @@ use crate::gc::swiper::minor::MinorCollector;
 use crate::gc::swiper::old::OldGen;
 use crate::gc::swiper::pcompact::ParallelFullCollector;
 use crate::gc::swiper::pminor::ParallelMinorCollector;
+use crate::gc::swiper::uncommit::Uncommitter;
 use crate::gc::swiper::verify::{Verifier, VerifierPhase};
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::tlab;
@@ -25,10 +28,12 @@ use crate::mem;
 use crate::object::Obj;
 use crate::os;
 use crate::safepoint;
+use crate::threads::{DoraThread, THREAD};
 use crate::vm::VM;
 
 pub mod card;
 mod compact;
+mod concurrent;
 mod controller;
 mod crossing;
 mod large;
@@ -37,6 +42,7 @@ pub mod old;
 mod pcompact;
 mod pminor;
 pub mod sweep;
+mod uncommit;
 mod verify;
 pub mod young;
 
@@ -53,6 +59,31 @@ pub const CARD_REFS: usize = CARD_SIZE / size_of::<usize>();
 
 pub const LARGE_OBJECT_SIZE: usize = 16 * K;
 
+pub struct ReserveSizes {
+    pub card_size: usize,
+    pub crossing_size: usize,
+    pub reserve_size: usize,
+}
+
+// the reservation contains young/old generation & large space, followed by
+// card table and crossing map
+pub fn reserve_sizes(max_heap_size: usize) -> ReserveSizes {
+    // determine size for card table
+    let card_size = mem::page_align((4 * max_heap_size) >> CARD_SIZE_BITS);
+
+    // determine size for crossing map
+    let crossing_size = mem::page_align(max_heap_size >> CARD_SIZE_BITS);
+
+    // determine full memory size
+    let reserve_size = max_heap_size * 4 + card_size + crossing_size;
+
+    ReserveSizes {
+        card_size,
+        crossing_size,
+        reserve_size,
+    }
+}
+
 pub struct Swiper {
     // contiguous memory for young/old generation and large space
     heap: Region,
@@ -79,6 +110,12 @@ pub struct Swiper {
 
     threadpool: Option<Mutex<Pool>>,
     config: SharedHeapConfig,
+
+    // returns free memory to the OS after a delay
+    uncommit: Arc<Uncommitter>,
+
+    // marks old generation concurrently, when enabled
+    marker: Option<Arc<ConcurrentMarker>>,
 }
 
 impl Swiper {
@@ -90,17 +127,15 @@ impl Swiper {
 
         controller::init(&mut config, args);
 
-        // determine size for card table
-        let card_size = mem::page_align((4 * max_heap_size) >> CARD_SIZE_BITS);
-
-        // determine size for crossing map
-        let crossing_size = mem::page_align(max_heap_size >> CARD_SIZE_BITS);
-
-        // determine full memory size
-        let reserve_size = max_heap_size * 4 + card_size + crossing_size;
+        // determine sizes of card table, crossing map and full memory
+        let ReserveSizes {
+            card_size,
+            crossing_size,
+            reserve_size,
+        } = reserve_sizes(max_heap_size);
 
         // reserve full memory
-        let reservation = os::reserve_align(reserve_size, GEN_SIZE);
+        let reservation = compressed::reserve_align(reserve_size, GEN_SIZE);
         let heap_start = reservation.start;
         assert!(heap_start.is_gen_aligned());
 
@@ -150,7 +185,14 @@ impl Swiper {
             max_heap_size,
         );
         let crossing_map = CrossingMap::new(crossing_start, crossing_end, max_heap_size);
-        let young = YoungGen::new(young, eden_size, semi_size, args.flag_gc_verify);
+        let uncommit = Arc::new(Uncommitter::new(args.gc_uncommit_delay()));
+        let young = YoungGen::new(
+            young,
+            eden_size,
+            semi_size,
+            args.flag_gc_verify,
+            uncommit.clone(),
+        );
 
         let config = Arc::new(Mutex::new(config));
         let old = OldGen::new(
@@ -159,8 +201,9 @@ impl Swiper {
             crossing_map.clone(),
             card_table.clone(),
             config.clone(),
+            uncommit.clone(),
         );
-        let large = LargeSpace::new(large_start, large_end, config.clone());
+        let large = LargeSpace::new(large_start, large_end, config.clone(), uncommit.clone());
 
         if args.flag_gc_verbose {
             println!(
@@ -186,6 +229,15 @@ impl Swiper {
             None
         };
 
+        let marker = if args.flag_gc_concurrent_mark {
+            Some(ConcurrentMarker::new(
+                Region::new(heap_start, heap_end),
+                Region::new(old_start, large_end),
+            ))
+        } else {
+            None
+        };
+
         Swiper {
             heap: Region::new(heap_start, heap_end),
             reserved_area,
@@ -206,6 +258,8 @@ impl Swiper {
             max_heap_size,
 
             threadpool,
+            uncommit,
+            marker,
         }
     }
 
@@ -221,6 +275,9 @@ impl Swiper {
         mut reason: GcReason,
     ) -> CollectionKind {
         safepoint::stop_the_world(vm, |threads| {
+            // the concurrent marker must not run during the collection
+            let mut marker = self.marker.as_ref().map(|marker| marker.pause());
+
             controller::start(&self.config, &self.young, &self.old, &self.large);
 
             tlab::make_iterable_all(vm, threads);
@@ -232,15 +289,19 @@ impl Swiper {
 
                     if promotion_failed {
                         reason = GcReason::PromotionFailure;
-                        self.full_collect(vm, reason, &rootset);
+                        self.full_collect(vm, reason, &rootset, threads, marker.as_mut());
                         CollectionKind::Full
                     } else {
+                        if let Some(ref mut marker) = marker {
+                            self.start_marking(vm, threads, &rootset, marker);
+                        }
+
                         CollectionKind::Minor
                     }
                 }
 
                 CollectionKind::Full => {
-                    self.full_collect(vm, reason, &rootset);
+                    self.full_collect(vm, reason, &rootset, threads, marker.as_mut());
                     CollectionKind::Full
                 }
             };
@@ -255,6 +316,12 @@ impl Swiper {
                 reason,
             );
 
+            let uncommitted = self.uncommit.release();
+
+            if uncommitted > 0 && vm.args.flag_gc_verbose {
+                println!("GC: uncommitted {}", formatted_size(uncommitted));
+            }
+
             kind
         })
     }
@@ -278,6 +345,10 @@ impl Swiper {
             .map(|r| r.top())
             .collect::<Vec<_>>();
 
+        if vm.args.alloc_site_tracking() {
+            vm.gc.alloc_sites.record_allocated(self.young.eden_active());
+        }
+
         let promotion_failed = if vm.args.parallel_minor() {
             let pool = self.threadpool.as_ref().unwrap();
             let mut pool = pool.lock();
@@ -298,7 +369,7 @@ impl Swiper {
 
             let promotion_failed = collector.collect();
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phase_timing() {
                 let mut config = self.config.lock();
                 config.add_minor(collector.phases());
             }
@@ -321,7 +392,7 @@ impl Swiper {
 
             let promotion_failed = collector.collect();
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phase_timing() {
                 let mut config = self.config.lock();
                 config.add_minor(collector.phases());
             }
@@ -339,10 +410,48 @@ impl Swiper {
             init_old_top,
         );
 
+        if vm.args.flag_gc_pretenure {
+            let pretenured = vm.gc.alloc_sites.update_pretenuring();
+
+            if pretenured > 0 && vm.args.flag_gc_verbose {
+                println!("GC: pretenuring {} allocation sites", pretenured);
+            }
+        }
+
         promotion_failed
     }
 
-    fn full_collect(&self, vm: &VM, reason: GcReason, rootset: &[Slot]) {
+    fn start_marking(
+        &self,
+        vm: &VM,
+        threads: &[Arc<DoraThread>],
+        rootset: &[Slot],
+        marker: &mut MarkerPause,
+    ) {
+        marker.complete(threads);
+
+        if marker.is_marking()
+            || !controller::should_start_marking(&self.config, &self.old, &self.large)
+        {
+            return;
+        }
+
+        if vm.args.flag_gc_verbose {
+            println!("GC: Start concurrent marking");
+        }
+
+        let old_protected = self.old.protected();
+        marker.start(threads, rootset, &self.young, &*old_protected, &self.large);
+    }
+
+    fn full_collect(
+        &self,
+        vm: &VM,
+        reason: GcReason,
+        rootset: &[Slot],
+        threads: &[Arc<DoraThread>],
+        marker: Option<&mut MarkerPause>,
+    ) {
         self.verify(
             vm,
             VerifierPhase::PreFull,
@@ -353,6 +462,10 @@ impl Swiper {
             Vec::new(),
         );
 
+        // finish marking that was started concurrently, the compaction
+        // itself still happens in this pause
+        let remark = marker.and_then(|marker| marker.stop(threads));
+
         if vm.args.parallel_full() {
             let pool = self.threadpool.as_ref().unwrap();
             let mut pool = pool.lock();
@@ -367,13 +480,14 @@ impl Swiper {
                 &vm.gc.perm_space,
                 rootset,
                 reason,
+                remark,
                 pool.thread_count() as usize,
                 self.min_heap_size,
                 self.max_heap_size,
             );
             collector.collect(&mut pool);
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phase_timing() {
                 let mut config = self.config.lock();
                 config.add_full(collector.phases());
             }
@@ -389,12 +503,13 @@ impl Swiper {
                 &vm.gc.perm_space,
                 rootset,
                 reason,
+                remark,
                 self.min_heap_size,
                 self.max_heap_size,
             );
             collector.collect();
 
-            if vm.args.flag_gc_stats {
+            if vm.args.gc_phase_timing() {
                 let mut config = self.config.lock();
                 config.add_full(collector.phases());
             }
@@ -461,6 +576,19 @@ impl Swiper {
         self.young.bump_alloc(size)
     }
 
+    fn alloc_in_old(&self, size: usize, array_ref: bool) -> Address {
+        let mut protected = self.old.protected();
+        let ptr = protected.alloc(&self.config, size);
+
+        if ptr.is_non_null() {
+            // objects allocated by other threads might start in the same card,
+            // so update crossing map while holding the lock
+            self.old.update_crossing(ptr, ptr.offset(size), array_ref);
+        }
+
+        ptr
+    }
+
     fn alloc_large(&self, vm: &VM, size: usize, _: bool) -> Address {
         let ptr = self.large.alloc(size);
 
@@ -512,6 +640,29 @@ impl Collector for Swiper {
         }
     }
 
+    fn alloc_old(&self, vm: &VM, size: usize, array_ref: bool) -> Address {
+        if size >= LARGE_OBJECT_SIZE {
+            return self.alloc_large(vm, size, array_ref);
+        }
+
+        let ptr = self.alloc_in_old(size, array_ref);
+
+        if ptr.is_non_null() {
+            return ptr;
+        }
+
+        self.perform_collection(vm, CollectionKind::Full, GcReason::AllocationFailure);
+
+        let ptr = self.alloc_in_old(size, array_ref);
+
+        if ptr.is_non_null() {
+            return ptr;
+        }
+
+        // old generation is still full, try young generation instead
+        self.alloc_normal(vm, size, array_ref)
+    }
+
     fn collect(&self, vm: &VM, reason: GcReason) {
         self.perform_collection(vm, CollectionKind::Full, reason);
     }
@@ -528,6 +679,39 @@ impl Collector for Swiper {
         self.card_table_offset
     }
 
+    fn needs_pre_barrier(&self) -> bool {
+        self.marker.is_some()
+    }
+
+    fn pre_barrier(&self, reference: Address) {
+        if let Some(ref marker) = self.marker {
+            marker.record(reference);
+        }
+    }
+
+    fn wait_for_marking(&self, vm: &VM) {
+        if let Some(ref marker) = self.marker {
+            // parked while waiting, such that collections can still happen
+            THREAD.with(|thread| thread.borrow().park(vm));
+            marker.wait();
+            THREAD.with(|thread| thread.borrow().unpark(vm));
+        }
+    }
+
+    fn attach_thread(&self, thread: &DoraThread) {
+        if let Some(ref marker) = self.marker {
+            marker.attach_thread(thread);
+        }
+    }
+
+    fn shutdown(&self) {
+        if let Some(ref marker) = self.marker {
+            marker.shutdown();
+        }
+
+        controller::shutdown(&self.config);
+    }
+
     fn dump_summary(&self, runtime: f32) {
         let config = self.config.lock();
         let total_gc = config.total_minor_pause + config.total_full_pause;
@@ -568,9 +752,44 @@ impl Collector for Swiper {
         );
         println!("GC stats: minor-total={}", config.minor_total_all());
         println!("GC stats: minor-roots={}", config.minor_roots_all());
+        println!("GC stats: minor-cards={}", config.minor_cards_all());
         println!("GC stats: minor-tracing={}", config.minor_tracing_all());
         println!("");
 
+        if let Some(max_pause) = config.pause_goal {
+            println!("GC stats: pause-goal={:.2}ms", max_pause);
+            println!("GC stats: pause-goal-misses={}", config.pause_goal_misses);
+            println!("GC stats: avg-minor-pause={:.2}ms", config.avg_minor_pause);
+            println!("GC stats: avg-full-pause={:.2}ms", config.avg_full_pause);
+            println!("");
+        }
+
+        let uncommit_pending = self.uncommit.pending_size();
+        let committed =
+            controller::memory_size(&self.young, &self.old, &self.large) + uncommit_pending;
+        println!("GC stats: committed={}", formatted_size(committed));
+        println!(
+            "GC stats: committed-max={}",
+            formatted_size(config.max_memory_size)
+        );
+        println!(
+            "GC stats: used={}",
+            formatted_size(controller::object_size(
+                &self.young,
+                &self.old,
+                &self.large
+            ))
+        );
+        println!(
+            "GC stats: uncommit-pending={}",
+            formatted_size(uncommit_pending)
+        );
+        println!(
+            "GC stats: uncommitted={}",
+            formatted_size(self.uncommit.uncommitted_size())
+        );
+        println!("");
+
         println!(
             "GC summary: {:.1}ms minor ({}), {:.1}ms full ({}), {:.1}ms collection, {:.1}ms mutator, {:.1}ms total ({}% mutator, {}% GC)",
             config.total_minor_pause,
@@ -586,6 +805,7 @@ impl Collector for Swiper {
 
         println!("\nMinor:");
         println!("\tRoots:\t\t{}", config.minor_roots());
+        println!("\tCards:\t\t{}", config.minor_cards());
         println!("\tTracing:\t{}", config.minor_tracing());
         println!("\tTotal:\t\t{}", config.minor_total());
 
@@ -601,20 +821,21 @@ impl Collector for Swiper {
         println!("");
     }
 
-    fn verify_ref(&self, vm: &VM, reference: Address) {
-        let found = self.young.eden_active().contains(reference)
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.young.eden_active().contains(reference)
             || self.young.to_active().contains(reference)
-            || vm.gc.perm_space.contains(reference)
             || self.large.contains(reference)
-            || (self.old.total().contains(reference) && self.old.contains_slow(reference));
-
-        assert!(found, "write barrier found invalid reference");
+            || (self.old.total().contains(reference) && self.old.contains_slow(reference))
     }
 }
 
 impl Drop for Swiper {
     fn drop(&mut self) {
-        os::free(
+        if let Some(ref marker) = self.marker {
+            marker.shutdown();
+        }
+
+        compressed::free(
             self.unaligned_reserved.start,
             self.unaligned_reserved.size(),
         );
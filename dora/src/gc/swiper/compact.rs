@@ -5,6 +5,7 @@ use crate::gc::marking;
 use crate::gc::root::Slot;
 use crate::gc::space::Space;
 use crate::gc::swiper::card::CardTable;
+use crate::gc::swiper::concurrent::Remark;
 use crate::gc::swiper::controller::FullCollectorPhases;
 use crate::gc::swiper::crossing::CrossingMap;
 use crate::gc::swiper::large::LargeSpace;
@@ -35,6 +36,7 @@ pub struct FullCollector<'a, 'ast: 'a> {
     init_old_top: Vec<Address>,
 
     reason: GcReason,
+    remark: Option<Remark>,
 
     min_heap_size: usize,
     max_heap_size: usize,
@@ -54,6 +56,7 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
         perm_space: &'a Space,
         rootset: &'a [Slot],
         reason: GcReason,
+        remark: Option<Remark>,
         min_heap_size: usize,
         max_heap_size: usize,
     ) -> FullCollector<'a, 'ast> {
@@ -77,6 +80,7 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
             init_old_top: Vec::new(),
 
             reason,
+            remark,
 
             min_heap_size,
             max_heap_size,
@@ -91,7 +95,7 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
 
     pub fn collect(&mut self) {
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
-        let stats = self.vm.args.flag_gc_stats;
+        let stats = self.vm.args.gc_phase_timing();
         self.init_old_top = self.old_protected.regions.iter().map(|r| r.top()).collect();
 
         let mut timer = Timer::new(stats);
@@ -179,7 +183,20 @@ impl<'a, 'ast> FullCollector<'a, 'ast> {
     }
 
     fn mark_live(&mut self) {
-        marking::start(self.rootset, self.heap, self.perm_space.total());
+        if let Some(remark) = self.remark.take() {
+            // old generation was already marked concurrently
+            let rescanned = remark.finish(
+                self.rootset,
+                self.young,
+                &*self.old_protected,
+                self.large_space,
+                self.card_table,
+                self.crossing_map,
+            );
+            self.phases.rescanned = Some(rescanned);
+        } else {
+            marking::start(self.rootset, self.heap, self.perm_space.total());
+        }
     }
 
     fn compute_forward(&mut self) {
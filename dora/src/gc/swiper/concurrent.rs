@@ -0,0 +1,501 @@
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use std::cmp;
+use std::mem;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::gc::compressed;
+use crate::gc::root::Slot;
+use crate::gc::swiper::card::CardTable;
+use crate::gc::swiper::crossing::{self, CrossingMap};
+use crate::gc::swiper::large::{LargeAlloc, LargeSpace};
+use crate::gc::swiper::old::OldGenProtected;
+use crate::gc::swiper::young::YoungGen;
+use crate::gc::swiper::{walk_region, CARD_SIZE};
+use crate::gc::{Address, Region};
+use crate::threads::DoraThread;
+
+// number of objects traced before the marker gives collections a
+// chance to stop it.
+const MARKING_BATCH: usize = 512;
+
+// Marks the old generation and large space concurrently to the mutator.
+//
+// Marking is snapshot-at-the-beginning: the initial mark happens at the end
+// of a minor collection and marks all old objects referenced from the roots
+// or the young generation. While marking, compiled code records every
+// reference it overwrites (see the pre-write barrier), so every object
+// reachable at the initial mark gets marked eventually. Once the marker is
+// done, the next minor collection drains the recorded references and turns
+// the barrier off.
+//
+// Objects promoted or allocated after the initial mark stay unmarked. The
+// initial mark remembers the top of each old region and the first large
+// object, so that the remark in the pause of the next full collection only
+// needs to rescan these objects and the dirty cards.
+pub struct ConcurrentMarker {
+    state: Mutex<MarkerState>,
+    cond: Condvar,
+
+    // references overwritten by the mutator while marking
+    satb: Mutex<Vec<Address>>,
+
+    // old generation and large space, the only objects marked concurrently
+    old_and_large: Region,
+
+    // whole heap, objects rescanned in the remark may reference all spaces
+    heap: Region,
+
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ConcurrentMarker {
+    pub fn new(heap: Region, old_and_large: Region) -> Arc<ConcurrentMarker> {
+        let marker = Arc::new(ConcurrentMarker {
+            state: Mutex::new(MarkerState {
+                phase: MarkingPhase::Idle,
+                stack: Vec::new(),
+                old_tops: Vec::new(),
+                large_head: Address::null(),
+            }),
+            cond: Condvar::new(),
+            satb: Mutex::new(Vec::new()),
+            old_and_large,
+            heap,
+            thread: Mutex::new(None),
+        });
+
+        let thread_marker = marker.clone();
+        let thread = thread::spawn(move || thread_marker.run());
+        *marker.thread.lock() = Some(thread);
+
+        marker
+    }
+
+    // Stops the marker thread until the returned pause is dropped, needs
+    // to be held during collections.
+    pub fn pause(&self) -> MarkerPause {
+        MarkerPause {
+            marker: self,
+            state: self.state.lock(),
+        }
+    }
+
+    // Threads attached while marking need the barrier as well, otherwise
+    // they could hide objects reachable at the initial mark. Takes the
+    // lock of the initial mark and of turning the barrier off.
+    pub fn attach_thread(&self, thread: &DoraThread) {
+        let state = self.state.lock();
+
+        let barrier = match state.phase {
+            MarkingPhase::Marking | MarkingPhase::Done => true,
+            MarkingPhase::Idle | MarkingPhase::Complete | MarkingPhase::Shutdown => false,
+        };
+
+        thread.tld.set_concurrent_marking(barrier);
+    }
+
+    pub fn record(&self, reference: Address) {
+        self.satb.lock().push(reference);
+    }
+
+    // blocks until the marker thread finished tracing or marking was
+    // stopped by a full collection.
+    pub fn wait(&self) {
+        let mut state = self.state.lock();
+
+        while state.phase == MarkingPhase::Marking {
+            self.cond.wait(&mut state);
+        }
+    }
+
+    pub fn shutdown(&self) {
+        {
+            let mut state = self.state.lock();
+            state.phase = MarkingPhase::Shutdown;
+            self.cond.notify_all();
+        }
+
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().expect("marker thread failed");
+        }
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock();
+
+        loop {
+            match state.phase {
+                MarkingPhase::Shutdown => break,
+                MarkingPhase::Idle | MarkingPhase::Done | MarkingPhase::Complete => {
+                    self.cond.wait(&mut state)
+                }
+                MarkingPhase::Marking => {
+                    if !self.trace(&mut state.stack, MARKING_BATCH) {
+                        let satb = mem::replace(&mut *self.satb.lock(), Vec::new());
+
+                        if satb.is_empty() {
+                            state.phase = MarkingPhase::Done;
+                            self.cond.notify_all();
+                        }
+
+                        for reference in satb {
+                            mark_gray(&mut state.stack, reference, self.old_and_large);
+                        }
+                    }
+
+                    // give a waiting collection the chance to run
+                    MutexGuard::bump(&mut state);
+                }
+            }
+        }
+    }
+
+    // traces at most `limit` objects, returns true if there is work left
+    fn trace(&self, stack: &mut Vec<Address>, limit: usize) -> bool {
+        for _ in 0..limit {
+            let object_addr = match stack.pop() {
+                Some(object_addr) => object_addr,
+                None => return false,
+            };
+
+            let object = object_addr.to_mut_obj();
+            let old_and_large = self.old_and_large;
+
+            object.visit_reference_fields(|field| {
+                mark_gray(stack, field.get(), old_and_large);
+            });
+        }
+
+        !stack.is_empty()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum MarkingPhase {
+    Idle,
+    Marking,
+    // marker thread finished, the barrier still records references
+    Done,
+    // recorded references were drained, the barrier is off
+    Complete,
+    Shutdown,
+}
+
+struct MarkerState {
+    phase: MarkingPhase,
+    stack: Vec<Address>,
+
+    // top of each old region and first large object at the initial mark
+    old_tops: Vec<Address>,
+    large_head: Address,
+}
+
+pub struct MarkerPause<'a> {
+    marker: &'a ConcurrentMarker,
+    state: MutexGuard<'a, MarkerState>,
+}
+
+impl<'a> MarkerPause<'a> {
+    pub fn is_marking(&self) -> bool {
+        match self.state.phase {
+            MarkingPhase::Marking | MarkingPhase::Done | MarkingPhase::Complete => true,
+            MarkingPhase::Idle | MarkingPhase::Shutdown => false,
+        }
+    }
+
+    // initial mark: marks old objects referenced from roots and the young
+    // generation and starts marking concurrently.
+    pub fn start(
+        &mut self,
+        threads: &[Arc<DoraThread>],
+        rootset: &[Slot],
+        young: &YoungGen,
+        old_protected: &OldGenProtected,
+        large: &LargeSpace,
+    ) {
+        assert!(self.state.phase == MarkingPhase::Idle);
+        let old_and_large = self.marker.old_and_large;
+        let stack = &mut self.state.stack;
+
+        for root in rootset {
+            mark_gray(stack, root.get(), old_and_large);
+        }
+
+        for region in &[young.eden_active(), young.from_active(), young.to_active()] {
+            walk_region(*region, |object, _address, _size| {
+                object.visit_reference_fields(|field| {
+                    mark_gray(stack, field.get(), old_and_large);
+                });
+            });
+        }
+
+        self.state.old_tops = old_protected.regions.iter().map(|r| r.top()).collect();
+
+        let large_head = large.head();
+
+        self.state.large_head = if large_head.is_null() {
+            Address::null()
+        } else {
+            LargeAlloc::from_address(large_head).object_address()
+        };
+
+        for thread in threads {
+            thread.tld.set_concurrent_marking(true);
+        }
+
+        self.state.phase = MarkingPhase::Marking;
+        self.marker.cond.notify_all();
+    }
+
+    // drains the references recorded since the marker thread finished and
+    // turns the barrier off. Every object reachable at the initial mark is
+    // marked afterwards, overwriting references doesn't matter anymore.
+    pub fn complete(&mut self, threads: &[Arc<DoraThread>]) {
+        if self.state.phase != MarkingPhase::Done {
+            return;
+        }
+
+        let old_and_large = self.marker.old_and_large;
+        let satb = mem::replace(&mut *self.marker.satb.lock(), Vec::new());
+        let stack = &mut self.state.stack;
+
+        for reference in satb {
+            mark_gray(stack, reference, old_and_large);
+        }
+
+        trace(stack, old_and_large);
+
+        for thread in threads {
+            thread.tld.set_concurrent_marking(false);
+        }
+
+        self.state.phase = MarkingPhase::Complete;
+        self.marker.cond.notify_all();
+    }
+
+    // ends concurrent marking, the marking needs to be finished with
+    // `Remark::finish`.
+    pub fn stop(&mut self, threads: &[Arc<DoraThread>]) -> Option<Remark> {
+        if !self.is_marking() {
+            return None;
+        }
+
+        for thread in threads {
+            thread.tld.set_concurrent_marking(false);
+        }
+
+        self.state.phase = MarkingPhase::Idle;
+        self.marker.cond.notify_all();
+        let stack = mem::replace(&mut self.state.stack, Vec::new());
+        let satb = mem::replace(&mut *self.marker.satb.lock(), Vec::new());
+        let old_tops = mem::replace(&mut self.state.old_tops, Vec::new());
+        let large_head = mem::replace(&mut self.state.large_head, Address::null());
+
+        Some(Remark {
+            stack,
+            satb,
+            old_and_large: self.marker.old_and_large,
+            heap: self.marker.heap,
+            old_tops,
+            large_head,
+        })
+    }
+}
+
+pub struct Remark {
+    stack: Vec<Address>,
+    satb: Vec<Address>,
+    old_and_large: Region,
+    heap: Region,
+    old_tops: Vec<Address>,
+    large_head: Address,
+}
+
+impl Remark {
+    // Finishes marking in the pause: all objects in old generation and large
+    // space reachable at the initial mark are marked after draining the
+    // remaining work. Objects promoted or allocated since the initial mark
+    // are marked and scanned, references from older objects into the young
+    // generation are found through the card table. Returns the number of
+    // bytes of promoted or allocated objects that were rescanned.
+    pub fn finish(
+        self,
+        rootset: &[Slot],
+        young: &YoungGen,
+        old_protected: &OldGenProtected,
+        large: &LargeSpace,
+        card_table: &CardTable,
+        crossing_map: &CrossingMap,
+    ) -> usize {
+        let old_and_large = self.old_and_large;
+        let heap = self.heap;
+        let young = young.total();
+        let mut stack = self.stack;
+        let mut rescanned = 0;
+
+        for reference in self.satb {
+            mark_gray(&mut stack, reference, old_and_large);
+        }
+
+        trace(&mut stack, old_and_large);
+
+        for root in rootset {
+            mark_gray(&mut stack, root.get(), heap);
+        }
+
+        for (idx, region) in old_protected.regions.iter().enumerate() {
+            let mark_top = self
+                .old_tops
+                .get(idx)
+                .cloned()
+                .unwrap_or_else(|| region.start());
+
+            let marked = Region::new(region.start(), mark_top);
+            visit_dirty_cards_in_old(marked, card_table, crossing_map, |field| {
+                mark_gray(&mut stack, field.get(), young);
+            });
+
+            let promoted = Region::new(mark_top, region.top());
+            walk_region(promoted, |object, _address, size| {
+                object.header_mut().try_mark_non_atomic();
+                object.visit_reference_fields(|field| {
+                    mark_gray(&mut stack, field.get(), heap);
+                });
+                rescanned += size;
+            });
+        }
+
+        // large objects are prepended, everything before the head at the
+        // initial mark was allocated since
+        let large_head = self.large_head;
+        let mut allocated = true;
+
+        large.visit_objects(|object_addr| {
+            let object = object_addr.to_mut_obj();
+
+            if object_addr == large_head {
+                allocated = false;
+            }
+
+            if allocated {
+                object.header_mut().try_mark_non_atomic();
+                object.visit_reference_fields(|field| {
+                    mark_gray(&mut stack, field.get(), heap);
+                });
+                rescanned += object.size();
+            } else if object.header().is_marked_non_atomic() {
+                visit_dirty_cards_in_large(object_addr, card_table, |field| {
+                    mark_gray(&mut stack, field.get(), young);
+                });
+            }
+        });
+
+        trace(&mut stack, heap);
+
+        rescanned
+    }
+}
+
+// visits references in dirty cards of the old region, like the minor
+// collection these include all references into the young generation.
+fn visit_dirty_cards_in_old<F>(
+    region: Region,
+    card_table: &CardTable,
+    crossing_map: &CrossingMap,
+    mut f: F,
+) where
+    F: FnMut(Slot),
+{
+    card_table.visit_dirty_in_old(region.start, region.end, |card_idx| {
+        let card_start = card_table.to_address(card_idx);
+        let card_end = card_start.offset(CARD_SIZE);
+
+        let (refs, first_object) =
+            crossing::dirty_card_start(card_table, crossing_map, card_idx, region);
+        let ref_size = compressed::ref_size_usize();
+        let mut ptr = refs.start;
+
+        while ptr < refs.end {
+            f(Slot::field(ptr));
+            ptr = ptr.offset(ref_size);
+        }
+
+        let mut ptr = cmp::max(first_object, region.start);
+        let end = cmp::min(card_end, region.end);
+
+        while ptr < end {
+            let object = ptr.to_mut_obj();
+
+            if object.header().vtblptr().is_null() {
+                ptr = ptr.add_ptr(1);
+                continue;
+            }
+
+            if object.header().is_marked_non_atomic() {
+                object.visit_reference_fields_within(Region::new(ptr, end), |field| f(field));
+            }
+
+            ptr = ptr.offset(object.size());
+        }
+    });
+}
+
+// visits references in dirty cards of the large object, the card of the
+// object start is used for objects without reference arrays.
+fn visit_dirty_cards_in_large<F>(object_start: Address, card_table: &CardTable, mut f: F)
+where
+    F: FnMut(Slot),
+{
+    let object = object_start.to_mut_obj();
+
+    if !object.is_array_ref() {
+        if card_table.get(card_table.card_idx(object_start)).is_dirty() {
+            object.visit_reference_fields(f);
+        }
+
+        return;
+    }
+
+    let object_end = object_start.offset(object.size());
+    let (start_card_idx, end_card_idx) = card_table.card_indices(object_start, object_end);
+
+    for card_idx in start_card_idx..end_card_idx {
+        let card_idx = card_idx.into();
+
+        if card_table.get(card_idx).is_clean() {
+            continue;
+        }
+
+        let card_start = card_table.to_address(card_idx);
+        let card_end = card_start.offset(CARD_SIZE);
+        let range = Region::new(
+            cmp::max(object_start, card_start),
+            cmp::min(object_end, card_end),
+        );
+
+        object.visit_reference_fields_within(range, |field| f(field));
+    }
+}
+
+fn trace(stack: &mut Vec<Address>, region: Region) {
+    while let Some(object_addr) = stack.pop() {
+        let object = object_addr.to_mut_obj();
+
+        object.visit_reference_fields(|field| {
+            mark_gray(stack, field.get(), region);
+        });
+    }
+}
+
+fn mark_gray(stack: &mut Vec<Address>, object_addr: Address, region: Region) {
+    if !region.contains(object_addr) {
+        return;
+    }
+
+    let object = object_addr.to_mut_obj();
+
+    if object.header().try_mark_non_atomic() {
+        stack.push(object_addr);
+    }
+}
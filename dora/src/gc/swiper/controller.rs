@@ -5,10 +5,12 @@ use std::fmt;
 use std::sync::Arc;
 
 use crate::driver::cmd::Args;
+use crate::gc::log::{self, GcLog};
 use crate::gc::swiper::large::LargeSpace;
 use crate::gc::swiper::young::YoungGen;
 use crate::gc::swiper::{CollectionKind, CommonOldGen};
 use crate::gc::{align_gen, align_gen_down, formatted_size, AllNumbers, GcReason, GEN_SIZE, M};
+use crate::json::Json;
 use crate::mem;
 use crate::stdlib;
 use crate::timer;
@@ -18,6 +20,19 @@ const INIT_HEAP_SIZE_RATIO: usize = 2;
 const INIT_YOUNG_RATIO: usize = 4;
 const INIT_SEMI_RATIO: usize = 3;
 
+// with --gc-max-pause the young generation is never smaller than this,
+// otherwise all collections would be full collections
+const MIN_PAUSE_YOUNG_SIZE: usize = 2 * M;
+const MIN_SEMI_RATIO: usize = 2;
+const MAX_SEMI_RATIO: usize = 8;
+
+// weight of the latest pause in the average pause time
+const PAUSE_WEIGHT: f32 = 0.3;
+
+// concurrent marking starts after a minor collection when old generation
+// and large space are filled to this ratio of the old limit
+const CONCURRENT_MARKING_RATIO: f64 = 0.5;
+
 pub fn init(config: &mut HeapConfig, args: &Args) {
     assert!(config.min_heap_size <= config.max_heap_size);
 
@@ -31,7 +46,7 @@ pub fn init(config: &mut HeapConfig, args: &Args) {
     };
 
     let young_size = max(young_size, GEN_SIZE);
-    let (eden_size, semi_size) = calculate_young_size(args, young_size, 0);
+    let (eden_size, semi_size) = calculate_young_size(args, config, young_size, 0);
 
     config.eden_size = eden_size;
     config.semi_size = semi_size;
@@ -49,10 +64,21 @@ pub fn init(config: &mut HeapConfig, args: &Args) {
 
     config.old_size = 0;
     config.old_limit = old_limit;
+    config.pause_goal = args.gc_max_pause();
+    config.log = args
+        .flag_gc_log
+        .as_ref()
+        .and_then(|path| GcLog::create(path));
+    config.pause_young_size = max(young_size, MIN_PAUSE_YOUNG_SIZE);
 }
 
-fn calculate_young_size(args: &Args, young_size: usize, min_semi_size: usize) -> (usize, usize) {
-    let semi_ratio = args.flag_gc_semi_ratio.unwrap_or(INIT_SEMI_RATIO);
+fn calculate_young_size(
+    args: &Args,
+    config: &HeapConfig,
+    young_size: usize,
+    min_semi_size: usize,
+) -> (usize, usize) {
+    let semi_ratio = args.flag_gc_semi_ratio.unwrap_or(config.semi_ratio);
     let semi_size = if semi_ratio == 0 {
         0
     } else {
@@ -81,6 +107,17 @@ pub fn choose_collection_kind(
     };
 }
 
+pub fn should_start_marking(
+    config: &SharedHeapConfig,
+    old: &dyn CommonOldGen,
+    large: &LargeSpace,
+) -> bool {
+    let config = config.lock();
+    let old_size = old.active_size() + large.committed_size();
+
+    old_size as f64 >= config.old_limit as f64 * CONCURRENT_MARKING_RATIO
+}
+
 pub fn start(
     config: &SharedHeapConfig,
     young: &YoungGen,
@@ -92,6 +129,21 @@ pub fn start(
     config.gc_start = timer::timestamp();
     config.start_object_size = object_size(young, old, large);
     config.start_memory_size = memory_size(young, old, large);
+    config.start_sizes = SpaceSizes::measure(young, old, large);
+}
+
+// writes the last record into the GC log, called once all mutator
+// threads have finished
+pub fn shutdown(config: &SharedHeapConfig) {
+    let mut config = config.lock();
+
+    if let Some(ref mut log) = config.log {
+        let time = log.time(timer::timestamp());
+        log.write(Json::object(vec![
+            ("kind", Json::from("end")),
+            ("time", log::ms(time)),
+        ]));
+    }
 }
 
 pub fn stop(
@@ -120,6 +172,13 @@ pub fn stop(
         std::usize::MAX
     };
 
+    let max_young_size = if let Some(max_pause) = args.gc_max_pause() {
+        adapt_to_pause_goal(&mut *config, kind, max_pause, young);
+        min(max_young_size, config.pause_young_size)
+    } else {
+        max_young_size
+    };
+
     let rest = config.max_heap_size - config.old_size;
     let target_young_size = align_gen_down(rest / 2);
     let target_young_size = min(target_young_size, max_young_size);
@@ -128,7 +187,8 @@ pub fn stop(
     let to_size = young.to_active().size();
     let min_semi_size = align_gen(mem::page_align(to_size) * 2);
 
-    let (eden_size, semi_size) = calculate_young_size(args, target_young_size, min_semi_size);
+    let (eden_size, semi_size) =
+        calculate_young_size(args, &*config, target_young_size, min_semi_size);
     let young_size = eden_size + semi_size;
 
     if old_size + young_size > config.max_heap_size {
@@ -141,6 +201,10 @@ pub fn stop(
 
     config.end_object_size = object_size(young, old, large);
     config.end_memory_size = memory_size(young, old, large);
+    config.max_memory_size = max(
+        config.max_memory_size,
+        max(config.start_memory_size, config.end_memory_size),
+    );
 
     assert!(young_size + config.old_limit <= config.max_heap_size);
 
@@ -149,7 +213,7 @@ pub fn stop(
             config.total_minor_collections += 1;
             config.total_minor_pause += config.gc_duration;
 
-            if args.flag_gc_stats {
+            if args.gc_phase_timing() {
                 config.minor_phases.last_mut().unwrap().total = config.gc_duration;
             }
         }
@@ -158,17 +222,183 @@ pub fn stop(
             config.total_full_collections += 1;
             config.total_full_pause += config.gc_duration;
 
-            if args.flag_gc_stats {
+            if args.gc_phase_timing() {
                 config.full_phases.last_mut().unwrap().total = config.gc_duration;
             }
         }
     }
 
+    if config.log.is_some() {
+        let end_sizes = SpaceSizes::measure(young, old, large);
+        write_log(&mut *config, kind, reason, args, end_sizes);
+
+        // phases are only needed for the summary of --gc-stats
+        if !args.flag_gc_stats {
+            config.minor_phases.clear();
+            config.full_phases.clear();
+        }
+    }
+
     if args.flag_gc_verbose {
         print(&*config, kind, reason);
+
+        if let Some(max_pause) = args.gc_max_pause() {
+            println!(
+                "GC: pause goal {:.2} ms (avg minor {:.2} ms, avg full {:.2} ms): young {}, semi {}, tenuring threshold {}",
+                max_pause,
+                config.avg_minor_pause,
+                config.avg_full_pause,
+                formatted_size(eden_size + semi_size),
+                formatted_size(semi_size),
+                young.tenuring_threshold(),
+            );
+        }
+    }
+}
+
+// Adapts young generation size, semi-space ratio and tenuring threshold
+// such that pauses stay below the given goal. The pause time of a minor
+// collection mostly depends on the amount of surviving objects, so a
+// smaller young generation reduces it. When the young generation can't get any
+// smaller, survivors are promoted right away to avoid copying them twice.
+// The young generation only tracks whether an object survived a collection
+// (see the age marker of the semi space), so the tenuring threshold is either
+// 0 or 1 and the young generation size is the main parameter adapted here.
+fn adapt_to_pause_goal(config: &mut HeapConfig, kind: CollectionKind, goal: f32, young: &YoungGen) {
+    let pause = config.gc_duration;
+
+    if pause > goal {
+        config.pause_goal_misses += 1;
+    }
+
+    match kind {
+        CollectionKind::Minor => {
+            config.avg_minor_pause = average_pause(config.avg_minor_pause, pause);
+            let avg_pause = config.avg_minor_pause;
+
+            if avg_pause > goal {
+                if config.pause_young_size > MIN_PAUSE_YOUNG_SIZE {
+                    let factor = f32::max(goal / avg_pause, 0.5);
+                    let young_size = (config.pause_young_size as f32 * factor) as usize;
+                    config.pause_young_size = max(align_gen(young_size), MIN_PAUSE_YOUNG_SIZE);
+                } else {
+                    config.tenuring_threshold = 0;
+                }
+            } else if avg_pause < goal * 0.5 {
+                if config.tenuring_threshold == 0 {
+                    config.tenuring_threshold = 1;
+                } else {
+                    let young_size = (config.pause_young_size as f32 * 1.2) as usize;
+                    config.pause_young_size = min(align_gen(young_size), config.max_heap_size / 2);
+                }
+            }
+
+            // one half of the semi-space should be large enough for twice
+            // the survivors of this collection
+            let (eden_size, semi_size) = young.committed_size();
+            let young_size = eden_size + semi_size;
+            let survivors = max(config.minor_copied, 1);
+
+            config.semi_ratio = if config.tenuring_threshold == 0 {
+                MAX_SEMI_RATIO
+            } else {
+                let semi_ratio = young_size / (4 * survivors);
+                max(min(semi_ratio, MAX_SEMI_RATIO), MIN_SEMI_RATIO)
+            };
+        }
+
+        CollectionKind::Full => {
+            config.avg_full_pause = average_pause(config.avg_full_pause, pause);
+
+            // promoting all survivors fills the old generation faster
+            // and causes more full collections
+            if config.avg_full_pause > goal {
+                config.tenuring_threshold = 1;
+            }
+        }
+    }
+
+    young.set_tenuring_threshold(config.tenuring_threshold);
+}
+
+fn average_pause(avg: f32, pause: f32) -> f32 {
+    if avg == 0f32 {
+        pause
+    } else {
+        avg * (1f32 - PAUSE_WEIGHT) + pause * PAUSE_WEIGHT
     }
 }
 
+fn write_log(
+    config: &mut HeapConfig,
+    kind: CollectionKind,
+    reason: GcReason,
+    args: &Args,
+    end_sizes: SpaceSizes,
+) {
+    let (kind_name, phases, workers, promoted) = match kind {
+        CollectionKind::Minor => {
+            let phases = config.minor_phases.last().unwrap();
+            let phases = Json::object(vec![
+                ("roots", log::ms(phases.roots)),
+                ("cards", log::ms(phases.cards)),
+                ("tracing", log::ms(phases.tracing)),
+            ]);
+            let workers = if args.parallel_minor() {
+                args.gc_workers()
+            } else {
+                1
+            };
+
+            ("minor", phases, workers, config.minor_promoted)
+        }
+
+        CollectionKind::Full => {
+            let phases = config.full_phases.last().unwrap();
+            let mut fields = vec![
+                ("marking", log::ms(phases.marking)),
+                ("compute_forward", log::ms(phases.compute_forward)),
+                ("update_refs", log::ms(phases.update_refs)),
+                ("relocate", log::ms(phases.relocate)),
+                ("reset_cards", log::ms(phases.reset_cards)),
+            ];
+
+            if let Some(rescanned) = phases.rescanned {
+                fields.push(("rescanned", Json::from(rescanned)));
+            }
+
+            let phases = Json::object(fields);
+            let workers = if args.parallel_full() {
+                args.gc_workers()
+            } else {
+                1
+            };
+
+            ("full", phases, workers, 0)
+        }
+    };
+
+    let collection = config.total_minor_collections + config.total_full_collections;
+    let record = {
+        let log = config.log.as_ref().unwrap();
+
+        Json::object(vec![
+            ("gc", Json::from(collection)),
+            ("time", log::ms(log.time(config.gc_start))),
+            ("kind", Json::from(kind_name)),
+            ("reason", Json::from(reason.to_string())),
+            ("pause", log::ms(config.gc_duration)),
+            ("phases", phases),
+            ("before", config.start_sizes.json()),
+            ("after", end_sizes.json()),
+            ("promoted", Json::from(promoted)),
+            ("workers", Json::from(workers)),
+        ])
+    };
+
+    config.log.as_mut().unwrap().write(record);
+}
+
 fn print(config: &HeapConfig, kind: CollectionKind, reason: GcReason) {
     match kind {
         CollectionKind::Minor => {
@@ -202,17 +432,46 @@ fn print(config: &HeapConfig, kind: CollectionKind, reason: GcReason) {
     }
 }
 
-fn object_size(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> usize {
+pub fn object_size(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> usize {
     young.active_size() + old.active_size() + large.committed_size()
 }
 
-fn memory_size(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> usize {
+pub fn memory_size(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> usize {
     let (eden_size, semi_size) = young.committed_size();
     let young_size = eden_size + semi_size;
 
     young_size + old.committed_size() + large.committed_size()
 }
 
+// bytes used in each space, for the GC log
+#[derive(Clone, Copy, Default)]
+struct SpaceSizes {
+    eden: usize,
+    semi: usize,
+    old: usize,
+    large: usize,
+}
+
+impl SpaceSizes {
+    fn measure(young: &YoungGen, old: &dyn CommonOldGen, large: &LargeSpace) -> SpaceSizes {
+        SpaceSizes {
+            eden: young.eden_active().size(),
+            semi: young.from_active().size() + young.to_active().size(),
+            old: old.active_size(),
+            large: large.committed_size(),
+        }
+    }
+
+    fn json(&self) -> Json {
+        Json::object(vec![
+            ("eden", Json::from(self.eden)),
+            ("semi", Json::from(self.semi)),
+            ("old", Json::from(self.old)),
+            ("large", Json::from(self.large)),
+        ])
+    }
+}
+
 pub struct HeapConfig {
     min_heap_size: usize,
     max_heap_size: usize,
@@ -227,8 +486,10 @@ pub struct HeapConfig {
 
     start_object_size: usize,
     start_memory_size: usize,
+    start_sizes: SpaceSizes,
     end_object_size: usize,
     end_memory_size: usize,
+    pub max_memory_size: usize,
 
     pub minor_promoted: usize,
     pub minor_copied: usize,
@@ -239,8 +500,19 @@ pub struct HeapConfig {
     pub total_full_collections: usize,
     pub total_full_pause: f32,
 
+    // state for --gc-max-pause
+    pub pause_goal: Option<f32>,
+    pause_young_size: usize,
+    semi_ratio: usize,
+    tenuring_threshold: usize,
+    pub avg_minor_pause: f32,
+    pub avg_full_pause: f32,
+    pub pause_goal_misses: usize,
+
     full_phases: Vec<FullCollectorPhases>,
     minor_phases: Vec<MinorCollectorPhases>,
+
+    log: Option<GcLog>,
 }
 
 impl HeapConfig {
@@ -261,8 +533,10 @@ impl HeapConfig {
 
             start_object_size: 0,
             start_memory_size: 0,
+            start_sizes: SpaceSizes::default(),
             end_object_size: 0,
             end_memory_size: 0,
+            max_memory_size: 0,
 
             minor_promoted: 0,
             minor_copied: 0,
@@ -273,8 +547,18 @@ impl HeapConfig {
             total_full_collections: 0,
             total_full_pause: 0f32,
 
+            pause_goal: None,
+            pause_young_size: 0,
+            semi_ratio: INIT_SEMI_RATIO,
+            tenuring_threshold: 1,
+            avg_minor_pause: 0f32,
+            avg_full_pause: 0f32,
+            pause_goal_misses: 0,
+
             full_phases: Vec::new(),
             minor_phases: Vec::new(),
+
+            log: None,
         }
     }
 
@@ -349,6 +633,15 @@ impl HeapConfig {
         AllNumbers(self.minor_phases.iter().map(|x| x.roots).collect())
     }
 
+    pub fn minor_cards(&self) -> Numbers {
+        let values: Vec<_> = self.minor_phases.iter().map(|x| x.cards).collect();
+        calculate_numbers(&values)
+    }
+
+    pub fn minor_cards_all(&self) -> AllNumbers {
+        AllNumbers(self.minor_phases.iter().map(|x| x.cards).collect())
+    }
+
     pub fn minor_tracing(&self) -> Numbers {
         let values: Vec<_> = self.minor_phases.iter().map(|x| x.tracing).collect();
         calculate_numbers(&values)
@@ -445,6 +738,9 @@ pub struct FullCollectorPhases {
     pub relocate: f32,
     pub reset_cards: f32,
     pub total: f32,
+
+    // bytes the remark rescanned, None without concurrent marking
+    pub rescanned: Option<usize>,
 }
 
 impl FullCollectorPhases {
@@ -456,6 +752,7 @@ impl FullCollectorPhases {
             relocate: 0f32,
             reset_cards: 0f32,
             total: 0f32,
+            rescanned: None,
         }
     }
 }
@@ -463,6 +760,7 @@ impl FullCollectorPhases {
 #[derive(Clone)]
 pub struct MinorCollectorPhases {
     pub roots: f32,
+    pub cards: f32,
     pub tracing: f32,
     pub total: f32,
 }
@@ -471,8 +769,121 @@ impl MinorCollectorPhases {
     pub fn new() -> MinorCollectorPhases {
         MinorCollectorPhases {
             roots: 0f32,
+            cards: 0f32,
             tracing: 0f32,
             total: 0f32,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gc::swiper::uncommit::Uncommitter;
+    use crate::gc::{Region, K};
+    use crate::os;
+
+    fn with_young_gen<F>(f: F)
+    where
+        F: FnOnce(&YoungGen),
+    {
+        let size = 8 * M;
+        let reservation = os::reserve_align(size, GEN_SIZE);
+        let total = Region::new(reservation.start, reservation.start.offset(size));
+        let uncommit = Arc::new(Uncommitter::new(0));
+        let young = YoungGen::new(total, 3 * M, M, false, uncommit);
+
+        f(&young);
+
+        os::free(reservation.start, size);
+    }
+
+    fn minor(config: &mut HeapConfig, young: &YoungGen, pause: f32) {
+        config.gc_duration = pause;
+        adapt_to_pause_goal(config, CollectionKind::Minor, 1.0, young);
+    }
+
+    #[test]
+    fn test_average_pause() {
+        assert_eq!(5.0, average_pause(0.0, 5.0));
+        assert_eq!(7.0, average_pause(10.0, 0.0));
+        assert_eq!(13.0, average_pause(10.0, 20.0));
+    }
+
+    #[test]
+    fn test_pause_goal_shrinks_young_and_stops_tenuring() {
+        with_young_gen(|young| {
+            let mut config = HeapConfig::new(64 * M, 64 * M);
+            config.pause_young_size = 8 * M;
+            config.minor_copied = 16 * K;
+
+            // young generation shrinks by at most half
+            minor(&mut config, young, 4.0);
+            assert_eq!(4 * M, config.pause_young_size);
+            assert_eq!(1, young.tenuring_threshold());
+            assert_eq!(1, config.pause_goal_misses);
+
+            minor(&mut config, young, 4.0);
+            assert_eq!(MIN_PAUSE_YOUNG_SIZE, config.pause_young_size);
+            assert_eq!(1, young.tenuring_threshold());
+
+            // can't get smaller, promote survivors right away
+            minor(&mut config, young, 4.0);
+            assert_eq!(MIN_PAUSE_YOUNG_SIZE, config.pause_young_size);
+            assert_eq!(0, young.tenuring_threshold());
+            assert_eq!(MAX_SEMI_RATIO, config.semi_ratio);
+            assert_eq!(3, config.pause_goal_misses);
+        });
+    }
+
+    #[test]
+    fn test_pause_goal_grows_young_again() {
+        with_young_gen(|young| {
+            let mut config = HeapConfig::new(64 * M, 64 * M);
+            config.pause_young_size = MIN_PAUSE_YOUNG_SIZE;
+            config.minor_copied = 16 * K;
+
+            minor(&mut config, young, 4.0);
+            assert_eq!(0, young.tenuring_threshold());
+
+            // tenuring is enabled first, once the average pause is
+            // below half of the goal
+            while config.tenuring_threshold == 0 {
+                minor(&mut config, young, 0.1);
+                assert_eq!(MIN_PAUSE_YOUNG_SIZE, config.pause_young_size);
+            }
+
+            assert!(config.avg_minor_pause < 0.5);
+            assert_eq!(1, young.tenuring_threshold());
+            assert_eq!(1, config.pause_goal_misses);
+
+            // then the young generation grows by 20% per collection
+            minor(&mut config, young, 0.1);
+            assert_eq!(
+                align_gen(MIN_PAUSE_YOUNG_SIZE * 6 / 5),
+                config.pause_young_size
+            );
+
+            for _ in 0..20 {
+                minor(&mut config, young, 0.1);
+            }
+
+            assert_eq!(32 * M, config.pause_young_size);
+        });
+    }
+
+    #[test]
+    fn test_pause_goal_full_collections_enable_tenuring() {
+        with_young_gen(|young| {
+            let mut config = HeapConfig::new(64 * M, 64 * M);
+            config.pause_young_size = MIN_PAUSE_YOUNG_SIZE;
+
+            minor(&mut config, young, 4.0);
+            assert_eq!(0, young.tenuring_threshold());
+
+            config.gc_duration = 4.0;
+            adapt_to_pause_goal(&mut config, CollectionKind::Full, 1.0, young);
+            assert_eq!(1, young.tenuring_threshold());
+        });
+    }
+}
@@ -1,3 +1,6 @@
+use std::cmp;
+
+use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::CardIdx;
 use crate::gc::swiper::{CARD_REFS, CARD_SIZE, CARD_SIZE_BITS};
 use crate::gc::{Address, Region};
@@ -15,7 +18,7 @@ use crate::object::offset_of_array_data;
 //     no references in this card
 //
 // 64 < v <= 128: [1-64] LeadingRefs
-//     there are (v-64) references before first object
+//     there are (v-64) words of references before first object
 //
 // 128 < v <= 130: [1-2] ArrayStart
 //     object starts (v-128) words before this card
@@ -157,6 +160,47 @@ impl CrossingMap {
     }
 }
 
+// Splits a dirty card of an old region for visiting its references: the
+// references at the start of the card that belong to an object from the
+// card before and the first object to visit in the card. Objects of the
+// first card in the region are visited from the region start.
+pub fn dirty_card_start(
+    card_table: &CardTable,
+    crossing_map: &CrossingMap,
+    card_idx: CardIdx,
+    region: Region,
+) -> (Region, Address) {
+    if card_idx == card_table.card_idx(region.start) {
+        return (Region::new(region.start, region.start), region.start);
+    }
+
+    let card_start = card_table.to_address(card_idx);
+
+    match crossing_map.get(card_idx) {
+        CrossingEntry::NoRefs => panic!("card dirty without any refs"),
+        CrossingEntry::LeadingRefs(refs) => {
+            let first_object = card_start.add_ptr(refs as usize);
+            let ref_start = cmp::max(card_start, region.start);
+            let ref_end = cmp::min(first_object, region.end);
+
+            (Region::new(ref_start, ref_end), first_object)
+        }
+
+        CrossingEntry::FirstObject(offset) => {
+            let first_object = card_start.add_ptr(offset as usize);
+            (Region::new(first_object, first_object), first_object)
+        }
+
+        CrossingEntry::PreviousObjectWords(_) => unimplemented!(),
+        CrossingEntry::PreviousObjectCards(_) => unimplemented!(),
+
+        CrossingEntry::ArrayStart(offset) => {
+            let first_object = card_start.sub_ptr(offset as usize);
+            (Region::new(card_start, card_start), first_object)
+        }
+    }
+}
+
 fn card_idx(address: Address, start: Address) -> usize {
     address.offset_from(start) >> CARD_SIZE_BITS
 }
@@ -1,11 +1,12 @@
 use parking_lot::Mutex;
 use std::mem::size_of;
+use std::sync::Arc;
 
 use crate::gc::swiper::controller::SharedHeapConfig;
+use crate::gc::swiper::uncommit::Uncommitter;
 use crate::gc::swiper::LARGE_OBJECT_SIZE;
 use crate::gc::{Address, Region};
 use crate::mem;
-use crate::os;
 
 pub struct LargeSpace {
     total: Region,
@@ -14,10 +15,15 @@ pub struct LargeSpace {
 }
 
 impl LargeSpace {
-    pub fn new(start: Address, end: Address, config: SharedHeapConfig) -> LargeSpace {
+    pub fn new(
+        start: Address,
+        end: Address,
+        config: SharedHeapConfig,
+        uncommit: Arc<Uncommitter>,
+    ) -> LargeSpace {
         LargeSpace {
             total: Region::new(start, end),
-            space: Mutex::new(LargeSpaceProtected::new(start, end)),
+            space: Mutex::new(LargeSpaceProtected::new(start, end, uncommit)),
             config,
         }
     }
@@ -131,14 +137,16 @@ struct LargeSpaceProtected {
     elements: Vec<Region>,
     head: Address,
     committed_size: usize,
+    uncommit: Arc<Uncommitter>,
 }
 
 impl LargeSpaceProtected {
-    fn new(start: Address, end: Address) -> LargeSpaceProtected {
+    fn new(start: Address, end: Address, uncommit: Arc<Uncommitter>) -> LargeSpaceProtected {
         LargeSpaceProtected {
             elements: vec![Region::new(start, end)],
             head: Address::null(),
             committed_size: 0,
+            uncommit,
         }
     }
 
@@ -161,7 +169,7 @@ impl LargeSpaceProtected {
                     self.elements[i] = Region::new(range.start.offset(size), range.end);
                 }
 
-                os::commit_at(addr, size, false);
+                self.uncommit.commit_at(addr, size);
                 self.append_large_alloc(addr, size);
                 self.committed_size += size;
 
@@ -174,7 +182,7 @@ impl LargeSpaceProtected {
 
     fn free(&mut self, ptr: Address, size: usize) {
         debug_assert!(mem::is_page_aligned(size));
-        os::discard(ptr, size);
+        self.uncommit.discard(ptr, size);
         self.elements.push(ptr.region_start(size));
         self.committed_size -= size;
     }
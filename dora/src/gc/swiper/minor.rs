@@ -1,10 +1,12 @@
 use parking_lot::MutexGuard;
 use std::cmp;
 
+use crate::gc::allocsite;
+use crate::gc::compressed;
 use crate::gc::root::Slot;
 use crate::gc::swiper::card::{CardEntry, CardTable};
 use crate::gc::swiper::controller::{MinorCollectorPhases, SharedHeapConfig};
-use crate::gc::swiper::crossing::{CrossingEntry, CrossingMap};
+use crate::gc::swiper::crossing::{self, CrossingMap};
 use crate::gc::swiper::large::LargeSpace;
 use crate::gc::swiper::old::{OldGen, OldGenProtected};
 use crate::gc::swiper::on_different_cards;
@@ -43,6 +45,9 @@ pub struct MinorCollector<'a, 'ast: 'a> {
 
     config: &'a SharedHeapConfig,
     phases: MinorCollectorPhases,
+
+    // surviving bytes of eden objects per allocation site
+    survived: Vec<usize>,
 }
 
 impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
@@ -86,6 +91,12 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
 
             config,
             phases: MinorCollectorPhases::new(),
+
+            survived: if vm.args.alloc_site_tracking() {
+                vec![0; vm.gc.alloc_sites.len() + 1]
+            } else {
+                Vec::new()
+            },
         }
     }
 
@@ -97,7 +108,8 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
         self.init_old_top = self.old_protected.regions.iter().map(|r| r.top()).collect();
 
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
-        let mut timer = Timer::new(self.vm.args.flag_gc_stats);
+        let timing = self.vm.args.gc_phase_timing();
+        let mut timer = Timer::new(timing);
 
         self.young.unprotect_from();
         self.young.swap_semi();
@@ -115,15 +127,20 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
 
         self.visit_roots();
 
+        if timing {
+            let duration = timer.stop();
+            self.phases.roots = duration;
+        }
+
         if dev_verbose {
             println!("Minor GC: Phase 2 (dirty cards)");
         }
 
         self.visit_dirty_cards();
 
-        if self.vm.args.flag_gc_stats {
+        if timing {
             let duration = timer.stop();
-            self.phases.roots = duration;
+            self.phases.cards = duration;
         }
 
         if dev_verbose {
@@ -132,7 +149,7 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
 
         self.trace_gray_objects();
 
-        if self.vm.args.flag_gc_stats {
+        if timing {
             let duration = timer.stop();
             self.phases.tracing = duration;
         }
@@ -141,6 +158,10 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
             println!("Minor GC: Phase 3 (traverse) finished");
         }
 
+        if !self.survived.is_empty() {
+            self.vm.gc.alloc_sites.record_survived(&self.survived);
+        }
+
         if self.promotion_failed {
             // oh no: promotion failed, we need a subsequent full GC
             self.remove_forwarding_pointers();
@@ -356,56 +377,28 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
     fn visit_dirty_cards_in_old_region(&mut self, region: Region) {
         self.card_table
             .visit_dirty_in_old(region.start, region.end, |card_idx| {
-                if card_idx == self.card_table.card_idx(region.start) {
-                    let first_object = region.start;
-                    let ref_to_young_gen = false;
-                    self.copy_old_card(card_idx, first_object, region, ref_to_young_gen);
-                    return;
-                }
-
-                let crossing_entry = self.crossing_map.get(card_idx);
-                let card_start = self.card_table.to_address(card_idx);
-
-                match crossing_entry {
-                    CrossingEntry::NoRefs => panic!("card dirty without any refs"),
-                    CrossingEntry::LeadingRefs(refs) => {
-                        let mut ref_to_young_gen = false;
-                        let first_object = card_start.add_ptr(refs as usize);
-
-                        // copy references at start of card
-                        let ref_start = cmp::max(card_start, region.start);
-                        let ref_end = cmp::min(first_object, region.end);
-                        self.copy_refs(ref_start, ref_end, &mut ref_to_young_gen);
-
-                        // copy all objects from this card
-                        self.copy_old_card(card_idx, first_object, region, ref_to_young_gen);
-                    }
-
-                    CrossingEntry::FirstObject(offset) => {
-                        let first_object = card_start.add_ptr(offset as usize);
-
-                        // copy all objects from this card
-                        self.copy_old_card(card_idx, first_object, region, false);
-                    }
-
-                    CrossingEntry::PreviousObjectWords(_) => unimplemented!(),
-                    CrossingEntry::PreviousObjectCards(_) => unimplemented!(),
-
-                    CrossingEntry::ArrayStart(offset) => {
-                        let first_object = card_start.sub_ptr(offset as usize);
-
-                        // copy all objects from this card
-                        self.copy_old_card(card_idx, first_object.into(), region, false);
-                    }
-                }
+                let (refs, first_object) = crossing::dirty_card_start(
+                    self.card_table,
+                    self.crossing_map,
+                    card_idx,
+                    region,
+                );
+                let mut ref_to_young_gen = false;
+
+                // copy references at start of card
+                self.copy_refs(refs.start, refs.end, &mut ref_to_young_gen);
+
+                // copy all objects from this card
+                self.copy_old_card(card_idx, first_object, region, ref_to_young_gen);
             });
     }
 
     fn copy_refs(&mut self, start: Address, end: Address, ref_to_young_gen: &mut bool) {
+        let ref_size = compressed::ref_size_usize();
         let mut ptr = start;
 
         while ptr < end {
-            let slot = Slot::at(ptr);
+            let slot = Slot::field(ptr);
             let obj = slot.get();
 
             if self.young.contains(obj) {
@@ -417,7 +410,7 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
                 }
             }
 
-            ptr = ptr.add_ptr(1);
+            ptr = ptr.offset(ref_size);
         }
     }
 
@@ -519,6 +512,12 @@ impl<'a, 'ast: 'a> MinorCollector<'a, 'ast> {
             "copy objects only from eden or from-space."
         );
 
+        if !self.survived.is_empty() && self.eden_active.contains(obj_addr) {
+            if let Some(survived) = self.survived.get_mut(allocsite::site_of(obj)) {
+                *survived += obj_size;
+            }
+        }
+
         let copy_addr = self.young_top;
         let next_young_top = copy_addr.offset(obj_size);
 
@@ -1,13 +1,14 @@
 use parking_lot::{Mutex, MutexGuard};
 use std::cmp::{max, min};
 use std::mem::replace;
+use std::sync::Arc;
 
 use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::controller::SharedHeapConfig;
 use crate::gc::swiper::crossing::CrossingMap;
+use crate::gc::swiper::uncommit::Uncommitter;
 use crate::gc::swiper::CommonOldGen;
 use crate::gc::{Address, Region, GEN_SIZE};
-use crate::os;
 
 pub struct OldGen {
     total: Region,
@@ -25,12 +26,13 @@ impl OldGen {
         crossing_map: CrossingMap,
         card_table: CardTable,
         config: SharedHeapConfig,
+        uncommit: Arc<Uncommitter>,
     ) -> OldGen {
         let total = Region::new(start, end);
 
         let old = OldGen {
             total: total.clone(),
-            protected: Mutex::new(OldGenProtected::new(total)),
+            protected: Mutex::new(OldGenProtected::new(total, uncommit)),
 
             crossing_map,
             card_table,
@@ -90,15 +92,17 @@ pub struct OldGenProtected {
     pub size: usize,
     pub regions: Vec<OldGenRegion>,
     pub alloc_region: usize,
+    uncommit: Arc<Uncommitter>,
 }
 
 impl OldGenProtected {
-    fn new(total: Region) -> OldGenProtected {
+    fn new(total: Region, uncommit: Arc<Uncommitter>) -> OldGenProtected {
         OldGenProtected {
             total: total.clone(),
             size: 0,
             regions: vec![OldGenRegion::single(total)],
             alloc_region: 0,
+            uncommit,
         }
     }
 
@@ -148,7 +152,7 @@ impl OldGenProtected {
 
             if mapping_end > last_mapped {
                 let size = mapping_end.offset_from(last_mapped);
-                os::commit_at(last_mapped, size, false);
+                self.uncommit.commit_at(last_mapped, size);
             }
 
             if mapping_end == limit {
@@ -160,7 +164,7 @@ impl OldGenProtected {
 
         if limit > last_mapped {
             let size = limit.offset_from(last_mapped);
-            os::commit_at(last_mapped, size, false);
+            self.uncommit.commit_at(last_mapped, size);
         }
     }
 
@@ -240,7 +244,7 @@ impl OldGenProtected {
                     // memory needs to be committed
                     if start < old.mapping_start() {
                         let size = old.mapping_start().offset_from(start);
-                        os::commit_at(start, size, false);
+                        self.uncommit.commit_at(start, size);
                     }
 
                     start = old.mapping_end();
@@ -250,7 +254,7 @@ impl OldGenProtected {
 
             if start < end {
                 let size = end.offset_from(start);
-                os::commit_at(start, size, false);
+                self.uncommit.commit_at(start, size);
             }
         }
     }
@@ -277,7 +281,7 @@ impl OldGenProtected {
             let size = region.mapping_end().offset_from(start);
 
             if size > 0 {
-                os::discard(start, size);
+                self.uncommit.discard(start, size);
             }
         }
 
@@ -311,7 +315,7 @@ impl OldGenProtected {
                     // memory needs to be forgotten
                     if start < new.mapping_start() {
                         let size = new.mapping_start().offset_from(start);
-                        os::discard(start, size);
+                        self.uncommit.discard(start, size);
                     }
 
                     start = new.mapping_end();
@@ -321,7 +325,7 @@ impl OldGenProtected {
 
             if start < end {
                 let size = end.offset_from(start);
-                os::discard(start, size);
+                self.uncommit.discard(start, size);
             }
         }
 
@@ -389,13 +393,13 @@ impl OldGenProtected {
     fn extend(&mut self, size: usize) -> bool {
         let alloc_region = self.alloc_region;
 
-        if self.regions[alloc_region].extend(size) {
+        if self.regions[alloc_region].extend(size, &self.uncommit) {
             self.size += size;
             return true;
         }
 
         for (idx, old_region) in &mut self.regions.iter_mut().enumerate() {
-            if old_region.extend(size) {
+            if old_region.extend(size, &self.uncommit) {
                 self.size += size;
                 self.alloc_region = idx;
                 return true;
@@ -517,11 +521,11 @@ impl OldGenRegion {
         None
     }
 
-    fn extend(&mut self, size: usize) -> bool {
+    fn extend(&mut self, size: usize, uncommit: &Uncommitter) -> bool {
         let new_mapping_top = self.mapping_top.offset(size);
 
         if new_mapping_top <= self.total_mapping.end {
-            os::commit_at(self.mapping_top, size, false);
+            uncommit.commit_at(self.mapping_top, size);
             self.mapping_top = new_mapping_top;
 
             true
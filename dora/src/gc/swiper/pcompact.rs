@@ -7,6 +7,7 @@ use crate::gc::root::Slot;
 use crate::gc::space::Space;
 use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::compact::verify_marking;
+use crate::gc::swiper::concurrent::Remark;
 use crate::gc::swiper::controller::FullCollectorPhases;
 use crate::gc::swiper::crossing::{CrossingEntry, CrossingMap};
 use crate::gc::swiper::large::{LargeAlloc, LargeSpace};
@@ -35,6 +36,7 @@ pub struct ParallelFullCollector<'a, 'ast: 'a> {
     old_total: Region,
 
     reason: GcReason,
+    remark: Option<Remark>,
     number_workers: usize,
 
     min_heap_size: usize,
@@ -59,6 +61,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
         perm_space: &'a Space,
         rootset: &'a [Slot],
         reason: GcReason,
+        remark: Option<Remark>,
         number_workers: usize,
         min_heap_size: usize,
         max_heap_size: usize,
@@ -78,6 +81,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
             old_total: old.total(),
 
             reason,
+            remark,
             number_workers,
 
             min_heap_size,
@@ -97,7 +101,7 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
 
     pub fn collect(&mut self, pool: &mut Pool) {
         let dev_verbose = self.vm.args.flag_gc_dev_verbose;
-        let stats = self.vm.args.flag_gc_stats;
+        let stats = self.vm.args.gc_phase_timing();
 
         let mut timer = Timer::new(stats);
 
@@ -225,12 +229,20 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
     }
 
     fn mark_live(&mut self, pool: &mut Pool) {
-        pmarking::start(
-            self.rootset,
-            self.heap.clone(),
-            self.perm_space.total(),
-            pool,
-        );
+        if let Some(remark) = self.remark.take() {
+            // old generation was already marked concurrently
+            let rescanned = remark.finish(
+                self.rootset,
+                self.young,
+                &*self.old_protected,
+                self.large_space,
+                self.card_table,
+                self.crossing_map,
+            );
+            self.phases.rescanned = Some(rescanned);
+        } else {
+            pmarking::start(self.rootset, self.heap, self.perm_space.total(), pool);
+        }
     }
 
     fn compute_forward(&mut self, pool: &mut Pool) {
@@ -445,6 +457,30 @@ impl<'a, 'ast> ParallelFullCollector<'a, 'ast> {
             );
         }
 
+        // young objects can only use the free space at the end of the old
+        // generation if the last region slides to the start: merge a
+        // last region sliding to the end into the previous one.
+        if !regions.is_empty() && regions.len() % 2 == 0 {
+            let last = regions.pop().unwrap();
+            let prev = regions.pop().unwrap();
+
+            let mut size = prev.live() + last.live();
+            let mut last_span_end = prev.span.start;
+            let mut last_mapping_end = regions
+                .last()
+                .map(|r| r.mapping.end)
+                .unwrap_or(self.old_total.start);
+
+            self.add_collect_region(
+                prev.start_idx,
+                last.start_idx + last.units - 1,
+                &mut size,
+                &mut last_span_end,
+                &mut last_mapping_end,
+                &mut regions,
+            );
+        }
+
         if regions.is_empty() {
             let object = self.old_total;
             let empty = Region::new(self.old_total.start, self.old_total.start);
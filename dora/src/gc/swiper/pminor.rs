@@ -3,11 +3,13 @@ use std::cmp;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Barrier;
 
+use crate::gc::allocsite;
+use crate::gc::compressed;
 use crate::gc::pmarking::Terminator;
 use crate::gc::root::Slot;
 use crate::gc::swiper::card::{CardEntry, CardTable};
 use crate::gc::swiper::controller::{MinorCollectorPhases, SharedHeapConfig};
-use crate::gc::swiper::crossing::{CrossingEntry, CrossingMap};
+use crate::gc::swiper::crossing::{self, CrossingMap};
 use crate::gc::swiper::large::{LargeAlloc, LargeSpace};
 use crate::gc::swiper::old::OldGen;
 use crate::gc::swiper::on_different_cards;
@@ -161,7 +163,7 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         let mut stealers = Vec::with_capacity(self.number_workers);
         let injector = Injector::new();
 
-        let stats = self.vm.args.flag_gc_stats;
+        let stats = self.vm.args.gc_phase_timing();
         let timer = Timer::new(stats);
 
         for _ in 0..self.number_workers {
@@ -222,13 +224,19 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
         let next_large = Mutex::new(head);
         let next_large = &next_large;
 
-        let prot_timer: Option<Mutex<(Timer, f32)>> = if stats {
-            Some(Mutex::new((timer, 0.0f32)))
+        let prot_timer: Option<Mutex<(Timer, f32, f32)>> = if stats {
+            Some(Mutex::new((timer, 0.0f32, 0.0f32)))
         } else {
             None
         };
         let prot_timer = &prot_timer;
 
+        let alloc_sites = if vm.args.alloc_site_tracking() {
+            vm.gc.alloc_sites.len() + 1
+        } else {
+            0
+        };
+
         self.threadpool.scoped(|scoped| {
             for (task_id, worker) in workers.into_iter().enumerate() {
                 let injector = &injector;
@@ -281,10 +289,15 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
                         copy_failed: false,
 
                         timer: prot_timer,
+                        survived: vec![0; alloc_sites],
                     };
 
                     task.run();
 
+                    if !task.survived.is_empty() {
+                        vm.gc.alloc_sites.record_survived(&task.survived);
+                    }
+
                     if task.promoted_size > 0 {
                         promoted_size.fetch_add(task.promoted_size, Ordering::SeqCst);
                     }
@@ -298,8 +311,9 @@ impl<'a, 'ast: 'a> ParallelMinorCollector<'a, 'ast> {
 
         if let Some(ref mutex) = prot_timer {
             let mut mutex = mutex.lock();
-            let (ref mut timer, duration_roots) = *mutex;
+            let (ref mut timer, duration_roots, duration_cards) = *mutex;
             self.phases.roots = duration_roots;
+            self.phases.cards = duration_cards;
             self.phases.tracing = timer.stop();
         }
 
@@ -434,7 +448,10 @@ struct CopyTask<'a, 'ast: 'a> {
     young_limit: Address,
     copy_failed: bool,
 
-    timer: &'a Option<Mutex<(Timer, f32)>>,
+    timer: &'a Option<Mutex<(Timer, f32, f32)>>,
+
+    // surviving bytes of eden objects per allocation site
+    survived: Vec<usize>,
 }
 
 impl<'a, 'ast> CopyTask<'a, 'ast>
@@ -443,6 +460,12 @@ where
 {
     fn run(&mut self) {
         self.visit_roots();
+
+        // only wait for all roots to be visited when phases are timed
+        if self.timer.is_some() {
+            self.barrier_roots();
+        }
+
         self.visit_dirty_cards();
 
         self.barrier();
@@ -450,18 +473,30 @@ where
         self.trace_gray_objects();
     }
 
-    fn barrier(&mut self) {
+    fn barrier_roots(&mut self) {
         self.barrier.wait();
 
         if self.task_id == 0 {
             if let Some(ref mutex) = self.timer {
                 let mut mutex = mutex.lock();
-                let (ref mut timer, ref mut duration_roots) = *mutex;
+                let (ref mut timer, ref mut duration_roots, _) = *mutex;
                 *duration_roots = timer.stop();
             }
         }
     }
 
+    fn barrier(&mut self) {
+        self.barrier.wait();
+
+        if self.task_id == 0 {
+            if let Some(ref mutex) = self.timer {
+                let mut mutex = mutex.lock();
+                let (ref mut timer, _, ref mut duration_cards) = *mutex;
+                *duration_cards = timer.stop();
+            }
+        }
+    }
+
     fn visit_roots(&mut self) {
         while let Some(stride) = self.next_root_stride() {
             self.visit_roots_in_stride(stride);
@@ -613,55 +648,23 @@ where
     }
 
     fn visit_dirty_card(&mut self, card_idx: CardIdx, region: Region) {
-        if card_idx == self.card_table.card_idx(region.start) {
-            let first_object = region.start;
-            let ref_to_young_gen = false;
-            self.copy_old_card(card_idx, first_object, region, ref_to_young_gen);
-            return;
-        }
-
-        let crossing_entry = self.crossing_map.get(card_idx);
-        let card_start = self.card_table.to_address(card_idx);
-
-        match crossing_entry {
-            CrossingEntry::NoRefs => panic!("card dirty without any refs"),
-            CrossingEntry::LeadingRefs(refs) => {
-                let mut ref_to_young_gen = false;
-                let first_object = card_start.add_ptr(refs as usize);
-
-                // copy references at start of card
-                let ref_start = cmp::max(card_start, region.start);
-                let ref_end = cmp::min(first_object, region.end);
-                self.copy_refs(ref_start, ref_end, &mut ref_to_young_gen);
-
-                // copy all objects from this card
-                self.copy_old_card(card_idx, first_object, region, ref_to_young_gen);
-            }
-
-            CrossingEntry::FirstObject(offset) => {
-                let first_object = card_start.add_ptr(offset as usize);
-
-                // copy all objects from this card
-                self.copy_old_card(card_idx, first_object, region, false);
-            }
-
-            CrossingEntry::PreviousObjectWords(_) => unimplemented!(),
-            CrossingEntry::PreviousObjectCards(_) => unimplemented!(),
+        let (refs, first_object) =
+            crossing::dirty_card_start(self.card_table, self.crossing_map, card_idx, region);
+        let mut ref_to_young_gen = false;
 
-            CrossingEntry::ArrayStart(offset) => {
-                let first_object = card_start.sub_ptr(offset as usize);
+        // copy references at start of card
+        self.copy_refs(refs.start, refs.end, &mut ref_to_young_gen);
 
-                // copy all objects from this card
-                self.copy_old_card(card_idx, first_object, region, false);
-            }
-        }
+        // copy all objects from this card
+        self.copy_old_card(card_idx, first_object, region, ref_to_young_gen);
     }
 
     fn copy_refs(&mut self, start: Address, end: Address, ref_to_young_gen: &mut bool) {
+        let ref_size = compressed::ref_size_usize();
         let mut ptr = start;
 
         while ptr < end {
-            let slot = Slot::at(ptr);
+            let slot = Slot::field(ptr);
             let obj = slot.get();
 
             if self.young.contains(obj) {
@@ -673,7 +676,7 @@ where
                 }
             }
 
-            ptr = ptr.add_ptr(1);
+            ptr = ptr.offset(ref_size);
         }
     }
 
@@ -1043,6 +1046,7 @@ where
 
         match res {
             Ok(()) => {
+                self.record_survived(obj_addr, copy_addr, obj_size);
                 self.push(copy_addr);
                 copy_addr
             }
@@ -1076,6 +1080,7 @@ where
         match res {
             Ok(()) => {
                 self.promoted_size += obj_size;
+                self.record_survived(obj.address(), copy_addr, obj_size);
                 self.push(copy_addr);
 
                 copy_addr
@@ -1089,6 +1094,16 @@ where
         }
     }
 
+    fn record_survived(&mut self, obj_addr: Address, copy_addr: Address, obj_size: usize) {
+        if !self.survived.is_empty() && self.eden_active.contains(obj_addr) {
+            let site = allocsite::site_of(copy_addr.to_obj());
+
+            if let Some(survived) = self.survived.get_mut(site) {
+                *survived += obj_size;
+            }
+        }
+    }
+
     fn push(&mut self, addr: Address) {
         if self.local.len() < LOCAL_MAXIMUM {
             self.local.push(addr);
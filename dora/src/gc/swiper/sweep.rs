@@ -3,19 +3,21 @@ use scoped_threadpool::Pool;
 use std::sync::Arc;
 
 use crate::driver::cmd::Args;
+use crate::gc::compressed;
 use crate::gc::root::{get_rootset, Slot};
 use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::controller::{self, HeapConfig, SharedHeapConfig};
 use crate::gc::swiper::crossing::CrossingMap;
 use crate::gc::swiper::large::LargeSpace;
 use crate::gc::swiper::sweep::old::OldGen;
+use crate::gc::swiper::uncommit::Uncommitter;
 use crate::gc::swiper::verify::VerifierPhase;
 use crate::gc::swiper::young::YoungGen;
-use crate::gc::swiper::{CollectionKind, CARD_SIZE_BITS, LARGE_OBJECT_SIZE};
+use crate::gc::swiper::{reserve_sizes, CollectionKind, ReserveSizes};
+use crate::gc::swiper::{CARD_SIZE_BITS, LARGE_OBJECT_SIZE};
 use crate::gc::tlab;
 use crate::gc::{align_gen, formatted_size, GEN_SIZE};
 use crate::gc::{Address, Collector, GcReason, Region};
-use crate::mem;
 use crate::os;
 use crate::safepoint;
 use crate::vm::VM;
@@ -46,6 +48,9 @@ pub struct SweepSwiper {
 
     threadpool: Mutex<Pool>,
     config: SharedHeapConfig,
+
+    // returns free memory to the OS after a delay
+    uncommit: Arc<Uncommitter>,
 }
 
 impl SweepSwiper {
@@ -57,17 +62,15 @@ impl SweepSwiper {
 
         controller::init(&mut config, args);
 
-        // determine size for card table
-        let card_size = mem::page_align((4 * max_heap_size) >> CARD_SIZE_BITS);
-
-        // determine size for crossing map
-        let crossing_size = mem::page_align(max_heap_size >> CARD_SIZE_BITS);
-
-        // determine full memory size
-        let reserve_size = max_heap_size * 4 + card_size + crossing_size;
+        // determine sizes of card table, crossing map and full memory
+        let ReserveSizes {
+            card_size,
+            crossing_size,
+            reserve_size,
+        } = reserve_sizes(max_heap_size);
 
         // reserve full memory
-        let reservation = os::reserve_align(reserve_size, GEN_SIZE);
+        let reservation = compressed::reserve_align(reserve_size, GEN_SIZE);
         let heap_start = reservation.start;
         assert!(heap_start.is_gen_aligned());
 
@@ -117,7 +120,14 @@ impl SweepSwiper {
             max_heap_size,
         );
         let crossing_map = CrossingMap::new(crossing_start, crossing_end, max_heap_size);
-        let young = YoungGen::new(young, eden_size, semi_size, args.flag_gc_verify);
+        let uncommit = Arc::new(Uncommitter::new(args.gc_uncommit_delay()));
+        let young = YoungGen::new(
+            young,
+            eden_size,
+            semi_size,
+            args.flag_gc_verify,
+            uncommit.clone(),
+        );
 
         let config = Arc::new(Mutex::new(config));
         let old = OldGen::new(
@@ -126,8 +136,9 @@ impl SweepSwiper {
             crossing_map.clone(),
             card_table.clone(),
             config.clone(),
+            uncommit.clone(),
         );
-        let large = LargeSpace::new(large_start, large_end, config.clone());
+        let large = LargeSpace::new(large_start, large_end, config.clone(), uncommit.clone());
 
         if args.flag_gc_verbose {
             println!(
@@ -163,6 +174,7 @@ impl SweepSwiper {
             max_heap_size,
 
             threadpool: Mutex::new(Pool::new(nworkers as u32)),
+            uncommit,
         }
     }
 }
@@ -204,8 +216,8 @@ impl Collector for SweepSwiper {
         unimplemented!()
     }
 
-    fn verify_ref(&self, _vm: &VM, _reference: Address) {
-        unimplemented!()
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.heap.contains(reference)
     }
 }
 
@@ -256,6 +268,12 @@ impl SweepSwiper {
                 reason,
             );
 
+            let uncommitted = self.uncommit.release();
+
+            if uncommitted > 0 && vm.args.flag_gc_verbose {
+                println!("GC: uncommitted {}", formatted_size(uncommitted));
+            }
+
             kind
         })
     }
@@ -1,13 +1,14 @@
 use fixedbitset::FixedBitSet;
 use parking_lot::{Mutex, MutexGuard};
 use std::cmp;
+use std::sync::Arc;
 
 use crate::gc::swiper::card::CardTable;
 use crate::gc::swiper::controller::SharedHeapConfig;
 use crate::gc::swiper::crossing::CrossingMap;
+use crate::gc::swiper::uncommit::Uncommitter;
 use crate::gc::swiper::CommonOldGen;
 use crate::gc::{Address, Region};
-use crate::os;
 
 // Choose 128K as chunk size for now
 const CHUNK_SIZE_BITS: usize = 17;
@@ -26,6 +27,9 @@ pub struct OldGen {
     crossing_map: CrossingMap,
     card_table: CardTable,
     config: SharedHeapConfig,
+
+    // chunks are committed and discarded through the uncommitter
+    uncommit: Arc<Uncommitter>,
 }
 
 impl OldGen {
@@ -35,6 +39,7 @@ impl OldGen {
         crossing_map: CrossingMap,
         card_table: CardTable,
         config: SharedHeapConfig,
+        uncommit: Arc<Uncommitter>,
     ) -> OldGen {
         assert!(start.is_chunk_aligned());
 
@@ -60,6 +65,7 @@ impl OldGen {
             crossing_map,
             card_table,
             config,
+            uncommit,
         }
     }
 
@@ -76,7 +82,8 @@ impl OldGen {
 
             if let Some(chunk) = prot.free_chunks.remove_leftmost() {
                 prot.used_chunks.add(chunk);
-                os::commit_at(chunk_addr(chunk, self.total.start), CHUNK_SIZE, false);
+                self.uncommit
+                    .commit_at(chunk_addr(chunk, self.total.start), CHUNK_SIZE);
                 return true;
             }
         }
@@ -89,7 +96,8 @@ impl OldGen {
             let mut prot = self.prot.lock();
             assert!(prot.used_chunks.remove(chunk));
             prot.free_chunks.add(chunk);
-            os::discard(chunk_addr(chunk, self.total.start), CHUNK_SIZE);
+            self.uncommit
+                .discard(chunk_addr(chunk, self.total.start), CHUNK_SIZE);
         }
 
         {
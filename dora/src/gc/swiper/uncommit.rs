@@ -0,0 +1,213 @@
+use parking_lot::Mutex;
+use std::cmp::{max, min};
+
+use crate::gc::{Address, Region};
+use crate::os;
+use crate::timer;
+
+// Memory that becomes free in the heap (e.g. after compacting the old
+// generation, freeing large objects or shrinking the young generation)
+// is only returned to the OS after it stayed unused for the configured delay.
+// Otherwise memory would be discarded and committed again right away
+// whenever the heap size oscillates. There is no timer: expired memory is
+// released at the end of the next collection, so a program that stops
+// allocating keeps its pending memory committed.
+pub struct Uncommitter {
+    // delay in ns
+    delay: u64,
+    protected: Mutex<UncommitterProtected>,
+}
+
+struct UncommitterProtected {
+    pending: Vec<PendingRegion>,
+    uncommitted_size: usize,
+}
+
+struct PendingRegion {
+    region: Region,
+    freed_at: u64,
+}
+
+impl Uncommitter {
+    pub fn new(delay_in_ms: usize) -> Uncommitter {
+        Uncommitter {
+            delay: delay_in_ms as u64 * 1000 * 1000,
+            protected: Mutex::new(UncommitterProtected {
+                pending: Vec::new(),
+                uncommitted_size: 0,
+            }),
+        }
+    }
+
+    // memory isn't used anymore, discard it once the delay expired
+    pub fn discard(&self, start: Address, size: usize) {
+        let mut protected = self.protected.lock();
+
+        if self.delay == 0 {
+            os::discard(start, size);
+            protected.uncommitted_size += size;
+        } else {
+            protected.pending.push(PendingRegion {
+                region: start.region_start(size),
+                freed_at: timer::timestamp(),
+            });
+        }
+    }
+
+    // memory is used again, it must not be discarded anymore
+    pub fn commit_at(&self, start: Address, size: usize) {
+        let region = start.region_start(size);
+        let mut protected = self.protected.lock();
+
+        if protected.pending.iter().any(|p| p.region.overlaps(&region)) {
+            let mut pending = Vec::with_capacity(protected.pending.len() + 1);
+
+            for p in protected.pending.drain(..) {
+                if !p.region.overlaps(&region) {
+                    pending.push(p);
+                    continue;
+                }
+
+                let before = Region::new(p.region.start, min(p.region.end, region.start));
+                let after = Region::new(max(p.region.start, region.end), p.region.end);
+
+                for part in &[before, after] {
+                    if part.start < part.end {
+                        pending.push(PendingRegion {
+                            region: *part,
+                            freed_at: p.freed_at,
+                        });
+                    }
+                }
+            }
+
+            protected.pending = pending;
+        }
+
+        os::commit_at(start, size, false);
+    }
+
+    // returns all memory to the OS that is unused for longer than the delay,
+    // called at the end of each collection
+    pub fn release(&self) -> usize {
+        let mut protected = self.protected.lock();
+
+        if protected.pending.is_empty() {
+            return 0;
+        }
+
+        let now = timer::timestamp();
+        let delay = self.delay;
+        let mut released = 0;
+
+        protected.pending.retain(|p| {
+            if now - p.freed_at >= delay {
+                os::discard(p.region.start, p.region.size());
+                released += p.region.size();
+                false
+            } else {
+                true
+            }
+        });
+
+        protected.uncommitted_size += released;
+        released
+    }
+
+    // memory that is unused but still committed
+    pub fn pending_size(&self) -> usize {
+        let protected = self.protected.lock();
+        protected.pending.iter().map(|p| p.region.size()).sum()
+    }
+
+    // total memory returned to the OS so far
+    pub fn uncommitted_size(&self) -> usize {
+        let protected = self.protected.lock();
+        protected.uncommitted_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gc::K;
+    use std::thread;
+    use std::time::Duration;
+
+    fn with_memory<F>(size: usize, f: F)
+    where
+        F: FnOnce(Address),
+    {
+        let start = os::commit(size, false);
+        f(start);
+        os::free(start, size);
+    }
+
+    #[test]
+    fn test_discard_without_delay() {
+        with_memory(64 * K, |start| {
+            let uncommit = Uncommitter::new(0);
+            uncommit.discard(start, 64 * K);
+
+            assert_eq!(0, uncommit.pending_size());
+            assert_eq!(64 * K, uncommit.uncommitted_size());
+            assert_eq!(0, uncommit.release());
+        });
+    }
+
+    #[test]
+    fn test_release_after_delay() {
+        with_memory(64 * K, |start| {
+            let uncommit = Uncommitter::new(1);
+            uncommit.discard(start, 64 * K);
+            assert_eq!(64 * K, uncommit.pending_size());
+
+            thread::sleep(Duration::from_millis(5));
+
+            assert_eq!(64 * K, uncommit.release());
+            assert_eq!(0, uncommit.pending_size());
+            assert_eq!(64 * K, uncommit.uncommitted_size());
+            assert_eq!(0, uncommit.release());
+        });
+    }
+
+    #[test]
+    fn test_release_keeps_memory_before_delay() {
+        with_memory(64 * K, |start| {
+            let uncommit = Uncommitter::new(60 * 60 * 1000);
+            uncommit.discard(start, 64 * K);
+
+            assert_eq!(0, uncommit.release());
+            assert_eq!(64 * K, uncommit.pending_size());
+            assert_eq!(0, uncommit.uncommitted_size());
+        });
+    }
+
+    #[test]
+    fn test_commit_at_splits_pending_region() {
+        with_memory(64 * K, |start| {
+            let uncommit = Uncommitter::new(1);
+            uncommit.discard(start, 64 * K);
+
+            // reuse the middle, the rest stays pending
+            uncommit.commit_at(start.offset(16 * K), 16 * K);
+            assert_eq!(48 * K, uncommit.pending_size());
+
+            // reuse the end and a range that isn't pending at all
+            uncommit.commit_at(start.offset(48 * K), 16 * K);
+            uncommit.commit_at(start.offset(16 * K), 16 * K);
+            assert_eq!(32 * K, uncommit.pending_size());
+
+            thread::sleep(Duration::from_millis(5));
+
+            assert_eq!(32 * K, uncommit.release());
+            assert_eq!(32 * K, uncommit.uncommitted_size());
+
+            // the reused memory is still writable
+            unsafe {
+                *start.offset(16 * K).to_mut_ptr::<usize>() = 1;
+                *start.offset(48 * K).to_mut_ptr::<usize>() = 1;
+            }
+        });
+    }
+}
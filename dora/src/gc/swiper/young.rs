@@ -1,6 +1,8 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use crate::gc::bump::BumpAllocator;
+use crate::gc::swiper::uncommit::Uncommitter;
 use crate::gc::{gen_aligned, Address, Region};
 use crate::mem;
 use crate::os::{self, Access};
@@ -14,10 +16,23 @@ pub struct YoungGen {
 
     // from/to-space
     semi: SemiSpace,
+
+    // returns memory to the OS when young generation shrinks
+    uncommit: Arc<Uncommitter>,
+
+    // number of minor collections an object needs to survive before it
+    // gets promoted: either 1 (default) or 0 (promote all survivors)
+    tenuring_threshold: AtomicUsize,
 }
 
 impl YoungGen {
-    pub fn new(total: Region, eden_size: usize, semi_size: usize, protect: bool) -> YoungGen {
+    pub fn new(
+        total: Region,
+        eden_size: usize,
+        semi_size: usize,
+        protect: bool,
+        uncommit: Arc<Uncommitter>,
+    ) -> YoungGen {
         let eden_total_size = total.size() / 2;
 
         let eden_total = total.start.region_start(eden_total_size);
@@ -27,6 +42,8 @@ impl YoungGen {
             total,
             eden: Eden::new(eden_total, eden_size),
             semi: SemiSpace::new(semi_total, semi_size, protect),
+            uncommit,
+            tenuring_threshold: AtomicUsize::new(1),
         };
 
         young.commit();
@@ -129,9 +146,22 @@ impl YoungGen {
         self.semi.set_age_marker(top);
     }
 
+    pub fn tenuring_threshold(&self) -> usize {
+        self.tenuring_threshold.load(Ordering::Relaxed)
+    }
+
+    pub fn set_tenuring_threshold(&self, threshold: usize) {
+        assert!(threshold <= 1);
+        self.tenuring_threshold.store(threshold, Ordering::Relaxed);
+    }
+
     pub fn should_be_promoted(&self, addr: Address) -> bool {
         debug_assert!(self.total.contains(addr));
 
+        if self.tenuring_threshold() == 0 {
+            return true;
+        }
+
         if addr < self.semi.total.start {
             return false;
         }
@@ -153,8 +183,8 @@ impl YoungGen {
         assert!(gen_aligned(eden_size));
         assert!(gen_aligned(semi_size));
 
-        self.eden.set_limit(eden_size);
-        self.semi.set_limit(semi_size);
+        self.eden.set_limit(eden_size, &self.uncommit);
+        self.semi.set_limit(semi_size, &self.uncommit);
     }
 
     pub fn committed_size(&self) -> (usize, usize) {
@@ -207,8 +237,8 @@ impl Eden {
         self.block.bump_alloc(size)
     }
 
-    fn set_limit(&self, size: usize) {
-        self.block.set_limit(size);
+    fn set_limit(&self, size: usize, uncommit: &Uncommitter) {
+        self.block.set_limit(size, uncommit);
     }
 }
 
@@ -369,9 +399,9 @@ impl SemiSpace {
         self.to_block().bump_alloc(size)
     }
 
-    fn set_limit(&self, size: usize) {
-        self.from_block().set_limit(size / 2);
-        self.to_block().set_limit(size / 2);
+    fn set_limit(&self, size: usize, uncommit: &Uncommitter) {
+        self.from_block().set_limit(size / 2, uncommit);
+        self.to_block().set_limit(size / 2, uncommit);
     }
 
     fn committed_size(&self) -> usize {
@@ -423,7 +453,7 @@ impl Block {
         committed - self.start.to_usize()
     }
 
-    fn set_limit(&self, new_size: usize) {
+    fn set_limit(&self, new_size: usize, uncommit: &Uncommitter) {
         assert!(mem::is_page_aligned(new_size));
 
         let old_committed = self.committed.load(Ordering::Relaxed);
@@ -443,10 +473,10 @@ impl Block {
 
         if old_committed < new_committed {
             let size = new_committed - old_committed;
-            os::commit_at(old_committed.into(), size, false);
+            uncommit.commit_at(old_committed.into(), size);
         } else if old_committed > new_committed {
             let size = old_committed - new_committed;
-            os::discard(new_committed.into(), size);
+            uncommit.discard(new_committed.into(), size);
         }
 
         self.alloc.reset_limit(new_committed.into());
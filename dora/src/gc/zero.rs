@@ -1,5 +1,6 @@
 use crate::driver::cmd::Args;
 use crate::gc::bump::BumpAllocator;
+use crate::gc::compressed;
 use crate::gc::{Address, Collector, GcReason, Region};
 use crate::os;
 use crate::vm::VM;
@@ -14,7 +15,7 @@ impl ZeroCollector {
     pub fn new(args: &Args) -> ZeroCollector {
         let heap_size: usize = args.max_heap_size();
 
-        let reservation = os::reserve_align(heap_size, os::page_size());
+        let reservation = compressed::reserve_align(heap_size, os::page_size());
         let start = reservation.start;
         let end = start.offset(heap_size);
 
@@ -68,11 +69,15 @@ impl Collector for ZeroCollector {
             mutator, runtime,
         );
     }
+
+    fn verify_ref(&self, _vm: &VM, reference: Address) -> bool {
+        self.start <= reference && reference < self.end
+    }
 }
 
 impl Drop for ZeroCollector {
     fn drop(&mut self) {
         let size = self.end.offset_from(self.start);
-        os::free(self.start, size);
+        compressed::free(self.start, size);
     }
 }
@@ -0,0 +1,471 @@
+use std::fmt;
+
+// Minimal JSON value used for machine readable output and the
+// language server protocol.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string<S: Into<String>>(value: S) -> Json {
+        Json::String(value.into())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    // Follows a path of object keys, e.g. ["textDocument", "uri"].
+    pub fn lookup(&self, path: &[&str]) -> Option<&Json> {
+        let mut value = self;
+
+        for key in path {
+            value = value.get(key)?;
+        }
+
+        Some(value)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            Json::Number(value) if value >= 0.0 && value <= u32::max_value() as f64 => {
+                Some(value as u32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values.as_slice()),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        match self {
+            Json::Null => true,
+            _ => false,
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            input: input.as_bytes(),
+            text: input,
+            idx: 0,
+        };
+
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+
+        if parser.idx != parser.input.len() {
+            return Err(format!("trailing characters at {}", parser.idx));
+        }
+
+        Ok(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Json {
+        Json::Number(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.into())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Json {
+        Json::Array(values)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => {
+                if value.fract() == 0.0 && value.abs() < 1e15 {
+                    write!(f, "{}", *value as i64)
+                } else {
+                    write!(f, "{}", value)
+                }
+            }
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", value)?;
+                }
+
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+
+                for (idx, (key, value)) in members.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for ch in value.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+struct JsonParser<'a> {
+    input: &'a [u8],
+    text: &'a str,
+    idx: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(b'n') => self.parse_keyword("null", Json::Null),
+            Some(b't') => self.parse_keyword("true", Json::Bool(true)),
+            Some(b'f') => self.parse_keyword("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.parse_string()?)),
+            Some(b'[') => self.parse_array(),
+            Some(b'{') => self.parse_object(),
+            Some(b'-') | Some(b'0'..=b'9') => self.parse_number(),
+            Some(ch) => Err(format!("unexpected `{}` at {}", ch as char, self.idx)),
+            None => Err("unexpected end of input".into()),
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.idx..].starts_with(keyword) {
+            self.idx += keyword.len();
+            Ok(value)
+        } else {
+            Err(format!("invalid literal at {}", self.idx))
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.idx;
+
+        while let Some(ch) = self.peek() {
+            match ch {
+                b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' => self.idx += 1,
+                _ => break,
+            }
+        }
+
+        self.text[start..self.idx]
+            .parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number at {}", start))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut result = String::new();
+
+        loop {
+            let start = self.idx;
+
+            while let Some(ch) = self.peek() {
+                if ch == b'"' || ch == b'\\' {
+                    break;
+                }
+
+                self.idx += 1;
+            }
+
+            result.push_str(&self.text[start..self.idx]);
+
+            match self.next() {
+                Some(b'"') => return Ok(result),
+                Some(b'\\') => {
+                    let ch = match self.next() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.parse_unicode_escape()?,
+                        _ => return Err(format!("invalid escape at {}", self.idx)),
+                    };
+
+                    result.push(ch);
+                }
+                _ => return Err("unterminated string".into()),
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+
+        let code = if high >= 0xD800 && high < 0xDC00 {
+            // surrogate pair: must be followed by \uDC00-\uDFFF
+            if self.next() != Some(b'\\') || self.next() != Some(b'u') {
+                return Err(format!("invalid surrogate pair at {}", self.idx));
+            }
+
+            let low = self.parse_hex4()?;
+
+            if low < 0xDC00 || low >= 0xE000 {
+                return Err(format!("invalid surrogate pair at {}", self.idx));
+            }
+
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        std::char::from_u32(code).ok_or_else(|| format!("invalid code point at {}", self.idx))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let end = self.idx + 4;
+
+        if end > self.input.len() || !self.text.is_char_boundary(end) {
+            return Err("unterminated unicode escape".into());
+        }
+
+        let value = u32::from_str_radix(&self.text[self.idx..end], 16)
+            .map_err(|_| format!("invalid unicode escape at {}", self.idx))?;
+        self.idx = end;
+
+        Ok(value)
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.peek() == Some(b']') {
+            self.idx += 1;
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.next() {
+                Some(b',') => {}
+                Some(b']') => return Ok(Json::Array(values)),
+                _ => return Err(format!("expected `,` or `]` at {}", self.idx)),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.idx += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            let value = self.parse_value()?;
+            members.push((key, value));
+            self.skip_whitespace();
+
+            match self.next() {
+                Some(b',') => {}
+                Some(b'}') => return Ok(Json::Object(members)),
+                _ => return Err(format!("expected `,` or `}}` at {}", self.idx)),
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}` at {}", expected as char, self.idx))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.idx += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.idx).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let ch = self.peek();
+
+        if ch.is_some() {
+            self.idx += 1;
+        }
+
+        ch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(Ok(Json::Null), Json::parse("null"));
+        assert_eq!(Ok(Json::Bool(true)), Json::parse(" true "));
+        assert_eq!(Ok(Json::Number(-1.5)), Json::parse("-1.5"));
+        assert_eq!(Ok(Json::string("a\"b\n")), Json::parse("\"a\\\"b\\n\""));
+        assert_eq!(
+            Ok(Json::string("\u{e9}\u{1f600}")),
+            Json::parse("\"\\u00e9\\ud83d\\ude00\"")
+        );
+        assert!(Json::parse("[1,").is_err());
+        assert!(Json::parse("{} x").is_err());
+    }
+
+    #[test]
+    fn parse_nested() {
+        let value = Json::parse(
+            "{\"id\": 1, \"params\": {\"textDocument\": {\"uri\": \"file:///a.dora\"}, \"list\": [1, 2]}}",
+        )
+        .unwrap();
+
+        assert_eq!(Some(1), value.get("id").and_then(|id| id.as_u32()));
+        assert_eq!(
+            Some("file:///a.dora"),
+            value
+                .lookup(&["params", "textDocument", "uri"])
+                .and_then(|uri| uri.as_str())
+        );
+        assert_eq!(
+            Some(2),
+            value
+                .lookup(&["params", "list"])
+                .and_then(|list| list.as_array())
+                .map(|list| list.len())
+        );
+        assert_eq!(None, value.lookup(&["params", "missing"]));
+    }
+
+    #[test]
+    fn write_values() {
+        let value = Json::object(vec![
+            ("a", Json::from(1u32)),
+            ("b", Json::from(vec![Json::Null, Json::from(true)])),
+            ("c", Json::from("x\"y")),
+            ("d", Json::Number(0.5)),
+        ]);
+
+        assert_eq!(
+            "{\"a\":1,\"b\":[null,true],\"c\":\"x\\\"y\",\"d\":0.5}",
+            value.to_string()
+        );
+        assert_eq!(Ok(value.clone()), Json::parse(&value.to_string()));
+    }
+}
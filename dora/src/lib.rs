@@ -26,16 +26,20 @@ mod bytecode;
 mod cannon;
 mod compiler;
 mod cpu;
+mod debugger;
 mod disassembler;
 mod driver;
 mod dseg;
 mod error;
 mod gc;
 mod handle;
+mod json;
+mod lsp;
 mod masm;
 mod mem;
 mod object;
 mod os;
+mod profiler;
 mod safepoint;
 mod semck;
 mod size;
@@ -62,3 +66,8 @@ pub fn run_content(content: &str) -> i32 {
 pub fn run() -> i32 {
     driver::start(None)
 }
+
+#[cfg(not(test))]
+pub fn run_lsp() -> i32 {
+    lsp::start()
+}
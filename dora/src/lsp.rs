@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::error::diag::Severity;
+use crate::json::Json;
+use crate::lsp::analysis::{Analysis, CompletionKind, Stdlib};
+use crate::lsp::text::Range;
+
+pub mod analysis;
+pub mod text;
+
+// Language server for Dora, speaking JSON-RPC over stdin/stdout. Documents
+// are type checked like with `--check` whenever they change, nothing gets
+// executed.
+pub fn start() -> i32 {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+
+        loop {
+            match read_message(&mut input) {
+                Ok(Some(message)) => {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+
+                Ok(None) => break,
+
+                Err(err) => {
+                    eprintln!("dora-lsp: failed to read message: {}", err);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut server = Server::new();
+    server.run(receiver)
+}
+
+struct Document {
+    path: String,
+    version: Option<u32>,
+    text: String,
+    dirty: bool,
+}
+
+struct Server {
+    stdlib: Stdlib,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn new() -> Server {
+        Server {
+            stdlib: Stdlib::new(),
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    fn run(&mut self, receiver: Receiver<String>) -> i32 {
+        loop {
+            let message = match receiver.recv() {
+                Ok(message) => message,
+                Err(_) => return 1,
+            };
+
+            if let Some(code) = self.handle(&message) {
+                return code;
+            }
+
+            // Apply all edits that already arrived before checking again,
+            // so a burst of keystrokes results in a single check.
+            while let Ok(message) = receiver.try_recv() {
+                if let Some(code) = self.handle(&message) {
+                    return code;
+                }
+            }
+
+            self.check_dirty();
+        }
+    }
+
+    // Returns the exit code once the client asks the server to exit.
+    fn handle(&mut self, message: &str) -> Option<i32> {
+        let message = match Json::parse(message) {
+            Ok(message) => message,
+
+            Err(msg) => {
+                send_error(Json::Null, -32700, &format!("parse error: {}", msg));
+                return None;
+            }
+        };
+
+        let method = message.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+
+            None => {
+                if method == "exit" {
+                    return Some(if self.shutdown { 0 } else { 1 });
+                }
+
+                self.notification(method, &params);
+                return None;
+            }
+        };
+
+        if self.shutdown {
+            send_error(id, -32600, "server is shutting down");
+            return None;
+        }
+
+        let result = match method {
+            "initialize" => Some(capabilities()),
+
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Json::Null)
+            }
+
+            "textDocument/hover" => Some(self.hover(&params)),
+            "textDocument/definition" => Some(self.definition(&params)),
+            "textDocument/completion" => Some(self.completion(&params)),
+            _ => None,
+        };
+
+        match result {
+            Some(result) => send(&Json::object(vec![
+                ("jsonrpc", Json::from("2.0")),
+                ("id", id),
+                ("result", result),
+            ])),
+
+            None => send_error(id, -32601, &format!("unknown method `{}`", method)),
+        }
+
+        None
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) {
+        let uri = match params
+            .lookup(&["textDocument", "uri"])
+            .and_then(|uri| uri.as_str())
+        {
+            Some(uri) => uri.to_string(),
+            None => return,
+        };
+
+        let version = params
+            .lookup(&["textDocument", "version"])
+            .and_then(|version| version.as_u32());
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params
+                    .lookup(&["textDocument", "text"])
+                    .and_then(|text| text.as_str())
+                    .unwrap_or("")
+                    .to_string();
+
+                let document = Document {
+                    path: text::uri_to_path(&uri),
+                    version,
+                    text,
+                    dirty: true,
+                };
+
+                self.documents.insert(uri, document);
+            }
+
+            "textDocument/didChange" => {
+                let document = match self.documents.get_mut(&uri) {
+                    Some(document) => document,
+                    None => return,
+                };
+
+                let changes = params
+                    .get("contentChanges")
+                    .and_then(|changes| changes.as_array())
+                    .unwrap_or(&[]);
+
+                for change in changes {
+                    let new_text = change.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                    let range = change.get("range").and_then(parse_range);
+
+                    text::apply_change(&mut document.text, range, new_text);
+                }
+
+                document.version = version;
+                document.dirty = true;
+            }
+
+            "textDocument/didClose" => {
+                if self.documents.remove(&uri).is_some() {
+                    publish_diagnostics(&uri, None, Json::Array(Vec::new()));
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn check_dirty(&mut self) {
+        for (uri, document) in &mut self.documents {
+            if !document.dirty {
+                continue;
+            }
+
+            document.dirty = false;
+
+            let diagnostics =
+                Analysis::check(&self.stdlib, &document.path, &document.text, |analysis| {
+                    analysis.diagnostics()
+                })
+                .into_iter()
+                .map(|diag| {
+                    Json::object(vec![
+                        ("range", range_json(diag.range)),
+                        ("severity", Json::from(severity(diag.severity))),
+                        ("code", Json::from(diag.code)),
+                        ("source", Json::from("dora")),
+                        ("message", Json::from(diag.message)),
+                    ])
+                })
+                .collect();
+
+            publish_diagnostics(uri, document.version, Json::Array(diagnostics));
+        }
+    }
+
+    // Returns the document and the offset of the requested position.
+    fn document_at(&self, params: &Json) -> Option<(&Document, usize)> {
+        let uri = params.lookup(&["textDocument", "uri"])?.as_str()?;
+        let line = params.lookup(&["position", "line"])?.as_u32()?;
+        let character = params.lookup(&["position", "character"])?.as_u32()?;
+
+        let document = self.documents.get(uri)?;
+        let offset = text::offset_at(&document.text, line, character);
+
+        Some((document, offset))
+    }
+
+    // Queries check the document again, the VM of a check can't outlive
+    // the AST it was built from.
+    fn hover(&self, params: &Json) -> Json {
+        let hover = self.document_at(params).and_then(|(document, offset)| {
+            Analysis::check(&self.stdlib, &document.path, &document.text, |analysis| {
+                analysis.hover(offset)
+            })
+        });
+
+        match hover {
+            Some(hover) => Json::object(vec![(
+                "contents",
+                Json::object(vec![
+                    ("kind", Json::from("markdown")),
+                    ("value", Json::from(format!("```dora\n{}\n```", hover))),
+                ]),
+            )]),
+
+            None => Json::Null,
+        }
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let location = self.document_at(params).and_then(|(document, offset)| {
+            Analysis::check(&self.stdlib, &document.path, &document.text, |analysis| {
+                analysis.definition(offset)
+            })
+        });
+
+        match location {
+            Some(location) => Json::object(vec![
+                ("uri", Json::from(text::path_to_uri(&location.path))),
+                ("range", range_json(location.range)),
+            ]),
+
+            None => Json::Null,
+        }
+    }
+
+    // Checks the document again without the member at the cursor.
+    fn completion(&self, params: &Json) -> Json {
+        let (document, offset) = match self.document_at(params) {
+            Some(result) => result,
+            None => return Json::Null,
+        };
+
+        let items = Analysis::completions(&self.stdlib, &document.path, &document.text, offset)
+            .into_iter()
+            .map(|completion| {
+                let kind = match completion.kind {
+                    CompletionKind::Method => 2u32,
+                    CompletionKind::Field => 5,
+                };
+
+                Json::object(vec![
+                    ("label", Json::from(completion.label)),
+                    ("kind", Json::from(kind)),
+                    ("detail", Json::from(completion.detail)),
+                ])
+            })
+            .collect();
+
+        Json::Array(items)
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                (
+                    "textDocumentSync",
+                    Json::object(vec![
+                        ("openClose", Json::from(true)),
+                        // incremental
+                        ("change", Json::from(2u32)),
+                    ]),
+                ),
+                ("hoverProvider", Json::from(true)),
+                ("definitionProvider", Json::from(true)),
+                (
+                    "completionProvider",
+                    Json::object(vec![(
+                        "triggerCharacters",
+                        Json::from(vec![Json::from(".")]),
+                    )]),
+                ),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![
+                ("name", Json::from("dora-lsp")),
+                ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+            ]),
+        ),
+    ])
+}
+
+fn parse_range(range: &Json) -> Option<Range> {
+    let position = |key: &str| -> Option<(u32, u32)> {
+        let position = range.get(key)?;
+        let line = position.get("line")?.as_u32()?;
+        let character = position.get("character")?.as_u32()?;
+
+        Some((line, character))
+    };
+
+    Some(Range {
+        start: position("start")?,
+        end: position("end")?,
+    })
+}
+
+fn range_json(range: Range) -> Json {
+    let position = |(line, character): (u32, u32)| {
+        Json::object(vec![
+            ("line", Json::from(line)),
+            ("character", Json::from(character)),
+        ])
+    };
+
+    Json::object(vec![
+        ("start", position(range.start)),
+        ("end", position(range.end)),
+    ])
+}
+
+fn severity(severity: Severity) -> u32 {
+    match severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    }
+}
+
+fn publish_diagnostics(uri: &str, version: Option<u32>, diagnostics: Json) {
+    let mut params = vec![("uri", Json::from(uri))];
+
+    if let Some(version) = version {
+        params.push(("version", Json::from(version)));
+    }
+
+    params.push(("diagnostics", diagnostics));
+
+    send(&Json::object(vec![
+        ("jsonrpc", Json::from("2.0")),
+        ("method", Json::from("textDocument/publishDiagnostics")),
+        ("params", Json::object(params)),
+    ]));
+}
+
+fn send_error(id: Json, code: i32, message: &str) {
+    send(&Json::object(vec![
+        ("jsonrpc", Json::from("2.0")),
+        ("id", id),
+        (
+            "error",
+            Json::object(vec![
+                ("code", Json::Number(code as f64)),
+                ("message", Json::from(message)),
+            ]),
+        ),
+    ]));
+}
+
+fn send(message: &Json) {
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+
+    write_message(&mut output, &message.to_string()).expect("failed to write message");
+}
+
+fn write_message<W: Write>(output: &mut W, content: &str) -> io::Result<()> {
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+// Reads the next message, returns None at the end of input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+
+            continue;
+        }
+
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+
+        if name.eq_ignore_ascii_case("Content-Length") {
+            length = Some(value.parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+            })?);
+        }
+    }
+
+    let mut content = vec![0; length.unwrap()];
+    input.read_exact(&mut content)?;
+
+    String::from_utf8(content)
+        .map(Some)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "message is not UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_message() {
+        let input = "Content-Length: 2\r\nContent-Type: x\r\n\r\n{}Content-Length: 4\r\n\r\nnull";
+        let mut input = input.as_bytes();
+
+        assert_eq!(Some("{}".into()), read_message(&mut input).unwrap());
+        assert_eq!(Some("null".into()), read_message(&mut input).unwrap());
+        assert_eq!(None, read_message(&mut input).unwrap());
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut output = Vec::new();
+        write_message(&mut output, "{\"a\":\"\u{e4}\"}").unwrap();
+
+        assert_eq!(
+            "Content-Length: 10\r\n\r\n{\"a\":\"\u{e4}\"}",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
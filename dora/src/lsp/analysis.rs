@@ -0,0 +1,1015 @@
+use std::collections::HashSet;
+use std::mem;
+
+use crate::driver::cmd::{Args, CollectorName};
+use crate::driver::start::{parse_bundled_stdlib, parse_reader};
+use crate::error::diag::Severity;
+use crate::lsp::text::{self, Range};
+use crate::semck;
+use crate::ty::BuiltinType;
+use crate::vm::module::ModuleId;
+use crate::vm::{
+    find_methods_in_class, CallType, ClassId, ConstId, EnumId, Fct, FctId, FctSrc, FieldId, FileId,
+    GlobalId, IdentType, StructId, VarId, VM,
+};
+
+use dora_parser::ast::visit::{self, Visitor};
+use dora_parser::ast::{Ast, Elem, Expr, Param, Stmt};
+use dora_parser::ast::{NodeId, Type};
+use dora_parser::interner::{Interner, Name};
+use dora_parser::lexer::position::{Position, Span};
+use dora_parser::lexer::reader::Reader;
+use dora_parser::lexer::File;
+use dora_parser::parser::NodeIdGenerator;
+
+// The bundled stdlib, parsed and checked once when the server starts.
+// Every check of a document starts from a copy of it.
+pub struct Stdlib {
+    ast: Ast,
+    files: Vec<File>,
+    interner: Interner,
+    id_generator: NodeIdGenerator,
+}
+
+impl Stdlib {
+    pub fn new() -> Stdlib {
+        let mut ast = Ast::new();
+        let empty = Ast::new();
+        let mut vm = VM::new(check_args(), &empty);
+
+        parse_bundled_stdlib(&mut vm, &mut ast).expect("failed parsing stdlib");
+        vm.program_files = vm.files.len();
+        vm.ast = &ast;
+        semck::check(&mut vm);
+        debug_assert!(!vm.diag.lock().has_errors());
+
+        let files = mem::replace(&mut vm.files, Vec::new());
+        let interner = vm.interner.clone();
+        let id_generator = vm.id_generator.clone();
+        drop(vm);
+
+        Stdlib {
+            ast,
+            files,
+            interner,
+            id_generator,
+        }
+    }
+}
+
+// Result of running the semantic passes on a single document, without
+// executing anything (like `--check`). Answers all queries for that
+// version of the document.
+pub struct Analysis<'ast> {
+    vm: Box<VM<'ast>>,
+    file: FileId,
+}
+
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+}
+
+pub struct Location {
+    pub path: String,
+    pub range: Range,
+}
+
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    Method,
+    Field,
+}
+
+enum Symbol {
+    Var(FctId, VarId),
+    Global(GlobalId),
+    Const(ConstId),
+    Field(BuiltinType, FieldId),
+    Fct(FctId),
+    Class(ClassId),
+    Struct(StructId),
+    Module(ModuleId),
+    Enum(EnumId),
+    Type(BuiltinType),
+}
+
+impl<'ast> Analysis<'ast> {
+    // Parses the document on top of the stdlib, runs semck on it and
+    // passes the result to `f`. The VM borrows the AST of this check, so
+    // the analysis only lives for the duration of `f`.
+    // Function bodies of the stdlib were checked before and are skipped.
+    // Checking continues after errors in function bodies where possible.
+    pub fn check<F, R>(stdlib: &Stdlib, path: &str, content: &str, f: F) -> R
+    where
+        F: FnOnce(&Analysis) -> R,
+    {
+        let empty = Ast::new();
+        let mut ast = stdlib.ast.clone();
+        let mut vm = VM::new(check_args(), &empty);
+
+        vm.interner = stdlib.interner.clone();
+        vm.id_generator = stdlib.id_generator.clone();
+        vm.files = stdlib.files.clone();
+        vm.program_files = vm.files.len();
+        vm.lib_bodies_checked = true;
+
+        let file = FileId::from(vm.files.len() as u32);
+        let reader = Reader::from_string(path, content);
+        parse_reader(reader, &mut vm, &mut ast).expect("failed parsing document");
+
+        vm.ast = &ast;
+        semck::check_keep_going(&mut vm);
+
+        f(&Analysis { vm, file })
+    }
+
+    fn content(&self) -> &str {
+        &self.vm.file(self.file).content
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let vm = &*self.vm;
+        let content = self.content();
+        let diag = vm.diag.lock();
+        let mut result = Vec::new();
+
+        for (file, err) in diag.parse_errors() {
+            if *file == self.file {
+                result.push(Diagnostic {
+                    range: error_range(content, err.pos, None),
+                    severity: Severity::Error,
+                    code: err.error.code(),
+                    message: err.error.message(),
+                });
+            }
+        }
+
+        for err in diag.errors() {
+            if err.file == self.file {
+                result.push(Diagnostic {
+                    range: error_range(content, err.pos, err.span),
+                    severity: Severity::Error,
+                    code: err.msg.code(),
+                    message: err.msg.message(),
+                });
+            }
+        }
+
+        for warning in diag.warnings() {
+            if warning.file == self.file {
+                result.push(Diagnostic {
+                    range: error_range(content, warning.pos, warning.span),
+                    severity: Severity::Warning,
+                    code: warning.msg.code(),
+                    message: warning.msg.message(),
+                });
+            }
+        }
+
+        result
+    }
+
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let vm = &*self.vm;
+        let symbol = self.symbol_at(offset)?;
+
+        let text = match symbol {
+            Symbol::Var(fct_id, var_id) => {
+                let fct = vm.fcts.idx(fct_id);
+                let fct = fct.read();
+                let src = fct.src().read();
+                let var = &src.vars[var_id];
+
+                format!(
+                    "{} {}: {}",
+                    if var.reassignable { "var" } else { "let" },
+                    vm.interner.str(var.name),
+                    var.ty.name(vm)
+                )
+            }
+
+            Symbol::Global(id) => {
+                let glob = vm.globals.idx(id);
+                let glob = glob.read();
+
+                format!(
+                    "{} {}: {}",
+                    if glob.reassignable { "var" } else { "let" },
+                    vm.interner.str(glob.name),
+                    glob.ty.name(vm)
+                )
+            }
+
+            Symbol::Const(id) => {
+                let xconst = vm.consts.idx(id);
+                let xconst = xconst.lock();
+
+                format!(
+                    "const {}: {}",
+                    vm.interner.str(xconst.name),
+                    xconst.ty.name(vm)
+                )
+            }
+
+            Symbol::Field(cls_ty, field_id) => {
+                let cls = vm.classes.idx(cls_ty.cls_id(vm)?);
+                let cls = cls.read();
+                let field = &cls.fields[field_id];
+
+                format!(
+                    "{} {}.{}: {}",
+                    if field.reassignable { "var" } else { "let" },
+                    vm.interner.str(cls.name),
+                    vm.interner.str(field.name),
+                    field.ty.name(vm)
+                )
+            }
+
+            Symbol::Fct(id) => {
+                let fct = vm.fcts.idx(id);
+                let fct = fct.read();
+                signature(vm, &fct)
+            }
+
+            Symbol::Class(id) => {
+                let cls = vm.classes.idx(id);
+                let cls = cls.read();
+                format!("class {}", vm.interner.str(cls.name))
+            }
+
+            Symbol::Struct(id) => {
+                let xstruct = vm.structs.idx(id);
+                let xstruct = xstruct.lock();
+                format!("struct {}", vm.interner.str(xstruct.name))
+            }
+
+            Symbol::Module(id) => {
+                let module = vm.modules.idx(id);
+                let module = module.read();
+                format!("module {}", vm.interner.str(module.name))
+            }
+
+            Symbol::Enum(id) => {
+                let xenum = vm.enums[id].read();
+                format!("enum {}", vm.interner.str(xenum.name))
+            }
+
+            Symbol::Type(ty) => {
+                if ty.is_error() {
+                    return None;
+                }
+
+                ty.name(vm)
+            }
+        };
+
+        Some(text)
+    }
+
+    pub fn definition(&self, offset: usize) -> Option<Location> {
+        let vm = &*self.vm;
+        let symbol = self.symbol_at(offset)?;
+
+        match symbol {
+            Symbol::Var(fct_id, var_id) => {
+                let fct = vm.fcts.idx(fct_id);
+                let fct = fct.read();
+                let src = fct.src().read();
+                let var = &src.vars[var_id];
+
+                let mut finder = DeclFinder {
+                    id: var.node_id,
+                    span: None,
+                };
+                finder.visit_fct(fct.ast);
+
+                let span = finder.span?;
+                self.location(fct.file, span.start() as usize, var.name)
+            }
+
+            Symbol::Global(id) => {
+                let glob = vm.globals.idx(id);
+                let glob = glob.read();
+                self.location_at(glob.file, glob.pos, glob.name)
+            }
+
+            Symbol::Const(id) => {
+                let xconst = vm.consts.idx(id);
+                let xconst = xconst.lock();
+                self.location_at(xconst.file, xconst.pos, xconst.name)
+            }
+
+            Symbol::Field(cls_ty, field_id) => {
+                let cls = vm.classes.idx(cls_ty.cls_id(vm)?);
+                let cls = cls.read();
+                let name = cls.fields[field_id].name;
+
+                let field_pos = self.vm.ast.files[cls.file.to_usize()]
+                    .elements
+                    .iter()
+                    .filter_map(|elem| match elem {
+                        Elem::ElemClass(ref class) if class.pos == cls.pos => Some(class),
+                        _ => None,
+                    })
+                    .flat_map(|class| class.fields.iter())
+                    .find(|field| field.name == name)
+                    .map(|field| field.pos)
+                    .unwrap_or(cls.pos);
+
+                self.location_at(cls.file, field_pos, name)
+            }
+
+            Symbol::Fct(id) => {
+                let fct = vm.fcts.idx(id);
+                let fct = fct.read();
+                self.location_at(fct.file, fct.pos, fct.name)
+            }
+
+            Symbol::Class(id) => {
+                let cls = vm.classes.idx(id);
+                let cls = cls.read();
+                self.location_at(cls.file, cls.pos, cls.name)
+            }
+
+            Symbol::Struct(id) => {
+                let xstruct = vm.structs.idx(id);
+                let xstruct = xstruct.lock();
+                self.location_at(xstruct.file, xstruct.pos, xstruct.name)
+            }
+
+            Symbol::Module(id) => {
+                let module = vm.modules.idx(id);
+                let module = module.read();
+                self.location_at(module.file, module.pos, module.name)
+            }
+
+            Symbol::Enum(id) => {
+                let xenum = vm.enums[id].read();
+                self.location_at(xenum.file, xenum.pos, xenum.name)
+            }
+
+            Symbol::Type(ty) => {
+                let cls = vm.classes.idx(ty.cls_id(vm)?);
+                let cls = cls.read();
+                self.location_at(cls.file, cls.pos, cls.name)
+            }
+        }
+    }
+
+    // Completes the member after a `.`: the partial member name is removed,
+    // the document checked again and the members of the receiver's class
+    // are listed.
+    pub fn completions(
+        stdlib: &Stdlib,
+        path: &str,
+        content: &str,
+        offset: usize,
+    ) -> Vec<Completion> {
+        let start = text::identifier_start(content, offset);
+
+        if !content[..start].ends_with('.') {
+            return Vec::new();
+        }
+
+        let dot = start - 1;
+        let end = text::identifier_end(content, offset);
+
+        // Dropping the member usually leaves a valid expression. When the
+        // dot ended a statement, a `;` keeps the receiver from becoming
+        // part of a syntax error.
+        for replacement in &["", ";"] {
+            let changed = format!("{}{}{}", &content[..dot], replacement, &content[end..]);
+
+            let completions = Analysis::check(stdlib, path, &changed, |analysis| {
+                analysis.receiver_type(dot).map(|ty| analysis.members(ty))
+            });
+
+            if let Some(completions) = completions {
+                return completions;
+            }
+        }
+
+        Vec::new()
+    }
+
+    fn receiver_type(&self, end: usize) -> Option<BuiltinType> {
+        let vm = &*self.vm;
+
+        for fct_id in self.fcts_at(end) {
+            let fct = vm.fcts.idx(fct_id);
+            let fct = fct.read();
+
+            let mut finder = ReceiverFinder {
+                end: end as u32,
+                exprs: Vec::new(),
+            };
+            finder.visit_fct(fct.ast);
+
+            let src = fct.src().read();
+
+            for expr in &finder.exprs {
+                if let Some(ty) = src.map_tys.get(expr.id()) {
+                    if !ty.is_error() {
+                        return Some(*ty);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn members(&self, ty: BuiltinType) -> Vec<Completion> {
+        let vm = &*self.vm;
+        let mut result = Vec::new();
+        let mut names = HashSet::new();
+
+        let mut cls_id = ty.cls_id(vm);
+
+        while let Some(id) = cls_id {
+            let cls = vm.classes.idx(id);
+            let cls = cls.read();
+
+            for field in &cls.fields {
+                result.push(Completion {
+                    label: vm.interner.str(field.name).to_string(),
+                    kind: CompletionKind::Field,
+                    detail: field.ty.name(vm),
+                });
+            }
+
+            for &method in &cls.methods {
+                let method = vm.fcts.idx(method);
+                let method = method.read();
+
+                if !method.is_static && !method.is_constructor {
+                    names.insert(method.name);
+                }
+            }
+
+            for &impl_id in &cls.impls {
+                let ximpl = vm.impls[impl_id].read();
+
+                for &method in &ximpl.methods {
+                    let method = vm.fcts.idx(method);
+                    let method = method.read();
+
+                    if !method.is_static {
+                        names.insert(method.name);
+                    }
+                }
+            }
+
+            cls_id = cls.parent_class.and_then(|parent| parent.cls_id(vm));
+        }
+
+        if let Some(object_cls_id) = ty.cls_id(vm) {
+            let cls = vm.classes.idx(object_cls_id);
+            let cls = cls.read();
+
+            for &extension_id in &cls.extensions {
+                let extension = vm.extensions[extension_id].read();
+                names.extend(extension.instance_names.keys().cloned());
+            }
+        }
+
+        let mut names = names
+            .into_iter()
+            .map(|name| (vm.interner.str(name).to_string(), name))
+            .collect::<Vec<_>>();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // resolve every name like a call would, so overridden methods
+        // and methods of non-matching extensions are left out
+        for (label, name) in names {
+            let candidates = find_methods_in_class(vm, ty, name, false);
+
+            if candidates.len() == 1 {
+                let fct = vm.fcts.idx(candidates[0].1);
+                let fct = fct.read();
+
+                result.push(Completion {
+                    label,
+                    kind: CompletionKind::Method,
+                    detail: signature(vm, &fct),
+                });
+            }
+        }
+
+        result
+    }
+
+    fn symbol_at(&self, offset: usize) -> Option<Symbol> {
+        let vm = &*self.vm;
+        let word = vm.interner.intern(identifier_at(self.content(), offset));
+
+        for fct_id in self.fcts_at(offset) {
+            let fct = vm.fcts.idx(fct_id);
+            let fct = fct.read();
+
+            let mut finder = NodeFinder {
+                offset: offset as u32,
+                word,
+                path: Vec::new(),
+                best: Vec::new(),
+                decl: None,
+            };
+            finder.visit_fct(fct.ast);
+
+            let src = fct.src().read();
+
+            if !finder.best.is_empty() {
+                if let Some(symbol) = resolve_expr(vm, fct_id, &src, &finder.best) {
+                    return Some(symbol);
+                }
+            } else if let Some(decl) = finder.decl {
+                if let Some(var) = src.vars.iter().find(|var| var.node_id == decl) {
+                    return Some(Symbol::Var(fct_id, var.id));
+                }
+            }
+        }
+
+        None
+    }
+
+    // Functions of the document containing the offset, innermost first.
+    fn fcts_at(&self, offset: usize) -> Vec<FctId> {
+        let vm = &*self.vm;
+        let mut fcts = Vec::new();
+
+        for fct in vm.fcts.iter() {
+            let fct = fct.read();
+            let span = fct.ast.span;
+
+            if fct.file == self.file && fct.is_src() && contains(span, offset as u32) {
+                fcts.push((span.count(), fct.id));
+            }
+        }
+
+        fcts.sort_by_key(|&(count, _)| count);
+        fcts.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn location_at(&self, file: FileId, pos: Position, name: Name) -> Option<Location> {
+        let content = &self.vm.file(file).content;
+        self.location(file, text::offset_of(content, pos), name)
+    }
+
+    // Location of `name` at or after the given offset.
+    fn location(&self, file: FileId, offset: usize, name: Name) -> Option<Location> {
+        let file_data = self.vm.file(file);
+
+        // all other files are from the stdlib bundled into the binary
+        if file != self.file {
+            return None;
+        }
+
+        let content = &file_data.content;
+        let name = self.vm.interner.str(name);
+        let start = find_word(content, offset, &name).unwrap_or(offset);
+
+        Some(Location {
+            path: file_data.name.clone(),
+            range: Range::new(content, start, start + name.len()),
+        })
+    }
+}
+
+fn resolve_expr(vm: &VM, fct_id: FctId, src: &FctSrc, path: &[&Expr]) -> Option<Symbol> {
+    let mut idx = path.len() - 1;
+
+    loop {
+        let expr = path[idx];
+
+        if let Some(ident) = src.map_idents.get(expr.id()) {
+            if let Some(symbol) = ident_symbol(fct_id, ident) {
+                return Some(symbol);
+            }
+        }
+
+        if let Some(call) = src.map_calls.get(expr.id()) {
+            match **call {
+                CallType::CtorNew(ty, _) | CallType::Ctor(ty, _) if ty.cls_id(vm).is_some() => {
+                    return Some(Symbol::Class(ty.cls_id(vm).unwrap()));
+                }
+
+                _ => {}
+            }
+
+            if let Some(fct_id) = call.fct_id() {
+                return Some(Symbol::Fct(fct_id));
+            }
+        }
+
+        // only continue with the parent if the expression names it
+        if idx == 0 || !is_name_of(path[idx - 1], expr) {
+            break;
+        }
+
+        idx -= 1;
+    }
+
+    let innermost = path[path.len() - 1];
+    src.map_tys.get(innermost.id()).map(|ty| Symbol::Type(*ty))
+}
+
+fn ident_symbol(fct_id: FctId, ident: &IdentType) -> Option<Symbol> {
+    let symbol = match *ident {
+        IdentType::Var(var_id) => Symbol::Var(fct_id, var_id),
+        IdentType::Global(id) => Symbol::Global(id),
+        IdentType::Field(ty, field_id) => Symbol::Field(ty, field_id),
+        IdentType::Struct(id) | IdentType::StructAndModule(id, _) => Symbol::Struct(id),
+        IdentType::Const(id) => Symbol::Const(id),
+        IdentType::Fct(id) | IdentType::FctType(id, _) => Symbol::Fct(id),
+        IdentType::Class(id) | IdentType::ClassType(id, _) | IdentType::ClassAndModule(id, _) => {
+            Symbol::Class(id)
+        }
+        IdentType::Module(id) => Symbol::Module(id),
+        IdentType::Enum(id) | IdentType::EnumValue(id, _) => Symbol::Enum(id),
+        IdentType::TypeParam(ty) => Symbol::Type(ty),
+
+        // methods are resolved at the enclosing call
+        IdentType::Method(_, _)
+        | IdentType::MethodType(_, _, _)
+        | IdentType::StaticMethod(_, _)
+        | IdentType::StaticMethodType(_, _, _)
+        | IdentType::TypeParamStaticMethod(_, _) => return None,
+    };
+
+    Some(symbol)
+}
+
+fn is_name_of(parent: &Expr, child: &Expr) -> bool {
+    let name = match *parent {
+        Expr::ExprDot(ref dot) => &dot.rhs,
+        Expr::ExprPath(ref path) => &path.rhs,
+        Expr::ExprCall(ref call) => &call.callee,
+        Expr::ExprTypeParam(ref type_param) => &type_param.callee,
+        _ => return false,
+    };
+
+    std::ptr::eq(&**name, child)
+}
+
+fn signature(vm: &VM, fct: &Fct) -> String {
+    format!("fun {}", fct.full_name(vm))
+}
+
+// Marks the span if there is one, otherwise the identifier or character
+// at the error position.
+// Arguments of a VM that only checks, like `--check`.
+fn check_args() -> Args {
+    let mut args: Args = Default::default();
+    args.flag_check = true;
+    args.flag_gc = Some(CollectorName::Zero);
+    args
+}
+
+fn error_range(content: &str, pos: Position, span: Option<Span>) -> Range {
+    if let Some(span) = span {
+        if span.is_valid() && span.end() as usize <= content.len() {
+            return Range::new(content, span.start() as usize, span.end() as usize);
+        }
+    }
+
+    let start = text::offset_of(content, pos);
+    let mut end = text::identifier_end(content, start);
+
+    if end == start {
+        end = content[start..]
+            .chars()
+            .next()
+            .map(|ch| start + ch.len_utf8())
+            .unwrap_or(start);
+    }
+
+    Range::new(content, start, end)
+}
+
+fn identifier_at(content: &str, offset: usize) -> &str {
+    let offset = offset.min(content.len());
+
+    if !content.is_char_boundary(offset) {
+        return "";
+    }
+
+    let start = text::identifier_start(content, offset);
+    let end = text::identifier_end(content, offset);
+
+    &content[start..end]
+}
+
+fn find_word(content: &str, offset: usize, word: &str) -> Option<usize> {
+    let mut start = offset.min(content.len());
+
+    while let Some(idx) = content[start..].find(word) {
+        let begin = start + idx;
+        let end = begin + word.len();
+
+        let before = content[..begin].chars().next_back();
+        let after = content[end..].chars().next();
+
+        if !before.map_or(false, text::is_identifier_char)
+            && !after.map_or(false, text::is_identifier_char)
+        {
+            return Some(begin);
+        }
+
+        start = end;
+    }
+
+    None
+}
+
+fn contains(span: Span, offset: u32) -> bool {
+    span.is_valid() && span.start() <= offset && offset <= span.end()
+}
+
+// Finds the innermost expression around an offset and the path to it.
+// Declarations of params and local variables are noted when the offset
+// is on their name.
+struct NodeFinder<'a> {
+    offset: u32,
+    word: Name,
+    path: Vec<&'a Expr>,
+    best: Vec<&'a Expr>,
+    decl: Option<NodeId>,
+}
+
+impl<'a> NodeFinder<'a> {
+    fn on_name(&self, span: Span, name_end: u32) -> bool {
+        span.is_valid() && span.start() <= self.offset && self.offset <= name_end
+    }
+}
+
+impl<'a> Visitor<'a> for NodeFinder<'a> {
+    fn visit_param(&mut self, p: &'a Param) {
+        if contains(p.span, self.offset) && self.word == p.name {
+            self.decl = Some(p.id);
+        }
+    }
+
+    fn visit_type(&mut self, _t: &'a Type) {}
+
+    fn visit_stmt(&mut self, s: &'a Stmt) {
+        match *s {
+            Stmt::StmtVar(ref var) => {
+                let name_end = var
+                    .data_type
+                    .as_ref()
+                    .map(|ty| ty.span().start())
+                    .or_else(|| var.expr.as_ref().map(|expr| expr.span().start()))
+                    .unwrap_or(var.span.end());
+
+                if self.on_name(var.span, name_end) && self.word == var.name {
+                    self.decl = Some(var.id);
+                }
+            }
+
+            Stmt::StmtFor(ref value) => {
+                if self.on_name(value.span, value.expr.span().start()) && self.word == value.name {
+                    self.decl = Some(value.id);
+                }
+            }
+
+            _ => {}
+        }
+
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_expr(&mut self, e: &'a Expr) {
+        let span = e.span();
+
+        if !span.is_valid() {
+            visit::walk_expr(self, e);
+            return;
+        }
+
+        if !contains(span, self.offset) {
+            return;
+        }
+
+        self.path.push(e);
+
+        if self.path.len() > self.best.len() {
+            self.best = self.path.clone();
+        }
+
+        visit::walk_expr(self, e);
+        self.path.pop();
+    }
+}
+
+// Collects the expressions ending at the given offset that can be the
+// receiver of a `.`. Parents are visited first, so the outermost
+// expression comes first.
+struct ReceiverFinder<'a> {
+    end: u32,
+    exprs: Vec<&'a Expr>,
+}
+
+impl<'a> Visitor<'a> for ReceiverFinder<'a> {
+    fn visit_type(&mut self, _t: &'a Type) {}
+
+    fn visit_expr(&mut self, e: &'a Expr) {
+        let span = e.span();
+
+        if span.is_valid() && span.end() == self.end {
+            match *e {
+                Expr::ExprUn(_)
+                | Expr::ExprBin(_)
+                | Expr::ExprConv(_)
+                | Expr::ExprLambda(_)
+                | Expr::ExprBlock(_)
+                | Expr::ExprIf(_)
+                | Expr::ExprError(_) => {}
+
+                _ => self.exprs.push(e),
+            }
+        }
+
+        visit::walk_expr(self, e);
+    }
+}
+
+// Finds the span of the param or statement that declares a variable.
+struct DeclFinder {
+    id: NodeId,
+    span: Option<Span>,
+}
+
+impl<'a> Visitor<'a> for DeclFinder {
+    fn visit_param(&mut self, p: &'a Param) {
+        if p.id == self.id {
+            self.span = Some(p.span);
+        }
+    }
+
+    fn visit_type(&mut self, _t: &'a Type) {}
+
+    fn visit_stmt(&mut self, s: &'a Stmt) {
+        if s.id() == self.id {
+            self.span = Some(s.span());
+        }
+
+        visit::walk_stmt(self, s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "@open class Foo(let x: Int32) {
+  var y: Int32 = 2;
+  fun sum() -> Int32 { self.x + self.y }
+}
+
+class Bar: Foo(1) {}
+
+fun main() {
+  let foo = Foo(1);
+  let z = foo.sum();
+  let b = Bar();
+  b.y = unknown;
+}
+";
+
+    fn offset(line: u32, character: u32) -> usize {
+        text::offset_at(CODE, line, character)
+    }
+
+    fn check<F, R>(path: &str, content: &str, f: F) -> R
+    where
+        F: FnOnce(&Analysis) -> R,
+    {
+        Analysis::check(&Stdlib::new(), path, content, f)
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = check("foo.dora", CODE, |analysis| analysis.diagnostics());
+
+        assert_eq!(1, diagnostics.len());
+        assert_eq!("E0004", diagnostics[0].code);
+        assert_eq!((11, 8), diagnostics[0].range.start);
+        assert_eq!((11, 15), diagnostics[0].range.end);
+    }
+
+    #[test]
+    fn test_hover() {
+        check("foo.dora", CODE, |analysis| {
+            // still answered although the document has errors
+            assert_eq!(Some("let foo: Foo".into()), analysis.hover(offset(8, 7)));
+            assert_eq!(Some("let z: Int32".into()), analysis.hover(offset(9, 6)));
+            assert_eq!(
+                Some("fun Foo.sum() -> Int32".into()),
+                analysis.hover(offset(9, 15))
+            );
+            assert_eq!(
+                Some("var Foo.y: Int32".into()),
+                analysis.hover(offset(11, 4))
+            );
+            assert_eq!(None, analysis.hover(offset(4, 0)));
+        });
+    }
+
+    #[test]
+    fn test_definition() {
+        check("foo.dora", CODE, |analysis| {
+            let location = analysis.definition(offset(9, 15)).unwrap();
+            assert_eq!("foo.dora", location.path);
+            assert_eq!((2, 6), location.range.start);
+            assert_eq!((2, 9), location.range.end);
+
+            let location = analysis.definition(offset(11, 4)).unwrap();
+            assert_eq!((1, 6), location.range.start);
+
+            let location = analysis.definition(offset(9, 11)).unwrap();
+            assert_eq!((8, 6), location.range.start);
+        });
+
+        // println is defined in the bundled stdlib
+        assert!(
+            check("a.dora", "fun main() { println(\"a\"); }", |analysis| {
+                analysis.definition(14)
+            })
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_errors_in_signatures() {
+        let code = "fun f(a: Int32, a: Int32) -> Int32 { a }
+module Foo {}
+fun main() {
+  let x = f(1, 2);
+  Foo::baz();
+}
+";
+        check("foo.dora", code, |analysis| {
+            let codes = analysis
+                .diagnostics()
+                .iter()
+                .map(|diag| diag.code)
+                .collect::<Vec<_>>();
+
+            assert_eq!(vec!["E0022", "E0008"], codes);
+            assert_eq!(
+                Some("let x: Int32".into()),
+                analysis.hover(text::offset_at(code, 3, 6))
+            );
+        });
+    }
+
+    #[test]
+    fn test_stdlib_bodies_skipped() {
+        check("foo.dora", "fun main() { let x = 1; }", |analysis| {
+            let vm = &analysis.vm;
+
+            for fct in vm.fcts.iter() {
+                let fct = fct.read();
+
+                if fct.is_src() {
+                    let checked = fct.src().read().map_tys.iter().next().is_some();
+                    assert_eq!(vm.is_program_file(fct.file), checked);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_completions() {
+        let code = "fun main() {\n  let b = Bar();\n  b.s\n}\n";
+        let code = format!("{}{}", &CODE[..CODE.find("fun main").unwrap()], code);
+        let offset = code.rfind("b.s").unwrap() + 3;
+
+        let stdlib = Stdlib::new();
+        let completions = Analysis::completions(&stdlib, "foo.dora", &code, offset);
+        let labels = completions
+            .iter()
+            .map(|completion| (completion.label.as_str(), completion.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                ("x", CompletionKind::Field),
+                ("y", CompletionKind::Field),
+                ("sum", CompletionKind::Method)
+            ],
+            labels
+        );
+
+        assert!(Analysis::completions(&stdlib, "foo.dora", &code, offset - 2).is_empty());
+    }
+}
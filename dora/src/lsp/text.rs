@@ -0,0 +1,245 @@
+use dora_parser::lexer::position::Position;
+
+// The protocol addresses text by zero-based line and UTF-16 code unit,
+// the parser by byte offset or one-based line and tab-expanded column.
+// These helpers convert between the three.
+
+const TABWIDTH: u32 = 4;
+
+// Zero-based (line, character) pairs as used by the protocol.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Range {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+}
+
+impl Range {
+    pub fn new(text: &str, start: usize, end: usize) -> Range {
+        Range {
+            start: position_at(text, start),
+            end: position_at(text, end),
+        }
+    }
+}
+
+// Converts a protocol position into a byte offset, clamped to the line.
+pub fn offset_at(text: &str, line: u32, character: u32) -> usize {
+    let start = line_start(text, line);
+    let mut units = 0;
+
+    for (idx, ch) in text[start..].char_indices() {
+        if ch == '\n' || units >= character {
+            return start + idx;
+        }
+
+        units += ch.len_utf16() as u32;
+    }
+
+    text.len()
+}
+
+// Converts a byte offset into a protocol position.
+pub fn position_at(text: &str, offset: usize) -> (u32, u32) {
+    let offset = floor_char_boundary(text, offset);
+    let before = &text[..offset];
+    let line = before.matches('\n').count() as u32;
+    let start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+    let character = before[start..]
+        .chars()
+        .map(|ch| ch.len_utf16() as u32)
+        .sum();
+
+    (line, character)
+}
+
+// Converts a parser position into a byte offset.
+pub fn offset_of(text: &str, pos: Position) -> usize {
+    if pos.line == 0 {
+        return 0;
+    }
+
+    let start = line_start(text, pos.line - 1);
+    let mut column = 1;
+
+    for (idx, ch) in text[start..].char_indices() {
+        if ch == '\n' || column >= pos.column {
+            return start + idx;
+        }
+
+        column = if ch == '\t' {
+            1 + TABWIDTH * ((column - 1) / TABWIDTH + 1)
+        } else {
+            column + 1
+        };
+    }
+
+    text.len()
+}
+
+// Replaces the given range, or the whole text if there is none.
+pub fn apply_change(text: &mut String, range: Option<Range>, new_text: &str) {
+    match range {
+        Some(range) => {
+            let start = offset_at(text, range.start.0, range.start.1);
+            let end = offset_at(text, range.end.0, range.end.1).max(start);
+            text.replace_range(start..end, new_text);
+        }
+
+        None => {
+            *text = new_text.to_string();
+        }
+    }
+}
+
+pub fn is_identifier_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+// Returns the start of the identifier that ends at the given offset.
+pub fn identifier_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, ch)| is_identifier_char(ch))
+        .last()
+        .map(|(idx, _)| idx)
+        .unwrap_or(offset)
+}
+
+// Returns the end of the identifier that starts at the given offset.
+pub fn identifier_end(text: &str, offset: usize) -> usize {
+    text[offset..]
+        .char_indices()
+        .find(|&(_, ch)| !is_identifier_char(ch))
+        .map(|(idx, _)| offset + idx)
+        .unwrap_or(text.len())
+}
+
+pub fn uri_to_path(uri: &str) -> String {
+    let path = if uri.starts_with("file://") {
+        &uri["file://".len()..]
+    } else {
+        uri
+    };
+
+    let bytes = path.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[idx + 1..idx + 3]).unwrap_or("");
+
+            if let Ok(value) = u8::from_str_radix(hex, 16) {
+                result.push(value);
+                idx += 3;
+                continue;
+            }
+        }
+
+        result.push(bytes[idx]);
+        idx += 1;
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+pub fn path_to_uri(path: &str) -> String {
+    let mut result = String::from("file://");
+
+    for &byte in path.as_bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    result
+}
+
+fn line_start(text: &str, line: u32) -> usize {
+    if line == 0 {
+        return 0;
+    }
+
+    text.match_indices('\n')
+        .nth(line as usize - 1)
+        .map(|(idx, _)| idx + 1)
+        .unwrap_or(text.len())
+}
+
+fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+    offset = offset.min(text.len());
+
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_and_position() {
+        let text = "fun main() {\n  let \u{e4}\u{1f600} = 1;\n}";
+
+        assert_eq!(0, offset_at(text, 0, 0));
+        assert_eq!(15, offset_at(text, 1, 2));
+        assert_eq!(19, offset_at(text, 1, 6));
+        assert_eq!(21, offset_at(text, 1, 7));
+        assert_eq!(30, offset_at(text, 1, 100));
+        assert_eq!(text.len(), offset_at(text, 7, 0));
+
+        assert_eq!((1, 2), position_at(text, 15));
+        assert_eq!((1, 7), position_at(text, 21));
+        assert_eq!((2, 1), position_at(text, text.len()));
+    }
+
+    #[test]
+    fn test_offset_of() {
+        let text = "a\n\tbc d\n";
+
+        assert_eq!(0, offset_of(text, Position::new(1, 1)));
+        assert_eq!(2, offset_of(text, Position::new(2, 1)));
+        assert_eq!(3, offset_of(text, Position::new(2, 5)));
+        assert_eq!(4, offset_of(text, Position::new(2, 6)));
+        assert_eq!(7, offset_of(text, Position::new(2, 20)));
+    }
+
+    #[test]
+    fn test_apply_change() {
+        let mut text = String::from("fun main() {\n  foo();\n}\n");
+
+        let range = |start, end| Some(Range { start, end });
+
+        apply_change(&mut text, range((1, 2), (1, 5)), "bar");
+        assert_eq!("fun main() {\n  bar();\n}\n", text);
+
+        apply_change(&mut text, range((1, 8), (2, 0)), "\n  x;\n");
+        assert_eq!("fun main() {\n  bar();\n  x;\n}\n", text);
+
+        apply_change(&mut text, None, "fun f() {}");
+        assert_eq!("fun f() {}", text);
+    }
+
+    #[test]
+    fn test_identifier_bounds() {
+        let text = "a.foo_1 + b";
+
+        assert_eq!(2, identifier_start(text, 5));
+        assert_eq!(7, identifier_end(text, 3));
+        assert_eq!(0, identifier_start(text, 1));
+        assert_eq!(2, identifier_start(text, 2));
+    }
+
+    #[test]
+    fn test_uri() {
+        assert_eq!("/tmp/a b.dora", uri_to_path("file:///tmp/a%20b.dora"));
+        assert_eq!("file:///tmp/a%20b.dora", path_to_uri("/tmp/a b.dora"));
+    }
+}
@@ -6,14 +6,17 @@ use crate::asm::Assembler;
 use crate::compiler::codegen::AnyReg;
 use crate::compiler::fct::{
     Code, Comments, GcPoint, GcPoints, JitDescriptor, LazyCompilationData, LazyCompilationSite,
-    PositionTable,
+    PositionTable, SourcePosition,
 };
-use crate::cpu::{Mem, Reg, SCRATCH};
+use crate::cpu::{Mem, Reg, REG_THREAD, SCRATCH};
 use crate::dseg::DSeg;
+use crate::gc::compressed;
+use crate::gc::Address;
 use crate::mem;
-use crate::object::Header;
-use crate::ty::MachineMode;
-use crate::vm::{Trap, VM};
+use crate::object::{offset_of_array_data, Header};
+use crate::threads::ThreadLocalData;
+use crate::ty::{BuiltinType, MachineMode};
+use crate::vm::{FileId, Trap, VM};
 use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use dora_parser::lexer::position::Position;
 
@@ -39,6 +42,7 @@ pub struct MacroAssembler {
     gcpoints: GcPoints,
     comments: Comments,
     positions: PositionTable,
+    file: Option<FileId>,
     scratch_registers: ScratchRegisters,
 }
 
@@ -54,6 +58,7 @@ impl MacroAssembler {
             gcpoints: GcPoints::new(),
             comments: Comments::new(),
             positions: PositionTable::new(),
+            file: None,
             scratch_registers: ScratchRegisters::new(),
         }
     }
@@ -139,9 +144,16 @@ impl MacroAssembler {
         lbl
     }
 
+    // sets the file for all following positions
+    pub fn set_file(&mut self, file: FileId) {
+        self.file = Some(file);
+    }
+
     pub fn emit_position(&mut self, position: Position) {
         let offset = self.pos() as u32;
-        self.positions.insert(offset, position);
+        let file = self.file.expect("file for position not set");
+        self.positions
+            .insert(offset, SourcePosition::new(file, position));
     }
 
     pub fn emit_gcpoint(&mut self, gcpoint: GcPoint) {
@@ -260,6 +272,101 @@ impl MacroAssembler {
         }
     }
 
+    // references in object fields and array elements are compressed when
+    // enabled: the slot holds a 32-bit offset from REG_HEAP_BASE
+    pub fn load_ref(&mut self, dest: Reg, mem: Mem) {
+        if compressed::enabled() {
+            self.load_mem(MachineMode::Int32, dest.into(), mem);
+            self.decode_ref(dest);
+        } else {
+            self.load_mem(MachineMode::Ptr, dest.into(), mem);
+        }
+    }
+
+    pub fn store_ref(&mut self, mem: Mem, src: Reg) {
+        if compressed::enabled() {
+            let scratch = self.get_scratch();
+            self.encode_ref(*scratch, src);
+            self.store_mem(MachineMode::Int32, mem, (*scratch).into());
+        } else {
+            self.store_mem(MachineMode::Ptr, mem, src.into());
+        }
+    }
+
+    pub fn load_field(&mut self, ty: BuiltinType, dest: AnyReg, mem: Mem) {
+        if ty.reference_type() {
+            self.load_ref(dest.reg(), mem);
+        } else {
+            self.load_mem(ty.mode(), dest, mem);
+        }
+    }
+
+    pub fn store_field(&mut self, ty: BuiltinType, mem: Mem, src: AnyReg) {
+        if ty.reference_type() {
+            self.store_ref(mem, src.reg());
+        } else {
+            self.store_mem(ty.mode(), mem, src);
+        }
+    }
+
+    // element `index` of `array`, object arrays store compressed references
+    pub fn array_elem(&self, mode: MachineMode, array: Reg, index: Reg) -> Mem {
+        let element_size = if mode == MachineMode::Ptr {
+            compressed::ref_size()
+        } else {
+            mode.size()
+        };
+
+        Mem::Index(array, index, element_size, offset_of_array_data())
+    }
+
+    pub fn store_array_elem(&mut self, mode: MachineMode, array: Reg, index: Reg, src: AnyReg) {
+        let elem = self.array_elem(mode, array, index);
+
+        if mode == MachineMode::Ptr {
+            self.store_ref(elem, src.reg());
+        } else {
+            self.store_mem(mode, elem, src);
+        }
+    }
+
+    // snapshot-at-the-beginning barrier: while the GC marks concurrently,
+    // the reference overwritten in `slot` is passed to the stub before the store.
+    // `narrow` slots hold compressed references, references in tuples never do.
+    pub fn emit_pre_barrier(&mut self, slot: Mem, narrow: bool, stub: Address) {
+        let done = self.create_label();
+        let scratch = self.get_scratch();
+
+        self.load_mem(
+            MachineMode::Int8,
+            (*scratch).into(),
+            Mem::Base(REG_THREAD, ThreadLocalData::concurrent_marking_offset()),
+        );
+        self.test_and_jump_if(CondCode::Zero, *scratch, done);
+
+        if narrow {
+            self.load_ref(*scratch, slot);
+        } else {
+            self.load_mem(MachineMode::Ptr, (*scratch).into(), slot);
+        }
+
+        self.cmp_reg_imm(MachineMode::Ptr, *scratch, 0);
+        self.jump_if(CondCode::Equal, done);
+
+        self.store_mem(
+            MachineMode::Ptr,
+            Mem::Base(REG_THREAD, ThreadLocalData::satb_value_offset()),
+            (*scratch).into(),
+        );
+
+        let disp = self.add_addr(stub.to_ptr());
+        let pos = self.pos() as i32;
+        self.load_constpool(*scratch, disp + pos);
+        self.call_reg(*scratch);
+
+        self.bind_label(done);
+    }
+
     pub fn fill_zero_dynamic(&mut self, obj: Reg, obj_end: Reg) {
         let done = self.create_label();
         let start = self.create_label();
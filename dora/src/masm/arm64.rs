@@ -7,10 +7,11 @@ use crate::cpu::asm;
 use crate::cpu::asm::*;
 use crate::cpu::reg::*;
 use crate::cpu::{FReg, Mem, Reg};
+use crate::gc::compressed;
 use crate::gc::swiper::CARD_SIZE_BITS;
 use crate::masm::{CondCode, Label, MacroAssembler};
 use crate::mem::ptr_width;
-use crate::object::{offset_of_array_data, offset_of_array_length, Header};
+use crate::object::{offset_of_array_length, Header};
 use crate::threads::ThreadLocalData;
 use crate::ty::{MachineMode, TypeList};
 use crate::vm::{get_vm, FctId, Trap};
@@ -118,7 +119,10 @@ impl MacroAssembler {
     }
 
     pub fn increase_stack_frame(&mut self, size: i32) {
-        if size > 0 {
+        if size > 0 && asm::fits_u12(size as u32) {
+            // does not clobber REG_TMP1
+            self.emit_u32(asm::sub_imm(1, REG_SP, REG_SP, size as u32, 0));
+        } else if size > 0 {
             self.load_int_const(MachineMode::Ptr, REG_TMP1, size as i64);
             self.emit_u32(asm::sub_extreg(
                 1,
@@ -132,7 +136,9 @@ impl MacroAssembler {
     }
 
     pub fn decrease_stack_frame(&mut self, size: i32) {
-        if size > 0 {
+        if size > 0 && asm::fits_u12(size as u32) {
+            self.emit_u32(asm::add_imm(1, REG_SP, REG_SP, size as u32, 0));
+        } else if size > 0 {
             self.load_int_const(MachineMode::Ptr, REG_TMP1, size as i64);
             self.emit_u32(asm::add_extreg(
                 1,
@@ -216,12 +222,53 @@ impl MacroAssembler {
         ));
     }
 
+    pub fn inline_cached_call(
+        &mut self,
+        pos: Position,
+        vtable_index: u32,
+        self_index: u32,
+        cls_type_params: TypeList,
+        _compile_stub: *const u8,
+    ) {
+        // inline caches are not implemented on arm64 yet
+        self.indirect_call(pos, vtable_index, self_index, cls_type_params);
+    }
+
     pub fn load_array_elem(&mut self, mode: MachineMode, dest: AnyReg, array: Reg, index: Reg) {
-        self.load_mem(
-            mode,
-            dest,
-            Mem::Index(array, index, mode.size(), offset_of_array_data()),
-        );
+        let elem = self.array_elem(mode, array, index);
+
+        if mode == MachineMode::Ptr {
+            self.load_ref(dest.reg(), elem);
+        } else {
+            self.load_mem(mode, dest, elem);
+        }
+    }
+
+    // reg = 0 stays nil, otherwise reg = heap base + (reg << 3)
+    pub fn decode_ref(&mut self, reg: Reg) {
+        let done = self.create_label();
+        self.emit_u32(asm::cmp_imm(1, reg, 0, 0));
+        self.jump_if(CondCode::Zero, done);
+        self.emit_u32(asm::add_shreg(
+            1,
+            reg,
+            REG_HEAP_BASE,
+            reg,
+            Shift::LSL,
+            compressed::shift() as u32,
+        ));
+        self.bind_label(done);
+    }
+
+    // dest = 0 for nil, otherwise dest = (src - heap base) >> 3
+    pub fn encode_ref(&mut self, dest: Reg, src: Reg) {
+        let done = self.create_label();
+        self.copy_reg(MachineMode::Ptr, dest, src);
+        self.emit_u32(asm::cmp_imm(1, dest, 0, 0));
+        self.jump_if(CondCode::Zero, done);
+        self.emit_u32(asm::sub_reg(1, dest, dest, REG_HEAP_BASE));
+        self.emit_u32(asm::lsr_imm(1, dest, dest, compressed::shift() as u32));
+        self.bind_label(done);
     }
 
     pub fn set(&mut self, dest: Reg, op: CondCode) {
@@ -1,16 +1,18 @@
 use byteorder::{LittleEndian, WriteBytesExt};
 use dora_parser::lexer::position::Position;
+use std::ptr;
 
 use crate::asm::{
     Address, Condition, Immediate, Register as AsmRegister, ScaleFactor, XmmRegister,
 };
 use crate::compiler::codegen::AnyReg;
-use crate::compiler::fct::LazyCompilationSite;
+use crate::compiler::fct::{InlineCacheSite, LazyCompilationSite, INLINE_CACHE_ENTRIES};
 use crate::cpu::*;
+use crate::gc::compressed;
 use crate::gc::swiper::CARD_SIZE_BITS;
 use crate::masm::{CondCode, Label, MacroAssembler};
 use crate::mem::{fits_i32, ptr_width};
-use crate::object::{offset_of_array_data, offset_of_array_length, Header};
+use crate::object::{offset_of_array_length, Header};
 use crate::threads::ThreadLocalData;
 use crate::ty::{MachineMode, TypeList};
 use crate::vm::{get_vm, FctId, Trap};
@@ -150,12 +152,112 @@ impl MacroAssembler {
         ));
     }
 
-    pub fn load_array_elem(&mut self, mode: MachineMode, dest: AnyReg, array: Reg, index: Reg) {
+    pub fn inline_cached_call(
+        &mut self,
+        pos: Position,
+        vtable_index: u32,
+        self_index: u32,
+        cls_type_params: TypeList,
+        compile_stub: *const u8,
+    ) {
+        let obj = REG_PARAMS[self_index as usize];
+        self.test_if_nil_bailout(pos, obj, Trap::NIL);
+
+        let miss_target = self.add_addr(compile_stub);
+        let entries: Vec<(i32, i32)> = (0..INLINE_CACHE_ENTRIES)
+            .map(|_| {
+                let fct = self.add_addr(ptr::null());
+                let vtable = self.add_addr(ptr::null());
+                (vtable, fct)
+            })
+            .collect();
+
+        let lbl_call = self.create_label();
+        let lbl_vtable = self.create_label();
+
+        // REG_RESULT = [obj] (load vtable)
+        self.load_mem(MachineMode::Ptr, REG_RESULT.into(), Mem::Base(obj, 0));
+
+        for &(vtable, fct) in &entries {
+            let lbl_next = self.create_label();
+
+            let pos = self.pos() as i32;
+            self.load_constpool(REG_TMP1, vtable + pos);
+            self.cmp_reg(MachineMode::Ptr, REG_RESULT, REG_TMP1);
+            self.jump_if(CondCode::NotEqual, lbl_next);
+
+            let pos = self.pos() as i32;
+            self.load_constpool(REG_RESULT, fct + pos);
+            self.jump(lbl_call);
+            self.bind_label(lbl_next);
+        }
+
+        // cache miss: invoke the compile stub to fill the cache or
+        // use the vtable once the call site became megamorphic
+        let pos = self.pos() as i32;
+        self.load_constpool(REG_TMP1, miss_target + pos);
+        self.cmp_zero(MachineMode::Ptr, REG_TMP1);
+        self.jump_if(CondCode::Zero, lbl_vtable);
+        self.copy_reg(MachineMode::Ptr, REG_RESULT, REG_TMP1);
+        self.jump(lbl_call);
+
+        self.bind_label(lbl_vtable);
+        let disp = VTable::offset_of_method_table() + (vtable_index as i32) * ptr_width();
         self.load_mem(
-            mode,
-            dest,
-            Mem::Index(array, index, mode.size(), offset_of_array_data()),
+            MachineMode::Ptr,
+            REG_RESULT.into(),
+            Mem::Base(REG_RESULT, disp),
+        );
+
+        // call *REG_RESULT
+        self.bind_label(lbl_call);
+        self.call_reg(REG_RESULT);
+
+        let pos = self.pos() as i32;
+        self.emit_lazy_compilation_site(LazyCompilationSite::InlineCache(InlineCacheSite {
+            receiver_is_first: self_index == 0,
+            vtable_index,
+            cls_tps: cls_type_params,
+            entries: entries
+                .into_iter()
+                .map(|(vtable, fct)| (vtable + pos, fct + pos))
+                .collect(),
+            miss_target: miss_target + pos,
+        }));
+    }
+
+    pub fn load_array_elem(&mut self, mode: MachineMode, dest: AnyReg, array: Reg, index: Reg) {
+        let elem = self.array_elem(mode, array, index);
+
+        if mode == MachineMode::Ptr {
+            self.load_ref(dest.reg(), elem);
+        } else {
+            self.load_mem(mode, dest, elem);
+        }
+    }
+
+    // reg = 0 stays nil, otherwise reg = heap base + (reg << 3)
+    pub fn decode_ref(&mut self, reg: Reg) {
+        let done = self.create_label();
+        self.asm.testl_rr(reg.into(), reg.into());
+        self.jump_if(CondCode::Zero, done);
+        self.asm.lea(
+            reg.into(),
+            Address::array(REG_HEAP_BASE.into(), reg.into(), ScaleFactor::Eight, 0),
         );
+        self.bind_label(done);
+    }
+
+    // dest = 0 for nil, otherwise dest = (src - heap base) >> 3
+    pub fn encode_ref(&mut self, dest: Reg, src: Reg) {
+        let done = self.create_label();
+        self.asm.movq_rr(dest.into(), src.into());
+        self.asm.testq_rr(dest.into(), dest.into());
+        self.jump_if(CondCode::Zero, done);
+        self.asm.subq_rr(dest.into(), REG_HEAP_BASE.into());
+        self.asm
+            .shrq_ri(dest.into(), Immediate(compressed::shift() as i64));
+        self.bind_label(done);
     }
 
     pub fn set(&mut self, dest: Reg, op: CondCode) {
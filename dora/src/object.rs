@@ -8,13 +8,14 @@ use std::slice;
 use std::str;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::gc::compressed;
 use crate::gc::root::Slot;
 use crate::gc::{Address, Region};
 use crate::handle::{root, Handle};
 use crate::mem;
 use crate::size::InstanceSize;
 use crate::ty::BuiltinType;
-use crate::vm::{ClassDef, ClassDefId, FieldId, VM};
+use crate::vm::{ClassDef, ClassDefId, FctId, FieldId, VM};
 use crate::vtable::VTable;
 
 #[repr(C)]
@@ -31,6 +32,10 @@ const MARK_BITS: usize = 2;
 const MARK_MASK: usize = (2 << MARK_BITS) - 1;
 const FWD_MASK: usize = !0 & !MARK_MASK;
 
+// compiled code stores the allocation site in the upper half of the
+// forwarding word, it is only valid while the object is in the young generation
+pub const ALLOC_SITE_SHIFT: usize = 32;
+
 impl Header {
     #[cfg(test)]
     fn new() -> Header {
@@ -162,6 +167,11 @@ impl Header {
         self.fwdptr.store(0, Ordering::Relaxed);
     }
 
+    #[inline(always)]
+    pub fn alloc_site(&self) -> u32 {
+        (self.fwdptr.load(Ordering::Relaxed) >> ALLOC_SITE_SHIFT) as u32
+    }
+
     #[inline(always)]
     pub fn fwdptr_non_atomic(&self) -> Address {
         let fwdptr = self.fwdptr.load(Ordering::Relaxed);
@@ -322,6 +332,10 @@ where
     F: FnMut(Slot),
 {
     for &offset in &cls.ref_fields {
+        f(Slot::field(object.offset(offset as usize)));
+    }
+
+    for &offset in &cls.tuple_ref_fields {
         f(Slot::at(object.offset(offset as usize)));
     }
 }
@@ -333,8 +347,9 @@ where
     let array = unsafe { &*object.to_ptr::<StrArray>() };
 
     // walk through all objects in array
+    let ref_size = compressed::ref_size_usize();
     let mut ptr = Address::from_ptr(array.data());
-    let mut limit = ptr.add_ptr(array.len() as usize);
+    let mut limit = ptr.offset(array.len() * ref_size);
 
     // visit elements until `limit` reached
     if let Some(range) = range {
@@ -343,8 +358,8 @@ where
     }
 
     while ptr < limit {
-        f(Slot::at(ptr));
-        ptr = ptr.add_ptr(1);
+        f(Slot::field(ptr));
+        ptr = ptr.offset(ref_size);
     }
 }
 
@@ -359,7 +374,7 @@ fn visit_tuple_array_refs<F>(
 {
     let array = unsafe { &*object.to_ptr::<StrArray>() };
 
-    if cls.ref_fields.is_empty() {
+    if cls.tuple_ref_fields.is_empty() {
         return;
     }
 
@@ -382,7 +397,7 @@ fn visit_tuple_array_refs<F>(
 
     while ptr < limit {
         // each of those elements might have multiple references
-        for &offset in &cls.ref_fields {
+        for &offset in &cls.tuple_ref_fields {
             f(Slot::at(ptr.offset(offset as usize)));
         }
         ptr = ptr.offset(element_size as usize);
@@ -515,6 +530,26 @@ impl Str {
         str_alloc_heap(vm, 0)
     }
 
+    /// returns the string for the literal at `site` in function `fct_id`,
+    /// functions compiled again after a code collection reuse the string
+    pub fn from_literal(vm: &VM, fct_id: FctId, site: usize, value: &str) -> Ref<Str> {
+        let mut literals = vm.str_literals.lock();
+
+        if let Some(&addr) = literals.get(&(fct_id, site)) {
+            let handle: Ref<Str> = addr.into();
+
+            // sites of the baseline compiler and cannon might overlap
+            if handle.content() == value.as_bytes() {
+                return handle;
+            }
+        }
+
+        let handle = Str::from_buffer_in_perm(vm, value.as_bytes());
+        literals.insert((fct_id, site), handle.address());
+
+        handle
+    }
+
     /// allocates string from buffer in permanent space
     pub fn from_buffer_in_perm(vm: &VM, buf: &[u8]) -> Ref<Str> {
         let mut handle = str_alloc_perm(vm, buf.len());
@@ -689,6 +724,7 @@ where
     let vtable: *const VTable = &**cls.vtable.as_ref().unwrap();
     let mut handle: Ref<Str> = ptr.into();
     handle.header_mut().set_vtblptr(Address::from_ptr(vtable));
+    handle.header_mut().clear_fwdptr();
 
     handle
 }
@@ -843,8 +879,28 @@ pub fn write_ref(vm: &VM, obj: Ref<Obj>, cls_id: ClassDefId, fid: FieldId, value
     let slot = obj.address().offset(field.offset as usize);
     assert!(field.ty.reference_type());
 
+    Slot::field(slot).set(value.address());
+}
+
+pub fn read_ref(vm: &VM, obj: Ref<Obj>, cls_id: ClassDefId, fid: FieldId) -> Ref<Obj> {
+    let cls_def = vm.class_defs.idx(cls_id);
+    let cls_def = cls_def.read();
+    let field = &cls_def.fields[fid.idx()];
+    let slot = obj.address().offset(field.offset as usize);
+    assert!(field.ty.reference_type());
+
+    Slot::field(slot).get().into()
+}
+
+pub fn write_bool(vm: &VM, obj: Ref<Obj>, cls_id: ClassDefId, fid: FieldId, value: bool) {
+    let cls_def = vm.class_defs.idx(cls_id);
+    let cls_def = cls_def.read();
+    let field = &cls_def.fields[fid.idx()];
+    let slot = obj.address().offset(field.offset as usize);
+    assert!(field.ty == BuiltinType::Bool);
+
     unsafe {
-        *slot.to_mut_ptr::<Address>() = value.address();
+        *slot.to_mut_ptr::<bool>() = value;
     }
 }
 
@@ -860,16 +916,16 @@ pub fn write_int32(vm: &VM, obj: Ref<Obj>, cls_id: ClassDefId, fid: FieldId, val
     }
 }
 
+// fields are accessed through the class layout, which depends on
+// compressed references
+#[repr(C)]
 pub struct Stacktrace {
     pub header: Header,
-    pub backtrace: Ref<Int32Array>,
-    pub elements: Ref<Obj>,
 }
 
+#[repr(C)]
 pub struct StacktraceElement {
     pub header: Header,
-    pub name: Ref<Str>,
-    pub line: i32,
 }
 
 #[cfg(test)]
@@ -0,0 +1,801 @@
+// Implements the GDB JIT compilation interface: every piece of generated code
+// is described by an in-memory ELF object containing a symbol for the code,
+// DWARF line tables built from the position table and call frame information.
+// gdb sets a breakpoint on `__jit_debug_register_code` and reads the objects
+// from `__jit_debug_descriptor`, which allows it to symbolize, backtrace
+// and set breakpoints in Dora code.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use crate::compiler::fct::{Code, SourcePosition};
+use crate::vm::{FileId, VM};
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+
+#[repr(C)]
+pub struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+pub struct JitDebugDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+#[no_mangle]
+pub static mut __jit_debug_descriptor: JitDebugDescriptor = JitDebugDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+// gdb places a breakpoint in this function, it must not be inlined or removed.
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    compiler_fence(Ordering::SeqCst);
+}
+
+lazy_static! {
+    static ref REGISTRATION_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub fn register_with_gdb(code: &Code, vm: &VM, name: &str) {
+    if !vm.args.flag_enable_gdb {
+        return;
+    }
+
+    let object = create_object(code, vm, name).into_boxed_slice();
+    let symfile_size = object.len() as u64;
+
+    // entries are never unregistered, the ELF object is kept alive
+    // for the rest of the program
+    let symfile_addr = Box::leak(object).as_ptr();
+
+    let _lock = REGISTRATION_LOCK.lock();
+
+    unsafe {
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: __jit_debug_descriptor.first_entry,
+            prev_entry: ptr::null_mut(),
+            symfile_addr,
+            symfile_size,
+        }));
+
+        if !__jit_debug_descriptor.first_entry.is_null() {
+            (*__jit_debug_descriptor.first_entry).prev_entry = entry;
+        }
+
+        __jit_debug_descriptor.first_entry = entry;
+        __jit_debug_descriptor.relevant_entry = entry;
+        __jit_debug_descriptor.action_flag = JIT_REGISTER_FN;
+        __jit_debug_register_code();
+        __jit_debug_descriptor.action_flag = JIT_NOACTION;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    pub const ELF_MACHINE: u16 = 62; // EM_X86_64
+
+    // DWARF register numbers of frame pointer and return address
+    pub const DWARF_FP: u8 = 6;
+    pub const DWARF_RA: u8 = 16;
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    pub const ELF_MACHINE: u16 = 183; // EM_AARCH64
+
+    // DWARF register numbers of frame pointer and link register
+    pub const DWARF_FP: u8 = 29;
+    pub const DWARF_RA: u8 = 30;
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+const TEXT_SECTION_INDEX: u16 = 1;
+
+const ELF_HEADER_SIZE: usize = 64;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+struct Section {
+    name: &'static str,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    entsize: u64,
+    data: Vec<u8>,
+}
+
+impl Section {
+    fn debug(name: &'static str, data: Vec<u8>) -> Section {
+        Section {
+            name,
+            kind: SHT_PROGBITS,
+            flags: 0,
+            addr: 0,
+            size: data.len() as u64,
+            link: 0,
+            info: 0,
+            entsize: 0,
+            data,
+        }
+    }
+}
+
+fn create_object(code: &Code, vm: &VM, name: &str) -> Vec<u8> {
+    let start = code.instruction_start().to_usize() as u64;
+    let size = (code.instruction_end().to_usize() - code.instruction_start().to_usize()) as u64;
+
+    let mut strtab = vec![0];
+    let name_offset = strtab.len() as u32;
+    strtab.extend_from_slice(name.as_bytes());
+    strtab.push(0);
+
+    let mut symtab = vec![0; SYMBOL_SIZE];
+    write_symbol(&mut symtab, name_offset, start, size);
+
+    let mut sections = vec![
+        Section {
+            name: ".text",
+            kind: SHT_NOBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            addr: start,
+            size,
+            link: 0,
+            info: 0,
+            entsize: 0,
+            data: Vec::new(),
+        },
+        Section {
+            name: ".symtab",
+            kind: SHT_SYMTAB,
+            flags: 0,
+            addr: 0,
+            size: symtab.len() as u64,
+            // index of .strtab, the first symbol is the only local one
+            link: 3,
+            info: 1,
+            entsize: SYMBOL_SIZE as u64,
+            data: symtab,
+        },
+        Section {
+            name: ".strtab",
+            kind: SHT_STRTAB,
+            flags: 0,
+            addr: 0,
+            size: strtab.len() as u64,
+            link: 0,
+            info: 0,
+            entsize: 0,
+            data: strtab,
+        },
+        Section::debug(".debug_frame", debug_frame(start, size)),
+    ];
+
+    let positions = code.positions();
+
+    if !positions.is_empty() {
+        let files = FileTable::new(vm, positions);
+
+        sections.push(Section::debug(".debug_abbrev", debug_abbrev()));
+        sections.push(Section::debug(
+            ".debug_info",
+            debug_info(name, &files, start, size),
+        ));
+        sections.push(Section::debug(
+            ".debug_line",
+            debug_line(&files, positions, start, size),
+        ));
+    }
+
+    write_elf(sections)
+}
+
+fn write_elf(mut sections: Vec<Section>) -> Vec<u8> {
+    let mut shstrtab = vec![0];
+    let mut names = Vec::new();
+
+    for section in &sections {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(section.name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    names.push(shstrtab.len() as u32);
+    shstrtab.extend_from_slice(b".shstrtab\0");
+
+    sections.push(Section {
+        name: ".shstrtab",
+        kind: SHT_STRTAB,
+        flags: 0,
+        addr: 0,
+        size: shstrtab.len() as u64,
+        link: 0,
+        info: 0,
+        entsize: 0,
+        data: shstrtab,
+    });
+
+    // section contents follow the ELF header, section headers come last
+    let mut offsets = Vec::new();
+    let mut offset = ELF_HEADER_SIZE;
+
+    for section in &sections {
+        offsets.push(offset);
+        offset += section.data.len();
+    }
+
+    let section_headers = align(offset, 8);
+    let section_count = sections.len() + 1;
+
+    let mut buf = Vec::with_capacity(section_headers + section_count * SECTION_HEADER_SIZE);
+
+    // e_ident: 64-bit, little endian, current version
+    buf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    buf.extend_from_slice(&[0; 8]);
+    buf.write_u16::<LittleEndian>(2).unwrap(); // ET_EXEC
+    buf.write_u16::<LittleEndian>(arch::ELF_MACHINE).unwrap();
+    buf.write_u32::<LittleEndian>(1).unwrap(); // e_version
+    buf.write_u64::<LittleEndian>(0).unwrap(); // e_entry
+    buf.write_u64::<LittleEndian>(0).unwrap(); // e_phoff
+    buf.write_u64::<LittleEndian>(section_headers as u64)
+        .unwrap();
+    buf.write_u32::<LittleEndian>(0).unwrap(); // e_flags
+    buf.write_u16::<LittleEndian>(ELF_HEADER_SIZE as u16)
+        .unwrap();
+    buf.write_u16::<LittleEndian>(0).unwrap(); // e_phentsize
+    buf.write_u16::<LittleEndian>(0).unwrap(); // e_phnum
+    buf.write_u16::<LittleEndian>(SECTION_HEADER_SIZE as u16)
+        .unwrap();
+    buf.write_u16::<LittleEndian>(section_count as u16).unwrap();
+    buf.write_u16::<LittleEndian>(sections.len() as u16)
+        .unwrap(); // e_shstrndx
+    debug_assert_eq!(buf.len(), ELF_HEADER_SIZE);
+
+    for section in &sections {
+        buf.extend_from_slice(&section.data);
+    }
+
+    buf.resize(section_headers, 0);
+
+    // null section
+    buf.extend_from_slice(&[0; SECTION_HEADER_SIZE]);
+
+    for ((section, &name), &offset) in sections.iter().zip(&names).zip(&offsets) {
+        buf.write_u32::<LittleEndian>(name).unwrap();
+        buf.write_u32::<LittleEndian>(section.kind).unwrap();
+        buf.write_u64::<LittleEndian>(section.flags).unwrap();
+        buf.write_u64::<LittleEndian>(section.addr).unwrap();
+        buf.write_u64::<LittleEndian>(offset as u64).unwrap();
+        buf.write_u64::<LittleEndian>(section.size).unwrap();
+        buf.write_u32::<LittleEndian>(section.link).unwrap();
+        buf.write_u32::<LittleEndian>(section.info).unwrap();
+        buf.write_u64::<LittleEndian>(1).unwrap(); // sh_addralign
+        buf.write_u64::<LittleEndian>(section.entsize).unwrap();
+    }
+
+    buf
+}
+
+fn write_symbol(buf: &mut Vec<u8>, name: u32, value: u64, size: u64) {
+    const STB_GLOBAL: u8 = 1;
+    const STT_FUNC: u8 = 2;
+
+    buf.write_u32::<LittleEndian>(name).unwrap();
+    buf.push((STB_GLOBAL << 4) | STT_FUNC);
+    buf.push(0); // st_other
+    buf.write_u16::<LittleEndian>(TEXT_SECTION_INDEX).unwrap();
+    buf.write_u64::<LittleEndian>(value).unwrap();
+    buf.write_u64::<LittleEndian>(size).unwrap();
+}
+
+// All generated code sets up a frame pointer in its prolog: the canonical
+// frame address is the frame pointer plus 16, the caller's frame pointer and
+// the return address are stored just below it. This is not precise while
+// executing the prolog or epilog but holds at all call sites and traps.
+fn debug_frame(start: u64, size: u64) -> Vec<u8> {
+    const DW_CFA_DEF_CFA: u8 = 0x0c;
+    const DW_CFA_OFFSET: u8 = 0x80;
+    const DATA_ALIGNMENT: i64 = -8;
+
+    let mut cie = Vec::new();
+    cie.write_u32::<LittleEndian>(0xffff_ffff).unwrap(); // CIE_id
+    cie.push(1); // version
+    cie.push(0); // empty augmentation
+    write_uleb128(&mut cie, 1); // code alignment
+    write_sleb128(&mut cie, DATA_ALIGNMENT);
+    cie.push(arch::DWARF_RA);
+    cie.push(DW_CFA_DEF_CFA);
+    write_uleb128(&mut cie, arch::DWARF_FP as u64);
+    write_uleb128(&mut cie, 16);
+    cie.push(DW_CFA_OFFSET | arch::DWARF_FP);
+    write_uleb128(&mut cie, 2);
+    cie.push(DW_CFA_OFFSET | arch::DWARF_RA);
+    write_uleb128(&mut cie, 1);
+
+    let mut buf = Vec::new();
+    write_frame_entry(&mut buf, cie);
+
+    let mut fde = Vec::new();
+    fde.write_u32::<LittleEndian>(0).unwrap(); // offset of CIE
+    fde.write_u64::<LittleEndian>(start).unwrap();
+    fde.write_u64::<LittleEndian>(size).unwrap();
+    write_frame_entry(&mut buf, fde);
+
+    buf
+}
+
+fn write_frame_entry(buf: &mut Vec<u8>, mut entry: Vec<u8>) {
+    // pad with DW_CFA_nop such that the entry is aligned to the address size
+    entry.resize(align(entry.len() + 4, 8) - 4, 0);
+    buf.write_u32::<LittleEndian>(entry.len() as u32).unwrap();
+    buf.extend_from_slice(&entry);
+}
+
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_AT_PRODUCER: u8 = 0x25;
+
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA4: u8 = 0x06;
+const DW_FORM_STRING: u8 = 0x08;
+
+fn debug_abbrev() -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    buf.extend_from_slice(&[1, DW_TAG_COMPILE_UNIT, 1]);
+    buf.extend_from_slice(&[DW_AT_NAME, DW_FORM_STRING]);
+    buf.extend_from_slice(&[DW_AT_PRODUCER, DW_FORM_STRING]);
+    buf.extend_from_slice(&[DW_AT_LOW_PC, DW_FORM_ADDR]);
+    buf.extend_from_slice(&[DW_AT_HIGH_PC, DW_FORM_ADDR]);
+    buf.extend_from_slice(&[DW_AT_STMT_LIST, DW_FORM_DATA4]);
+    buf.extend_from_slice(&[0, 0]);
+
+    buf.extend_from_slice(&[2, DW_TAG_SUBPROGRAM, 0]);
+    buf.extend_from_slice(&[DW_AT_NAME, DW_FORM_STRING]);
+    buf.extend_from_slice(&[DW_AT_LOW_PC, DW_FORM_ADDR]);
+    buf.extend_from_slice(&[DW_AT_HIGH_PC, DW_FORM_ADDR]);
+    buf.extend_from_slice(&[0, 0]);
+
+    buf.push(0);
+    buf
+}
+
+fn debug_info(name: &str, files: &FileTable, start: u64, size: u64) -> Vec<u8> {
+    let mut unit = Vec::new();
+    unit.write_u16::<LittleEndian>(2).unwrap(); // DWARF version
+    unit.write_u32::<LittleEndian>(0).unwrap(); // offset into .debug_abbrev
+    unit.push(8); // address size
+
+    unit.push(1);
+    write_string(&mut unit, &files.names[0]);
+    write_string(&mut unit, "dora");
+    unit.write_u64::<LittleEndian>(start).unwrap();
+    unit.write_u64::<LittleEndian>(start + size).unwrap();
+    unit.write_u32::<LittleEndian>(0).unwrap(); // offset into .debug_line
+
+    unit.push(2);
+    write_string(&mut unit, name);
+    unit.write_u64::<LittleEndian>(start).unwrap();
+    unit.write_u64::<LittleEndian>(start + size).unwrap();
+
+    // end of children of the compile unit
+    unit.push(0);
+
+    let mut buf = Vec::new();
+    buf.write_u32::<LittleEndian>(unit.len() as u32).unwrap();
+    buf.extend_from_slice(&unit);
+    buf
+}
+
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_SET_COLUMN: u8 = 5;
+
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+fn debug_line(
+    files: &FileTable,
+    positions: &[(u32, SourcePosition)],
+    start: u64,
+    size: u64,
+) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.push(1); // minimum instruction length
+    header.push(1); // default is_stmt
+    header.push(LINE_BASE as u8);
+    header.push(LINE_RANGE);
+    header.push(OPCODE_BASE);
+    header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+    header.push(0); // no include directories
+
+    for name in &files.names {
+        write_string(&mut header, name);
+        write_uleb128(&mut header, 0); // directory
+        write_uleb128(&mut header, 0); // modification time
+        write_uleb128(&mut header, 0); // file size
+    }
+
+    header.push(0);
+
+    let mut program = Vec::new();
+    program.extend_from_slice(&[0, 9, DW_LNE_SET_ADDRESS]);
+    program.write_u64::<LittleEndian>(start).unwrap();
+
+    let mut address = 0;
+    let mut file = 1;
+    let mut line: i64 = 1;
+
+    // Positions are recorded after the instruction they belong to (e.g. the
+    // return address of a call), so a row starts right after the previous
+    // position.
+    for &(offset, position) in positions {
+        let position_file = files.index(position.file);
+
+        if position_file != file {
+            program.push(DW_LNS_SET_FILE);
+            write_uleb128(&mut program, position_file);
+            file = position_file;
+        }
+
+        let position_line = position.pos.line as i64;
+
+        if position_line != line {
+            program.push(DW_LNS_ADVANCE_LINE);
+            write_sleb128(&mut program, position_line - line);
+            line = position_line;
+        }
+
+        program.push(DW_LNS_SET_COLUMN);
+        write_uleb128(&mut program, position.pos.column as u64);
+        program.push(DW_LNS_COPY);
+
+        program.push(DW_LNS_ADVANCE_PC);
+        write_uleb128(&mut program, (offset - address) as u64);
+        address = offset;
+    }
+
+    program.push(DW_LNS_ADVANCE_PC);
+    write_uleb128(&mut program, size - address as u64);
+    program.extend_from_slice(&[0, 1, DW_LNE_END_SEQUENCE]);
+
+    let mut unit = Vec::new();
+    unit.write_u16::<LittleEndian>(2).unwrap(); // DWARF version
+    unit.write_u32::<LittleEndian>(header.len() as u32).unwrap();
+    unit.extend_from_slice(&header);
+    unit.extend_from_slice(&program);
+
+    let mut buf = Vec::new();
+    buf.write_u32::<LittleEndian>(unit.len() as u32).unwrap();
+    buf.extend_from_slice(&unit);
+    buf
+}
+
+// files referenced by the position table, file indices start at 1
+struct FileTable {
+    names: Vec<String>,
+    indices: HashMap<FileId, u64>,
+}
+
+impl FileTable {
+    fn new(vm: &VM, positions: &[(u32, SourcePosition)]) -> FileTable {
+        let mut names = Vec::new();
+        let mut indices = HashMap::new();
+
+        for &(_, position) in positions {
+            indices.entry(position.file).or_insert_with(|| {
+                names.push(vm.file(position.file).name.clone());
+                names.len() as u64
+            });
+        }
+
+        FileTable { names, indices }
+    }
+
+    fn index(&self, file: FileId) -> u64 {
+        self.indices[&file]
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+fn write_uleb128(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buf.push(byte);
+            break;
+        }
+
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_sleb128(buf: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+
+        if done {
+            buf.push(byte);
+            break;
+        }
+
+        buf.push(byte | 0x80);
+    }
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::fct::{
+        Comments, GcPoints, JitDescriptor, LazyCompilationData, PositionTable,
+    };
+    use crate::dseg::DSeg;
+    use crate::test;
+    use byteorder::ReadBytesExt;
+    use dora_parser::Position;
+
+    #[test]
+    fn test_leb128() {
+        let mut buf = Vec::new();
+        write_uleb128(&mut buf, 2);
+        write_uleb128(&mut buf, 127);
+        write_uleb128(&mut buf, 624485);
+        assert_eq!(vec![2, 0x7f, 0xe5, 0x8e, 0x26], buf);
+
+        let mut buf = Vec::new();
+        write_sleb128(&mut buf, 2);
+        write_sleb128(&mut buf, -2);
+        write_sleb128(&mut buf, 127);
+        write_sleb128(&mut buf, -123456);
+        assert_eq!(vec![2, 0x7e, 0xff, 0x00, 0xc0, 0xbb, 0x78], buf);
+    }
+
+    #[test]
+    fn test_debug_frame_is_aligned() {
+        let buf = debug_frame(0x1000, 0x20);
+        assert_eq!(0, buf.len() % 8);
+
+        let cie_length = (&buf[0..4]).read_u32::<LittleEndian>().unwrap() as usize;
+        assert_eq!(0, (cie_length + 4) % 8);
+    }
+
+    #[test]
+    fn test_elf_header() {
+        let object = write_elf(vec![Section::debug(".debug_frame", vec![1, 2, 3])]);
+
+        assert_eq!(&[0x7f, b'E', b'L', b'F'], &object[0..4]);
+
+        let shoff = (&object[40..48]).read_u64::<LittleEndian>().unwrap() as usize;
+        let shnum = (&object[60..62]).read_u16::<LittleEndian>().unwrap() as usize;
+        let shstrndx = (&object[62..64]).read_u16::<LittleEndian>().unwrap() as usize;
+
+        assert_eq!(3, shnum);
+        assert_eq!(2, shstrndx);
+        assert_eq!(shoff + shnum * SECTION_HEADER_SIZE, object.len());
+    }
+
+    #[test]
+    fn test_object_for_code() {
+        test::parse("fun f() {}", |vm| {
+            let fct_id = vm.fct_by_name("f").unwrap();
+            let file = FileId::from(vm.files.len() as u32 - 1);
+
+            let mut positions = PositionTable::new();
+            positions.insert(4, SourcePosition::new(file, Position::new(3, 5)));
+            positions.insert(10, SourcePosition::new(file, Position::new(7, 2)));
+
+            let code = Code::from_buffer(
+                vm,
+                &DSeg::new(),
+                &[0; 16],
+                LazyCompilationData::new(),
+                GcPoints::new(),
+                0,
+                Comments::new(),
+                positions,
+                JitDescriptor::DoraFct(fct_id),
+            );
+            let start = code.instruction_start().to_usize() as u64;
+
+            let object = create_object(&code, vm, "f");
+
+            let symtab = section(&object, ".symtab");
+            assert_eq!(2 * SYMBOL_SIZE, symtab.len());
+            let symbol = &symtab[SYMBOL_SIZE..];
+            let name = (&symbol[0..4]).read_u32::<LittleEndian>().unwrap() as usize;
+            assert_eq!(b"f\0", &section(&object, ".strtab")[name..name + 2]);
+            assert_eq!(0x12, symbol[4]);
+            assert_eq!(start, (&symbol[8..16]).read_u64::<LittleEndian>().unwrap());
+            assert_eq!(16, (&symbol[16..24]).read_u64::<LittleEndian>().unwrap());
+
+            let (files, rows) = line_table(section(&object, ".debug_line"));
+            assert_eq!(vec![vm.file(file).name.clone()], files);
+            assert_eq!(
+                vec![
+                    (start, 1, 3, 5, false),
+                    (start + 4, 1, 7, 2, false),
+                    (start + 16, 1, 7, 2, true)
+                ],
+                rows
+            );
+        });
+    }
+
+    // contents of the section with the given name
+    fn section<'a>(object: &'a [u8], name: &str) -> &'a [u8] {
+        let read_u32 = |offset: usize| (&object[offset..]).read_u32::<LittleEndian>().unwrap();
+        let read_u64 = |offset: usize| (&object[offset..]).read_u64::<LittleEndian>().unwrap();
+
+        let shoff = read_u64(40) as usize;
+        let shnum = (&object[60..62]).read_u16::<LittleEndian>().unwrap() as usize;
+        let shstrndx = (&object[62..64]).read_u16::<LittleEndian>().unwrap() as usize;
+
+        let header = |idx: usize| shoff + idx * SECTION_HEADER_SIZE;
+        let contents = |idx: usize| {
+            let offset = read_u64(header(idx) + 24) as usize;
+            let size = read_u64(header(idx) + 32) as usize;
+            &object[offset..offset + size]
+        };
+
+        let names = contents(shstrndx);
+
+        for idx in 1..shnum {
+            let start = read_u32(header(idx)) as usize;
+            let end = start + names[start..].iter().position(|&b| b == 0).unwrap();
+
+            if &names[start..end] == name.as_bytes() {
+                return contents(idx);
+            }
+        }
+
+        panic!("section {} not found", name);
+    }
+
+    // runs the line number program, returns file names and the rows
+    // (address, file, line, column, end_sequence)
+    fn line_table(data: &[u8]) -> (Vec<String>, Vec<(u64, u64, i64, u64, bool)>) {
+        let mut reader = data;
+        let unit_length = reader.read_u32::<LittleEndian>().unwrap() as usize;
+        assert_eq!(data.len(), unit_length + 4);
+        assert_eq!(2, reader.read_u16::<LittleEndian>().unwrap());
+        let header_length = reader.read_u32::<LittleEndian>().unwrap() as usize;
+        let program = &data[10 + header_length..];
+
+        assert_eq!(
+            &[1, 1, LINE_BASE as u8, LINE_RANGE, OPCODE_BASE],
+            &reader[0..5]
+        );
+        reader = &reader[5 + OPCODE_BASE as usize - 1..];
+        assert_eq!(0, reader[0]);
+        reader = &reader[1..];
+
+        let mut files = Vec::new();
+
+        while reader[0] != 0 {
+            let end = reader.iter().position(|&b| b == 0).unwrap();
+            files.push(String::from_utf8(reader[..end].to_vec()).unwrap());
+            reader = &reader[end + 1..];
+            assert_eq!(&[0, 0, 0], &reader[0..3]);
+            reader = &reader[3..];
+        }
+
+        let mut reader = program;
+        let mut rows = Vec::new();
+        let (mut address, mut file, mut line, mut column) = (0, 1, 1, 0);
+
+        while !reader.is_empty() {
+            let opcode = reader[0];
+            reader = &reader[1..];
+
+            match opcode {
+                0 => {
+                    let length = read_uleb128(&mut reader) as usize;
+                    let (extended, rest) = reader.split_at(length);
+                    reader = rest;
+
+                    match extended[0] {
+                        DW_LNE_SET_ADDRESS => {
+                            address = (&extended[1..]).read_u64::<LittleEndian>().unwrap();
+                        }
+                        DW_LNE_END_SEQUENCE => rows.push((address, file, line, column, true)),
+                        _ => panic!("unexpected extended opcode {}", extended[0]),
+                    }
+                }
+                DW_LNS_COPY => rows.push((address, file, line, column, false)),
+                DW_LNS_ADVANCE_PC => address += read_uleb128(&mut reader),
+                DW_LNS_ADVANCE_LINE => line += read_sleb128(&mut reader),
+                DW_LNS_SET_FILE => file = read_uleb128(&mut reader),
+                DW_LNS_SET_COLUMN => column = read_uleb128(&mut reader),
+                _ => panic!("unexpected opcode {}", opcode),
+            }
+        }
+
+        (files, rows)
+    }
+
+    fn read_uleb128(reader: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = reader[0];
+            *reader = &reader[1..];
+            value |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    fn read_sleb128(reader: &mut &[u8]) -> i64 {
+        let mut value = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = reader[0];
+            *reader = &reader[1..];
+            value |= ((byte & 0x7f) as i64) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                if byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+
+                return value;
+            }
+        }
+    }
+}
@@ -2,6 +2,9 @@ use crate::compiler::Code;
 use crate::vm::VM;
 use dora_parser::interner::Name;
 
+#[cfg(target_os = "linux")]
+pub use self::jitdump::register_with_jitdump;
+
 #[cfg(target_os = "linux")]
 pub fn register_with_perf(code: &Code, vm: &VM, name: Name) {
     use std::fs::OpenOptions;
@@ -30,3 +33,218 @@ pub fn register_with_perf(code: &Code, vm: &VM, name: Name) {
 pub fn register_with_perf(_: &Code, _: &VM, _: Name) {
     // nothing to do
 }
+
+#[cfg(not(target_os = "linux"))]
+pub fn register_with_jitdump(_: &Code, _: &VM, _: &str) {
+    // nothing to do
+}
+
+// Writes `jit-<pid>.dump` in the jitdump format of perf. For every function
+// a debug info record maps code addresses to source lines, followed by a
+// code load record containing the machine code. `perf inject --jit` uses the
+// file to create ELF objects for the generated code.
+#[cfg(target_os = "linux")]
+mod jitdump {
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use lazy_static::lazy_static;
+    use parking_lot::Mutex;
+    use std::fs::{File, OpenOptions};
+    use std::io::prelude::*;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+    use std::slice;
+
+    use crate::compiler::Code;
+    use crate::os::page_size;
+    use crate::vm::VM;
+
+    const MAGIC: u32 = 0x4A69_5444;
+    const VERSION: u32 = 1;
+    const HEADER_SIZE: u32 = 40;
+    const RECORD_HEADER_SIZE: u32 = 16;
+
+    const JIT_CODE_LOAD: u32 = 0;
+    const JIT_CODE_DEBUG_INFO: u32 = 2;
+
+    #[cfg(target_arch = "x86_64")]
+    const ELF_MACHINE: u32 = 62;
+
+    #[cfg(target_arch = "aarch64")]
+    const ELF_MACHINE: u32 = 183;
+
+    struct JitDump {
+        file: File,
+        code_index: u64,
+    }
+
+    lazy_static! {
+        static ref JIT_DUMP: Mutex<Option<JitDump>> = Mutex::new(None);
+    }
+
+    pub fn register_with_jitdump(code: &Code, vm: &VM, name: &str) {
+        let mut jit_dump = JIT_DUMP.lock();
+
+        if jit_dump.is_none() {
+            *jit_dump = Some(JitDump::new());
+        }
+
+        let jit_dump = jit_dump.as_mut().unwrap();
+
+        let mut buf = Vec::new();
+        write_debug_info(&mut buf, code, vm);
+        write_code_load(&mut buf, code, name, jit_dump.code_index);
+        jit_dump.code_index += 1;
+
+        jit_dump.file.write_all(&buf).unwrap();
+    }
+
+    impl JitDump {
+        fn new() -> JitDump {
+            let fname = format!("jit-{}.dump", pid());
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&fname)
+                .unwrap();
+
+            // perf record finds the dump through this executable mapping
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    page_size(),
+                    libc::PROT_READ | libc::PROT_EXEC,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+
+            if ptr == libc::MAP_FAILED {
+                panic!("mmap of {} failed", fname);
+            }
+
+            let mut header = Vec::new();
+            header.write_u32::<LittleEndian>(MAGIC).unwrap();
+            header.write_u32::<LittleEndian>(VERSION).unwrap();
+            header.write_u32::<LittleEndian>(HEADER_SIZE).unwrap();
+            header.write_u32::<LittleEndian>(ELF_MACHINE).unwrap();
+            header.write_u32::<LittleEndian>(0).unwrap(); // padding
+            header.write_u32::<LittleEndian>(pid()).unwrap();
+            header.write_u64::<LittleEndian>(timestamp()).unwrap();
+            header.write_u64::<LittleEndian>(0).unwrap(); // flags
+            debug_assert_eq!(header.len(), HEADER_SIZE as usize);
+
+            file.write_all(&header).unwrap();
+
+            JitDump {
+                file,
+                code_index: 0,
+            }
+        }
+    }
+
+    fn write_debug_info(buf: &mut Vec<u8>, code: &Code, vm: &VM) {
+        let start = code.instruction_start().to_usize() as u64;
+        let positions = code.positions();
+
+        let mut record = Vec::new();
+        record.write_u64::<LittleEndian>(start).unwrap();
+        record
+            .write_u64::<LittleEndian>(positions.len() as u64)
+            .unwrap();
+
+        let mut address = 0;
+
+        // Positions are recorded after their instruction (e.g. the return
+        // address of a call), so an entry starts after the previous position.
+        for &(offset, position) in positions {
+            record
+                .write_u64::<LittleEndian>(start + address as u64)
+                .unwrap();
+            record.write_u32::<LittleEndian>(position.pos.line).unwrap();
+            record.write_u32::<LittleEndian>(0).unwrap(); // discriminator
+            record.extend_from_slice(vm.file(position.file).name.as_bytes());
+            record.push(0);
+
+            address = offset;
+        }
+
+        write_record(buf, JIT_CODE_DEBUG_INFO, &record);
+    }
+
+    fn write_code_load(buf: &mut Vec<u8>, code: &Code, name: &str, code_index: u64) {
+        let start = code.instruction_start();
+        let size = code.instruction_end().to_usize() - start.to_usize();
+
+        let mut record = Vec::new();
+        record.write_u32::<LittleEndian>(pid()).unwrap();
+        record.write_u32::<LittleEndian>(tid()).unwrap();
+        record
+            .write_u64::<LittleEndian>(start.to_usize() as u64)
+            .unwrap(); // vma
+        record
+            .write_u64::<LittleEndian>(start.to_usize() as u64)
+            .unwrap(); // code_addr
+        record.write_u64::<LittleEndian>(size as u64).unwrap();
+        record.write_u64::<LittleEndian>(code_index).unwrap();
+        record.extend_from_slice(name.as_bytes());
+        record.push(0);
+
+        let instructions = unsafe { slice::from_raw_parts(start.to_ptr::<u8>(), size) };
+        record.extend_from_slice(instructions);
+
+        write_record(buf, JIT_CODE_LOAD, &record);
+    }
+
+    fn write_record(buf: &mut Vec<u8>, id: u32, record: &[u8]) {
+        buf.write_u32::<LittleEndian>(id).unwrap();
+        buf.write_u32::<LittleEndian>(RECORD_HEADER_SIZE + record.len() as u32)
+            .unwrap();
+        buf.write_u64::<LittleEndian>(timestamp()).unwrap();
+        buf.extend_from_slice(record);
+    }
+
+    fn pid() -> u32 {
+        unsafe { libc::getpid() as u32 }
+    }
+
+    fn tid() -> u32 {
+        unsafe { libc::syscall(libc::SYS_gettid) as u32 }
+    }
+
+    // perf needs to be run with `-k mono` to use the same clock
+    fn timestamp() -> u64 {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+
+        unsafe {
+            libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+        }
+
+        ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use byteorder::ReadBytesExt;
+        use std::io::Cursor;
+
+        #[test]
+        fn test_write_record() {
+            let mut buf = Vec::new();
+            write_record(&mut buf, JIT_CODE_LOAD, &[1, 2, 3]);
+            assert_eq!(RECORD_HEADER_SIZE as usize + 3, buf.len());
+
+            let mut cursor = Cursor::new(&buf);
+            assert_eq!(JIT_CODE_LOAD, cursor.read_u32::<LittleEndian>().unwrap());
+            assert_eq!(19, cursor.read_u32::<LittleEndian>().unwrap());
+            assert!(cursor.read_u64::<LittleEndian>().unwrap() > 0);
+            assert_eq!(&[1, 2, 3], &buf[16..]);
+        }
+    }
+}
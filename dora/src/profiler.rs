@@ -0,0 +1,667 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use crate::compiler::fct::SourcePosition;
+use crate::compiler::map::{CodeDescriptor, CodeMap};
+use crate::threads::THREAD;
+use crate::vm::{FctId, FileId, VM};
+
+// Sampling profiler for Dora code, enabled with --profile=<file>.
+//
+// Every Dora thread gets a timer on its own CPU clock that sends SIGPROF to
+// that thread. The signal handler walks the frames of the interrupted thread
+// and stores the raw program counters into a preallocated buffer, it neither
+// allocates nor takes locks. All samples are resolved to functions and lines
+// with the code map when the program has finished. Code collection is
+// disabled with --profile, so the recorded addresses stay valid.
+
+// sampling interval in microseconds
+const INTERVAL_US: i64 = 1000;
+
+// maximum number of frames recorded for a single sample
+const MAX_FRAMES: usize = 256;
+
+// capacity of the sample buffer in words
+const BUFFER_SIZE: usize = 4 << 20;
+
+static SAMPLES: AtomicPtr<SampleBuffer> = AtomicPtr::new(ptr::null_mut());
+
+// Each sample is stored as its number of frames followed by the program
+// counters of these frames, starting with the innermost one.
+struct SampleBuffer {
+    data: *mut usize,
+    top: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl SampleBuffer {
+    fn new() -> SampleBuffer {
+        let data = vec![0usize; BUFFER_SIZE].into_boxed_slice();
+
+        SampleBuffer {
+            data: Box::into_raw(data) as *mut usize,
+            top: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    fn push(&self, frames: &[usize]) {
+        let size = frames.len() + 1;
+        let mut top = self.top.load(Ordering::Relaxed);
+
+        loop {
+            if top + size > BUFFER_SIZE {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            match self.top.compare_exchange_weak(
+                top,
+                top + size,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => top = actual,
+            }
+        }
+
+        unsafe {
+            *self.data.add(top) = frames.len();
+            ptr::copy_nonoverlapping(frames.as_ptr(), self.data.add(top + 1), frames.len());
+        }
+    }
+
+    // Only valid after all sampled threads have stopped their timers.
+    fn samples(&self) -> Vec<&[usize]> {
+        let top = self.top.load(Ordering::SeqCst);
+        let data = unsafe { std::slice::from_raw_parts(self.data, top) };
+        let mut samples = Vec::new();
+        let mut idx = 0;
+
+        while idx < top {
+            let len = data[idx];
+            samples.push(&data[idx + 1..idx + 1 + len]);
+            idx += len + 1;
+        }
+
+        samples
+    }
+}
+
+impl Drop for SampleBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            let data = std::slice::from_raw_parts_mut(self.data, BUFFER_SIZE);
+            drop(Box::from_raw(data as *mut [usize]));
+        }
+    }
+}
+
+pub fn start(vm: &VM) {
+    if vm.args.flag_profile.is_none() {
+        return;
+    }
+
+    if !sys::SUPPORTED {
+        eprintln!("warning: --profile is not supported on this platform.");
+        return;
+    }
+
+    let buffer = Box::into_raw(Box::new(SampleBuffer::new()));
+    SAMPLES.store(buffer, Ordering::SeqCst);
+    sys::install_signal_handler();
+}
+
+// Samples the current thread until the returned value is dropped.
+pub fn register_current_thread() -> ThreadSampler {
+    if SAMPLES.load(Ordering::SeqCst).is_null() {
+        return ThreadSampler { timer: None };
+    }
+
+    ThreadSampler {
+        timer: Some(sys::start_timer(INTERVAL_US)),
+    }
+}
+
+pub struct ThreadSampler {
+    timer: Option<sys::Timer>,
+}
+
+impl Drop for ThreadSampler {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            sys::stop_timer(timer);
+        }
+    }
+}
+
+// Writes the flat report to the file given with --profile and the collapsed
+// stacks for flame graphs next to it. Needs to be called after all threads
+// have finished.
+pub fn finish(vm: &VM) {
+    let buffer = SAMPLES.swap(ptr::null_mut(), Ordering::SeqCst);
+
+    if buffer.is_null() {
+        return;
+    }
+
+    let buffer = unsafe { Box::from_raw(buffer) };
+    let mut profile = Profile::new();
+    profile.dropped = buffer.dropped.load(Ordering::SeqCst);
+
+    {
+        let code_map = vm.code_map.lock();
+
+        for sample in buffer.samples() {
+            let frames = resolve_frames(vm, &code_map, sample);
+            profile.add_sample(&frames);
+        }
+    }
+
+    let names = Names::lookup(vm, &profile);
+    let file = vm.args.flag_profile.as_ref().unwrap();
+    let collapsed_file = format!("{}.collapsed", file);
+
+    if let Err(err) = write_file(file, |out| profile.write_report(&names, out)) {
+        eprintln!("could not write profile to {}: {}", file, err);
+    }
+
+    if let Err(err) = write_file(&collapsed_file, |out| profile.write_collapsed(&names, out)) {
+        eprintln!("could not write profile to {}: {}", collapsed_file, err);
+    }
+}
+
+fn write_file<F>(file: &str, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let mut out = BufWriter::new(File::create(file)?);
+    write(&mut out)?;
+    out.flush()
+}
+
+// Samples contain all addresses of the frame chain that are in the code
+// space, only Dora functions and native stubs are kept.
+fn resolve_frames(vm: &VM, code_map: &CodeMap, sample: &[usize]) -> Vec<(FctId, SourcePosition)> {
+    let mut frames = Vec::new();
+
+    for (idx, &pc) in sample.iter().enumerate() {
+        match code_map.get(pc.into()) {
+            Some(CodeDescriptor::DoraFct(jit_fct_id)) => {
+                let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+                let offset = (pc - jit_fct.instruction_start().to_usize()) as u32;
+
+                // only the first frame was interrupted, all others are
+                // return addresses which point behind the call instruction
+                let offset = if idx == 0 { offset } else { offset - 1 };
+
+                if let Some(inlined_frames) = jit_fct.frames_for_instruction(offset) {
+                    frames.extend(inlined_frames);
+                }
+            }
+
+            Some(CodeDescriptor::NativeStub(jit_fct_id)) => {
+                let jit_fct = vm.jit_fcts.idx(jit_fct_id);
+                let fct = vm.fcts.idx(jit_fct.fct_id());
+                let fct = fct.read();
+
+                frames.push((fct.id, SourcePosition::new(fct.file, fct.ast.pos)));
+            }
+
+            _ => {}
+        }
+    }
+
+    frames
+}
+
+// Called from the signal handler: records the frame chain of the current
+// thread as long as it stays in the code space, frames of native code are
+// skipped through the native transitions. Must not take locks, the frames
+// are filtered with the code map in `finish`. The interrupted function might
+// not have set up its frame yet, in this case its caller is missing from the
+// sample.
+unsafe fn collect_frames(vm: &VM, pc: usize, fp: usize, frames: &mut [usize]) -> Option<usize> {
+    let mut len = 0;
+    walk_frames(vm, pc, fp, frames, &mut len);
+
+    let mut dtn = THREAD
+        .try_with(|thread| thread.try_borrow().ok().map(|thread| thread.dtn()))
+        .ok()??;
+
+    while !dtn.is_null() {
+        let current = &*dtn;
+        walk_frames(vm, current.pc, current.fp, frames, &mut len);
+        dtn = current.last;
+    }
+
+    Some(len)
+}
+
+unsafe fn walk_frames(
+    vm: &VM,
+    mut pc: usize,
+    mut fp: usize,
+    frames: &mut [usize],
+    len: &mut usize,
+) {
+    while vm.gc.code_contains(pc.into()) && *len < frames.len() {
+        frames[*len] = pc;
+        *len += 1;
+
+        if fp == 0 {
+            return;
+        }
+
+        pc = *((fp + 8) as *const usize);
+        fp = *(fp as *const usize);
+    }
+}
+
+fn record_sample(pc: usize, fp: usize) {
+    let buffer = SAMPLES.load(Ordering::Relaxed);
+
+    if buffer.is_null() {
+        return;
+    }
+
+    let buffer = unsafe { &*buffer };
+    let vm = crate::vm::get_vm();
+    let mut frames = [0usize; MAX_FRAMES];
+
+    match unsafe { collect_frames(vm, pc, fp, &mut frames) } {
+        Some(len) => buffer.push(&frames[..len]),
+        None => {
+            buffer.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+struct Profile {
+    samples: usize,
+    dropped: usize,
+    fct_self: HashMap<FctId, usize>,
+    fct_total: HashMap<FctId, usize>,
+    lines: HashMap<(FctId, FileId, u32), usize>,
+    stacks: HashMap<Vec<FctId>, usize>,
+}
+
+impl Profile {
+    fn new() -> Profile {
+        Profile {
+            samples: 0,
+            dropped: 0,
+            fct_self: HashMap::new(),
+            fct_total: HashMap::new(),
+            lines: HashMap::new(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    // frames start with the innermost function
+    fn add_sample(&mut self, frames: &[(FctId, SourcePosition)]) {
+        self.samples += 1;
+
+        if let Some(&(fct_id, position)) = frames.first() {
+            *self.fct_self.entry(fct_id).or_insert(0) += 1;
+
+            let line = (fct_id, position.file, position.pos.line);
+            *self.lines.entry(line).or_insert(0) += 1;
+        }
+
+        let mut seen = HashSet::new();
+
+        for &(fct_id, _) in frames {
+            if seen.insert(fct_id) {
+                *self.fct_total.entry(fct_id).or_insert(0) += 1;
+            }
+        }
+
+        let stack = frames.iter().rev().map(|&(fct_id, _)| fct_id).collect();
+        *self.stacks.entry(stack).or_insert(0) += 1;
+    }
+
+    fn percent(&self, count: usize) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.samples as f64
+        }
+    }
+
+    fn write_report<W: Write>(&self, names: &Names, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "{} samples with {}us interval, {} dropped",
+            self.samples, INTERVAL_US, self.dropped
+        )?;
+
+        let outside: usize = self.stacks.get(&Vec::new()).cloned().unwrap_or(0);
+        writeln!(out, "{} samples outside of Dora code", outside)?;
+
+        writeln!(out)?;
+        writeln!(out, "  self%     self  total%    total  function")?;
+
+        let mut fcts: Vec<(usize, usize, String)> = self
+            .fct_total
+            .iter()
+            .map(|(&fct_id, &total)| {
+                let own = self.fct_self.get(&fct_id).cloned().unwrap_or(0);
+                (own, total, names.fct(fct_id).to_string())
+            })
+            .collect();
+        fcts.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)).then_with(|| a.2.cmp(&b.2)));
+
+        for (own, total, name) in fcts {
+            writeln!(
+                out,
+                "{:6.2}% {:8} {:6.2}% {:8}  {}",
+                self.percent(own),
+                own,
+                self.percent(total),
+                total,
+                name
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "  self%     self  line")?;
+
+        let mut lines: Vec<(usize, String)> = self
+            .lines
+            .iter()
+            .map(|(&(fct_id, file, line), &count)| {
+                let location = format!("{}:{} ({})", names.file(file), line, names.fct(fct_id));
+                (count, location)
+            })
+            .collect();
+        lines.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        for (count, location) in lines {
+            writeln!(
+                out,
+                "{:6.2}% {:8}  {}",
+                self.percent(count),
+                count,
+                location
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // One line per distinct stack, outermost function first, in the format
+    // expected by flamegraph.pl and similar tools.
+    fn write_collapsed<W: Write>(&self, names: &Names, out: &mut W) -> io::Result<()> {
+        let mut stacks: Vec<(String, usize)> = self
+            .stacks
+            .iter()
+            .map(|(stack, &count)| {
+                let names = if stack.is_empty() {
+                    "[native]".into()
+                } else {
+                    stack
+                        .iter()
+                        .map(|&fct_id| names.fct(fct_id).replace(';', ","))
+                        .collect::<Vec<_>>()
+                        .join(";")
+                };
+
+                (names, count)
+            })
+            .collect();
+        stacks.sort();
+
+        for (names, count) in stacks {
+            writeln!(out, "{} {}", names, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Names of all functions and files in a profile, the reports are written
+// without looking at the VM.
+struct Names {
+    fcts: HashMap<FctId, String>,
+    files: HashMap<FileId, String>,
+}
+
+impl Names {
+    fn lookup(vm: &VM, profile: &Profile) -> Names {
+        let mut fcts = HashMap::new();
+        let mut files = HashMap::new();
+
+        for &fct_id in profile.fct_total.keys() {
+            let fct = vm.fcts.idx(fct_id);
+            let fct = fct.read();
+            fcts.insert(fct_id, fct.full_name(vm));
+        }
+
+        for &(_, file, _) in profile.lines.keys() {
+            files.insert(file, vm.file(file).name.clone());
+        }
+
+        Names { fcts, files }
+    }
+
+    fn fct(&self, fct_id: FctId) -> &str {
+        &self.fcts[&fct_id]
+    }
+
+    fn file(&self, file: FileId) -> &str {
+        &self.files[&file]
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod sys {
+    use std::mem;
+    use std::ptr;
+
+    pub const SUPPORTED: bool = true;
+
+    pub type Timer = *mut libc::c_void;
+
+    #[link(name = "rt")]
+    extern "C" {
+        fn timer_create(
+            clockid: libc::clockid_t,
+            sevp: *mut libc::sigevent,
+            timerid: *mut Timer,
+        ) -> libc::c_int;
+        fn timer_settime(
+            timerid: Timer,
+            flags: libc::c_int,
+            new_value: *const libc::itimerspec,
+            old_value: *mut libc::itimerspec,
+        ) -> libc::c_int;
+        fn timer_delete(timerid: Timer) -> libc::c_int;
+    }
+
+    pub fn install_signal_handler() {
+        unsafe {
+            let mut sa: libc::sigaction = mem::zeroed();
+            sa.sa_sigaction = handle_sigprof as *const () as usize;
+            sa.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+            libc::sigemptyset(&mut sa.sa_mask);
+
+            if libc::sigaction(libc::SIGPROF, &sa, ptr::null_mut()) != 0 {
+                panic!("sigaction for SIGPROF failed");
+            }
+        }
+    }
+
+    // Creates a timer on the CPU clock of the current thread that sends
+    // SIGPROF to this thread.
+    pub fn start_timer(interval_us: i64) -> Timer {
+        unsafe {
+            let mut sev: libc::sigevent = mem::zeroed();
+            sev.sigev_notify = libc::SIGEV_THREAD_ID;
+            sev.sigev_signo = libc::SIGPROF;
+            sev.sigev_notify_thread_id = libc::syscall(libc::SYS_gettid) as libc::c_int;
+
+            let mut timer: Timer = ptr::null_mut();
+
+            if timer_create(libc::CLOCK_THREAD_CPUTIME_ID, &mut sev, &mut timer) != 0 {
+                panic!("timer_create failed");
+            }
+
+            let interval = libc::timespec {
+                tv_sec: 0,
+                tv_nsec: interval_us * 1000,
+            };
+
+            let spec = libc::itimerspec {
+                it_interval: interval,
+                it_value: interval,
+            };
+
+            if timer_settime(timer, 0, &spec, ptr::null_mut()) != 0 {
+                panic!("timer_settime failed");
+            }
+
+            timer
+        }
+    }
+
+    pub fn stop_timer(timer: Timer) {
+        unsafe {
+            timer_delete(timer);
+        }
+    }
+
+    extern "C" fn handle_sigprof(
+        _signo: libc::c_int,
+        _info: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        unsafe {
+            let errno = *libc::__errno_location();
+
+            let context = &*(context as *const libc::ucontext_t);
+            let pc = context.uc_mcontext.gregs[libc::REG_RIP as usize] as usize;
+            let fp = context.uc_mcontext.gregs[libc::REG_RBP as usize] as usize;
+
+            super::record_sample(pc, fp);
+
+            *libc::__errno_location() = errno;
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+mod sys {
+    pub const SUPPORTED: bool = false;
+
+    pub type Timer = ();
+
+    pub fn install_signal_handler() {
+        unreachable!();
+    }
+
+    pub fn start_timer(_interval_us: i64) -> Timer {
+        unreachable!();
+    }
+
+    pub fn stop_timer(_timer: Timer) {
+        unreachable!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_parser::lexer::position::Position;
+
+    #[test]
+    fn test_add_sample() {
+        let file = FileId::from(0);
+        let pos = |line| SourcePosition::new(file, Position::new(line, 1));
+        let mut profile = Profile::new();
+
+        profile.add_sample(&[(FctId(2), pos(3)), (FctId(2), pos(4)), (FctId(1), pos(10))]);
+        profile.add_sample(&[(FctId(2), pos(3)), (FctId(1), pos(10))]);
+        profile.add_sample(&[(FctId(1), pos(11))]);
+        profile.add_sample(&[]);
+
+        assert_eq!(4, profile.samples);
+        assert_eq!(Some(&2), profile.fct_self.get(&FctId(2)));
+        assert_eq!(Some(&1), profile.fct_self.get(&FctId(1)));
+        assert_eq!(Some(&2), profile.fct_total.get(&FctId(2)));
+        assert_eq!(Some(&3), profile.fct_total.get(&FctId(1)));
+        assert_eq!(Some(&2), profile.lines.get(&(FctId(2), file, 3)));
+        assert_eq!(Some(&1), profile.lines.get(&(FctId(1), file, 11)));
+        assert_eq!(
+            Some(&1),
+            profile.stacks.get(&vec![FctId(1), FctId(2), FctId(2)])
+        );
+        assert_eq!(Some(&1), profile.stacks.get(&vec![FctId(1), FctId(2)]));
+        assert_eq!(Some(&1), profile.stacks.get(&Vec::new()));
+        assert_eq!(50.0, profile.percent(2));
+    }
+
+    fn profile_with_names() -> (Profile, Names) {
+        let file = FileId::from(0);
+        let pos = |line| SourcePosition::new(file, Position::new(line, 1));
+        let mut profile = Profile::new();
+        profile.dropped = 1;
+
+        profile.add_sample(&[(FctId(2), pos(3)), (FctId(2), pos(4)), (FctId(1), pos(10))]);
+        profile.add_sample(&[(FctId(2), pos(3)), (FctId(1), pos(10))]);
+        profile.add_sample(&[(FctId(1), pos(11))]);
+        profile.add_sample(&[]);
+
+        let mut names = Names {
+            fcts: HashMap::new(),
+            files: HashMap::new(),
+        };
+        names.fcts.insert(FctId(1), "main()".into());
+        names.fcts.insert(FctId(2), "fib(Int32) -> Int32".into());
+        names.files.insert(file, "test.dora".into());
+
+        (profile, names)
+    }
+
+    #[test]
+    fn test_write_report() {
+        let (profile, names) = profile_with_names();
+        let mut out = Vec::new();
+        profile.write_report(&names, &mut out).unwrap();
+
+        assert_eq!(
+            "4 samples with 1000us interval, 1 dropped
+1 samples outside of Dora code
+
+  self%     self  total%    total  function
+ 50.00%        2  50.00%        2  fib(Int32) -> Int32
+ 25.00%        1  75.00%        3  main()
+
+  self%     self  line
+ 50.00%        2  test.dora:3 (fib(Int32) -> Int32)
+ 25.00%        1  test.dora:11 (main())
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_collapsed() {
+        let (profile, names) = profile_with_names();
+        let mut out = Vec::new();
+        profile.write_collapsed(&names, &mut out).unwrap();
+
+        assert_eq!(
+            "[native] 1
+main() 1
+main();fib(Int32) -> Int32 1
+main();fib(Int32) -> Int32;fib(Int32) -> Int32 1
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
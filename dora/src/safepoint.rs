@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::thread;
 
 use crate::stdlib;
 use crate::threads::{DoraThread, ThreadState, THREAD};
@@ -11,15 +12,47 @@ where
     THREAD.with(|thread| thread.borrow().park(vm));
 
     let threads = vm.threads.threads.lock();
+    stop_the_world_locked(vm, &*threads, f)
+}
+
+// Gives up when another thread is stopping the world right now, the
+// current thread never waits while parked. The lock is also held briefly
+// while attaching or detaching threads, in that case it is retried.
+pub fn try_stop_the_world<F, R>(vm: &VM, f: F) -> Option<R>
+where
+    F: FnOnce(&[Arc<DoraThread>]) -> R,
+{
+    loop {
+        if let Some(threads) = vm.threads.threads.try_lock() {
+            THREAD.with(|thread| thread.borrow().park(vm));
+            return Some(stop_the_world_locked(vm, &*threads, f));
+        }
+
+        if vm.threads.safepoint_requested() {
+            return None;
+        }
+
+        thread::yield_now();
+    }
+}
+
+fn stop_the_world_locked<F, R>(vm: &VM, threads: &[Arc<DoraThread>], f: F) -> R
+where
+    F: FnOnce(&[Arc<DoraThread>]) -> R,
+{
     if threads.len() == 1 {
-        let ret = f(&*threads);
+        let ret = f(threads);
         THREAD.with(|thread| thread.borrow().unpark(vm));
         return ret;
     }
 
-    let safepoint_id = stop_threads(vm, &*threads);
-    let ret = f(&*threads);
-    resume_threads(vm, &*threads, safepoint_id);
+    let safepoint_id = stop_threads(vm, threads);
+    let ret = f(threads);
+
+    // compilations that ran while stopping the world couldn't collect code
+    vm.gc.collect_requested_code(vm, threads);
+
+    resume_threads(vm, threads, safepoint_id);
     THREAD.with(|thread| thread.borrow().unpark(vm));
     ret
 }
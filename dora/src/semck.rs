@@ -22,6 +22,7 @@ mod globaldef;
 mod globaldefck;
 mod implck;
 mod impldefck;
+mod lintck;
 mod moduledefck;
 mod nameck;
 pub(crate) mod prelude;
@@ -34,13 +35,31 @@ pub mod typeparamck;
 
 macro_rules! return_on_error {
     ($vm: ident) => {{
-        if $vm.diag.lock().has_errors() {
+        if $vm.diag.lock().has_sem_errors() {
+            return;
+        }
+    }};
+
+    ($vm: ident, $keep_going: ident) => {{
+        if !$keep_going && $vm.diag.lock().has_sem_errors() {
             return;
         }
     }};
 }
 
 pub fn check<'ast>(vm: &mut VM<'ast>) {
+    check_program(vm, false);
+}
+
+// Like `check` but doesn't stop after errors in function signatures or
+// bodies, so types of all other expressions are still recorded. Used by
+// the language server. The passes after nameck must not rely on the
+// earlier ones having succeeded.
+pub fn check_keep_going<'ast>(vm: &mut VM<'ast>) {
+    check_program(vm, true);
+}
+
+fn check_program<'ast>(vm: &mut VM<'ast>, keep_going: bool) {
     let mut map_cls_defs = NodeMap::new(); // get ClassId from ast node
     let mut map_struct_defs = NodeMap::new(); // get StructId from ast node
     let mut map_trait_defs = NodeMap::new(); // get TraitId from ast node
@@ -86,24 +105,25 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
 
     // check super class definition of classes
     clsdefck::check_super_definition(vm, &vm.ast, &map_cls_defs);
+    superck::cycle_detection(vm);
     return_on_error!(vm);
 
     // check names/identifiers of local variables
     // and their usage (variable def/use, function calls) in function bodies
     nameck::check(vm);
-    return_on_error!(vm);
+    return_on_error!(vm, keep_going);
 
     // check type definitions of params,
     // return types and local variables in functions
     fctdefck::check(vm);
-    return_on_error!(vm);
+    return_on_error!(vm, keep_going);
 
     superck::check_override(vm);
-    return_on_error!(vm);
+    return_on_error!(vm, keep_going);
 
     // check impl methods against trait definition
     implck::check(vm);
-    return_on_error!(vm);
+    return_on_error!(vm, keep_going);
 
     // define internal functions & methods
     prelude::internal_functions(vm);
@@ -131,6 +151,9 @@ pub fn check<'ast>(vm: &mut VM<'ast>) {
 
     // initialize addresses for global variables
     init_global_addresses(vm);
+
+    // warn about valid but suspicious code
+    lintck::check(vm);
 }
 
 fn internalck<'ast>(vm: &VM<'ast>) {
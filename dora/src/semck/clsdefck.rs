@@ -121,10 +121,10 @@ impl<'x, 'ast> ClsDefCheck<'x, 'ast> {
 
         match sym {
             Some(TypeSym::SymClass(cls_id)) => {
-                let super_cls = self.vm.classes.idx(cls_id);
-                let super_cls = super_cls.read();
+                // the class might derive from itself, don't keep the lock
+                let has_open = self.vm.classes.idx(cls_id).read().has_open;
 
-                if !super_cls.has_open {
+                if !has_open {
                     let msg = SemError::UnderivableType(name);
                     self.vm
                         .diag
@@ -43,30 +43,35 @@ impl<'x, 'ast> ExtensionCheck<'x, 'ast> {
         self.extension_id = Some(*self.map_extension_defs.get(i.id).unwrap());
 
         self.vm.sym.lock().push_level();
+        self.extension_ty = BuiltinType::Error;
 
-        if let Some(ref type_params) = i.type_params {
-            self.check_type_params(self.extension_id.unwrap(), type_params);
-        }
-
-        if let Some(class_ty) = semck::read_type(self.vm, self.file_id.into(), &i.class_type) {
-            self.extension_ty = class_ty;
-
+        if i.type_params.is_some() {
+            // We don't support type parameters for extensions yet.
+            report(self.vm, self.file_id.into(), i.pos, SemError::Unimplemented);
+        } else if let Some(class_ty) = semck::read_type(self.vm, self.file_id.into(), &i.class_type)
+        {
             match class_ty {
                 BuiltinType::Enum(enum_id, _) => {
                     let mut xenum = self.vm.enums[enum_id].write();
                     xenum.extensions.push(self.extension_id.unwrap());
+                    self.extension_ty = class_ty;
                 }
 
                 _ => {
-                    let cls_id = class_ty.cls_id(self.vm).unwrap();
-                    let cls = self.vm.classes.idx(cls_id);
-                    let mut cls = cls.write();
-                    cls.extensions.push(self.extension_id.unwrap());
+                    if let Some(cls_id) = class_ty.cls_id(self.vm) {
+                        let cls = self.vm.classes.idx(cls_id);
+                        let mut cls = cls.write();
+                        cls.extensions.push(self.extension_id.unwrap());
+                        self.extension_ty = class_ty;
+                    } else {
+                        let pos = i.class_type.pos();
+                        report(self.vm, self.file_id.into(), pos, SemError::ClassExpected);
+                    }
                 }
             }
 
             let mut extension = self.vm.extensions[self.extension_id.unwrap()].write();
-            extension.class_ty = class_ty;
+            extension.class_ty = self.extension_ty;
         }
 
         visit::walk_impl(self, i);
@@ -75,14 +80,6 @@ impl<'x, 'ast> ExtensionCheck<'x, 'ast> {
         self.vm.sym.lock().pop_level();
     }
 
-    fn check_type_params(
-        &mut self,
-        _extension_id: ExtensionId,
-        _type_params: &'ast [ast::TypeParam],
-    ) {
-        unimplemented!();
-    }
-
     fn check_in_enum(&self, f: &ast::Function, enum_id: EnumId) -> bool {
         let xenum = self.vm.enums[enum_id].read();
 
@@ -273,6 +270,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extension_without_class() {
+        err("trait T {} impl T {}", pos(1, 17), SemError::ClassExpected);
+        err(
+            "class A[T] impl[T] A[T] {}",
+            pos(1, 12),
+            SemError::Unimplemented,
+        );
+    }
+
     #[test]
     fn extension_method() {
         ok("class A impl A { fun foo() {} fun bar() {} }");
@@ -138,7 +138,7 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
                 fct.variadic_arguments = true;
             }
 
-            if fct.is_src() {
+            if vm.checks_body(&*fct) {
                 let src = fct.src();
                 let mut src = src.write();
 
@@ -150,8 +150,11 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
                     ty
                 };
 
-                let var = *src.map_vars.get(p.id).unwrap();
-                src.vars[var].ty = ty;
+                // nameck defines no variable for a parameter whose name is
+                // already taken, that error is already reported
+                if let Some(&var) = src.map_vars.get(p.id) {
+                    src.vars[var].ty = ty;
+                }
             }
         }
 
@@ -195,7 +198,7 @@ pub fn check<'a, 'ast>(vm: &VM<'ast>) {
             _ => {}
         }
 
-        if !fct.is_src() {
+        if !vm.checks_body(&*fct) {
             vm.sym.lock().pop_level();
             continue;
         }
@@ -9,7 +9,7 @@ pub fn check<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        if !vm.checks_body(&fct) {
             continue;
         }
 
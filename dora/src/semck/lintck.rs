@@ -0,0 +1,452 @@
+use std::collections::HashSet;
+
+use crate::error::msg::SemWarning;
+use crate::semck::returnck;
+use crate::vm::{CallType, Fct, FctId, FctParent, FctSrc, FileId, IdentType, VarId, VM};
+
+use dora_parser::ast::visit::*;
+use dora_parser::ast::Expr::*;
+use dora_parser::ast::Stmt::*;
+use dora_parser::ast::*;
+use dora_parser::lexer::position::{Position, Span};
+
+pub fn check<'ast>(vm: &VM<'ast>) {
+    // lints assume a completely checked program
+    if vm.diag.lock().has_errors() {
+        return;
+    }
+
+    let used_fcts = used_fcts(vm);
+
+    for fct in vm.fcts.iter() {
+        let fct = fct.read();
+
+        if !fct.is_src() || !vm.is_program_file(fct.file) {
+            continue;
+        }
+
+        let src = fct.src();
+        let src = src.read();
+
+        let mut lintck = LintCheck {
+            vm,
+            fct: &fct,
+            src: &src,
+            decls: Vec::new(),
+            used: vec![false; src.vars.len()],
+            assigned: vec![false; src.vars.len()],
+        };
+
+        lintck.check();
+
+        if is_unused_fct(vm, &fct, &used_fcts) {
+            let name = vm.interner.str(fct.name).to_string();
+            warn(
+                vm,
+                fct.file,
+                fct.pos,
+                fct.ast.span,
+                SemWarning::UnusedFunction(name),
+            );
+        }
+    }
+}
+
+fn warn(vm: &VM, file: FileId, pos: Position, span: Span, msg: SemWarning) {
+    if !vm.args.allows_lint(msg.lint()) {
+        vm.diag.lock().warn(file, pos, Some(span), msg);
+    }
+}
+
+// Collects all functions that are called somewhere or that are run as
+// initializer of a global variable.
+fn used_fcts<'ast>(vm: &VM<'ast>) -> HashSet<FctId> {
+    let mut used = HashSet::new();
+
+    for fct in vm.fcts.iter() {
+        let fct = fct.read();
+
+        if !fct.is_src() {
+            continue;
+        }
+
+        let src = fct.src();
+        let src = src.read();
+
+        for (_, call) in src.map_calls.iter() {
+            match **call {
+                CallType::Fct(fct_id, _, _)
+                | CallType::Method(_, fct_id, _)
+                | CallType::ModuleMethod(_, fct_id, _)
+                | CallType::CtorNew(_, fct_id)
+                | CallType::Ctor(_, fct_id)
+                | CallType::Expr(_, fct_id)
+                | CallType::Trait(_, fct_id)
+                | CallType::TraitStatic(_, _, fct_id) => {
+                    used.insert(fct_id);
+                }
+
+                CallType::Intrinsic(_) => {}
+            }
+        }
+
+        for (_, ident) in src.map_idents.iter() {
+            match *ident {
+                IdentType::Fct(fct_id) | IdentType::FctType(fct_id, _) => {
+                    used.insert(fct_id);
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    for glob in vm.globals.iter() {
+        let glob = glob.read();
+
+        if let Some(initializer) = glob.initializer {
+            used.insert(initializer);
+        }
+    }
+
+    used
+}
+
+// Only top-level functions are reported, they cannot be exported with
+// `@pub`. Methods could still be called through a trait or a super class
+// or implicitly like `makeIterator`.
+fn is_unused_fct(vm: &VM, fct: &Fct, used: &HashSet<FctId>) -> bool {
+    match fct.parent {
+        FctParent::None => {}
+        _ => return false,
+    }
+
+    !fct.is_test
+        && !fct.internal
+        && vm.interner.str(fct.name).as_str() != "main"
+        && !vm.interner.str(fct.name).starts_with('_')
+        && !used.contains(&fct.id)
+}
+
+struct VarDecl {
+    var_id: VarId,
+    pos: Position,
+    span: Span,
+    param: bool,
+}
+
+struct LintCheck<'a, 'ast: 'a> {
+    vm: &'a VM<'ast>,
+    fct: &'a Fct<'ast>,
+    src: &'a FctSrc,
+    decls: Vec<VarDecl>,
+    used: Vec<bool>,
+    assigned: Vec<bool>,
+}
+
+impl<'a, 'ast> LintCheck<'a, 'ast> {
+    fn check(&mut self) {
+        self.visit_fct(self.fct.ast);
+        self.check_vars();
+    }
+
+    fn warn(&self, pos: Position, span: Span, msg: SemWarning) {
+        warn(self.vm, self.fct.file, pos, span, msg);
+    }
+
+    fn declare(&mut self, id: NodeId, pos: Position, span: Span, param: bool) {
+        if let Some(&var_id) = self.src.map_vars.get(id) {
+            self.decls.push(VarDecl {
+                var_id,
+                pos,
+                span,
+                param,
+            });
+        }
+    }
+
+    fn var(&self, id: NodeId) -> Option<VarId> {
+        match self.src.map_idents.get(id) {
+            Some(&IdentType::Var(var_id)) => Some(var_id),
+            _ => None,
+        }
+    }
+
+    fn check_vars(&self) {
+        for decl in &self.decls {
+            let var = &self.src.vars[decl.var_id];
+            let name = self.vm.interner.str(var.name).to_string();
+
+            if name.starts_with('_') {
+                continue;
+            }
+
+            if self.used[decl.var_id.0] {
+                if var.reassignable && !self.assigned[decl.var_id.0] {
+                    self.warn(decl.pos, decl.span, SemWarning::VarNeverReassigned(name));
+                }
+            } else if !decl.param {
+                self.warn(decl.pos, decl.span, SemWarning::UnusedVariable(name));
+            } else if self.lints_params() {
+                self.warn(decl.pos, decl.span, SemWarning::UnusedParam(name));
+            }
+        }
+    }
+
+    // The signature of methods that override or implement another method
+    // is given, so they may ignore some of their parameters.
+    fn lints_params(&self) -> bool {
+        let fct = self.fct;
+
+        match fct.parent {
+            FctParent::Trait(_) | FctParent::Impl(_) => return false,
+            _ => {}
+        }
+
+        !fct.is_test
+            && !fct.is_abstract
+            && !fct.has_open
+            && !fct.has_override
+            && fct.overrides.is_none()
+    }
+
+    // Everything after a statement that always returns, breaks or continues
+    // is unreachable, only the first unreachable statement is reported.
+    fn check_block(&mut self, block: &'ast ExprBlockType) {
+        let mut diverges = false;
+        let mut reported = false;
+
+        for stmt in &block.stmts {
+            if diverges && !reported {
+                self.warn(stmt.pos(), stmt.span(), SemWarning::UnreachableCode);
+                reported = true;
+            }
+
+            self.visit_stmt(stmt);
+            diverges = diverges || stmt_diverges(stmt);
+        }
+
+        if let Some(ref expr) = block.expr {
+            if diverges && !reported {
+                self.warn(expr.pos(), expr.span(), SemWarning::UnreachableCode);
+            }
+
+            self.visit_expr(expr);
+        }
+    }
+
+    // `==` calls `equals`, which isn't meant for comparing with nil and
+    // would recurse when used on the receiver inside `equals` itself.
+    fn check_reference_equality(&self, e: &'ast ExprBinType) {
+        let (op, identity) = match e.op {
+            BinOp::Cmp(CmpOp::Eq) => ("==", "==="),
+            BinOp::Cmp(CmpOp::Ne) => ("!=", "!=="),
+            _ => return,
+        };
+
+        let compares_nil = e.lhs.is_nil() || e.rhs.is_nil();
+
+        let calls_itself = match self.src.map_calls.get(e.id).map(|call| &**call) {
+            Some(&CallType::Method(ty, fct_id, _)) => fct_id == self.fct.id && ty.reference_type(),
+            _ => false,
+        };
+
+        if compares_nil || calls_itself {
+            self.warn(
+                e.pos,
+                e.span,
+                SemWarning::ReferenceEquality(op.into(), identity.into()),
+            );
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for LintCheck<'a, 'ast> {
+    fn visit_fct(&mut self, f: &'ast Function) {
+        for param in &f.params {
+            self.visit_param(param);
+        }
+
+        if let Some(ref block) = f.block {
+            self.check_block(block);
+        }
+    }
+
+    fn visit_param(&mut self, p: &'ast Param) {
+        self.declare(p.id, p.pos, p.span, true);
+    }
+
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        match *s {
+            StmtVar(ref var) => self.declare(var.id, var.pos, var.span, false),
+            StmtFor(ref fl) => self.declare(fl.id, fl.pos, fl.span, false),
+            _ => {}
+        }
+
+        walk_stmt(self, s);
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match *e {
+            ExprIdent(ref ident) => {
+                if let Some(var_id) = self.var(ident.id) {
+                    self.used[var_id.0] = true;
+                }
+            }
+
+            ExprBin(ref bin) if bin.op == BinOp::Assign && bin.lhs.is_ident() => {
+                if let Some(var_id) = self.var(bin.lhs.id()) {
+                    self.assigned[var_id.0] = true;
+                }
+
+                self.visit_expr(&bin.rhs);
+            }
+
+            ExprBin(ref bin) => {
+                self.check_reference_equality(bin);
+                walk_expr(self, e);
+            }
+
+            ExprBlock(ref block) => self.check_block(block),
+
+            // lambda bodies are not checked yet
+            ExprLambda(_) => {}
+
+            _ => walk_expr(self, e),
+        }
+    }
+}
+
+fn stmt_diverges(stmt: &Stmt) -> bool {
+    match *stmt {
+        StmtBreak(_) | StmtContinue(_) => true,
+        _ => returnck::returns_value(stmt).is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::msg::SemWarning;
+    use crate::test;
+    use dora_parser::lexer::position::Position;
+
+    fn warnings(code: &'static str, expected: &[(Position, SemWarning)]) {
+        test::parse(code, |vm| {
+            let diag = vm.diag.lock();
+            let warnings = diag
+                .warnings()
+                .iter()
+                .map(|warning| (warning.pos, warning.msg.clone()))
+                .collect::<Vec<_>>();
+
+            assert_eq!(expected, &warnings[..]);
+        });
+    }
+
+    fn pos(line: u32, column: u32) -> Position {
+        Position::new(line, column)
+    }
+
+    #[test]
+    fn lint_unused_variable() {
+        warnings("fun main() { let x = 1; x; }", &[]);
+        warnings(
+            "fun main() { let x = 1; }",
+            &[(pos(1, 14), SemWarning::UnusedVariable("x".into()))],
+        );
+        warnings(
+            "fun main() { var x = 1; x = 2; }",
+            &[(pos(1, 14), SemWarning::UnusedVariable("x".into()))],
+        );
+        warnings(
+            "fun main() { for i in range(0, 3) { } }",
+            &[(pos(1, 14), SemWarning::UnusedVariable("i".into()))],
+        );
+        warnings("fun main() { let _x = 1; }", &[]);
+    }
+
+    #[test]
+    fn lint_unused_param() {
+        warnings(
+            "fun main() { f(1); } fun f(a: Int32) {}",
+            &[(pos(1, 28), SemWarning::UnusedParam("a".into()))],
+        );
+        warnings("fun main() { f(1); } fun f(a: Int32) -> Int32 = a;", &[]);
+        warnings(
+            "@open class A { @open fun f(a: Int32) {} }
+             class B: A { @override fun f(a: Int32) {} }
+             fun main() { B().f(1); }",
+            &[],
+        );
+        warnings(
+            "trait T { fun f(a: Int32); }
+             class A impl T for A { fun f(a: Int32) {} }
+             fun main() { A().f(1); }",
+            &[],
+        );
+    }
+
+    #[test]
+    fn lint_never_reassigned() {
+        warnings("fun main() { var x = 1; x = x + 1; }", &[]);
+        warnings(
+            "fun main() { var x = 1; x; }",
+            &[(pos(1, 14), SemWarning::VarNeverReassigned("x".into()))],
+        );
+    }
+
+    #[test]
+    fn lint_unreachable_code() {
+        warnings(
+            "fun main() { return; 1; 2; }",
+            &[(pos(1, 22), SemWarning::UnreachableCode)],
+        );
+        warnings(
+            "fun f() -> Int32 { return 1; 2 } fun main() { f(); }",
+            &[(pos(1, 30), SemWarning::UnreachableCode)],
+        );
+        warnings(
+            "fun main() { while true { break; 1; } }",
+            &[(pos(1, 34), SemWarning::UnreachableCode)],
+        );
+        warnings(
+            "fun main() { if true { return; } else { return; } 1; }",
+            &[(pos(1, 51), SemWarning::UnreachableCode)],
+        );
+        warnings("fun main() { if true { return; } 1; }", &[]);
+    }
+
+    #[test]
+    fn lint_unused_function() {
+        warnings(
+            "fun main() {} fun f() {}",
+            &[(pos(1, 15), SemWarning::UnusedFunction("f".into()))],
+        );
+        warnings("fun main() { f(); } fun f() {}", &[]);
+        warnings("fun main() {} fun _f() {}", &[]);
+        warnings(
+            "fun main() {} let x: Int32 = f(); fun f() -> Int32 = 1;",
+            &[],
+        );
+    }
+
+    #[test]
+    fn lint_reference_equality() {
+        warnings(
+            "class A { fun equals(other: A) -> Bool = self == other; }
+             fun main() { A().equals(A()); }",
+            &[(
+                pos(1, 47),
+                SemWarning::ReferenceEquality("==".into(), "===".into()),
+            )],
+        );
+        warnings(
+            "class A { fun equals(other: A) -> Bool = self === other; }
+             fun main() { let a = A(); a == a; a != nil; }",
+            &[(
+                pos(2, 50),
+                SemWarning::ReferenceEquality("!=".into(), "!==".into()),
+            )],
+        );
+    }
+}
@@ -13,14 +13,16 @@ use crate::sym::TermSym::{
     SymClassConstructor, SymClassConstructorAndModule, SymConst, SymFct, SymGlobal, SymModule,
     SymStructConstructor, SymStructConstructorAndModule, SymVar,
 };
-use crate::sym::TypeSym::{SymClass, SymClassTypeParam, SymEnum, SymFctTypeParam, SymStruct};
+use crate::sym::TypeSym::{
+    SymClass, SymClassTypeParam, SymEnum, SymFctTypeParam, SymStruct, SymTrait,
+};
 use crate::ty::BuiltinType;
 
 pub fn check<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        if !vm.checks_body(&fct) {
             continue;
         }
 
@@ -191,7 +193,8 @@ impl<'a, 'ast> NameCheck<'a, 'ast> {
         let type_sym = self.vm.sym.lock().get_type(ident.name);
 
         match (term_sym, type_sym) {
-            (Some(SymVar(id)), None) => {
+            // local variables shadow types
+            (Some(SymVar(id)), _) => {
                 self.src.map_idents.insert(ident.id, IdentType::Var(id));
             }
 
@@ -256,12 +259,12 @@ impl<'a, 'ast> NameCheck<'a, 'ast> {
                 self.src.map_idents.insert(ident.id, IdentType::Struct(id))
             }
 
-            (None, None) => {
+            (None, None) | (None, Some(SymTrait(_))) => {
                 let name = self.vm.interner.str(ident.name).to_string();
-                report(
-                    self.vm,
+                self.vm.diag.lock().report_span(
                     self.fct.file,
                     ident.pos,
+                    ident.span,
                     SemError::UnknownIdentifier(name),
                 );
             }
@@ -341,10 +344,6 @@ impl<'a, 'ast> Visitor<'ast> for NameCheck<'a, 'ast> {
     }
 }
 
-fn report(vm: &VM, file: FileId, pos: Position, msg: SemError) {
-    vm.diag.lock().report(file, pos, msg);
-}
-
 fn str(vm: &VM, name: Name) -> String {
     vm.interner.str(name).to_string()
 }
@@ -390,6 +389,7 @@ mod tests {
     #[test]
     fn shadow_type_with_var() {
         ok("fun test() { let String = 3; }");
+        ok("fun test() -> Int32 { let String = 3; String }");
     }
 
     #[test]
@@ -444,6 +444,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn trait_used_as_identifier() {
+        err(
+            "trait Foo {}\nfun f() { Foo; }",
+            pos(2, 11),
+            SemError::UnknownIdentifier("Foo".into()),
+        );
+    }
+
     #[test]
     fn recursive_function_call() {
         ok("fun f() { f(); }");
@@ -80,6 +80,7 @@ fn internal_free_classes<'ast>(vm: &mut VM<'ast>) {
             size: InstanceSize::Fixed(Header::size()),
             fields: Vec::new(),
             ref_fields: Vec::new(),
+            tuple_ref_fields: Vec::new(),
             vtable: None,
         })));
 
@@ -91,6 +92,7 @@ fn internal_free_classes<'ast>(vm: &mut VM<'ast>) {
             size: InstanceSize::FreeArray,
             fields: Vec::new(),
             ref_fields: Vec::new(),
+            tuple_ref_fields: Vec::new(),
             vtable: None,
         })));
 
@@ -189,8 +191,18 @@ pub fn internal_functions<'ast>(vm: &mut VM<'ast>) {
         "forceMinorCollect",
         stdlib::gc_minor_collect as *const u8,
     );
+    native_fct(
+        vm,
+        "waitForConcurrentMarking",
+        stdlib::gc_wait_for_marking as *const u8,
+    );
     native_fct(vm, "sleep", stdlib::sleep as *const u8);
     native_fct(vm, "encodedBytecode", stdlib::bytecode as *const u8);
+    native_fct(
+        vm,
+        "encodedBytecodeForFct",
+        stdlib::bytecode_for_fct as *const u8,
+    );
 
     native_fct(vm, "call", stdlib::call as *const u8);
 
@@ -326,6 +338,32 @@ pub fn internal_functions<'ast>(vm: &mut VM<'ast>) {
     intrinsic_class_method(vm, clsid, "size", Intrinsic::StrLen);
     intrinsic_class_method(vm, clsid, "getByte", Intrinsic::StrGet);
     native_class_method(vm, clsid, "clone", stdlib::str_clone as *const u8);
+    native_class_method(
+        vm,
+        clsid,
+        "internalFindFrom",
+        stdlib::str_find_from as *const u8,
+    );
+    native_class_method(
+        vm,
+        clsid,
+        "internalFindLast",
+        stdlib::str_find_last as *const u8,
+    );
+    native_class_method(
+        vm,
+        clsid,
+        "internalCodePointOffset",
+        stdlib::str_code_point_offset as *const u8,
+    );
+    native_class_method(
+        vm,
+        clsid,
+        "startsWith",
+        stdlib::str_starts_with as *const u8,
+    );
+    native_class_method(vm, clsid, "endsWith", stdlib::str_ends_with as *const u8);
+    native_class_method(vm, clsid, "trim", stdlib::str_trim as *const u8);
     native_module_method(
         vm,
         module_id,
@@ -9,7 +9,7 @@ pub fn check<'ast>(vm: &VM<'ast>) {
     for fct in vm.fcts.iter() {
         let fct = fct.read();
 
-        if !fct.is_src() {
+        if !vm.checks_body(&fct) {
             continue;
         }
 
@@ -64,6 +64,8 @@ pub fn returns_value(s: &Stmt) -> Result<(), Position> {
         StmtContinue(ref stmt) => Err(stmt.pos),
         StmtVar(ref stmt) => Err(stmt.pos),
         StmtExpr(ref stmt) => expr_returns_value(&stmt.expr),
+        // don't report a missing return for code that couldn't be parsed
+        StmtError(_) => Ok(()),
     }
 }
 
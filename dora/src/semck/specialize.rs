@@ -3,6 +3,7 @@ use std::cmp::max;
 use std::ptr;
 use std::sync::Arc;
 
+use crate::gc::compressed;
 use crate::mem;
 use crate::object::Header;
 use crate::size::InstanceSize;
@@ -201,6 +202,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
             size: InstanceSize::Fixed(0),
             fields: Vec::new(),
             ref_fields: Vec::new(),
+            tuple_ref_fields: Vec::new(),
             vtable: None,
         })));
 
@@ -209,12 +211,14 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
 
     let mut fields;
     let mut ref_fields;
+    let mut tuple_ref_fields;
     let size;
     let parent_id;
 
     if cls.is_array || cls.is_str {
         fields = Vec::new();
         ref_fields = Vec::new();
+        tuple_ref_fields = Vec::new();
 
         size = if cls.is_array {
             let element_ty = type_params[0];
@@ -229,7 +233,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
 
                 if tuple.contains_references() {
                     for &offset in tuple.references() {
-                        ref_fields.push(offset);
+                        tuple_ref_fields.push(offset);
                     }
 
                     InstanceSize::TupleArray(tuple.size())
@@ -259,6 +263,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
 
             fields = Vec::new();
             ref_fields = cls_def.ref_fields.clone();
+            tuple_ref_fields = cls_def.tuple_ref_fields.clone();
             csize = match cls_def.size {
                 InstanceSize::Fixed(size) => size,
                 _ => unreachable!(),
@@ -267,6 +272,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
         } else {
             fields = Vec::with_capacity(cls.fields.len());
             ref_fields = Vec::new();
+            tuple_ref_fields = Vec::new();
             csize = Header::size();
             parent_id = None;
         };
@@ -275,8 +281,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
             let ty = specialize_type(vm, f.ty, &type_params, &TypeList::empty());
             debug_assert!(!ty.contains_type_param(vm));
 
-            let field_size = ty.size(vm);
-            let field_align = ty.align(vm);
+            let (field_size, field_align) = ClassDef::field_layout(vm, ty);
 
             let offset = mem::align_i32(csize, field_align);
             fields.push(FieldDef { offset, ty });
@@ -288,7 +293,7 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
                 let tuple = tuples.get_tuple(tuple_id);
 
                 for &ref_offset in tuple.references() {
-                    ref_fields.push(offset + ref_offset);
+                    tuple_ref_fields.push(offset + ref_offset);
                 }
             } else if ty.reference_type() {
                 ref_fields.push(offset);
@@ -306,12 +311,13 @@ fn create_specialized_class(vm: &VM, cls: &Class, type_params: &TypeList) -> Cla
     cls_def.size = size;
     cls_def.fields = fields;
     cls_def.ref_fields = ref_fields;
+    cls_def.tuple_ref_fields = tuple_ref_fields;
     cls_def.parent_id = parent_id;
 
     let (instance_size, element_size) = match size {
         InstanceSize::Fixed(instance_size) => (instance_size as usize, 0),
         InstanceSize::PrimitiveArray(element_size) => (0, element_size as usize),
-        InstanceSize::ObjArray => (0, mem::ptr_width_usize()),
+        InstanceSize::ObjArray => (0, compressed::ref_size_usize()),
         InstanceSize::UnitArray => (Header::size() as usize + mem::ptr_width_usize(), 0),
         InstanceSize::FreeArray => (0, mem::ptr_width_usize()),
         InstanceSize::Str => (0, 1),
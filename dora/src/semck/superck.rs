@@ -3,20 +3,17 @@ use std::collections::HashSet;
 use crate::error::msg::SemError;
 use crate::semck::specialize::replace_type_param;
 use crate::ty::TypeList;
+use crate::vm::cha;
 use crate::vm::{find_method_in_class, Class, ClassId, Fct, FctId, VM};
 
 pub fn check<'ast>(vm: &mut VM<'ast>) {
-    cycle_detection(vm);
-
-    if vm.diag.lock().has_errors() {
-        return;
-    }
-
     // determine_struct_sizes(vm);
     determine_vtables(vm);
+    cha::analyze(vm);
 }
 
-fn cycle_detection<'ast>(vm: &mut VM<'ast>) {
+// Runs before any pass walks the super classes, they would loop forever.
+pub fn cycle_detection<'ast>(vm: &mut VM<'ast>) {
     for cls in vm.classes.iter() {
         let cls = cls.read();
 
@@ -347,6 +344,20 @@ mod tests {
                 (pos(1, 24), SemError::CycleInHierarchy),
             ],
         );
+
+        errors(
+            "@open class A: B {} @open class B: A {} fun f(a: A) { a.foo(); }",
+            &[
+                (pos(1, 7), SemError::CycleInHierarchy),
+                (pos(1, 27), SemError::CycleInHierarchy),
+            ],
+        );
+
+        err(
+            "@open class A(a: Int32): A(a)",
+            pos(1, 7),
+            SemError::CycleInHierarchy,
+        );
     }
 
     #[test]
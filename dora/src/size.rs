@@ -1,4 +1,4 @@
-use crate::mem;
+use crate::gc::compressed;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum InstanceSize {
@@ -15,7 +15,7 @@ impl InstanceSize {
     pub fn element_size(&self) -> Option<i32> {
         match self {
             InstanceSize::PrimitiveArray(esize) => Some(*esize),
-            InstanceSize::ObjArray => Some(mem::ptr_width()),
+            InstanceSize::ObjArray => Some(compressed::ref_size()),
             InstanceSize::Str => Some(1),
             InstanceSize::Fixed(_) => None,
             InstanceSize::FreeArray => unreachable!(),
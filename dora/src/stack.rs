@@ -1,11 +1,16 @@
 use std::ptr;
 
-use crate::compiler::fct::JitFctId;
 use crate::compiler::map::CodeDescriptor;
 use crate::handle::{root, Handle};
-use crate::object::{alloc, Array, Int32Array, Ref, Stacktrace, StacktraceElement, Str};
+use crate::object::{
+    alloc, read_ref, write_int32, write_ref, Array, Int32Array, Ref, Stacktrace, StacktraceElement,
+    Str,
+};
+use crate::semck::specialize::specialize_class_id;
 use crate::threads::THREAD;
-use crate::vm::{get_vm, FctParent, VM};
+use crate::vm::{get_vm, FctId, FctParent, FileId, VM};
+
+use dora_parser::lexer::position::Position;
 
 pub struct NativeStacktrace {
     elems: Vec<StackElem>,
@@ -20,50 +25,50 @@ impl NativeStacktrace {
         self.elems.len()
     }
 
-    pub fn push_entry(&mut self, fct_id: JitFctId, lineno: i32) {
-        self.elems.push(StackElem { fct_id, lineno });
+    pub fn push_entry(&mut self, fct_id: FctId, file: FileId, pos: Position) {
+        self.elems.push(StackElem { fct_id, file, pos });
     }
 
     pub fn dump(&self, vm: &VM) {
-        let frames = self.elems.len();
-        for (ind, elem) in self.elems.iter().enumerate() {
-            let jit_fct = vm.jit_fcts.idx(elem.fct_id);
-            let fct_id = jit_fct.fct_id();
-            let fct = vm.fcts.idx(fct_id);
-            let fct = fct.read();
-            let name = fct.full_name(vm);
-            print!("{}: {}: ", frames - ind, name);
-
-            if elem.lineno == 0 {
-                println!("?");
-            } else {
-                println!("{}", elem.lineno);
-            }
+        for line in self.lines(vm) {
+            println!("{}", line);
         }
     }
 
     pub fn dump_err(&self, vm: &VM) {
+        for line in self.lines(vm) {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn lines(&self, vm: &VM) -> Vec<String> {
         let frames = self.elems.len();
-        for (ind, elem) in self.elems.iter().enumerate() {
-            let jit_fct = vm.jit_fcts.idx(elem.fct_id);
-            let fct_id = jit_fct.fct_id();
-            let fct = vm.fcts.idx(fct_id);
-            let fct = fct.read();
-            let name = fct.full_name(vm);
-            eprint!("{}: {}: ", frames - ind, name);
 
-            if elem.lineno == 0 {
-                eprintln!("?");
-            } else {
-                eprintln!("{}", elem.lineno);
-            }
-        }
+        self.elems
+            .iter()
+            .enumerate()
+            .map(|(ind, elem)| {
+                let fct = vm.fcts.idx(elem.fct_id);
+                let fct = fct.read();
+                let name = fct.full_name(vm);
+
+                format!(
+                    "{}: {}: {}:{}:{}",
+                    frames - ind,
+                    name,
+                    vm.file(elem.file).name,
+                    elem.pos.line,
+                    elem.pos.column
+                )
+            })
+            .collect()
     }
 }
 
 struct StackElem {
-    fct_id: JitFctId,
-    lineno: i32,
+    fct_id: FctId,
+    file: FileId,
+    pos: Position,
 }
 
 #[repr(C)]
@@ -151,11 +156,13 @@ fn determine_stack_entry(stacktrace: &mut NativeStacktrace, vm: &VM, pc: usize)
             let jit_fct = vm.jit_fcts.idx(fct_id);
 
             let offset = pc - jit_fct.instruction_start().to_usize();
-            let position = jit_fct
-                .position_for_offset(offset as u32)
+            let frames = jit_fct
+                .frames_for_offset(offset as u32)
                 .expect("position not found for program point");
 
-            stacktrace.push_entry(fct_id, position.line as i32);
+            for (fct_id, position) in frames {
+                stacktrace.push_entry(fct_id, position.file, position.pos);
+            }
 
             true
         }
@@ -165,13 +172,14 @@ fn determine_stack_entry(stacktrace: &mut NativeStacktrace, vm: &VM, pc: usize)
             let fct = vm.fcts.idx(jit_fct.fct_id());
             let fct = fct.read();
 
-            stacktrace.push_entry(fct_id, fct.ast.pos.line as i32);
+            stacktrace.push_entry(fct.id, fct.file, fct.ast.pos);
 
             true
         }
 
         Some(CodeDescriptor::TrapStub) => true,
         Some(CodeDescriptor::GuardCheckStub) => true,
+        Some(CodeDescriptor::DebugStub) => true,
         Some(CodeDescriptor::CompileStub) => true,
         Some(CodeDescriptor::AllocStub) => true,
         Some(CodeDescriptor::DoraStub) => false,
@@ -184,6 +192,10 @@ fn determine_stack_entry(stacktrace: &mut NativeStacktrace, vm: &VM, pc: usize)
     }
 }
 
+// number of Int32 entries per frame in Stacktrace.backtrace:
+// function, file, line and column
+const STACK_ELEMENT_SIZE: usize = 4;
+
 pub struct ThrowResume {
     pc: usize,
     sp: usize,
@@ -197,29 +209,36 @@ pub extern "C" fn retrieve_stack_trace(obj: Handle<Stacktrace>) {
 
 pub extern "C" fn stack_element(obj: Handle<Stacktrace>, ind: i32) -> Ref<StacktraceElement> {
     let vm = get_vm();
-    let array = obj.backtrace;
+    let stacktrace_cls_id = specialize_class_id(vm, vm.vips.stacktrace_class);
+    let backtrace = vm.field_in_class(stacktrace_cls_id, "backtrace");
+    let array: Ref<Int32Array> =
+        read_ref(vm, obj.direct().cast(), stacktrace_cls_id, backtrace).cast();
 
-    let ind = ind as usize * 2;
+    let ind = ind as usize * STACK_ELEMENT_SIZE;
 
-    let lineno = array.get_at(ind);
-    let fct_id = array.get_at(ind + 1);
+    let fct_id = FctId(array.get_at(ind) as usize);
+    let file = FileId::from(array.get_at(ind + 1) as u32);
+    let line = array.get_at(ind + 2);
+    let column = array.get_at(ind + 3);
     let cls_def_id = vm.vips.stack_trace_element(vm);
 
-    let ste: Ref<StacktraceElement> = alloc(vm, cls_def_id).cast();
-    let mut ste = root(ste);
-    ste.line = lineno;
+    let ste = root(alloc(vm, cls_def_id));
+    let field = |name| vm.field_in_class(cls_def_id, name);
+    write_int32(vm, ste.direct(), cls_def_id, field("line"), line);
+    write_int32(vm, ste.direct(), cls_def_id, field("column"), column);
 
-    let jit_fct_id = JitFctId::from(fct_id as usize);
-    let jit_fct = vm.jit_fcts.idx(jit_fct_id);
-    let fct = vm.fcts.idx(jit_fct.fct_id());
+    let fct = vm.fcts.idx(fct_id);
     let fct = fct.read();
     let name = fct.full_name(vm);
-    ste.name = Str::from_buffer(vm, name.as_bytes());
+    let name = Str::from_buffer(vm, name.as_bytes());
+    write_ref(vm, ste.direct(), cls_def_id, field("name"), name.cast());
+    let file = Str::from_buffer(vm, vm.file(file).name.as_bytes());
+    write_ref(vm, ste.direct(), cls_def_id, field("file"), file.cast());
 
-    ste.direct()
+    ste.direct().cast()
 }
 
-fn set_backtrace(vm: &VM, mut obj: Handle<Stacktrace>, via_retrieve: bool) {
+fn set_backtrace(vm: &VM, obj: Handle<Stacktrace>, via_retrieve: bool) {
     let stacktrace = stacktrace_from_last_dtn(vm);
     let mut skip = 0;
 
@@ -229,9 +248,7 @@ fn set_backtrace(vm: &VM, mut obj: Handle<Stacktrace>, via_retrieve: bool) {
     // ignore every element until first not inside susubclass of Stacktrace (ctor of Exception)
     if via_retrieve {
         for elem in stacktrace.elems.iter() {
-            let jit_fct_id = JitFctId::from(elem.fct_id.idx() as usize);
-            let jit_fct = vm.jit_fcts.idx(jit_fct_id);
-            let fct_id = jit_fct.fct_id();
+            let fct_id = elem.fct_id;
             let fct = vm.fcts.idx(fct_id);
             let fct = fct.read();
 
@@ -254,7 +271,8 @@ fn set_backtrace(vm: &VM, mut obj: Handle<Stacktrace>, via_retrieve: bool) {
                 assert!(skip_retrieve_stack);
                 if let FctParent::Class(owner_class) = fct.parent {
                     if fct.is_constructor {
-                        let throw_object_cls_id = (&obj.header)
+                        let throw_object_cls_id = obj
+                            .header
                             .vtbl()
                             .class()
                             .cls_id
@@ -289,14 +307,24 @@ fn set_backtrace(vm: &VM, mut obj: Handle<Stacktrace>, via_retrieve: bool) {
     let len = stacktrace.len() - skip;
 
     let cls_id = vm.vips.int_array(vm);
-    let array: Ref<Int32Array> = Array::alloc(vm, len * 2, 0, cls_id);
+    let array: Ref<Int32Array> = Array::alloc(vm, len * STACK_ELEMENT_SIZE, 0, cls_id);
     let mut array = root(array);
     let mut i = 0;
 
     for elem in stacktrace.elems.iter().skip(skip) {
-        array.set_at(i, elem.lineno);
-        array.set_at(i + 1, elem.fct_id.idx() as i32);
-        i += 2;
+        array.set_at(i, elem.fct_id.to_usize() as i32);
+        array.set_at(i + 1, elem.file.to_usize() as i32);
+        array.set_at(i + 2, elem.pos.line as i32);
+        array.set_at(i + 3, elem.pos.column as i32);
+        i += STACK_ELEMENT_SIZE;
     }
-    obj.backtrace = array.direct();
+    let stacktrace_cls_id = specialize_class_id(vm, vm.vips.stacktrace_class);
+    let backtrace = vm.field_in_class(stacktrace_cls_id, "backtrace");
+    write_ref(
+        vm,
+        obj.direct().cast(),
+        stacktrace_cls_id,
+        backtrace,
+        array.direct().cast(),
+    );
 }
@@ -1,5 +1,6 @@
 use libc;
 
+use std::cmp::Ordering;
 use std::io::{self, Write};
 use std::mem;
 use std::process;
@@ -11,11 +12,12 @@ use crate::boots;
 use crate::gc::{Address, GcReason};
 use crate::handle::{scope as handle_scope, Handle};
 use crate::object::{Obj, Ref, Str, UInt8Array};
+use crate::profiler;
 use crate::stack::stacktrace_from_last_dtn;
 use crate::sym::TermSym::SymFct;
 use crate::threads::{DoraThread, STACK_SIZE, THREAD};
 use crate::ty::TypeList;
-use crate::vm::{get_vm, stack_pointer, Trap};
+use crate::vm::{get_vm, stack_pointer, FctDefId, Trap};
 
 pub extern "C" fn uint8_to_string(val: u8) -> Ref<Str> {
     handle_scope(|| {
@@ -156,12 +158,22 @@ pub extern "C" fn bytecode(name: Handle<Str>) -> Ref<Obj> {
     boots::bytecode(vm, &fct_name)
 }
 
+pub extern "C" fn bytecode_for_fct(fct_def_id: i32) -> Ref<Obj> {
+    let vm = get_vm();
+
+    if fct_def_id < 0 || fct_def_id as usize >= vm.fct_defs.len() {
+        return Ref::null();
+    }
+
+    boots::bytecode_for_fct_def(vm, FctDefId(fct_def_id as usize))
+}
+
 pub extern "C" fn strcmp(lhs: Handle<Str>, rhs: Handle<Str>) -> i32 {
-    unsafe {
-        libc::strcmp(
-            lhs.data() as *const libc::c_char,
-            rhs.data() as *const libc::c_char,
-        )
+    // comparing UTF-8 byte-wise orders strings by code points
+    match lhs.content().cmp(rhs.content()) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
     }
 }
 
@@ -189,11 +201,114 @@ pub extern "C" fn str_from_bytes(val: Handle<UInt8Array>, offset: usize, len: us
     })
 }
 
+pub extern "C" fn str_find_from(val: Handle<Str>, needle: Handle<Str>, start: i64) -> i64 {
+    let haystack = val.content();
+    let needle = needle.content();
+
+    if start < 0 || start as usize > haystack.len() {
+        return -1;
+    }
+
+    let start = start as usize;
+
+    if needle.is_empty() {
+        return start as i64;
+    }
+
+    haystack[start..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map_or(-1, |idx| (start + idx) as i64)
+}
+
+pub extern "C" fn str_find_last(val: Handle<Str>, needle: Handle<Str>) -> i64 {
+    let haystack = val.content();
+    let needle = needle.content();
+
+    if needle.is_empty() {
+        return haystack.len() as i64;
+    }
+
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+        .map_or(-1, |idx| idx as i64)
+}
+
+pub extern "C" fn str_code_point_offset(val: Handle<Str>, idx: i64) -> i64 {
+    if idx < 0 {
+        return -1;
+    }
+
+    let mut remaining = idx;
+
+    for (offset, &byte) in val.content().iter().enumerate() {
+        // skip continuation bytes
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+
+        if remaining == 0 {
+            return offset as i64;
+        }
+
+        remaining -= 1;
+    }
+
+    if remaining == 0 {
+        val.len() as i64
+    } else {
+        -1
+    }
+}
+
+pub extern "C" fn str_starts_with(val: Handle<Str>, prefix: Handle<Str>) -> bool {
+    val.content().starts_with(prefix.content())
+}
+
+pub extern "C" fn str_ends_with(val: Handle<Str>, suffix: Handle<Str>) -> bool {
+    val.content().ends_with(suffix.content())
+}
+
+pub extern "C" fn str_trim(val: Handle<Str>) -> Ref<Str> {
+    handle_scope(|| {
+        let vm = get_vm();
+        let content = val.content();
+
+        let start = content
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .unwrap_or(content.len());
+        let end = content
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(start, |idx| idx + 1);
+
+        Str::from_str(vm, val, start, end - start)
+    })
+}
+
 pub extern "C" fn gc_alloc(size: usize, array_ref: bool) -> *mut Obj {
     let vm = get_vm();
     vm.gc.alloc(vm, size, array_ref).to_mut_ptr()
 }
 
+pub extern "C" fn gc_alloc_old(size: usize, array_ref: bool, site: u32) -> *mut Obj {
+    let vm = get_vm();
+    let object = vm.gc.alloc_old(vm, size, array_ref);
+
+    if !object.is_null() {
+        vm.gc.alloc_sites.record_pretenured(site, size);
+    }
+
+    object.to_mut_ptr()
+}
+
+pub extern "C" fn gc_pre_barrier(reference: Address) {
+    let vm = get_vm();
+    vm.gc.pre_barrier(reference);
+}
+
 pub extern "C" fn gc_collect() {
     let vm = get_vm();
     vm.gc.collect(vm, GcReason::ForceCollect);
@@ -204,6 +319,11 @@ pub extern "C" fn gc_minor_collect() {
     vm.gc.minor_collect(vm, GcReason::ForceMinorCollect);
 }
 
+pub extern "C" fn gc_wait_for_marking() {
+    let vm = get_vm();
+    vm.gc.wait_for_marking(vm);
+}
+
 pub extern "C" fn argc() -> i32 {
     let vm = get_vm();
 
@@ -300,6 +420,8 @@ pub extern "C" fn spawn_thread(obj: Handle<Obj>) {
             thread.borrow().tld.set_stack_limit(stack_limit);
         });
 
+        let sampler = profiler::register_current_thread();
+
         let main = {
             let cls_id = obj.header().vtbl().class().cls_id;
             let cls_id = cls_id.expect("no corresponding class");
@@ -333,6 +455,7 @@ pub extern "C" fn spawn_thread(obj: Handle<Obj>) {
         let fct: extern "C" fn(Address, Address, Ref<Obj>) =
             unsafe { mem::transmute(dora_stub_address) };
         fct(tld, fct_ptr, obj);
+        drop(sampler);
 
         // remove thread from list of all threads
         vm.threads.detach_current_thread();
@@ -32,27 +32,29 @@ where
     let mut vm = VM::new(args, &empty);
 
     parse_bundled_stdlib(&mut vm, &mut ast).expect("failed parsing stdlib");
+    vm.program_files = vm.files.len();
 
     {
         let filename = "<<code>>";
         let reader = Reader::from_string(filename, code);
         let parser = Parser::new(reader, &vm.id_generator, &mut ast, &mut vm.interner);
-        match parser.parse() {
-            Ok(file) => {
-                vm.files.push(file);
-                assert_eq!(ast.files.len(), vm.files.len());
-            }
-
-            Err(error) => {
-                println!(
-                    "error in {} at {}: {}",
-                    filename,
-                    error.pos,
-                    error.error.message()
-                );
-                panic!("error during parsing.");
-            }
+        let (file, errors) = parser.parse();
+
+        for error in &errors {
+            println!(
+                "error in {} at {}: {}",
+                filename,
+                error.pos,
+                error.error.message()
+            );
+        }
+
+        if !errors.is_empty() {
+            panic!("error during parsing.");
         }
+
+        vm.files.push(file);
+        assert_eq!(ast.files.len(), vm.files.len());
     }
 
     vm.ast = &ast;
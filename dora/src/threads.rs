@@ -6,7 +6,6 @@ use std::sync::Arc;
 
 use crate::gc::{tlab, Address, Region, K};
 use crate::handle::HandleMemory;
-use crate::safepoint;
 use crate::stack::DoraToNativeInfo;
 use crate::vm::{get_vm, VM};
 
@@ -20,6 +19,9 @@ pub struct Threads {
     pub threads: Mutex<Vec<Arc<DoraThread>>>,
     pub cond_join: Condvar,
 
+    // number of threads, readable without waiting for the lock
+    attached: AtomicUsize,
+
     pub next_id: AtomicUsize,
     pub safepoint: Mutex<(usize, usize)>,
 
@@ -31,6 +33,7 @@ impl Threads {
         Threads {
             threads: Mutex::new(Vec::new()),
             cond_join: Condvar::new(),
+            attached: AtomicUsize::new(0),
             next_id: AtomicUsize::new(1),
             safepoint: Mutex::new((0, 1)),
             barrier: Barrier::new(),
@@ -41,12 +44,30 @@ impl Threads {
         THREAD.with(|thread| {
             let mut threads = self.threads.lock();
             threads.push(thread.borrow().clone());
+            self.attached.store(threads.len(), Ordering::SeqCst);
         });
     }
 
     pub fn attach_thread(&self, thread: Arc<DoraThread>) {
-        let mut threads = self.threads.lock();
-        threads.push(thread);
+        let vm = get_vm();
+
+        // Another thread might hold the lock while stopping the world,
+        // so the current thread needs to reach the safepoint.
+        THREAD.with(|current| {
+            current.borrow().park(vm);
+            let mut threads = self.threads.lock();
+            vm.gc.attach_thread(&thread);
+            threads.push(thread);
+            self.attached.store(threads.len(), Ordering::SeqCst);
+            drop(threads);
+            current.borrow().unpark(vm);
+        });
+    }
+
+    // Doesn't wait for the lock, which another thread might hold while
+    // stopping the world.
+    pub fn count(&self) -> usize {
+        self.attached.load(Ordering::SeqCst)
     }
 
     pub fn next_id(&self) -> usize {
@@ -89,6 +110,7 @@ impl Threads {
             thread.borrow().park(vm);
             let mut threads = self.threads.lock();
             threads.retain(|elem| !Arc::ptr_eq(elem, &*thread.borrow()));
+            self.attached.store(threads.len(), Ordering::SeqCst);
             self.cond_join.notify_all();
         });
     }
@@ -193,11 +215,7 @@ impl DoraThread {
     }
 
     pub fn unpark(&self, vm: &VM) {
-        if vm.threads.safepoint_id() != 0 {
-            safepoint::block(vm, self);
-        }
-
-        self.state.unpark(vm);
+        self.state.unpark(&vm.threads);
     }
 
     pub fn block(&self, safepoint_id: usize) {
@@ -235,10 +253,24 @@ impl StateManager {
         mtx.0 = ThreadState::Parked;
     }
 
-    fn unpark(&self, _vm: &VM) {
-        let mut mtx = self.mtx.lock();
-        assert!(mtx.0.is_parked());
-        mtx.0 = ThreadState::Running;
+    fn unpark(&self, threads: &Threads) {
+        loop {
+            let mut mtx = self.mtx.lock();
+            assert!(mtx.0.is_parked());
+
+            // checked while holding the lock: a thread stopping the world
+            // either sees this thread still parked or already running
+            let safepoint_id = threads.safepoint_id();
+
+            if safepoint_id == 0 {
+                mtx.0 = ThreadState::Running;
+                return;
+            }
+
+            // stay parked until the world is resumed
+            drop(mtx);
+            threads.barrier.wait(safepoint_id);
+        }
     }
 
     fn block(&self, safepoint_id: usize) {
@@ -322,6 +354,7 @@ pub struct ThreadLocalData {
     tlab_top: AtomicUsize,
     tlab_end: AtomicUsize,
     concurrent_marking: AtomicBool,
+    satb_value: AtomicUsize,
     guard_stack_limit: AtomicUsize,
     real_stack_limit: AtomicUsize,
     dtn: AtomicUsize,
@@ -333,6 +366,7 @@ impl ThreadLocalData {
             tlab_top: AtomicUsize::new(0),
             tlab_end: AtomicUsize::new(0),
             concurrent_marking: AtomicBool::new(false),
+            satb_value: AtomicUsize::new(0),
             guard_stack_limit: AtomicUsize::new(0),
             real_stack_limit: AtomicUsize::new(0),
             dtn: AtomicUsize::new(0),
@@ -379,6 +413,18 @@ impl ThreadLocalData {
         offset_of!(ThreadLocalData, concurrent_marking) as i32
     }
 
+    pub fn set_concurrent_marking(&self, value: bool) {
+        self.concurrent_marking.store(value, Ordering::Relaxed);
+    }
+
+    pub fn satb_value(&self) -> Address {
+        Address::from(self.satb_value.load(Ordering::Relaxed))
+    }
+
+    pub fn satb_value_offset() -> i32 {
+        offset_of!(ThreadLocalData, satb_value) as i32
+    }
+
     pub fn guard_stack_limit_offset() -> i32 {
         offset_of!(ThreadLocalData, guard_stack_limit) as i32
     }
@@ -442,3 +488,38 @@ impl Barrier {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_unpark_waits_for_resume() {
+        let threads = Arc::new(Threads::new());
+        let state = Arc::new(StateManager::new());
+        state.mtx.lock().0 = ThreadState::Parked;
+
+        let safepoint_id = threads.request_safepoint();
+        threads.barrier.guard(safepoint_id);
+
+        let unparking = {
+            let threads = threads.clone();
+            let state = state.clone();
+            thread::spawn(move || state.unpark(&threads))
+        };
+
+        // the thread needs to stay parked for the stopped world, however
+        // long it tries to unpark
+        thread::sleep(Duration::from_millis(10));
+        assert!(state.state().is_parked());
+        assert!(state.in_safepoint(safepoint_id));
+
+        threads.barrier.resume(safepoint_id);
+        threads.clear_safepoint_request();
+
+        unparking.join().unwrap();
+        assert!(state.state().is_running());
+    }
+}
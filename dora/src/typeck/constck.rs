@@ -34,7 +34,10 @@ impl<'a, 'ast> ConstCheck<'a, 'ast> {
                 if lit_int.suffix == IntSuffix::UInt8 {
                     let ty = BuiltinType::UInt8.name(self.vm);
                     let msg = SemError::UnOpType(expr.op.as_str().into(), ty);
-                    self.vm.diag.lock().report(self.xconst.file, expr.pos, msg);
+                    self.vm
+                        .diag
+                        .lock()
+                        .report_span(self.xconst.file, expr.pos, expr.span, msg);
                 }
 
                 let (ty, val) = check_lit_int(
@@ -62,7 +65,7 @@ impl<'a, 'ast> ConstCheck<'a, 'ast> {
                 self.vm
                     .diag
                     .lock()
-                    .report(self.xconst.file, expr.pos(), msg);
+                    .report_span(self.xconst.file, expr.pos(), expr.span(), msg);
                 return (BuiltinType::Error, ConstValue::None);
             }
         };
@@ -75,7 +78,7 @@ impl<'a, 'ast> ConstCheck<'a, 'ast> {
             self.vm
                 .diag
                 .lock()
-                .report(self.xconst.file, expr.pos(), msg);
+                .report_span(self.xconst.file, expr.pos(), expr.span(), msg);
         }
 
         (ty, lit)
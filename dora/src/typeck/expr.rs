@@ -84,10 +84,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
         if !defined_type.is_error() && !defined_type.is_defined_type(self.vm) {
             let tyname = self.vm.interner.str(s.name).to_string();
-            self.vm
-                .diag
-                .lock()
-                .report(self.file, s.pos, SemError::VarNeedsTypeInfo(tyname));
+            self.vm.diag.lock().report_span(
+                self.file,
+                s.pos,
+                s.span,
+                SemError::VarNeedsTypeInfo(tyname),
+            );
 
             return;
         }
@@ -105,15 +107,20 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 let defined_type = defined_type.name(self.vm);
                 let expr_type = expr_type.name(self.vm);
                 let msg = SemError::AssignType(name, defined_type, expr_type);
-                self.vm.diag.lock().report(self.file, s.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, s.pos, s.span, msg);
             }
 
         // let variable binding needs to be assigned
         } else if !s.reassignable {
-            self.vm
-                .diag
-                .lock()
-                .report(self.file, s.pos, SemError::LetMissingInitialization);
+            self.vm.diag.lock().report_span(
+                self.file,
+                s.pos,
+                s.span,
+                SemError::LetMissingInitialization,
+            );
         }
     }
 
@@ -203,7 +210,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             } else {
                 let ret = make_iterator_ret.name(self.vm);
                 let msg = SemError::MakeIteratorReturnType(ret);
-                self.vm.diag.lock().report(self.file, s.expr.pos(), msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, s.expr.pos(), s.expr.span(), msg);
 
                 // set invalid error type
                 let var_id = *self.src.map_vars.get(s.id).unwrap();
@@ -224,7 +234,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         if !expr_type.is_error() && !expr_type.is_bool() {
             let expr_type = expr_type.name(self.vm);
             let msg = SemError::WhileCondType(expr_type);
-            self.vm.diag.lock().report(self.file, s.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, s.pos, s.span, msg);
         }
 
         self.visit_stmt(&s.block);
@@ -293,9 +306,24 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
         for value in &tuple.values {
             let subtype = self.check_expr(value, BuiltinType::Any);
+
+            if subtype.is_nil() {
+                let msg = SemError::TupleElementNeedsTypeInfo;
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, value.pos(), value.span(), msg);
+            }
+
             subtypes.push(subtype);
         }
 
+        // the tuple can't be laid out without the types of all elements
+        if subtypes.iter().any(|ty| ty.is_error() || ty.is_nil()) {
+            self.src.set_ty(tuple.id, BuiltinType::Error);
+            return BuiltinType::Error;
+        }
+
         let tuple_id = ensure_tuple(self.vm, subtypes);
 
         let ty = BuiltinType::Tuple(tuple_id);
@@ -310,7 +338,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         if !expr_type.is_bool() && !expr_type.is_error() {
             let expr_type = expr_type.name(self.vm);
             let msg = SemError::IfCondType(expr_type);
-            self.vm.diag.lock().report(self.file, expr.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, expr.pos, expr.span, msg);
         }
 
         let then_type = self.check_expr(&expr.then_block, BuiltinType::Any);
@@ -330,7 +361,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 let then_type_name = then_type.name(self.vm);
                 let else_type_name = else_type.name(self.vm);
                 let msg = SemError::IfBranchTypesIncompatible(then_type_name, else_type_name);
-                self.vm.diag.lock().report(self.file, expr.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, expr.pos, expr.span, msg);
                 then_type
             } else {
                 then_type
@@ -349,7 +383,15 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         e: &'ast ExprIdentType,
         _expected_ty: BuiltinType,
     ) -> BuiltinType {
-        let ident_type = self.src.map_idents.get(e.id).unwrap();
+        let ident_type = match self.src.map_idents.get(e.id) {
+            Some(ident_type) => ident_type,
+
+            // nameck already reported the unknown identifier
+            None => {
+                self.src.set_ty(e.id, BuiltinType::Error);
+                return BuiltinType::Error;
+            }
+        };
 
         match ident_type {
             &IdentType::Var(varid) => {
@@ -397,10 +439,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             &IdentType::Fct(_) => {
                 if !self.used_in_call.contains(&e.id) {
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::FctUsedAsIdentifier);
+                    self.vm.diag.lock().report_span(
+                        self.file,
+                        e.pos,
+                        e.span,
+                        SemError::FctUsedAsIdentifier,
+                    );
                 }
 
                 self.src.set_ty(e.id, BuiltinType::Error);
@@ -410,10 +454,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             &IdentType::Class(_) => {
                 if !self.used_in_call.contains(&e.id) {
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::ClsUsedAsIdentifier);
+                    self.vm.diag.lock().report_span(
+                        self.file,
+                        e.pos,
+                        e.span,
+                        SemError::ClsUsedAsIdentifier,
+                    );
                 }
 
                 self.src.set_ty(e.id, BuiltinType::Error);
@@ -438,7 +484,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                     SemError::TypeParamUsedAsIdentifier
                 };
 
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
                 self.src.set_ty(e.id, BuiltinType::Error);
 
                 BuiltinType::Error
@@ -446,7 +495,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             &IdentType::Enum(_) => {
                 let msg = SemError::EnumUsedAsIdentifier;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
                 self.src.set_ty(e.id, BuiltinType::Error);
 
                 BuiltinType::Error
@@ -473,7 +525,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             self.vm
                 .diag
                 .lock()
-                .report(self.file, e.pos, SemError::LvalueExpected);
+                .report_span(self.file, e.pos, e.span, SemError::LvalueExpected);
         }
 
         self.src.set_ty(e.id, BuiltinType::Unit);
@@ -497,10 +549,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         match ident_type {
             &IdentType::Var(varid) => {
                 if !self.src.vars[varid].reassignable {
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::LetReassigned);
+                    self.vm.diag.lock().report_span(
+                        self.file,
+                        e.pos,
+                        e.span,
+                        SemError::LetReassigned,
+                    );
                 }
 
                 lhs_type = self.src.vars[varid].ty;
@@ -511,10 +565,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 let glob = glob.read();
 
                 if !e.initializer && !glob.reassignable {
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::LetReassigned);
+                    self.vm.diag.lock().report_span(
+                        self.file,
+                        e.pos,
+                        e.span,
+                        SemError::LetReassigned,
+                    );
                 }
 
                 lhs_type = glob.ty;
@@ -529,10 +585,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             }
 
             &IdentType::Const(_) => {
-                self.vm
-                    .diag
-                    .lock()
-                    .report(self.file, e.pos, SemError::AssignmentToConst);
+                self.vm.diag.lock().report_span(
+                    self.file,
+                    e.pos,
+                    e.span,
+                    SemError::AssignmentToConst,
+                );
 
                 return;
             }
@@ -541,7 +599,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 self.vm
                     .diag
                     .lock()
-                    .report(self.file, e.pos, SemError::FctReassigned);
+                    .report_span(self.file, e.pos, e.span, SemError::FctReassigned);
 
                 return;
             }
@@ -549,10 +607,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             &IdentType::Class(_)
             | &IdentType::ClassType(_, _)
             | &IdentType::ClassAndModule(_, _) => {
-                self.vm
-                    .diag
-                    .lock()
-                    .report(self.file, e.pos, SemError::ClassReassigned);
+                self.vm.diag.lock().report_span(
+                    self.file,
+                    e.pos,
+                    e.span,
+                    SemError::ClassReassigned,
+                );
 
                 return;
             }
@@ -560,19 +620,23 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             &IdentType::Module(_) => unreachable!(),
 
             &IdentType::TypeParam(_) | &IdentType::TypeParamStaticMethod(_, _) => {
-                self.vm
-                    .diag
-                    .lock()
-                    .report(self.file, e.pos, SemError::TypeParamReassigned);
+                self.vm.diag.lock().report_span(
+                    self.file,
+                    e.pos,
+                    e.span,
+                    SemError::TypeParamReassigned,
+                );
 
                 return;
             }
 
             &IdentType::Enum(_) | &IdentType::EnumValue(_, _) => {
-                self.vm
-                    .diag
-                    .lock()
-                    .report(self.file, e.pos, SemError::InvalidLhsAssignment);
+                self.vm.diag.lock().report_span(
+                    self.file,
+                    e.pos,
+                    e.span,
+                    SemError::InvalidLhsAssignment,
+                );
 
                 return;
             }
@@ -592,7 +656,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             self.src.set_ty(e.id, BuiltinType::Unit);
 
             let msg = SemError::AssignType(name, lhs_type, rhs_type);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
     }
 
@@ -634,7 +701,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             None => {
                 let msg = SemError::NameExpected;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
 
                 self.src.set_ty(e.id, BuiltinType::Error);
                 return;
@@ -669,10 +739,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 );
 
                 if !e.initializer && !field.reassignable {
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::LetReassigned);
+                    self.vm.diag.lock().report_span(
+                        self.file,
+                        e.pos,
+                        e.span,
+                        SemError::LetReassigned,
+                    );
                 }
 
                 if !fty.allows(self.vm, rhs_type) && !rhs_type.is_error() {
@@ -683,7 +755,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                     let rhs_type = rhs_type.name(self.vm);
 
                     let msg = SemError::AssignField(name, object_type, lhs_type, rhs_type);
-                    self.vm.diag.lock().report(self.file, e.pos, msg);
+                    self.vm
+                        .diag
+                        .lock()
+                        .report_span(self.file, e.pos, e.span, msg);
                 }
 
                 self.src.set_ty(e.id, BuiltinType::Unit);
@@ -695,7 +770,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         let field_name = self.vm.interner.str(name).to_string();
         let expr_name = object_type.name(self.vm);
         let msg = SemError::UnknownField(field_name, expr_name);
-        self.vm.diag.lock().report(self.file, field_expr.pos, msg);
+        self.vm
+            .diag
+            .lock()
+            .report_span(self.file, field_expr.pos, field_expr.span, msg);
 
         self.src.set_ty(e.id, BuiltinType::Unit);
     }
@@ -785,7 +863,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let ty = ty.name(self.vm);
             let msg = SemError::UnOpType(op.as_str().into(), ty);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
         self.src.set_ty(e.id, BuiltinType::Error);
@@ -875,7 +956,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let rhs_type = rhs_type.name(self.vm);
             let msg = SemError::BinOpType(op.as_str().into(), lhs_type, rhs_type);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
 
@@ -897,9 +981,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 {
                     let lhs_type = lhs_type.name(self.vm);
                     let rhs_type = rhs_type.name(self.vm);
-                    self.vm.diag.lock().report(
+                    self.vm.diag.lock().report_span(
                         self.file,
                         e.pos,
+                        e.span,
                         SemError::TypesIncompatible(lhs_type, rhs_type),
                     );
                 }
@@ -950,7 +1035,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let rhs_type = rhs_type.name(self.vm);
             let msg = SemError::BinOpType("equals".into(), lhs_type, rhs_type);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
         }
@@ -970,7 +1058,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let rhs_type = rhs_type.name(self.vm);
             let msg = SemError::BinOpType(op, lhs_type, rhs_type);
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
     }
 
@@ -1088,12 +1179,18 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 .collect::<Vec<_>>();
             let msg =
                 SemError::EnumArgsIncompatible(enum_name, variant_name, variant_types, arg_types);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         } else if variant.types.is_empty() {
             let enum_name = self.vm.interner.str(xenum.name).to_string();
             let variant_name = self.vm.interner.str(variant.name).to_string();
             let msg = SemError::EnumArgsNoParens(enum_name, variant_name);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
         let list_id = self.vm.lists.lock().insert(TypeList::empty());
@@ -1159,7 +1256,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 SemError::UnknownStaticMethodWithTypeParam
             };
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
             return BuiltinType::Error;
@@ -1195,7 +1295,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 .map(|a| a.name(self.vm))
                 .collect::<Vec<_>>();
             let msg = SemError::ParamTypesIncompatible(fct_name, fct_params, arg_types);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
         let call_type = CallType::TraitStatic(TypeParamId::Fct(tp_id), trait_id, fct_id);
@@ -1384,7 +1487,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             if cls.is_abstract {
                 let msg = SemError::NewAbstractClass;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
             }
 
             lookup.found_ret().unwrap()
@@ -1464,7 +1570,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 SemError::MultipleCandidatesForTypeParam(type_name, name, param_names)
             };
 
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
 
@@ -1489,7 +1598,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             class = class_expr.name;
         } else {
             let msg = SemError::ExpectedSomeIdentifier;
-            self.vm.diag.lock().report(self.file, class_expr.pos(), msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, class_expr.pos(), class_expr.span(), msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
             return BuiltinType::Error;
@@ -1499,10 +1611,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             method_name = method_name_expr.name;
         } else {
             let msg = SemError::ExpectedSomeIdentifier;
-            self.vm
-                .diag
-                .lock()
-                .report(self.file, method_name_expr.pos(), msg);
+            self.vm.diag.lock().report_span(
+                self.file,
+                method_name_expr.pos(),
+                method_name_expr.span(),
+                msg,
+            );
 
             self.src.set_ty(e.id, BuiltinType::Error);
             return BuiltinType::Error;
@@ -1542,7 +1656,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         }
 
         let msg = SemError::ClassExpected;
-        self.vm.diag.lock().report(self.file, e.pos, msg);
+        self.vm
+            .diag
+            .lock()
+            .report_span(self.file, e.pos, e.span, msg);
 
         self.src.set_ty(e.id, BuiltinType::Error);
 
@@ -1596,7 +1713,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         let name = self.vm.interner.str(cls.name).to_string();
         let arg_types = arg_types.iter().map(|t| t.name(self.vm)).collect();
         let msg = SemError::UnknownCtor(name, arg_types);
-        self.vm.diag.lock().report(self.file, e.pos, msg);
+        self.vm
+            .diag
+            .lock()
+            .report_span(self.file, e.pos, e.span, msg);
 
         BuiltinType::Error
     }
@@ -1624,7 +1744,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             ident.name
         } else {
             let msg = SemError::NameOfStaticMethodExpected;
-            self.vm.diag.lock().report(self.file, e.rhs.pos(), msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.rhs.pos(), e.rhs.span(), msg);
             return BuiltinType::Error;
         };
 
@@ -1673,7 +1796,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                             variant_types,
                             arg_types,
                         );
-                        self.vm.diag.lock().report(self.file, e.pos, msg);
+                        self.vm
+                            .diag
+                            .lock()
+                            .report_span(self.file, e.pos, e.span, msg);
                     }
 
                     self.src
@@ -1681,10 +1807,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                         .insert(e.id, IdentType::EnumValue(id, value));
                 } else {
                     let name = self.vm.interner.str(name).to_string();
-                    self.vm
-                        .diag
-                        .lock()
-                        .report(self.file, e.pos, SemError::UnknownEnumValue(name));
+                    self.vm.diag.lock().report_span(
+                        self.file,
+                        e.pos,
+                        e.span,
+                        SemError::UnknownEnumValue(name),
+                    );
                 }
 
                 let list_id = self.vm.lists.lock().insert(TypeList::empty());
@@ -1695,7 +1823,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             _ => {
                 let msg = SemError::InvalidLeftSideOfSeparator;
-                self.vm.diag.lock().report(self.file, e.lhs.pos(), msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.lhs.pos(), e.lhs.span(), msg);
 
                 self.src.set_ty(e.id, BuiltinType::Error);
                 return BuiltinType::Error;
@@ -1710,7 +1841,7 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         self.vm
             .diag
             .lock()
-            .report(self.file, e.pos, SemError::FctUsedAsIdentifier);
+            .report_span(self.file, e.pos, e.span, SemError::FctUsedAsIdentifier);
 
         BuiltinType::Error
     }
@@ -1757,7 +1888,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             _ => {
                 let msg = SemError::InvalidUseOfTypeParams;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
                 return expr_type;
             }
         }
@@ -1781,7 +1915,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             None => {
                 let msg = SemError::NameExpected;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
 
                 self.src.set_ty(e.id, BuiltinType::Error);
                 return BuiltinType::Error;
@@ -1826,7 +1963,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let field_name = self.vm.interner.str(name).to_string();
             let expr_name = object_type.name(self.vm);
             let msg = SemError::UnknownField(field_name, expr_name);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
         self.src.set_ty(e.id, BuiltinType::Error);
@@ -1844,7 +1984,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             None => {
                 let msg = SemError::IndexExpected;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
 
                 self.src.set_ty(e.id, BuiltinType::Error);
                 return BuiltinType::Error;
@@ -1860,7 +2003,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
         if index >= tuple.len() as u64 {
             let msg = SemError::IllegalTupleIndex(index, object_type.name(self.vm));
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
 
             self.src.set_ty(e.id, BuiltinType::Error);
             return BuiltinType::Error;
@@ -1903,7 +2049,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
             _ => {
                 let msg = SemError::ThisUnavailable;
-                self.vm.diag.lock().report(self.file, e.pos, msg);
+                self.vm
+                    .diag
+                    .lock()
+                    .report_span(self.file, e.pos, e.span, msg);
                 self.src.set_ty(e.id, BuiltinType::Unit);
 
                 BuiltinType::Unit
@@ -1917,7 +2066,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
         _expected_ty: BuiltinType,
     ) -> BuiltinType {
         let msg = SemError::SuperNeedsMethodCall;
-        self.vm.diag.lock().report(self.file, e.pos, msg);
+        self.vm
+            .diag
+            .lock()
+            .report_span(self.file, e.pos, e.span, msg);
         self.src.set_ty(e.id, BuiltinType::Unit);
 
         BuiltinType::Unit
@@ -1962,10 +2114,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
 
         if !check_type.is_cls() {
             let name = check_type.name(self.vm);
-            self.vm
-                .diag
-                .lock()
-                .report(self.file, e.pos, SemError::ReferenceTypeExpected(name));
+            self.vm.diag.lock().report_span(
+                self.file,
+                e.pos,
+                e.span,
+                SemError::ReferenceTypeExpected(name),
+            );
             let ty = if e.is {
                 BuiltinType::Bool
             } else {
@@ -1998,7 +2152,10 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             let object_type = object_type.name(self.vm);
             let check_type = check_type.name(self.vm);
             let msg = SemError::TypesIncompatible(object_type, check_type);
-            self.vm.diag.lock().report(self.file, e.pos, msg);
+            self.vm
+                .diag
+                .lock()
+                .report_span(self.file, e.pos, e.span, msg);
         }
 
         self.src.map_convs.insert(
@@ -2108,10 +2265,12 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
                 }
 
                 let ty = part_expr.name(self.vm);
-                self.vm
-                    .diag
-                    .lock()
-                    .report(self.file, part.pos(), SemError::ExpectedStringable(ty));
+                self.vm.diag.lock().report_span(
+                    self.file,
+                    part.pos(),
+                    part.span(),
+                    SemError::ExpectedStringable(ty),
+                );
             } else {
                 assert!(part.is_lit_str());
             }
@@ -2147,6 +2306,11 @@ impl<'a, 'ast> TypeCheck<'a, 'ast> {
             ExprBlock(ref expr) => self.check_expr_block(expr, expected_ty),
             ExprIf(ref expr) => self.check_expr_if(expr, expected_ty),
             ExprTuple(ref expr) => self.check_expr_tuple(expr, expected_ty),
+            ExprError(ref expr) => {
+                // syntax error was already reported
+                self.src.set_ty(expr.id, BuiltinType::Error);
+                BuiltinType::Error
+            }
         }
     }
 }
@@ -2166,6 +2330,7 @@ impl<'a, 'ast> Visitor<'ast> for TypeCheck<'a, 'ast> {
             // for the rest of the statements, no special handling is necessary
             StmtBreak(_) => visit::walk_stmt(self, s),
             StmtContinue(_) => visit::walk_stmt(self, s),
+            StmtError(_) => {}
             StmtExpr(ref stmt) => {
                 self.check_expr(&stmt.expr, BuiltinType::Any);
             }
@@ -2436,9 +2601,12 @@ pub fn check_lit_int(
         };
 
         if (negate && value > max) || (!negate && value >= max) {
-            vm.diag
-                .lock()
-                .report(file, e.pos, SemError::NumberOverflow(ty_name.into()));
+            vm.diag.lock().report_span(
+                file,
+                e.pos,
+                e.span,
+                SemError::NumberOverflow(ty_name.into()),
+            );
         }
     } else {
         assert!(!negate);
@@ -2451,9 +2619,12 @@ pub fn check_lit_int(
         };
 
         if value > max {
-            vm.diag
-                .lock()
-                .report(file, e.pos, SemError::NumberOverflow(ty_name.into()));
+            vm.diag.lock().report_span(
+                file,
+                e.pos,
+                e.span,
+                SemError::NumberOverflow(ty_name.into()),
+            );
         }
     }
 
@@ -2492,7 +2663,7 @@ pub fn check_lit_float(
 
         vm.diag
             .lock()
-            .report(file, e.pos, SemError::NumberOverflow(ty.into()));
+            .report_span(file, e.pos, e.span, SemError::NumberOverflow(ty.into()));
     }
 
     (ty, value)
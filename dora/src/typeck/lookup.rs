@@ -72,18 +72,10 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
     }
 
     pub fn method(mut self, obj: BuiltinType) -> MethodLookup<'a, 'ast> {
-        self.kind = if let Some(_) = obj.cls_id(self.vm) {
-            Some(LookupKind::Method(obj))
-        } else if let Some(_) = obj.module_id() {
-            Some(LookupKind::Method(obj))
-        } else if let BuiltinType::Trait(trait_id) = obj {
+        self.kind = if let BuiltinType::Trait(trait_id) = obj {
             Some(LookupKind::Trait(trait_id))
-        } else if obj.is_nil() {
-            Some(LookupKind::Method(obj))
-        } else if obj.is_enum() {
-            Some(LookupKind::Method(obj))
         } else {
-            panic!("neither object nor trait object: {:?}", obj);
+            Some(LookupKind::Method(obj))
         };
 
         self
@@ -341,16 +333,15 @@ impl<'a, 'ast> MethodLookup<'a, 'ast> {
         name: Name,
         is_static: bool,
     ) -> Option<FctId> {
-        if object_type.is_nil() {
-            return None;
-        }
-
         let candidates = if object_type.is_module() {
             find_methods_in_module(self.vm, object_type, name)
         } else if object_type.is_enum() {
             find_methods_in_enum(self.vm, object_type, name, is_static)
-        } else {
+        } else if object_type.cls_id(self.vm).is_some() {
             find_methods_in_class(self.vm, object_type, name, is_static)
+        } else {
+            // nil, tuples and lambdas don't have any methods
+            return None;
         };
 
         self.found_multiple_functions = candidates.len() > 1;
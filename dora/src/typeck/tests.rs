@@ -134,6 +134,13 @@ fn type_module_method_call() {
         pos(5, 34),
         SemError::ReturnType("String".into(), "Int32".into()),
     );
+
+    err(
+        "module Foo {}
+             fun f() { Foo::baz(); }",
+        pos(2, 32),
+        SemError::UnknownMethod("Foo".into(), "baz".into(), Vec::new()),
+    );
 }
 
 #[test]
@@ -1781,6 +1788,14 @@ fn test_tuple_literal() {
         pos(2, 9),
         SemError::ReturnType("(Int32, Int32)".into(), "(Int32, Bool)".into()),
     );
+
+    err(
+        "fun f() {
+        let x = (1, nil);
+    }",
+        pos(2, 21),
+        SemError::TupleElementNeedsTypeInfo,
+    );
 }
 
 #[test]
@@ -1818,6 +1833,19 @@ fn test_tuple_element() {
     );
 }
 
+#[test]
+fn test_for_over_tuple() {
+    err(
+        "
+        fun f() {
+            for i in (0, 1) {}
+        }
+    ",
+        pos(3, 13),
+        SemError::UnknownMethod("(Int32, Int32)".into(), "makeIterator".into(), Vec::new()),
+    );
+}
+
 #[test]
 fn test_inheritance_with_generics() {
     ok("
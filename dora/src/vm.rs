@@ -1,4 +1,5 @@
 use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
 use std::mem;
 use std::ptr;
 use std::sync::Arc;
@@ -9,6 +10,8 @@ use crate::compiler::dora_stub;
 use crate::compiler::fct::JitFct;
 use crate::compiler::map::{CodeDescriptor, CodeMap};
 use crate::compiler::native_stub::{self, NativeFct, NativeFctDescriptor, NativeStubs};
+use crate::compiler::pre_barrier_stub;
+use crate::debugger::{self, Debugger};
 use crate::driver::cmd::Args;
 use crate::error::diag::Diagnostic;
 use crate::gc::{Address, Gc};
@@ -28,6 +31,7 @@ use dora_parser::interner::*;
 use dora_parser::lexer::File;
 use dora_parser::parser::NodeIdGenerator;
 
+pub use self::cha::ClassHierarchy;
 pub use self::class::{
     find_field_in_class, find_method_in_class, find_methods_in_class, Class, ClassDef, ClassDefId,
     ClassId, TypeParam,
@@ -49,6 +53,7 @@ pub use self::traits::{TraitData, TraitId};
 pub use self::tuple::{ensure_tuple, TupleId, Tuples};
 pub use self::vip::{KnownClasses, KnownElements, KnownFunctions, KnownModules};
 
+pub mod cha;
 pub mod class;
 mod cnst;
 mod enums;
@@ -90,6 +95,8 @@ pub struct VM<'ast> {
     pub ast: &'ast ast::Ast,
     pub id_generator: NodeIdGenerator,
     pub files: Vec<File>,
+    pub program_files: usize, // files before this index are stdlib or boots
+    pub lib_bodies_checked: bool, // semck skips function bodies before program_files
     pub diag: Mutex<Diagnostic>,
     pub sym: Mutex<SymTable>,
     pub vips: KnownElements,
@@ -97,6 +104,9 @@ pub struct VM<'ast> {
     pub structs: GrowableVec<Mutex<StructData>>, // stores all struct source definitions
     pub struct_defs: GrowableVec<Mutex<StructDef>>, // stores all struct definitions
     pub classes: GrowableVec<RwLock<Class>>,   // stores all class source definitions
+    pub cha: RwLock<ClassHierarchy>,           // stores overridden methods and their dependents
+    pub debugger: Mutex<Debugger>,             // breakpoints and stepping state for `dora debug`
+    pub debugger_session: Mutex<()>,           // held by the thread stopped in the debugger
     pub class_defs: GrowableVec<RwLock<ClassDef>>, // stores all class definitions
     pub extensions: Vec<RwLock<ExtensionData>>, // stores all extension definitions
     pub tuples: Mutex<Tuples>,                 // stores all tuple definitions
@@ -110,6 +120,7 @@ pub struct VM<'ast> {
     pub traits: Vec<RwLock<TraitData>>,        // stores all trait definitions
     pub impls: Vec<RwLock<ImplData>>,          // stores all impl definitions
     pub code_map: Mutex<CodeMap>,              // stores all compiled functions
+    pub str_literals: Mutex<HashMap<(FctId, usize), Address>>, // string literals in perm space
     pub globals: GrowableVec<RwLock<GlobalData>>, // stores all global variables
     pub gc: Gc,                                // garbage collector
     pub native_stubs: Mutex<NativeStubs>,
@@ -119,6 +130,8 @@ pub struct VM<'ast> {
     pub dora_stub: Mutex<Address>,
     pub trap_stub: Mutex<Address>,
     pub guard_check_stub: Mutex<Address>,
+    pub debug_stub: Mutex<Address>,
+    pub pre_barrier_stub: Mutex<Address>,
     pub threads: Threads,
 }
 
@@ -134,10 +147,15 @@ impl<'ast> VM<'ast> {
         let vm = Box::new(VM {
             args,
             files: Vec::new(),
+            program_files: 0,
+            lib_bodies_checked: false,
             consts: GrowableVec::new(),
             structs: GrowableVec::new(),
             struct_defs: GrowableVec::new(),
             classes: GrowableVec::new(),
+            cha: RwLock::new(ClassHierarchy::new()),
+            debugger: Mutex::new(Debugger::new()),
+            debugger_session: Mutex::new(()),
             class_defs: GrowableVec::new(),
             extensions: Vec::new(),
             tuples: Mutex::new(Tuples::new()),
@@ -207,6 +225,7 @@ impl<'ast> VM<'ast> {
             jit_fcts: GrowableVec::new(),
             fct_defs: GrowableVec::new(),
             code_map: Mutex::new(CodeMap::new()),
+            str_literals: Mutex::new(HashMap::new()),
             lists: Mutex::new(TypeLists::new()),
             lambda_types: Mutex::new(LambdaTypes::new()),
             native_stubs: Mutex::new(NativeStubs::new()),
@@ -214,6 +233,8 @@ impl<'ast> VM<'ast> {
             dora_stub: Mutex::new(Address::null()),
             trap_stub: Mutex::new(Address::null()),
             guard_check_stub: Mutex::new(Address::null()),
+            debug_stub: Mutex::new(Address::null()),
+            pre_barrier_stub: Mutex::new(Address::null()),
             threads: Threads::new(),
         });
 
@@ -567,6 +588,16 @@ impl<'ast> VM<'ast> {
         *compile_stub_address
     }
 
+    pub fn pre_barrier_stub(&self) -> Address {
+        let mut pre_barrier_stub_address = self.pre_barrier_stub.lock();
+
+        if pre_barrier_stub_address.is_null() {
+            *pre_barrier_stub_address = pre_barrier_stub::generate(self);
+        }
+
+        *pre_barrier_stub_address
+    }
+
     pub fn trap_stub(&self) -> Address {
         let mut trap_stub_address = self.trap_stub.lock();
 
@@ -605,9 +636,38 @@ impl<'ast> VM<'ast> {
         *guard_check_stub_address
     }
 
+    pub fn debug_stub(&self) -> Address {
+        let mut debug_stub_address = self.debug_stub.lock();
+
+        if debug_stub_address.is_null() {
+            let ifct = NativeFct {
+                ptr: Address::from_ptr(debugger::statement_hook as *const u8),
+                args: &[],
+                return_type: BuiltinType::Unit,
+                desc: NativeFctDescriptor::DebugStub,
+            };
+            let jit_fct_id = native_stub::generate(self, ifct, false);
+            let jit_fct = self.jit_fcts.idx(jit_fct_id);
+            let fct_ptr = jit_fct.instruction_start();
+            *debug_stub_address = fct_ptr;
+        }
+
+        *debug_stub_address
+    }
+
     pub fn file(&self, idx: FileId) -> &File {
         &self.files[idx.0 as usize]
     }
+
+    pub fn is_program_file(&self, idx: FileId) -> bool {
+        idx.to_usize() >= self.program_files
+    }
+
+    // semck checks bodies of all source functions, except those of the
+    // stdlib if they were checked before.
+    pub fn checks_body(&self, fct: &Fct) -> bool {
+        fct.is_src() && (!self.lib_bodies_checked || self.is_program_file(fct.file))
+    }
 }
 
 unsafe impl<'ast> Sync for VM<'ast> {}
@@ -615,6 +675,12 @@ unsafe impl<'ast> Sync for VM<'ast> {}
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct FileId(u32);
 
+impl FileId {
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
 impl From<u32> for FileId {
     fn from(data: u32) -> FileId {
         FileId(data)
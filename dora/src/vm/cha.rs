@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::vm::{ClassId, Fct, FctId, VM};
+
+// Class hierarchy analysis: knows which virtual methods are overridden in
+// some subclass. Calls to virtual methods without any override have a single
+// implementation and can be invoked directly. Every function whose bytecode
+// relies on this is recorded as a dependency of the callee, so that adding a
+// class later on can determine which functions need to be recompiled.
+pub struct ClassHierarchy {
+    overridden: HashSet<FctId>,
+    dependencies: HashMap<FctId, Vec<FctId>>,
+}
+
+impl ClassHierarchy {
+    pub fn new() -> ClassHierarchy {
+        ClassHierarchy {
+            overridden: HashSet::new(),
+            dependencies: HashMap::new(),
+        }
+    }
+
+    pub fn has_single_implementation(&self, fct: &Fct) -> bool {
+        fct.is_virtual() && !fct.is_abstract && !self.overridden.contains(&fct.id)
+    }
+
+    pub fn is_overridden(&self, fct_id: FctId) -> bool {
+        self.overridden.contains(&fct_id)
+    }
+
+    pub fn add_dependency(&mut self, callee: FctId, caller: FctId) {
+        let callers = self.dependencies.entry(callee).or_insert_with(Vec::new);
+
+        if !callers.contains(&caller) {
+            callers.push(caller);
+        }
+    }
+
+    pub fn dependencies(&self, callee: FctId) -> &[FctId] {
+        self.dependencies
+            .get(&callee)
+            .map(|callers| callers.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // Registers the overrides of the given class. Returns all functions that
+    // devirtualized calls to a method that now has more than one implementation.
+    pub fn add_class(&mut self, vm: &VM, cls_id: ClassId) -> Vec<FctId> {
+        let cls = vm.classes.idx(cls_id);
+        let cls = cls.read();
+        let mut invalidated = Vec::new();
+
+        for &method in &cls.methods {
+            let fct = vm.fcts.idx(method);
+            let fct = fct.read();
+
+            if let Some(overrides) = fct.overrides {
+                if self.overridden.insert(overrides) {
+                    if let Some(callers) = self.dependencies.remove(&overrides) {
+                        invalidated.extend(callers);
+                    }
+                }
+            }
+        }
+
+        invalidated
+    }
+}
+
+pub fn analyze<'ast>(vm: &VM<'ast>) {
+    let mut cha = vm.cha.write();
+
+    for cls in vm.classes.iter() {
+        let cls_id = cls.read().id;
+        let invalidated = cha.add_class(vm, cls_id);
+        assert!(invalidated.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::semck::tests::ok_with_test;
+
+    #[test]
+    fn test_single_implementation() {
+        ok_with_test(
+            "@open class A { @open fun f() {} @open fun g() {} }
+            class B: A { @override fun f() {} }",
+            |vm| {
+                let cha = vm.cha.read();
+
+                let a_f = vm.cls_method_by_name("A", "f", false).unwrap();
+                let a_g = vm.cls_method_by_name("A", "g", false).unwrap();
+                let b_f = vm.cls_method_by_name("B", "f", false).unwrap();
+
+                assert!(cha.is_overridden(a_f));
+                assert!(!cha.is_overridden(a_g));
+                assert!(!cha.is_overridden(b_f));
+
+                assert!(!cha.has_single_implementation(&vm.fcts.idx(a_f).read()));
+                assert!(cha.has_single_implementation(&vm.fcts.idx(a_g).read()));
+                assert!(cha.has_single_implementation(&vm.fcts.idx(b_f).read()));
+            },
+        );
+    }
+
+    #[test]
+    fn test_add_class_invalidates_dependencies() {
+        ok_with_test(
+            "@open class A { @open fun f() {} }
+            class B: A { @override fun f() {} }
+            fun caller() {}",
+            |vm| {
+                let a_f = vm.cls_method_by_name("A", "f", false).unwrap();
+                let caller = vm.fct_by_name("caller").unwrap();
+                let b = vm.cls_by_name("B");
+
+                let mut cha = vm.cha.write();
+                cha.overridden.clear();
+                cha.add_dependency(a_f, caller);
+                cha.add_dependency(a_f, caller);
+                assert_eq!(&[caller], cha.dependencies(a_f));
+
+                assert_eq!(vec![caller], cha.add_class(vm, b));
+                assert!(cha.dependencies(a_f).is_empty());
+                assert!(cha.add_class(vm, b).is_empty());
+            },
+        );
+    }
+}
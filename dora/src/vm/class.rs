@@ -5,6 +5,7 @@ use std::convert::From;
 use std::iter::Iterator;
 use std::sync::Arc;
 
+use crate::gc::compressed;
 use crate::semck::specialize::replace_type_param;
 use crate::size::InstanceSize;
 use crate::sym::SymLevel;
@@ -398,10 +399,23 @@ pub struct ClassDef {
     pub fields: Vec<FieldDef>,
     pub size: InstanceSize,
     pub ref_fields: Vec<i32>,
+    // references inside of tuples always take the full pointer width
+    pub tuple_ref_fields: Vec<i32>,
     pub vtable: Option<VTableBox>,
 }
 
 impl ClassDef {
+    // size and alignment of a field with the given type, references take
+    // only 4 bytes with compressed references
+    pub fn field_layout(vm: &VM, ty: BuiltinType) -> (i32, i32) {
+        if ty.reference_type() {
+            let size = compressed::ref_size();
+            (size, size)
+        } else {
+            (ty.size(vm), ty.align(vm))
+        }
+    }
+
     pub fn name(&self, vm: &VM) -> String {
         if let Some(cls_id) = self.cls_id {
             let cls = vm.classes.idx(cls_id);
@@ -71,7 +71,12 @@ pub fn find_methods_in_module(
     let mut module_type = object_type;
 
     loop {
-        let module_id = module_type.module_id().expect("no module");
+        // the parent of a module is a class, its methods can't be called
+        // through the module
+        let module_id = match module_type.module_id() {
+            Some(module_id) => module_id,
+            None => break,
+        };
         let module = vm.modules.idx(module_id);
         let module = module.read();
 
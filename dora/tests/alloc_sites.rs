@@ -0,0 +1,78 @@
+use std::fs;
+
+mod common;
+
+const PROGRAM: &str = "
+fun main() {
+    var list: Node = nil;
+    var i = 0L;
+
+    while i < 200_000L {
+        // every node survives, the site gets pretenured
+        list = Node(i, list);
+
+        // dies right away, the site stays in the young generation
+        Temp(i);
+
+        i = i + 1L;
+    }
+
+    var sum = 0L;
+
+    while list !== nil {
+        sum = sum + list.value;
+        list = list.next;
+    }
+
+    assert(sum == 199_999L * 200_000L / 2L);
+}
+
+class Node(let value: Int64, let next: Node)
+class Temp(let value: Int64)
+";
+
+// columns of a line in the report: allocated, survived, pretenured and site
+fn site<'a>(report: &'a str, cls: &str) -> Vec<&'a str> {
+    let prefix = format!("{} in main ", cls);
+
+    report
+        .lines()
+        .find(|line| line.contains(&prefix))
+        .unwrap_or_else(|| panic!("no site for {} in report:\n{}", cls, report))
+        .split_whitespace()
+        .collect()
+}
+
+#[test]
+fn surviving_site_is_pretenured() {
+    let program = common::temp_file("allocsites.dora");
+    fs::write(&program, PROGRAM).unwrap();
+
+    let output = common::dora()
+        .arg("--gc=swiper")
+        .arg("--gc-young-size=2M")
+        .arg("--gc-alloc-sites")
+        .arg("--gc-pretenure")
+        .arg("--gc-verify")
+        .arg(&program)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let report = stdout
+        .split("Allocation sites:\n")
+        .nth(1)
+        .expect("report missing");
+    assert!(report.starts_with(" allocated  survived pretenured  site\n"));
+
+    // objects allocated with gc_alloc_old are counted as pretenured
+    let node = site(report, "Node");
+    assert_ne!("0B", node[2]);
+
+    let temp = site(report, "Temp");
+    assert_eq!("0.0%", temp[1]);
+    assert_eq!("0B", temp[2]);
+
+    fs::remove_file(&program).unwrap();
+}
@@ -0,0 +1,15 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Helpers for tests that inspect files or reports written by the dora
+// binary, all other tests are run by tools/tester.rb.
+
+// path in the temp directory that is unique for the test process
+pub fn temp_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("dora-{}-{}", std::process::id(), name))
+}
+
+pub fn dora() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_dora"))
+}
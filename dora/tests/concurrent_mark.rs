@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::PathBuf;
+
+mod common;
+
+// marks a list concurrently and then either forces the remark or, with
+// the argument `garbage`, allocates until marking finishes
+fn program() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/swiper/concurrent5.dora")
+}
+
+// runs the program and returns the records of all full collections
+fn full_collections(args: &[&str], program_args: &[&str], name: &str) -> Vec<String> {
+    let log = common::temp_file(name);
+
+    let status = common::dora()
+        .arg("--gc=swiper")
+        .args(args)
+        .arg(format!("--gc-log={}", log.display()))
+        .arg(program())
+        .args(program_args)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let content = fs::read_to_string(&log).unwrap();
+    fs::remove_file(&log).unwrap();
+
+    content
+        .lines()
+        .filter(|record| record.contains("\"kind\":\"full\""))
+        .map(|record| record.to_string())
+        .collect()
+}
+
+fn number(record: &str, name: &str) -> u64 {
+    let key = format!("\"{}\":", name);
+    let start = record.find(&key).expect("field missing") + key.len();
+    let value: String = record[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    value.parse().unwrap()
+}
+
+#[test]
+fn remark_only_rescans_objects_promoted_since_initial_mark() {
+    let args = ["--max-heap-size=16M", "--gc-concurrent-mark"];
+    let concurrent = full_collections(&args, &[], "remark1.json");
+    let stw = full_collections(&["--max-heap-size=16M"], &[], "remark2.json");
+
+    assert_eq!(2, concurrent.len());
+    assert_eq!(2, stw.len());
+
+    // nothing was promoted while marking, the list isn't traced again
+    let remark = concurrent.last().unwrap();
+    assert_eq!(0, number(remark, "rescanned"));
+    assert!(number(remark, "old") > 6_000_000);
+    assert!(!stw.last().unwrap().contains("\"rescanned\""));
+}
+
+#[test]
+fn finished_marking_forces_no_full_collection() {
+    let args = ["--max-heap-size=16M", "--gc-concurrent-mark"];
+    let concurrent = full_collections(&args, &["garbage"], "nofull1.json");
+    let stw = full_collections(&["--max-heap-size=16M"], &["garbage"], "nofull2.json");
+
+    // only the forced collection is a full collection
+    assert_eq!(1, concurrent.len());
+    assert_eq!(1, stw.len());
+}
@@ -0,0 +1,64 @@
+use std::fs;
+
+mod common;
+
+const PROGRAM: &str = "
+fun main() {
+    let x = Foo(Foo(nil));
+    forceMinorCollect();
+    forceMinorCollect();
+    forceCollect();
+    assert(x.next.next === nil);
+}
+
+class Foo(let next: Foo)
+";
+
+#[test]
+fn gc_log_is_summarized_by_gc_report() {
+    let program = common::temp_file("gclog.dora");
+    let log = common::temp_file("gclog.json");
+    fs::write(&program, PROGRAM).unwrap();
+
+    let status = common::dora()
+        .arg("--gc=swiper")
+        .arg(format!("--gc-log={}", log.display()))
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let content = fs::read_to_string(&log).unwrap();
+    let records: Vec<&str> = content.lines().collect();
+    assert_eq!(4, records.len());
+
+    for (idx, record) in records[..3].iter().enumerate() {
+        assert!(record.starts_with(&format!("{{\"gc\":{},\"time\":", idx + 1)));
+        assert!(record.contains("\"pause\":"));
+        assert!(record.contains("\"phases\":{"));
+        assert!(record.contains("\"before\":{\"eden\":"));
+        assert!(record.contains("\"after\":{\"eden\":"));
+        assert!(record.contains("\"promoted\":"));
+        assert!(record.contains("\"workers\":1"));
+    }
+
+    assert!(records[0].contains("\"kind\":\"minor\",\"reason\":\"force minor collect\""));
+    assert!(records[1].contains("\"kind\":\"minor\",\"reason\":\"force minor collect\""));
+    assert!(records[2].contains("\"kind\":\"full\",\"reason\":\"force collect\""));
+    assert!(records[3].starts_with("{\"kind\":\"end\",\"time\":"));
+
+    let output = common::dora().arg("gc-report").arg(&log).output().unwrap();
+    assert!(output.status.success());
+
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.starts_with("collections: 3 (2 minor, 1 full)\n"));
+    assert!(
+        report.contains("\npauses     count       p50       p90       p99       max     total\n")
+    );
+    assert!(report.contains("\nminor          2"));
+    assert!(report.contains("\nfull           1"));
+    assert!(report.contains("\nall            3"));
+
+    fs::remove_file(&program).unwrap();
+    fs::remove_file(&log).unwrap();
+}
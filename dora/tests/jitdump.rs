@@ -0,0 +1,120 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fs;
+use std::io::{Cursor, Read};
+
+mod common;
+
+const JIT_CODE_LOAD: u32 = 0;
+const JIT_CODE_DEBUG_INFO: u32 = 2;
+
+const PROGRAM: &str = "fun main() {
+    assert(add(1, 2) == 3);
+}
+
+fun add(a: Int32, b: Int32) -> Int32 {
+    a + b
+}
+";
+
+fn read_string(cursor: &mut Cursor<&[u8]>) -> String {
+    let mut bytes = Vec::new();
+
+    loop {
+        match cursor.read_u8().unwrap() {
+            0 => return String::from_utf8(bytes).unwrap(),
+            byte => bytes.push(byte),
+        }
+    }
+}
+
+#[test]
+fn jitdump_contains_code_and_line_info() {
+    let dir = common::temp_file("jitdump");
+    fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("add.dora");
+    fs::write(&program, PROGRAM).unwrap();
+
+    let mut child = common::dora()
+        .arg("--perf-jitdump")
+        .arg(&program)
+        .current_dir(&dir)
+        .spawn()
+        .unwrap();
+    let pid = child.id();
+    assert!(child.wait().unwrap().success());
+
+    let dump = fs::read(dir.join(format!("jit-{}.dump", pid))).unwrap();
+    let mut cursor = Cursor::new(&dump[..]);
+
+    assert_eq!(0x4A69_5444, cursor.read_u32::<LittleEndian>().unwrap());
+    assert_eq!(1, cursor.read_u32::<LittleEndian>().unwrap());
+    let header_size = cursor.read_u32::<LittleEndian>().unwrap();
+    assert_eq!(40, header_size);
+    cursor.read_u32::<LittleEndian>().unwrap(); // elf machine
+    cursor.read_u32::<LittleEndian>().unwrap(); // padding
+    assert_eq!(pid, cursor.read_u32::<LittleEndian>().unwrap());
+    cursor.set_position(header_size as u64);
+
+    let mut loaded = Vec::new();
+    let mut lines = Vec::new();
+    let mut last_debug_info = None;
+
+    while (cursor.position() as usize) < dump.len() {
+        let start = cursor.position();
+        let id = cursor.read_u32::<LittleEndian>().unwrap();
+        let size = cursor.read_u32::<LittleEndian>().unwrap() as u64;
+        assert!(cursor.read_u64::<LittleEndian>().unwrap() > 0);
+
+        match id {
+            JIT_CODE_LOAD => {
+                assert_eq!(pid, cursor.read_u32::<LittleEndian>().unwrap());
+                cursor.read_u32::<LittleEndian>().unwrap(); // tid
+                let vma = cursor.read_u64::<LittleEndian>().unwrap();
+                assert_eq!(vma, cursor.read_u64::<LittleEndian>().unwrap());
+                let code_size = cursor.read_u64::<LittleEndian>().unwrap();
+                assert_eq!(
+                    loaded.len() as u64,
+                    cursor.read_u64::<LittleEndian>().unwrap()
+                );
+                let name = read_string(&mut cursor);
+
+                let mut code = vec![0; code_size as usize];
+                cursor.read_exact(&mut code).unwrap();
+                assert!(code_size > 0);
+
+                // line info precedes the code of the same function
+                assert_eq!(Some(vma), last_debug_info.take());
+                loaded.push(name);
+            }
+
+            JIT_CODE_DEBUG_INFO => {
+                let code_addr = cursor.read_u64::<LittleEndian>().unwrap();
+                let entries = cursor.read_u64::<LittleEndian>().unwrap();
+
+                for _ in 0..entries {
+                    let addr = cursor.read_u64::<LittleEndian>().unwrap();
+                    assert!(addr >= code_addr);
+                    let line = cursor.read_u32::<LittleEndian>().unwrap();
+                    cursor.read_u32::<LittleEndian>().unwrap(); // discriminator
+                    let file = read_string(&mut cursor);
+
+                    if file == program.to_str().unwrap() {
+                        lines.push(line);
+                    }
+                }
+
+                last_debug_info = Some(code_addr);
+            }
+
+            _ => panic!("unexpected record {}", id),
+        }
+
+        assert_eq!(start + size, cursor.position());
+    }
+
+    assert!(loaded.contains(&"main()".to_string()));
+    assert!(loaded.contains(&"add(Int32, Int32) -> Int32".to_string()));
+    assert!(lines.contains(&2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+use std::fs;
+
+mod common;
+
+// Which functions end up in the profile depends on where the signals hit,
+// only the structure of both reports is checked.
+#[test]
+fn profile_reports_are_written() {
+    let report_file = common::temp_file("profile.txt");
+    let collapsed_file = common::temp_file("profile.txt.collapsed");
+
+    let status = common::dora()
+        .arg(format!("--profile={}", report_file.display()))
+        .arg("../tests/profile1.dora")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let report = fs::read_to_string(&report_file).unwrap();
+    let mut lines = report.lines();
+    let header = lines.next().unwrap();
+    assert!(header.contains(" samples with 1000us interval, "));
+    let samples: usize = header.split(' ').next().unwrap().parse().unwrap();
+    assert!(samples > 0);
+
+    let outside = lines.next().unwrap();
+    assert!(outside.ends_with(" samples outside of Dora code"));
+    assert!(report.contains("\n  self%     self  total%    total  function\n"));
+    assert!(report.contains("\n  self%     self  line\n"));
+
+    let collapsed = fs::read_to_string(&collapsed_file).unwrap();
+    let mut collapsed_samples = 0;
+
+    for line in collapsed.lines() {
+        let count: usize = line.rsplit(' ').next().unwrap().parse().unwrap();
+        assert!(count > 0);
+        collapsed_samples += count;
+    }
+
+    assert_eq!(samples, collapsed_samples);
+
+    fs::remove_file(&report_file).unwrap();
+    fs::remove_file(&collapsed_file).unwrap();
+}
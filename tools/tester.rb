@@ -29,7 +29,7 @@ $ARGS.delete_if do |arg|
 end
 
 class TestUtility
-  def self.spawn_with_timeout(cmd, timeout)
+  def self.spawn_with_timeout(cmd, timeout, input = nil)
     result = {
       :pid     => nil,
       :status  => nil,
@@ -45,6 +45,7 @@ class TestUtility
       Timeout.timeout(timeout) do
         result[:pid] = wait_thr.pid
 
+        stdin.write(input) if input
         stdin.close
         out_reader = Thread.new { stdout.read }
         err_reader = Thread.new { stderr.read }
@@ -87,6 +88,7 @@ class TestCase
                 :test_file,
                 :vm_args,
                 :args,
+                :stdin,
                 :expectation,
                 :configs,
                 :results,
@@ -144,7 +146,7 @@ class TestCase
   private
   def run_test(optional_vm_args, mutex)
     cmdline = "#{binary} #{vm_args} #{optional_vm_args} #{test_file} #{args}"
-    process_result = TestUtility.spawn_with_timeout(cmdline, self.timeout)
+    process_result = TestUtility.spawn_with_timeout(cmdline, self.timeout, self.stdin)
     result = check_test_run_result(process_result)
     if $no_capture || result != true
       mutex.synchronize do
@@ -172,6 +174,11 @@ class TestCase
     timeout = result[:timeout]
     exit_code = status.exitstatus
 
+    # warnings only fail tests with --deny-warnings, they are compared
+    # only in tests that expect them
+    stderr = strip_warnings(stderr) unless
+      self.expectation.stderr && self.expectation.stderr.include?("warning")
+
     return "test timed out after #{self.timeout} seconds" if
       timeout
 
@@ -330,6 +337,10 @@ def test_name(num)
   num == 1 ? "test" : "tests"
 end
 
+def strip_warnings(content)
+  content.each_line.reject { |line| line.start_with?("warning in ") }.join
+end
+
 def read_error_message(content)
   position = nil
   message = nil
@@ -364,6 +375,8 @@ def read_cmdline(text)
       arg +=  case char
               when "n" then "\n"
               when "t" then "\t"
+              when "\"" then "\""
+              when "\\" then "\\"
               else
                 return "unknown escape sequence \\#{char}"
               end
@@ -462,6 +475,9 @@ def parse_test_file(file)
       when "stderr"
         test_case.expectation.stderr = arguments[1]
 
+      when "stdin"
+        test_case.stdin = arguments[1]
+
       when "args"
         test_case.args = arguments[1..-1].join(" ")
 